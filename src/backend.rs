@@ -0,0 +1,185 @@
+use swh_graph::graph::{NodeId, SwhBackwardGraph, SwhForwardGraph, SwhGraph, SwhGraphWithProperties, SwhLabeledForwardGraph};
+use swh_graph::labels::{EdgeLabel, Permission, VisitStatus};
+use swh_graph::{properties, NodeType, SWHID};
+
+/// Graph operations needed to compute origin metrics.
+///
+/// Implemented for compressed Software Heritage graphs loaded with the maps, timestamps,
+/// persons and strings properties and the edge labels, and by
+/// [`InMemoryGraph`](crate::memory_graph::InMemoryGraph) for small synthetic graphs.
+pub trait GraphBackend {
+    fn num_nodes(&self) -> usize;
+
+    fn num_arcs(&self) -> u64;
+
+    fn successors(&self, node: NodeId) -> impl Iterator<Item = NodeId> + '_;
+
+    fn node_type(&self, node: NodeId) -> NodeType;
+
+    fn swhid(&self, node: NodeId) -> SWHID;
+
+    /// Node of a SWHID, found through the minimal perfect hash of the maps, `None` if it
+    /// is not in the graph
+    fn node_id(&self, swhid: &SWHID) -> Option<NodeId>;
+
+    /// Commit date of a revision or release, in seconds since the epoch
+    fn committer_timestamp(&self, node: NodeId) -> Option<i64>;
+
+    /// Timezone of the commit date of a revision or release, in minutes east of UTC
+    fn committer_timestamp_offset(&self, node: NodeId) -> Option<i16>;
+
+    fn committer_id(&self, node: NodeId) -> Option<u32>;
+
+    /// Commit message, or URL for origins
+    fn message(&self, node: NodeId) -> Option<Vec<u8>>;
+
+    /// Snapshot of the most recent full visit of an origin, with the visit timestamp
+    fn latest_snapshot(&self, origin: NodeId) -> Option<(NodeId, u64)>;
+}
+
+impl<G> GraphBackend for G
+where
+    G: SwhLabeledForwardGraph
+    + SwhGraphWithProperties<
+        Maps: properties::Maps,
+        Timestamps: properties::Timestamps,
+        Persons: properties::Persons,
+        Strings: properties::Strings,
+    >,
+{
+    fn num_nodes(&self) -> usize {
+        SwhGraph::num_nodes(self)
+    }
+
+    fn num_arcs(&self) -> u64 {
+        SwhGraph::num_arcs(self)
+    }
+
+    fn successors(&self, node: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        SwhForwardGraph::successors(self, node).into_iter()
+    }
+
+    fn node_type(&self, node: NodeId) -> NodeType {
+        self.properties().node_type(node)
+    }
+
+    fn swhid(&self, node: NodeId) -> SWHID {
+        self.properties().swhid(node)
+    }
+
+    fn node_id(&self, swhid: &SWHID) -> Option<NodeId> {
+        self.properties().node_id(*swhid).ok()
+    }
+
+    fn committer_timestamp(&self, node: NodeId) -> Option<i64> {
+        self.properties().committer_timestamp(node)
+    }
+
+    fn committer_timestamp_offset(&self, node: NodeId) -> Option<i16> {
+        self.properties().committer_timestamp_offset(node)
+    }
+
+    fn committer_id(&self, node: NodeId) -> Option<u32> {
+        self.properties().committer_id(node)
+    }
+
+    fn message(&self, node: NodeId) -> Option<Vec<u8>> {
+        self.properties().message(node)
+    }
+
+    fn latest_snapshot(&self, origin: NodeId) -> Option<(NodeId, u64)> {
+        swh_graph_stdlib::find_latest_snp(self, origin).ok().flatten()
+    }
+}
+
+/// Backward traversal, for graphs loaded along with their transposed graph (see the
+/// server's `--bidirectional`), on which predecessor-based features rely.
+pub trait BackwardCapable: GraphBackend {
+    fn predecessors(&self, node: NodeId) -> impl Iterator<Item = NodeId> + '_;
+}
+
+impl<G> BackwardCapable for G
+where
+    G: GraphBackend + SwhBackwardGraph,
+{
+    fn predecessors(&self, node: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        SwhBackwardGraph::predecessors(self, node).into_iter()
+    }
+}
+
+/// Content properties, for graphs loaded with the contents property family (the
+/// server's `--properties all`)
+pub trait ContentCapable: GraphBackend {
+    /// Length of a content in bytes, `None` if unknown
+    fn content_length(&self, node: NodeId) -> Option<u64>;
+
+    /// Whether the archive skipped a content, e.g. for being too large, and only knows
+    /// its hashes
+    fn is_skipped_content(&self, node: NodeId) -> bool;
+}
+
+impl<G> ContentCapable for G
+where
+    G: GraphBackend + SwhGraphWithProperties<Contents: properties::Contents>,
+{
+    fn content_length(&self, node: NodeId) -> Option<u64> {
+        self.properties().content_length(node)
+    }
+
+    fn is_skipped_content(&self, node: NodeId) -> bool {
+        self.properties().is_skipped_content(node)
+    }
+}
+
+/// Label of an arc, with its name decoded
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ArcLabel {
+    /// `snp -> *` arcs: name of the branch
+    Branch { name: Vec<u8> },
+    /// `dir -> *` arcs: name of the entry and its permission, if known
+    DirEntry { name: Vec<u8>, permission: Option<Permission> },
+    /// `ori -> snp` arcs: when the visit happened (seconds since the epoch), and whether it
+    /// was a full one
+    Visit { timestamp: u64, full: bool },
+}
+
+/// Arc labels and their names, for graphs loaded with the label names property (the
+/// server's `--properties all`), on which label-based features rely.
+pub trait LabelCapable: GraphBackend {
+    /// Successors of `node`, each with the labels of the arc to it: there may be several,
+    /// e.g. two branches of a snapshot pointing to the same revision, or none.
+    ///
+    /// See [`crate::labels`] for typed views of each kind of label.
+    fn labeled_arcs(&self, node: NodeId) -> Vec<(NodeId, Vec<ArcLabel>)>;
+}
+
+impl<G> LabelCapable for G
+where
+    G: GraphBackend
+    + SwhLabeledForwardGraph
+    + SwhGraphWithProperties<Maps: properties::Maps, LabelNames: properties::LabelNames>,
+{
+    fn labeled_arcs(&self, node: NodeId) -> Vec<(NodeId, Vec<ArcLabel>)> {
+        let properties = self.properties();
+        self.labeled_successors(node)
+            .map(|(succ, labels)| {
+                let labels = labels
+                    .map(|label| match label {
+                        EdgeLabel::Branch(branch) => ArcLabel::Branch {
+                            name: properties.label_name(branch.label_name_id()),
+                        },
+                        EdgeLabel::DirEntry(entry) => ArcLabel::DirEntry {
+                            name: properties.label_name(entry.label_name_id()),
+                            permission: entry.permission(),
+                        },
+                        EdgeLabel::Visit(visit) => ArcLabel::Visit {
+                            timestamp: visit.timestamp(),
+                            full: visit.status() == VisitStatus::Full,
+                        },
+                    })
+                    .collect();
+                (succ, labels)
+            })
+            .collect()
+    }
+}
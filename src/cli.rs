@@ -0,0 +1,31 @@
+use clap::{CommandFactory, Subcommand};
+use clap_complete::Shell;
+use std::io;
+
+/// Subcommands of both binaries printing their shell integration instead of running
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Print the completion script of a shell, e.g.
+    /// `swh-test completions bash > /etc/bash_completion.d/swh-test`
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Print the man page, e.g. `swh-test man > swh-test.1`
+    Man,
+}
+
+impl Command {
+    /// Print what the subcommand asks for, for the binary whose arguments are `C`
+    pub fn run<C: CommandFactory>(&self) -> io::Result<()> {
+        let mut command = C::command();
+        let name = command.get_name().to_string();
+        match self {
+            Command::Completions { shell } => {
+                clap_complete::generate(*shell, &mut command, name, &mut io::stdout());
+                Ok(())
+            }
+            Command::Man => clap_mangen::Man::new(command).render(&mut io::stdout()),
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use swh_graph::graph::NodeId;
+use swh_graph::NodeType;
+use tokio_util::sync::CancellationToken;
+
+use crate::backend::GraphBackend;
+use crate::error::GraphError;
+use crate::graph::Graph;
+use crate::origin::Origin;
+use crate::progress::Progress;
+use crate::utils::{bfs, TraversalOptions, Visit};
+
+/// Name of the person commit totals file in the cache directory
+pub const PERSON_COMMITS_FILE: &str = "person_commits.bin";
+
+/// First bytes of the person commit totals file. The number of persons follows as a
+/// little-endian `u64`, then each [`PersonCommits`] as its little-endian fields.
+const MAGIC: &[u8; 8] = b"SWHPERS1";
+
+/// Origins whose contributions are held in memory at a time while writing them, so that
+/// the output is in origin order without holding every edge at once
+const CHUNK_SIZE: usize = 100_000;
+
+/// (committer id, number of commits) pairs of an origin
+pub type Contributions = Vec<(u32, u32)>;
+
+/// Number of commits of each committer in the latest snapshot of `origin`, sorted by
+/// committer id. Empty if the origin has no snapshot, `None` if cancelled meanwhile.
+pub fn origin_contributions<G>(origin: &Origin<G>, cancel: &CancellationToken) -> Result<Option<Contributions>, GraphError>
+where
+    G: GraphBackend + Send + Sync,
+{
+    let Some((snapshot, _)) = origin.get_latest_snapshot()? else {
+        return Ok(Some(Vec::new()));
+    };
+    let graph = origin.get_graph()?;
+    let props = graph.as_ref();
+    let mut commits: HashMap<u32, u32> = HashMap::new();
+    let end = bfs(props, [snapshot], &TraversalOptions::new().cancel(cancel), |node, _| {
+        if props.node_type(node) == NodeType::Revision {
+            if let Some(committer) = props.committer_id(node) {
+                let count = commits.entry(committer).or_default();
+                *count = count.saturating_add(1);
+            }
+        }
+        Visit::Continue
+    });
+    if end.is_cancelled() {
+        return Ok(None);
+    }
+    let mut contributions: Contributions = commits.into_iter().collect();
+    contributions.sort_unstable();
+    Ok(Some(contributions))
+}
+
+/// Write the committer to origin bipartite graph of the loaded origins to `path`, as CSV
+/// `person_id,origin_id,commits` lines in origin order, each weighted by the number of
+/// commits of the person in the latest snapshot of the origin. If the extension of `path`
+/// is `.parquet`, the edges are written as a Parquet file with these columns instead (with
+/// the `parquet` feature).
+///
+/// Person ids are the graph's (pseudonymized) committer ids. Returns the number of edges
+/// written, or [`GraphError::Cancelled`].
+pub fn write_contributions<G>(
+    graph: &Graph<G>,
+    path: &Path,
+    progress: &Progress,
+    cancel: &CancellationToken,
+) -> Result<usize, GraphError>
+where
+    G: GraphBackend + Send + Sync,
+{
+    let mut writer = EdgeWriter::create(path)?;
+    let mut edges = 0;
+    for chunk in graph.loaded_origins().chunks(CHUNK_SIZE) {
+        let contributions: Vec<(NodeId, Option<Contributions>)> = chunk
+            .par_iter()
+            .map_init(|| progress.batch(), |batch, origin| {
+                batch.inc();
+                Ok((origin.id, origin_contributions(origin, cancel)?))
+            })
+            .collect::<Result<_, GraphError>>()?;
+        let mut columns = EdgeColumns::default();
+        for (origin, contributions) in contributions {
+            for (person, commits) in contributions.ok_or(GraphError::Cancelled)? {
+                columns.persons.push(person);
+                columns.origins.push(origin as u64);
+                columns.commits.push(commits);
+            }
+        }
+        edges += columns.persons.len();
+        writer.write(columns)?;
+    }
+    writer.finish()?;
+    Ok(edges)
+}
+
+/// Edges of the bipartite graph of a chunk of origins, as columns
+#[derive(Default)]
+struct EdgeColumns {
+    persons: Vec<u32>,
+    origins: Vec<u64>,
+    commits: Vec<u32>,
+}
+
+/// Output of [`write_contributions`]
+enum EdgeWriter {
+    Csv(BufWriter<File>),
+    #[cfg(feature = "parquet")]
+    Parquet { writer: Box<parquet::arrow::ArrowWriter<File>>, schema: arrow_schema::SchemaRef },
+}
+
+impl EdgeWriter {
+    /// Parquet if the extension of `path` is `.parquet`, CSV otherwise
+    fn create(path: &Path) -> Result<Self, GraphError> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("parquet") => Self::create_parquet(path),
+            _ => {
+                let mut writer = BufWriter::new(File::create(path)?);
+                writeln!(writer, "person_id,origin_id,commits")?;
+                Ok(EdgeWriter::Csv(writer))
+            }
+        }
+    }
+
+    #[cfg(feature = "parquet")]
+    fn create_parquet(path: &Path) -> Result<Self, GraphError> {
+        use arrow_schema::{DataType, Field, Schema};
+
+        let schema = std::sync::Arc::new(Schema::new(vec![
+            Field::new("person_id", DataType::UInt32, false),
+            Field::new("origin_id", DataType::UInt64, false),
+            Field::new("commits", DataType::UInt32, false),
+        ]));
+        let writer = parquet::arrow::ArrowWriter::try_new(File::create(path)?, schema.clone(), None)
+            .map_err(GraphError::serialization)?;
+        Ok(EdgeWriter::Parquet { writer: Box::new(writer), schema })
+    }
+
+    #[cfg(not(feature = "parquet"))]
+    fn create_parquet(_path: &Path) -> Result<Self, GraphError> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "Parquet files need swh-test to be built with `--features parquet`",
+        )
+        .into())
+    }
+
+    fn write(&mut self, columns: EdgeColumns) -> Result<(), GraphError> {
+        match self {
+            EdgeWriter::Csv(writer) => {
+                let edges = columns.persons.iter().zip(&columns.origins).zip(&columns.commits);
+                for ((person, origin), commits) in edges {
+                    writeln!(writer, "{},{},{}", person, origin, commits)?;
+                }
+            }
+            #[cfg(feature = "parquet")]
+            EdgeWriter::Parquet { writer, schema } => {
+                use arrow_array::{ArrayRef, RecordBatch, UInt32Array, UInt64Array};
+                use std::sync::Arc;
+
+                if columns.persons.is_empty() {
+                    return Ok(());
+                }
+                let batch = RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(UInt32Array::from(columns.persons)) as ArrayRef,
+                        Arc::new(UInt64Array::from(columns.origins)),
+                        Arc::new(UInt32Array::from(columns.commits)),
+                    ],
+                )
+                .map_err(GraphError::serialization)?;
+                writer.write(&batch).map_err(GraphError::serialization)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), GraphError> {
+        match self {
+            EdgeWriter::Csv(mut writer) => writer.flush()?,
+            #[cfg(feature = "parquet")]
+            EdgeWriter::Parquet { writer, .. } => {
+                writer.close().map_err(GraphError::serialization)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Commits of a person across the corpus
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PersonCommits {
+    /// Pseudonymized committer id of the graph
+    pub person: u32,
+    /// Sum of the commits of the person in the latest snapshot of each loaded origin, a
+    /// commit being counted once per origin containing it (e.g. forks)
+    pub commits: u64,
+    /// Number of loaded origins the person committed to
+    pub origins: u32,
+}
+
+/// Commits of every person over all loaded origins, most active first, for contributor
+/// centric studies without going through the bipartite graph of [`write_contributions`]
+pub struct PersonTotals {
+    /// Sorted by decreasing commits, then by person id
+    persons: Vec<PersonCommits>,
+}
+
+impl PersonTotals {
+    /// Add up the contributions of each loaded origin by person. Returns `None` if
+    /// cancelled meanwhile.
+    pub fn build<G>(graph: &Graph<G>, progress: &Progress, cancel: &CancellationToken) -> Result<Option<Self>, GraphError>
+    where
+        G: GraphBackend + Send + Sync,
+    {
+        let mut totals: HashMap<u32, (u64, u32)> = HashMap::new();
+        for chunk in graph.loaded_origins().chunks(CHUNK_SIZE) {
+            let contributions: Vec<Option<Contributions>> = chunk
+                .par_iter()
+                .map_init(|| progress.batch(), |batch, origin| {
+                    batch.inc();
+                    origin_contributions(origin, cancel)
+                })
+                .collect::<Result<_, GraphError>>()?;
+            for contributions in contributions {
+                let Some(contributions) = contributions else {
+                    return Ok(None);
+                };
+                for (person, commits) in contributions {
+                    let total = totals.entry(person).or_default();
+                    total.0 += u64::from(commits);
+                    total.1 += 1;
+                }
+            }
+        }
+        let mut persons: Vec<PersonCommits> = totals
+            .into_iter()
+            .map(|(person, (commits, origins))| PersonCommits { person, commits, origins })
+            .collect();
+        persons.par_sort_unstable_by_key(|total| (std::cmp::Reverse(total.commits), total.person));
+        Ok(Some(PersonTotals { persons }))
+    }
+
+    /// The `n` persons with the most commits
+    pub fn top(&self, n: usize) -> &[PersonCommits] {
+        &self.persons[..n.min(self.persons.len())]
+    }
+
+    /// Number of persons with at least one commit
+    pub fn len(&self) -> usize {
+        self.persons.len()
+    }
+
+    /// Whether no person has any commit
+    pub fn is_empty(&self) -> bool {
+        self.persons.is_empty()
+    }
+
+    /// Commits of all persons across the corpus
+    pub fn commit_count(&self) -> u64 {
+        self.persons.iter().map(|total| total.commits).sum()
+    }
+
+    /// Write the `n` persons with the most commits as CSV `person_id,commits,origins` lines
+    pub fn write_csv(&self, writer: &mut impl Write, n: usize) -> std::io::Result<()> {
+        writeln!(writer, "person_id,commits,origins")?;
+        for total in self.top(n) {
+            writeln!(writer, "{},{},{}", total.person, total.commits, total.origins)?;
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), GraphError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(self.persons.len() as u64).to_le_bytes())?;
+        for total in &self.persons {
+            writer.write_all(&total.person.to_le_bytes())?;
+            writer.write_all(&total.commits.to_le_bytes())?;
+            writer.write_all(&total.origins.to_le_bytes())?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, GraphError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(GraphError::CacheCorrupted {
+                path: path.to_path_buf(),
+                reason: "not a person commit totals file".to_string(),
+            });
+        }
+        let mut count = [0u8; 8];
+        reader.read_exact(&mut count)?;
+        let count = u64::from_le_bytes(count) as usize;
+        // Don't trust a corrupted count for the allocation
+        let mut persons = Vec::with_capacity(count.min(1 << 24));
+        let mut record = [0u8; 16];
+        for _ in 0..count {
+            reader.read_exact(&mut record)?;
+            persons.push(PersonCommits {
+                person: u32::from_le_bytes(record[0..4].try_into().unwrap()),
+                commits: u64::from_le_bytes(record[4..12].try_into().unwrap()),
+                origins: u32::from_le_bytes(record[12..16].try_into().unwrap()),
+            });
+        }
+        Ok(PersonTotals { persons })
+    }
+}
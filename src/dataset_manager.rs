@@ -0,0 +1,210 @@
+use axum::{extract::State, http::StatusCode, response::Json, routing::get, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+use crate::error::GraphError;
+use crate::progress::LoadProgress;
+use crate::redis::RedisCache;
+use crate::server::{health_check, load_app, spawn_warm_up, ServerArgs};
+
+/// Dataset declared in the `--datasets` file, a JSON array of them. The fields not
+/// declared here are taken from the command line, the same for all datasets.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct DatasetConfig {
+    /// Served under `/graphs/<name>`
+    pub name: String,
+    /// Path to the graph data directory
+    pub graph_path: String,
+    /// Path to store cached data
+    pub data_path: String,
+    /// Directory for the origins cache and its sidecar files (defaults to the data path's
+    /// parent)
+    #[serde(default)]
+    pub cache_dir: Option<String>,
+    /// Also load the transposed graph
+    #[serde(default)]
+    pub bidirectional: bool,
+    /// Redis caching the responses of this dataset, defaults to `--redis-url`
+    #[serde(default)]
+    pub redis_url: Option<String>,
+    /// Prefix of the Redis keys, defaults to `<--redis-prefix>:<name>` so that datasets
+    /// sharing a Redis keep their responses apart
+    #[serde(default)]
+    pub redis_prefix: Option<String>,
+    /// Endpoints to warm up, relative to `/graphs/<name>`, after those of `--warm-up`
+    #[serde(default)]
+    pub warm_up: Vec<String>,
+}
+
+impl DatasetConfig {
+    /// Read and check the datasets declared in `path`
+    pub fn read(path: &Path) -> Result<Vec<DatasetConfig>, GraphError> {
+        let invalid = |reason: String| GraphError::InvalidDatasets { path: path.to_path_buf(), reason };
+        let datasets: Vec<DatasetConfig> = serde_json::from_reader(BufReader::new(File::open(path)?))
+            .map_err(|e| invalid(e.to_string()))?;
+        if datasets.is_empty() {
+            return Err(invalid("no dataset declared".to_string()));
+        }
+        let mut names = HashSet::new();
+        let mut cache_dirs = HashSet::new();
+        for dataset in &datasets {
+            if dataset.name.is_empty()
+                || !dataset.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(invalid(format!("dataset name {:?} is not made of letters, digits, `-` and `_`", dataset.name)));
+            }
+            if !names.insert(dataset.name.as_str()) {
+                return Err(invalid(format!("dataset {} is declared twice", dataset.name)));
+            }
+            // Their origins caches would overwrite each other
+            if !cache_dirs.insert(dataset.cache_dir()) {
+                return Err(invalid(format!("dataset {} shares its cache directory with another one", dataset.name)));
+            }
+            if let Some(url) = &dataset.redis_url {
+                RedisCache::parse_url(url).map_err(|e| invalid(format!("dataset {}: {}", dataset.name, e)))?;
+            }
+        }
+        Ok(datasets)
+    }
+
+    /// Directory of the origins cache, as picked by [`crate::graph::GraphBuilder::build`]
+    fn cache_dir(&self) -> PathBuf {
+        match &self.cache_dir {
+            Some(dir) => PathBuf::from(dir),
+            None => Path::new(&self.data_path).parent().map(PathBuf::from).unwrap_or_default(),
+        }
+    }
+
+    /// `args` with the paths and Redis of this dataset
+    pub(crate) fn server_args(&self, args: &ServerArgs) -> ServerArgs {
+        let mut args = args.clone();
+        args.datasets = None;
+        args.graph_path = Some(self.graph_path.clone());
+        args.data_path = self.data_path.clone();
+        args.cache_dir = self.cache_dir.clone();
+        args.bidirectional = self.bidirectional;
+        if self.redis_url.is_some() {
+            args.redis_url = self.redis_url.clone();
+        }
+        args.redis_prefix = match &self.redis_prefix {
+            Some(prefix) => prefix.clone(),
+            None => format!("{}:{}", args.redis_prefix, self.name),
+        };
+        // The origin tables of several datasets can't share the Flight port
+        #[cfg(feature = "flight")]
+        {
+            args.flight_port = None;
+        }
+        args
+    }
+}
+
+/// A dataset with its graph loaded
+struct Dataset {
+    config: DatasetConfig,
+    /// Loading progress of its origins, served at `/graphs/<name>/readyz`
+    progress: Arc<LoadProgress>,
+    router: Router,
+}
+
+/// Loading progress of each dataset, by name
+type DatasetProgress = Arc<Vec<(String, Arc<LoadProgress>)>>;
+
+/// Owns the graphs of the `--datasets` and their background tasks, which stop with the
+/// shutdown token, and serves each one under `/graphs/<name>` with the routes of a single
+/// graph server
+pub struct DatasetManager {
+    datasets: Vec<Dataset>,
+}
+
+impl DatasetManager {
+    /// Load the graphs of `configs` one after the other, so that only one of them is
+    /// being mapped at a time. Their origins then load in the background.
+    pub async fn load(
+        args: &ServerArgs,
+        configs: Vec<DatasetConfig>,
+        shutdown: CancellationToken,
+    ) -> Result<Self, GraphError> {
+        let mut datasets = Vec::with_capacity(configs.len());
+        for config in configs {
+            info!("Loading dataset {} from {}", config.name, config.graph_path);
+            let progress = Arc::new(LoadProgress::default());
+            let app = load_app(config.server_args(args), progress.clone(), shutdown.clone()).await?;
+            datasets.push(Dataset { config, progress, router: app.router });
+        }
+        Ok(DatasetManager { datasets })
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.datasets.iter().map(|dataset| dataset.config.name.as_str())
+    }
+
+    /// Warm up `uris` then the `warm_up` endpoints of each dataset, once its origins are
+    /// loaded, from the server at `address`
+    pub fn spawn_warm_ups(&self, address: SocketAddr, uris: &[String]) {
+        for dataset in &self.datasets {
+            let name = &dataset.config.name;
+            let uris: Vec<String> = uris
+                .iter()
+                .chain(&dataset.config.warm_up)
+                .map(|uri| format!("/graphs/{}{}", name, uri))
+                .collect();
+            if !uris.is_empty() {
+                spawn_warm_up(address, dataset.progress.clone(), uris);
+            }
+        }
+    }
+
+    /// Router nesting the router of each dataset under `/graphs/<name>`, next to
+    /// `/graphs` listing them and `/readyz` ready once all of them are
+    pub fn router(self) -> Router {
+        let progress: DatasetProgress = Arc::new(
+            self.datasets
+                .iter()
+                .map(|dataset| (dataset.config.name.clone(), dataset.progress.clone()))
+                .collect(),
+        );
+        let mut router = Router::new()
+            .route("/health", get(health_check))
+            .route("/readyz", get(readiness_check))
+            .route("/graphs", get(get_datasets))
+            .with_state(progress);
+        for dataset in self.datasets {
+            router = router.nest(&format!("/graphs/{}", dataset.config.name), dataset.router);
+        }
+        router
+    }
+}
+
+fn datasets_json(progress: &DatasetProgress) -> Value {
+    json!({
+        "datasets": progress
+            .iter()
+            .map(|(name, progress)| json!({ "name": name, "readiness": progress.to_json() }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// GET /graphs - Name and loading status of each dataset
+async fn get_datasets(State(progress): State<DatasetProgress>) -> Json<Value> {
+    Json(datasets_json(&progress))
+}
+
+/// GET /readyz - Ready once the origins of every dataset are loaded
+async fn readiness_check(State(progress): State<DatasetProgress>) -> (StatusCode, Json<Value>) {
+    let status = if progress.iter().all(|(_, progress)| progress.is_ready()) {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(datasets_json(&progress)))
+}
@@ -0,0 +1,142 @@
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::origin::{Metric, OriginData};
+
+/// Value of a metric in two exports, `None` where it is unknown
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MetricDelta {
+    pub old: Option<i64>,
+    pub new: Option<i64>,
+}
+
+impl MetricDelta {
+    /// `new - old`, if both are known
+    pub fn delta(&self) -> Option<i64> {
+        Some(self.new? - self.old?)
+    }
+
+    fn to_json(self) -> Value {
+        json!({ "old": self.old, "new": self.new, "delta": self.delta() })
+    }
+}
+
+/// Origin found in both exports with different metrics
+pub struct OriginDelta {
+    pub url: String,
+    /// Node ids differ between exports, only the URL identifies an origin across them
+    pub old_id: usize,
+    pub new_id: usize,
+    /// The metrics which changed
+    pub metrics: Vec<(Metric, MetricDelta)>,
+}
+
+/// Differences between the origins of two graph exports, matched by URL (origin SWHIDs
+/// are hashes of their URL, so this is the same as matching them by SWHID)
+pub struct DatasetDiff {
+    /// Origins only in the new export
+    pub added: Vec<OriginData>,
+    /// Origins only in the old export
+    pub disappeared: Vec<OriginData>,
+    pub changed: Vec<OriginDelta>,
+    pub unchanged: usize,
+    /// Origins skipped because their URL is not in the cache
+    pub without_url: usize,
+}
+
+const METRICS: [Metric; 3] = [Metric::LatestCommitDate, Metric::CommitCount, Metric::CommitterCount];
+
+fn metric_value(origin: &OriginData, metric: Metric) -> Option<i64> {
+    match metric {
+        Metric::LatestCommitDate => origin.latest_commit_date,
+        Metric::CommitCount => origin.number_of_commits.map(i64::from),
+        Metric::CommitterCount => origin.number_of_commiters.map(i64::from),
+    }
+}
+
+fn metric_name(metric: Metric) -> &'static str {
+    match metric {
+        Metric::LatestCommitDate => "latest_commit_date",
+        Metric::CommitCount => "commit_count",
+        Metric::CommitterCount => "committer_count",
+    }
+}
+
+/// Compare the origins of an old and a new export. Lists are sorted by URL.
+pub fn diff_origins(old: &[OriginData], new: &[OriginData]) -> DatasetDiff {
+    let mut without_url = 0;
+    let mut old_by_url: HashMap<&str, &OriginData> = HashMap::with_capacity(old.len());
+    for origin in old {
+        match &origin.url {
+            Some(url) => {
+                old_by_url.insert(url, origin);
+            }
+            None => without_url += 1,
+        }
+    }
+
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+    let mut unchanged = 0;
+    for origin in new {
+        let Some(url) = &origin.url else {
+            without_url += 1;
+            continue;
+        };
+        let Some(previous) = old_by_url.remove(url.as_str()) else {
+            added.push(origin.clone());
+            continue;
+        };
+        let metrics: Vec<_> = METRICS
+            .into_iter()
+            .map(|metric| {
+                (metric, MetricDelta { old: metric_value(previous, metric), new: metric_value(origin, metric) })
+            })
+            .filter(|(_, delta)| delta.old != delta.new)
+            .collect();
+        if metrics.is_empty() {
+            unchanged += 1;
+        } else {
+            changed.push(OriginDelta { url: url.clone(), old_id: previous.id, new_id: origin.id, metrics });
+        }
+    }
+    let mut disappeared: Vec<OriginData> = old_by_url.into_values().cloned().collect();
+
+    added.sort_unstable_by(|a, b| a.url.cmp(&b.url));
+    disappeared.sort_unstable_by(|a, b| a.url.cmp(&b.url));
+    changed.sort_unstable_by(|a, b| a.url.cmp(&b.url));
+    DatasetDiff { added, disappeared, changed, unchanged, without_url }
+}
+
+impl DatasetDiff {
+    /// JSON representation, listing at most `limit` origins of each kind
+    pub fn to_json(&self, limit: usize) -> Value {
+        let origin = |origin: &OriginData| json!({ "id": origin.id, "url": origin.url });
+        let truncated = [self.added.len(), self.disappeared.len(), self.changed.len()]
+            .into_iter()
+            .any(|count| count > limit);
+        json!({
+            "added": self.added.iter().take(limit).map(origin).collect::<Vec<_>>(),
+            "added_count": self.added.len(),
+            "disappeared": self.disappeared.iter().take(limit).map(origin).collect::<Vec<_>>(),
+            "disappeared_count": self.disappeared.len(),
+            "changed": self.changed.iter().take(limit).map(|delta| {
+                let metrics: serde_json::Map<String, Value> = delta
+                    .metrics
+                    .iter()
+                    .map(|(metric, values)| (metric_name(*metric).to_string(), values.to_json()))
+                    .collect();
+                json!({
+                    "url": delta.url,
+                    "old_id": delta.old_id,
+                    "new_id": delta.new_id,
+                    "metrics": metrics,
+                })
+            }).collect::<Vec<_>>(),
+            "changed_count": self.changed.len(),
+            "unchanged_count": self.unchanged,
+            "without_url_count": self.without_url,
+            "truncated": truncated,
+        })
+    }
+}
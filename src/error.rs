@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+use swh_graph::NodeType;
+use thiserror::Error;
+
+/// Errors raised while loading the graph or maintaining the origins cache
+#[derive(Debug, Error)]
+pub enum GraphError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The origins cache exists but cannot be deserialized
+    #[error("origins cache {path:?} is corrupted: {reason}")]
+    CacheCorrupted { path: PathBuf, reason: String },
+
+    /// The compressed graph or one of its property families failed to load
+    #[error("failed to load the graph: {0:#}")]
+    GraphLoad(#[source] anyhow::Error),
+
+    #[error("failed to serialize origins: {0}")]
+    Serialization(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("origin {0} not found")]
+    OriginNotFound(usize),
+
+    /// The origin was built without a graph reference, e.g. deserialized and never given
+    /// one with `Origin::set_graph`, so its metrics can't be computed
+    #[error("origin {0} has no graph attached")]
+    GraphNotAttached(usize),
+
+    /// Computing the metrics of the origin went wrong, e.g. a compute worker panicked on
+    /// corrupted graph data
+    #[error("computing origin {origin} failed: {reason}")]
+    ComputeFailed { origin: usize, reason: String },
+
+    /// A traversal computing metrics of the origin would visit more nodes than the budget
+    /// set with `origin::set_origin_node_budget`
+    #[error("origin {origin} has more than {max_nodes} nodes to traverse")]
+    TraversalBudgetExceeded { origin: usize, max_nodes: usize },
+
+    /// No loaded origin belongs to this cluster
+    #[error("cluster {0} not found")]
+    ClusterNotFound(usize),
+
+    /// The node id is out of the graph or the node is not of the type the operation expects
+    #[error("no {expected} node {node}")]
+    NodeNotFound { node: usize, expected: NodeType },
+
+    /// A line of an `--origins-file` is neither a node id, an origin SWHID nor a URL
+    #[error("{path:?} line {line}: {reason}")]
+    InvalidOriginList { path: PathBuf, line: usize, reason: String },
+
+    /// A file of externally computed metrics can't be read, see `MetricsTable::read`
+    #[error("{path:?}: {reason}")]
+    InvalidMetricsTable { path: PathBuf, reason: String },
+
+    /// The `--datasets` file of the server can't be read as a list of datasets, or
+    /// declares the same name or cache directory twice
+    #[error("{path:?}: {reason}")]
+    InvalidDatasets { path: PathBuf, reason: String },
+
+    /// A computation over all origins was stopped through its cancellation token
+    #[error("origins computation was cancelled")]
+    Cancelled,
+}
+
+impl GraphError {
+    pub fn serialization(e: impl std::error::Error + Send + Sync + 'static) -> Self {
+        GraphError::Serialization(Box::new(e))
+    }
+}
+
+impl From<anyhow::Error> for GraphError {
+    fn from(e: anyhow::Error) -> Self {
+        GraphError::GraphLoad(e)
+    }
+}
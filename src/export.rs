@@ -0,0 +1,487 @@
+use std::fmt::{self, Write as _};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tracing::warn;
+#[cfg(feature = "kafka")]
+use rdkafka::{
+    config::ClientConfig,
+    error::{KafkaError, RDKafkaErrorCode},
+    producer::{BaseProducer, BaseRecord, DeliveryResult, Producer, ProducerContext},
+    ClientContext,
+};
+#[cfg(feature = "kafka")]
+use std::sync::atomic::AtomicUsize;
+
+use crate::error::GraphError;
+use crate::origin::OriginData;
+
+/// Attempts to write a batch before giving up on the export
+const MAX_ATTEMPTS: u32 = 6;
+
+/// Wait before the first retry, doubled at each attempt
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Rendered batches waiting for the writer thread, past which publishers wait for it
+const QUEUED_BATCHES: usize = 4;
+
+/// Longest time Kafka may take to acknowledge the records of a batch
+#[cfg(feature = "kafka")]
+const KAFKA_DELIVERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Shape of the records published by [`OriginExport`]
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One JSON object per line, with the same fields as the cache
+    Json,
+    /// `psql` script upserting each batch into a table in its own transaction, or these
+    /// upserts made directly to a `postgres://` destination (feature `postgres`)
+    Postgres,
+}
+
+/// Destination of the origin records as they are computed, so downstream pipelines can
+/// consume them incrementally instead of waiting for the final cache file.
+///
+/// Records are written to a file or a named pipe in batches, each flushed as soon as it
+/// is full, to be fed to a message broker client (e.g. `kcat -P -t origins` for Kafka) or
+/// to `psql`. With the `postgres` feature, a `postgres://` connection string as
+/// destination upserts the records directly into the database, each batch in a
+/// transaction. With the `kafka` feature, a `kafka://<brokers>/<topic>` destination
+/// publishes each JSON record as a message of the topic keyed by origin id, a batch
+/// being acknowledged by the brokers before the next one is sent. Batches are written by a thread of their own, so that the compute threads
+/// publishing records never wait for the destination, unless [`QUEUED_BATCHES`] batches
+/// are already waiting. A batch which fails to be written is retried with exponential
+/// backoff, reopening the destination in case its reader or server restarted, from the
+/// first byte not written yet: records are neither lost nor duplicated in a regular file,
+/// a batch is upserted again as a whole into Postgres, and published again as a whole to
+/// Kafka (so its records may be delivered twice, under the same key).
+///
+/// Once retries are exhausted the error is reported by [`Self::finish`], later records
+/// are dropped.
+pub struct OriginExport {
+    format: ExportFormat,
+    /// Table upserted by [`ExportFormat::Postgres`]
+    table: String,
+    batch_size: usize,
+    pending: Mutex<Vec<OriginData>>,
+    /// Batches for the writer thread, `None` once finished
+    batches: Mutex<Option<SyncSender<Batch>>>,
+    writer: Mutex<Option<JoinHandle<Result<usize, io::Error>>>>,
+    /// Set by the writer thread when it gave up
+    failed: Arc<AtomicBool>,
+}
+
+/// Records rendered by [`OriginExport::render`]
+struct Batch {
+    text: String,
+    records: usize,
+}
+
+impl OriginExport {
+    pub fn create(path: &Path, format: ExportFormat, table: &str, batch_size: usize) -> Result<Self, GraphError> {
+        if table.is_empty() || !table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid table name {:?}", table)).into());
+        }
+        let destination = Destination::open(path, format)?;
+        let (sender, receiver) = mpsc::sync_channel(QUEUED_BATCHES);
+        let failed = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let failed = failed.clone();
+            thread::Builder::new()
+                .name("origin-export".to_string())
+                .spawn(move || write_batches(destination, receiver, &failed))?
+        };
+        let export = OriginExport {
+            format,
+            table: table.to_string(),
+            batch_size: batch_size.max(1),
+            pending: Mutex::new(Vec::new()),
+            batches: Mutex::new(Some(sender)),
+            writer: Mutex::new(Some(writer)),
+            failed,
+        };
+        if format == ExportFormat::Postgres {
+            export.send(Batch { text: export.create_table(), records: 0 });
+        }
+        Ok(export)
+    }
+
+    pub fn publish(&self, data: &OriginData) {
+        if self.failed.load(Ordering::Relaxed) {
+            return;
+        }
+        let batch = {
+            let mut pending = self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            pending.push(data.clone());
+            if pending.len() < self.batch_size {
+                return;
+            }
+            std::mem::take(&mut *pending)
+        };
+        // Rendered and queued without the lock, batches being independent of each other
+        self.send(Batch { text: self.render(&batch), records: batch.len() });
+    }
+
+    /// Write the last batch, returning how many records were published or the error
+    /// which stopped the export
+    pub fn finish(&self) -> Result<usize, GraphError> {
+        let batch = std::mem::take(&mut *self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+        if !batch.is_empty() {
+            self.send(Batch { text: self.render(&batch), records: batch.len() });
+        }
+        // The writer thread stops once it has written the batches queued before
+        self.batches.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+        let Some(writer) = self.writer.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take() else {
+            return Ok(0);
+        };
+        match writer.join() {
+            Ok(published) => Ok(published?),
+            Err(_) => Err(io::Error::other("origin export thread panicked").into()),
+        }
+    }
+
+    /// Queue `batch` for the writer thread, dropping it if the export stopped
+    fn send(&self, batch: Batch) {
+        let sender = self.batches.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+        if let Some(sender) = sender {
+            // Only fails if the writer thread gave up
+            let _ = sender.send(batch);
+        }
+    }
+
+    fn render(&self, batch: &[OriginData]) -> String {
+        let mut out = String::new();
+        match self.format {
+            ExportFormat::Json => {
+                for data in batch {
+                    // Serializing plain fields can't fail
+                    out.push_str(&serde_json::to_string(data).unwrap_or_default());
+                    out.push('\n');
+                }
+            }
+            ExportFormat::Postgres => {
+                let _ = writeln!(
+                    out,
+                    "BEGIN;\nINSERT INTO {} (id, url, latest_commit_date, commit_count, committer_count, cluster) VALUES",
+                    self.table
+                );
+                for (i, data) in batch.iter().enumerate() {
+                    let _ = writeln!(
+                        out,
+                        "  ({}, {}, {}, {}, {}, {}){}",
+                        data.id,
+                        data.url.as_deref().map_or("NULL".to_string(), sql_string),
+                        sql_number(data.latest_commit_date),
+                        sql_number(data.number_of_commits),
+                        sql_number(data.number_of_commiters),
+                        sql_number(data.cluster),
+                        if i + 1 < batch.len() { "," } else { "" }
+                    );
+                }
+                out.push_str(
+                    "ON CONFLICT (id) DO UPDATE SET url = EXCLUDED.url, latest_commit_date = EXCLUDED.latest_commit_date, \
+                     commit_count = EXCLUDED.commit_count, committer_count = EXCLUDED.committer_count, cluster = EXCLUDED.cluster;\nCOMMIT;\n",
+                );
+            }
+        }
+        out
+    }
+
+    fn create_table(&self) -> String {
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} (id BIGINT PRIMARY KEY, url TEXT, latest_commit_date BIGINT, \
+             commit_count BIGINT, committer_count BIGINT, cluster BIGINT);\n",
+            self.table
+        )
+    }
+}
+
+/// Where the writer thread of an [`OriginExport`] writes its batches
+enum Destination {
+    File { path: PathBuf, file: File },
+    /// Connection string and client of the database upserted directly
+    #[cfg(feature = "postgres")]
+    Postgres { url: String, client: Box<postgres::Client> },
+    /// Comma-separated brokers, topic and producer of the Kafka topic published to
+    #[cfg(feature = "kafka")]
+    Kafka { brokers: String, topic: String, producer: BaseProducer<KafkaDelivery> },
+}
+
+impl Destination {
+    /// Create the file `path`, or connect to the database if `path` is a `postgres://`
+    /// connection string
+    fn open(path: &Path, format: ExportFormat) -> Result<Self, io::Error> {
+        if let Some(url) = path.to_str().and_then(|path| path.strip_prefix("kafka://")) {
+            if format != ExportFormat::Json {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "exporting to Kafka needs --export-format json"));
+            }
+            let (brokers, topic) = url
+                .rsplit_once('/')
+                .filter(|(brokers, topic)| !brokers.is_empty() && !topic.is_empty())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Kafka destinations are kafka://<brokers>/<topic>"))?;
+            return Destination::connect_kafka(brokers, topic);
+        }
+        let Some(url) = path.to_str().filter(|path| path.starts_with("postgres://") || path.starts_with("postgresql://")) else {
+            return Ok(Destination::File { path: path.to_path_buf(), file: File::create(path)? });
+        };
+        if format != ExportFormat::Postgres {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "exporting to Postgres needs --export-format postgres"));
+        }
+        Destination::connect(url)
+    }
+
+    #[cfg(feature = "postgres")]
+    fn connect(url: &str) -> Result<Self, io::Error> {
+        let client = postgres::Client::connect(url, postgres::NoTls).map_err(io::Error::other)?;
+        Ok(Destination::Postgres { url: url.to_string(), client: Box::new(client) })
+    }
+
+    #[cfg(not(feature = "postgres"))]
+    fn connect(_url: &str) -> Result<Self, io::Error> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "exporting to Postgres needs the postgres feature"))
+    }
+
+    #[cfg(feature = "kafka")]
+    fn connect_kafka(brokers: &str, topic: &str) -> Result<Self, io::Error> {
+        Ok(Destination::Kafka { brokers: brokers.to_string(), topic: topic.to_string(), producer: kafka_producer(brokers)? })
+    }
+
+    #[cfg(not(feature = "kafka"))]
+    fn connect_kafka(_brokers: &str, _topic: &str) -> Result<Self, io::Error> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "exporting to Kafka needs the kafka feature"))
+    }
+
+    /// Write `batch` from its byte `*written` on, counting the bytes written in `written`
+    /// even on failure
+    fn write(&mut self, batch: &str, written: &mut usize) -> Result<(), io::Error> {
+        match self {
+            Destination::File { file, .. } => write_from(file, batch.as_bytes(), written).and_then(|()| file.flush()),
+            #[cfg(feature = "postgres")]
+            Destination::Postgres { client, .. } => {
+                // A transaction of its own, upserted again as a whole if it failed
+                client.batch_execute(batch).map_err(io::Error::other)?;
+                *written = batch.len();
+                Ok(())
+            }
+            #[cfg(feature = "kafka")]
+            Destination::Kafka { topic, producer, .. } => {
+                for record in batch.lines() {
+                    let key = serde_json::from_str::<serde_json::Value>(record)
+                        .map_or_else(|_| String::new(), |record| record["id"].to_string());
+                    let mut message = BaseRecord::to(topic.as_str()).key(&key).payload(record);
+                    loop {
+                        match producer.send(message) {
+                            Ok(()) => break,
+                            // Wait for deliveries to make room in the producer queue
+                            Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), unsent)) => {
+                                producer.poll(Duration::from_millis(100));
+                                message = unsent;
+                            }
+                            Err((e, _)) => return Err(io::Error::other(e)),
+                        }
+                    }
+                }
+                producer.flush(KAFKA_DELIVERY_TIMEOUT).map_err(io::Error::other)?;
+                let failed = producer.context().failed.swap(0, Ordering::Relaxed);
+                if failed > 0 {
+                    return Err(io::Error::other(format!("{} records were not acknowledged by Kafka", failed)));
+                }
+                *written = batch.len();
+                Ok(())
+            }
+        }
+    }
+
+    /// Open the destination again after a failure, in case its reader or server restarted
+    fn reopen(&mut self) {
+        match self {
+            Destination::File { path, file } => {
+                // Appending, not to lose what was written before the failure
+                if let Ok(reopened) = OpenOptions::new().append(true).open(&*path) {
+                    *file = reopened;
+                }
+            }
+            #[cfg(feature = "postgres")]
+            Destination::Postgres { url, client } => {
+                // The failed transaction may still be open on the previous connection
+                if let Ok(reconnected) = postgres::Client::connect(url, postgres::NoTls) {
+                    **client = reconnected;
+                }
+            }
+            #[cfg(feature = "kafka")]
+            Destination::Kafka { brokers, producer, .. } => {
+                // Drops what the previous producer still queued, the batch being sent again
+                if let Ok(reconnected) = kafka_producer(brokers) {
+                    *producer = reconnected;
+                }
+            }
+        }
+    }
+}
+
+/// Counts the records Kafka failed to acknowledge, reported by [`Producer::flush`]'s
+/// delivery callbacks
+#[cfg(feature = "kafka")]
+#[derive(Default)]
+struct KafkaDelivery {
+    failed: AtomicUsize,
+}
+
+#[cfg(feature = "kafka")]
+impl ClientContext for KafkaDelivery {}
+
+#[cfg(feature = "kafka")]
+impl ProducerContext for KafkaDelivery {
+    type DeliveryOpaque = ();
+
+    fn delivery(&self, result: &DeliveryResult<'_>, _: ()) {
+        if let Err((e, _)) = result {
+            warn!("Kafka failed to deliver an origin record: {}", e);
+            self.failed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Producer to the comma-separated `brokers`, giving up on records not acknowledged
+/// within [`KAFKA_DELIVERY_TIMEOUT`]
+#[cfg(feature = "kafka")]
+fn kafka_producer(brokers: &str) -> Result<BaseProducer<KafkaDelivery>, io::Error> {
+    ClientConfig::new()
+        .set("bootstrap.servers", brokers)
+        .set("message.timeout.ms", KAFKA_DELIVERY_TIMEOUT.as_millis().to_string())
+        .create_with_context(KafkaDelivery::default())
+        .map_err(io::Error::other)
+}
+
+/// Path of the file, but not the connection string which may hold a password
+impl fmt::Display for Destination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Destination::File { path, .. } => write!(f, "{:?}", path),
+            #[cfg(feature = "postgres")]
+            Destination::Postgres { .. } => write!(f, "Postgres"),
+            #[cfg(feature = "kafka")]
+            Destination::Kafka { topic, .. } => write!(f, "Kafka topic {}", topic),
+        }
+    }
+}
+
+/// Body of the writer thread of an [`OriginExport`]: write the batches of `receiver` until
+/// it is closed, returning how many records were written
+fn write_batches(mut destination: Destination, receiver: Receiver<Batch>, failed: &AtomicBool) -> Result<usize, io::Error> {
+    let mut published = 0;
+    for batch in receiver {
+        if let Err(e) = write_with_retries(&mut destination, &batch.text) {
+            failed.store(true, Ordering::Relaxed);
+            return Err(e);
+        }
+        published += batch.records;
+    }
+    Ok(published)
+}
+
+/// Write `batch` to `destination`, retrying on failure from the first byte not written
+/// yet, so that a partial write is neither duplicated nor truncated
+fn write_with_retries(destination: &mut Destination, batch: &str) -> Result<(), io::Error> {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+    let mut written = 0;
+    loop {
+        let Err(e) = destination.write(batch, &mut written) else {
+            return Ok(());
+        };
+        if attempt == MAX_ATTEMPTS {
+            return Err(e);
+        }
+        warn!(
+            "Failed to export origins to {} after {} of {} bytes (attempt {}/{}): {}, retrying in {:?}",
+            destination,
+            written,
+            batch.len(),
+            attempt,
+            MAX_ATTEMPTS,
+            e,
+            backoff
+        );
+        thread::sleep(backoff);
+        backoff *= 2;
+        attempt += 1;
+        destination.reopen();
+    }
+}
+
+/// Write `bytes` from `*written` on, counting the bytes written in `written` even on failure
+fn write_from(writer: &mut impl Write, bytes: &[u8], written: &mut usize) -> Result<(), io::Error> {
+    while *written < bytes.len() {
+        match writer.write(&bytes[*written..]) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => *written += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// SQL string literal, Postgres text not holding NUL bytes
+fn sql_string(value: &str) -> String {
+    format!("'{}'", value.replace('\0', "").replace('\'', "''"))
+}
+
+fn sql_number(value: Option<impl ToString>) -> String {
+    value.map_or("NULL".to_string(), |value| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writer accepting at most 3 bytes at a time, failing every other call
+    #[derive(Default)]
+    struct Flaky {
+        written: Vec<u8>,
+        calls: usize,
+    }
+
+    impl Write for Flaky {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.calls += 1;
+            if self.calls.is_multiple_of(2) {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            let n = buf.len().min(3);
+            self.written.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn partial_writes_resume() {
+        let bytes = b"{\"id\":1}\n{\"id\":2}\n";
+        let mut writer = Flaky::default();
+        let mut written = 0;
+        while write_from(&mut writer, bytes, &mut written).is_err() {
+            assert_eq!(writer.written.len(), written);
+        }
+        assert_eq!(writer.written, bytes);
+    }
+
+    #[test]
+    fn kafka_destinations() {
+        let open = |path: &str, format| Destination::open(Path::new(path), format).map(|_| ()).unwrap_err().kind();
+        assert_eq!(open("kafka://localhost:9092", ExportFormat::Json), io::ErrorKind::InvalidInput);
+        assert_eq!(open("kafka:///origins", ExportFormat::Json), io::ErrorKind::InvalidInput);
+        assert_eq!(open("kafka://localhost:9092/origins", ExportFormat::Postgres), io::ErrorKind::InvalidInput);
+        #[cfg(not(feature = "kafka"))]
+        assert_eq!(open("kafka://localhost:9092/origins", ExportFormat::Json), io::ErrorKind::Unsupported);
+    }
+}
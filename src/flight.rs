@@ -0,0 +1,193 @@
+// `Status` and `FlightError` are large, but they are what the Flight service returns
+#![allow(clippy::result_large_err)]
+
+use arrow_array::RecordBatch;
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::error::FlightError;
+use arrow_flight::flight_service_server::{FlightService, FlightServiceServer};
+use arrow_flight::{
+    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo, HandshakeRequest,
+    HandshakeResponse, PollInfo, PutResult, SchemaResult, Ticket,
+};
+use arrow_schema::SchemaRef;
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+use tonic::{Request, Response, Status, Streaming};
+use tracing::error;
+
+use crate::backend::GraphBackend;
+use crate::error::GraphError;
+use crate::graph::Graph;
+use crate::origin_table::{record_batch, BATCH_ROWS};
+use crate::progress::LoadProgress;
+
+pub use crate::origin_table::schema;
+
+/// Only flight served, as its ticket and descriptor path
+const ORIGINS: &str = "origins";
+
+/// Arrow Flight service streaming the origin table, for clients fetching every origin at
+/// once: record batches cost a fraction of what `/origins/*` JSON does to encode and
+/// decode.
+///
+/// Serves a single flight, `origins`, with the columns of [`schema`] and one row per
+/// loaded origin, metrics not computed yet being null. Answers `UNAVAILABLE` until the
+/// origins are loaded, like the HTTP endpoints answer 503.
+pub struct OriginsFlight<G>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    graph: Arc<RwLock<Graph<G>>>,
+    load_progress: Arc<LoadProgress>,
+    schema: SchemaRef,
+}
+
+impl<G> OriginsFlight<G>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    pub fn new(graph: Arc<RwLock<Graph<G>>>, load_progress: Arc<LoadProgress>) -> Self {
+        OriginsFlight { graph, load_progress, schema: Arc::new(schema()) }
+    }
+
+    /// Serve on `address` until `shutdown` is cancelled
+    pub async fn serve(self, address: SocketAddr, shutdown: CancellationToken) -> Result<(), GraphError> {
+        tonic::transport::Server::builder()
+            .add_service(FlightServiceServer::new(self))
+            .serve_with_shutdown(address, shutdown.cancelled_owned())
+            .await
+            .map_err(|e| GraphError::GraphLoad(e.into()))
+    }
+
+    fn require_ready(&self) -> Result<(), Status> {
+        if self.load_progress.is_ready() {
+            Ok(())
+        } else {
+            Err(Status::unavailable(format!("origins are loading: {}", self.load_progress.to_json())))
+        }
+    }
+
+    async fn flight_info(&self) -> Result<FlightInfo, Status> {
+        self.require_ready()?;
+        let rows = self.graph.read().await.loaded_origins().len();
+        let info = FlightInfo::new()
+            .try_with_schema(&self.schema)
+            .map_err(|e| Status::internal(e.to_string()))?
+            .with_descriptor(FlightDescriptor::new_path(vec![ORIGINS.to_string()]))
+            .with_endpoint(FlightEndpoint::new().with_ticket(Ticket::new(ORIGINS)))
+            .with_total_records(rows as i64);
+        Ok(info)
+    }
+
+    /// Record batches of the origins from `start` on, empty once past the last one
+    async fn batch(graph: Arc<RwLock<Graph<G>>>, schema: SchemaRef, start: usize) -> Result<Option<RecordBatch>, FlightError> {
+        let graph = graph.read_owned().await;
+        tokio::task::spawn_blocking(move || {
+            let origins = graph.loaded_origins();
+            if start >= origins.len() {
+                return Ok(None);
+            }
+            // Each batch is copied under a short read lock, see `do_get`
+            let origins = &origins[start..origins.len().min(start + BATCH_ROWS)];
+            Ok(Some(record_batch(schema, origins)?))
+        })
+        .await
+        .map_err(|e| {
+            error!("Flight batch task failed: {}", e);
+            FlightError::ExternalError(Box::new(e))
+        })?
+    }
+}
+
+/// Requests for anything but the `origins` flight
+fn unknown_flight(name: impl std::fmt::Debug) -> Status {
+    Status::not_found(format!("unknown flight {:?}, only {:?} is served", name, ORIGINS))
+}
+
+#[tonic::async_trait]
+impl<G> FlightService for OriginsFlight<G>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    type HandshakeStream = BoxStream<'static, Result<HandshakeResponse, Status>>;
+    type ListFlightsStream = BoxStream<'static, Result<FlightInfo, Status>>;
+    type DoGetStream = BoxStream<'static, Result<FlightData, Status>>;
+    type DoPutStream = BoxStream<'static, Result<PutResult, Status>>;
+    type DoActionStream = BoxStream<'static, Result<arrow_flight::Result, Status>>;
+    type ListActionsStream = BoxStream<'static, Result<ActionType, Status>>;
+    type DoExchangeStream = BoxStream<'static, Result<FlightData, Status>>;
+
+    async fn handshake(&self, _request: Request<Streaming<HandshakeRequest>>) -> Result<Response<Self::HandshakeStream>, Status> {
+        Err(Status::unimplemented("no authentication"))
+    }
+
+    async fn list_flights(&self, _request: Request<Criteria>) -> Result<Response<Self::ListFlightsStream>, Status> {
+        let info = self.flight_info().await?;
+        Ok(Response::new(stream::once(async { Ok(info) }).boxed()))
+    }
+
+    async fn get_flight_info(&self, request: Request<FlightDescriptor>) -> Result<Response<FlightInfo>, Status> {
+        let descriptor = request.into_inner();
+        if descriptor.path != [ORIGINS] {
+            return Err(unknown_flight(descriptor.path));
+        }
+        Ok(Response::new(self.flight_info().await?))
+    }
+
+    async fn poll_flight_info(&self, _request: Request<FlightDescriptor>) -> Result<Response<PollInfo>, Status> {
+        Err(Status::unimplemented("the origins flight is ready at once, use GetFlightInfo"))
+    }
+
+    async fn get_schema(&self, request: Request<FlightDescriptor>) -> Result<Response<SchemaResult>, Status> {
+        let descriptor = request.into_inner();
+        if descriptor.path != [ORIGINS] {
+            return Err(unknown_flight(descriptor.path));
+        }
+        // Both carry the schema as an IPC message
+        let info = FlightInfo::new().try_with_schema(&self.schema).map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(SchemaResult { schema: info.schema }))
+    }
+
+    async fn do_get(&self, request: Request<Ticket>) -> Result<Response<Self::DoGetStream>, Status> {
+        let ticket = request.into_inner().ticket;
+        if ticket != ORIGINS.as_bytes() {
+            return Err(unknown_flight(String::from_utf8_lossy(&ticket)));
+        }
+        self.require_ready()?;
+        let graph = self.graph.clone();
+        let schema = self.schema.clone();
+        // The lock is taken again for every batch, so that recomputations writing to the
+        // graph are not held up for the whole transfer
+        let batches = stream::try_unfold(0, move |start| {
+            let (graph, schema) = (graph.clone(), schema.clone());
+            async move {
+                let batch = Self::batch(graph, schema, start).await?;
+                Ok(batch.map(|batch| (batch, start + BATCH_ROWS)))
+            }
+        });
+        let flight_data = FlightDataEncoderBuilder::new()
+            .with_schema(self.schema.clone())
+            .build(batches)
+            .map_err(Status::from);
+        Ok(Response::new(flight_data.boxed()))
+    }
+
+    async fn do_put(&self, _request: Request<Streaming<FlightData>>) -> Result<Response<Self::DoPutStream>, Status> {
+        Err(Status::unimplemented("the origins flight is read-only"))
+    }
+
+    async fn do_action(&self, _request: Request<Action>) -> Result<Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented("no actions"))
+    }
+
+    async fn list_actions(&self, _request: Request<Empty>) -> Result<Response<Self::ListActionsStream>, Status> {
+        Ok(Response::new(stream::empty().boxed()))
+    }
+
+    async fn do_exchange(&self, _request: Request<Streaming<FlightData>>) -> Result<Response<Self::DoExchangeStream>, Status> {
+        Err(Status::unimplemented("the origins flight is read-only"))
+    }
+}
@@ -0,0 +1,254 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::backend::GraphBackend;
+use crate::error::GraphError;
+use crate::graph::Graph;
+
+/// Directory of the frozen datasets, in the cache directory
+pub const FROZEN_DIR: &str = "frozen";
+
+/// Description of a frozen dataset, next to its files
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Every loaded origin with its metrics, one JSON object per line
+const ORIGINS_FILE: &str = "origins.jsonl";
+
+/// Ids of the eligible origins, one per line
+const ELIGIBLE_FILE: &str = "origins_eligible.txt";
+
+/// Longest dataset name, in bytes
+const MAX_NAME_LEN: usize = 128;
+
+/// Package name and version of this code, recorded with the metrics it serves
+pub const CODE_VERSION: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+
+/// Graph export the origins were computed from
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GraphExport {
+    /// Base path of the compressed graph
+    pub path: String,
+    /// `export_id` of the export metadata (`meta/export.json` next to the compressed
+    /// graph), if there is one
+    pub export_id: Option<String>,
+}
+
+impl GraphExport {
+    pub fn describe(graph_path: &str) -> Self {
+        let metadata = Path::new(graph_path).parent().map(|dir| dir.join("meta").join("export.json"));
+        let export_id = metadata
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|metadata| serde_json::from_str::<Value>(&metadata).ok())
+            .and_then(|metadata| metadata.get("export_id")?.as_str().map(str::to_string));
+        GraphExport { path: graph_path.to_string(), export_id }
+    }
+}
+
+/// File of a frozen dataset
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FrozenFile {
+    pub name: String,
+    /// Size in bytes
+    pub size: u64,
+    /// Hex encoded SHA-256 of the contents
+    pub sha256: String,
+}
+
+/// What a frozen dataset was derived from and how, to cite it and check its files
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Manifest {
+    pub name: String,
+    /// RFC 3339
+    pub created_at: String,
+    pub graph: GraphExport,
+    /// Package name and version of the code which computed the metrics
+    pub code_version: String,
+    /// Options deciding which origins are loaded and eligible
+    pub compute_options: Value,
+    pub origin_count: usize,
+    pub eligible_count: usize,
+    pub files: Vec<FrozenFile>,
+}
+
+/// Immutable snapshots of the origin table, each in its own directory with a
+/// [`Manifest`], so that datasets derived from the metrics can be cited and retrieved
+/// as they were.
+///
+/// A dataset is written to a temporary directory renamed once complete, then its files
+/// are made read-only. Existing datasets are never overwritten.
+pub struct FrozenDatasets {
+    dir: PathBuf,
+    graph: GraphExport,
+    compute_options: Value,
+    /// Held while freezing, so that two datasets don't race for the same name
+    freezing: Mutex<()>,
+}
+
+impl FrozenDatasets {
+    pub fn new(dir: PathBuf, graph: GraphExport, compute_options: Value) -> Self {
+        FrozenDatasets { dir, graph, compute_options, freezing: Mutex::new(()) }
+    }
+
+    /// Check that `name` can name a dataset: 1 to [`MAX_NAME_LEN`] ASCII letters, digits,
+    /// `-`, `_` or `.`, not starting with `.`
+    pub fn validate_name(name: &str) -> Result<(), String> {
+        let valid = !name.is_empty()
+            && name.len() <= MAX_NAME_LEN
+            && !name.starts_with('.')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if valid {
+            Ok(())
+        } else {
+            Err(format!(
+                "invalid dataset name {:?}: 1 to {} ASCII letters, digits, '-', '_' or '.', not starting with '.'",
+                name, MAX_NAME_LEN
+            ))
+        }
+    }
+
+    /// Freeze the origins of `graph` as dataset `name`, by default named after the current
+    /// time (e.g. `20240305T140712Z`), which must be [valid](Self::validate_name). Fails
+    /// with [`io::ErrorKind::AlreadyExists`] if the name is taken.
+    pub fn freeze<G>(&self, graph: &Graph<G>, name: Option<&str>) -> Result<Manifest, GraphError>
+    where
+        G: GraphBackend + Send + Sync + 'static,
+    {
+        // A freeze which panicked left at most its temporary directory, removed below
+        let _freezing = self.freezing.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let now = Utc::now();
+        let name = name.map_or_else(|| now.format("%Y%m%dT%H%M%SZ").to_string(), str::to_string);
+        let target = self.dir.join(&name);
+        if target.exists() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("dataset {:?} already exists", name)).into());
+        }
+        let tmp = self.dir.join(format!(".{}.tmp", name));
+        if tmp.exists() {
+            // Left by a freeze which failed midway
+            fs::remove_dir_all(&tmp)?;
+        }
+        fs::create_dir_all(&tmp)?;
+
+        let origins = graph.loaded_origins();
+        let mut writer = ChecksumWriter::create(&tmp.join(ORIGINS_FILE))?;
+        for origin in origins {
+            serde_json::to_writer(&mut writer, &origin.to_data()).map_err(GraphError::serialization)?;
+            writer.write_all(b"\n")?;
+        }
+        let mut files = vec![writer.finish(ORIGINS_FILE)?];
+        let mut writer = ChecksumWriter::create(&tmp.join(ELIGIBLE_FILE))?;
+        let mut eligible_count = 0;
+        for origin in origins.iter().filter(|origin| graph.is_listed(origin.id) == Some(true)) {
+            writeln!(writer, "{}", origin.id)?;
+            eligible_count += 1;
+        }
+        files.push(writer.finish(ELIGIBLE_FILE)?);
+
+        let manifest = Manifest {
+            name: name.clone(),
+            created_at: now.to_rfc3339(),
+            graph: self.graph.clone(),
+            code_version: CODE_VERSION.to_string(),
+            compute_options: self.compute_options.clone(),
+            origin_count: origins.len(),
+            eligible_count,
+            files,
+        };
+        let contents = serde_json::to_vec_pretty(&manifest).map_err(GraphError::serialization)?;
+        fs::write(tmp.join(MANIFEST_FILE), contents)?;
+        for file in [ORIGINS_FILE, ELIGIBLE_FILE, MANIFEST_FILE] {
+            let path = tmp.join(file);
+            let mut permissions = fs::metadata(&path)?.permissions();
+            permissions.set_readonly(true);
+            fs::set_permissions(&path, permissions)?;
+        }
+        fs::rename(&tmp, &target)?;
+        Ok(manifest)
+    }
+
+    /// Manifests of the frozen datasets, oldest first
+    pub fn list(&self) -> Result<Vec<Manifest>, GraphError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut manifests = Vec::new();
+        for entry in entries {
+            let name = entry?.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            if Self::validate_name(name).is_ok() {
+                if let Some(manifest) = self.manifest(name)? {
+                    manifests.push(manifest);
+                }
+            }
+        }
+        manifests.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        Ok(manifests)
+    }
+
+    /// Manifest of dataset `name`, `None` if there is no such dataset
+    pub fn manifest(&self, name: &str) -> Result<Option<Manifest>, GraphError> {
+        if Self::validate_name(name).is_err() {
+            return Ok(None);
+        }
+        let path = self.dir.join(name).join(MANIFEST_FILE);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let manifest = serde_json::from_str(&contents)
+            .map_err(|e| GraphError::CacheCorrupted { path, reason: e.to_string() })?;
+        Ok(Some(manifest))
+    }
+
+    /// Path of `file` in dataset `name`, if it is one of its files or its manifest
+    pub fn file_path(&self, name: &str, file: &str) -> Result<Option<PathBuf>, GraphError> {
+        let Some(manifest) = self.manifest(name)? else {
+            return Ok(None);
+        };
+        let listed = file == MANIFEST_FILE || manifest.files.iter().any(|frozen| frozen.name == file);
+        Ok(listed.then(|| self.dir.join(name).join(file)))
+    }
+}
+
+/// Buffered file writer keeping the size and SHA-256 of what it wrote
+struct ChecksumWriter {
+    writer: BufWriter<File>,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl ChecksumWriter {
+    fn create(path: &Path) -> io::Result<Self> {
+        Ok(ChecksumWriter { writer: BufWriter::new(File::create(path)?), hasher: Sha256::new(), size: 0 })
+    }
+
+    fn finish(mut self, name: &str) -> io::Result<FrozenFile> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_all()?;
+        let sha256 = self.hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect();
+        Ok(FrozenFile { name: name.to_string(), size: self.size, sha256 })
+    }
+}
+
+impl Write for ChecksumWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
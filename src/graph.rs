@@ -1,214 +1,933 @@
-use std::path::PathBuf;
+use std::collections::hash_map::DefaultHasher;
+use std::convert::Infallible;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
 use std::fs::{self, File};
-use std::io::{BufReader, BufWriter};
-use std::sync::Arc;
-use swh_graph::properties::{self};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use swh_graph::{graph::*, NodeType };
-use crate::utils::filter_by_node_type;
-use crate::origin::{Origin, OriginData};
-use serde_json;
-use indicatif::{ProgressBar, ProgressStyle};
-use rayon::prelude::*;
-use rand::seq::{IndexedRandom, SliceRandom};
+use crate::backend::{BackwardCapable, GraphBackend, LabelCapable};
+use crate::error::GraphError;
+use crate::export::OriginExport;
+use crate::labels::{snapshot_branches, Branch};
+use crate::memory::{resident_set_size, MemoryUsage};
+use crate::utils::{bfs, bfs_backward, filter_by_node_type, load_or_build_node_type_index, read_node_ids, write_node_ids, TraversalEnd, TraversalOptions, Visit};
+use crate::origin::{skip_over_budget, AsOfMetrics, ComputePolicy, Eligibility, Exclusion, LegacyOriginData, Metric, Metrics, Origin, OriginData, OriginFilter};
+use crate::parallel::*;
+use crate::quantiles::MetricDigests;
+use crate::progress::{LoadProgress, LoadStage, Progress, ProgressBar, ProgressSink, ProgressStyle};
+use crate::origin_list::OriginList;
+use crate::urls::{canonical_url, host};
+use rand::seq::IndexedRandom;
+use serde_json::{json, Value};
+use chrono::{DateTime, Datelike};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+#[cfg(feature = "sql")]
+use arrow_array::RecordBatch;
 
+/// Format of the origins cache
 #[derive(Clone, Copy, Debug)]
 pub enum SerializationFormat {
     Json,
-    Bincode,
+    /// Chunks of CBOR-encoded origins in `origins.bin`, starting with the `SWHORIGC` magic.
+    ///
+    /// Formerly `Bincode`: caches written in the bincode layout of the first versions are
+    /// still read, and rewritten in this layout the next time the cache is saved.
+    /// `"bincode"` is still accepted as its name.
+    Compact,
 }
 
+impl std::str::FromStr for SerializationFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "json" => Ok(SerializationFormat::Json),
+            "compact" | "bincode" => Ok(SerializationFormat::Compact),
+            _ => Err(format!("unknown cache format {:?}, expected compact or json", name)),
+        }
+    }
+}
 
 pub struct Graph<G>
 where
-    G: SwhLabeledForwardGraph 
-    + SwhGraphWithProperties<
-        Maps: properties::Maps,
-        Timestamps: properties::Timestamps,
-        Persons: properties::Persons,
-        Contents: properties::Contents,
-        Strings: properties::Strings,
-        LabelNames: properties::LabelNames,
-    > + Send + Sync,
+    G: GraphBackend + Send + Sync,
 {
     graph: Arc<G>,
-    #[allow(dead_code)]
-    base_path: PathBuf,
+    /// Directory holding the cache and its sidecar files
+    cache_dir: PathBuf,
     origins_cache_file: PathBuf,
     origins: Option<Vec<Origin<G>>>,
+    /// Sidecar file holding the ids of the origins served by `/origins`
+    eligible_origins_file: PathBuf,
+    eligible_origins: Option<Vec<NodeId>>,
+    /// Which origins are kept, and which of them are listed in `eligible_origins`
+    filter: OriginFilter,
+    /// Whether origin discovery reads/writes a persisted node-type index
+    use_node_type_index: bool,
+    /// Number of origins with metrics computed since the cache was last saved
+    unsaved_origins: AtomicUsize,
+    /// Number of loaded origins whose metrics are all known, see [`Self::origin_counts`]
+    complete_origins: AtomicUsize,
+    /// Number of cache records set aside by the last load, see [`QUARANTINE_FILE`]
+    quarantined_origins: usize,
+    /// Bumped whenever metrics change, telling whether `date_index` is still up to date
+    metrics_generation: AtomicU64,
+    /// Bumped whenever the loaded origins are replaced, see [`Self::set_origins`]
+    origins_generation: u64,
+    /// Loaded origins with a known latest commit date sorted by it, see
+    /// [`Self::committed_between`]
+    date_index: Memo<Vec<(i64, NodeId)>>,
+    /// Quantile estimators of the metrics, see [`Self::metric_digests`]
+    metric_digests: Memo<MetricDigests>,
+    /// Origins by forge and bucket of each metric, in the order of [`FACET_METRICS`], see
+    /// [`Self::forge_facets`]
+    forge_facets: [Memo<ForgeFacets>; 3],
+    /// Origins sharing their canonical URL, see [`Self::url_duplicates`]
+    url_duplicates: Memo<UrlDuplicates>,
+    /// Record batches of the loaded origins, see [`Self::origin_table`]
+    #[cfg(feature = "sql")]
+    origin_table: Memo<Vec<RecordBatch>>,
+    /// Where loading the origins is at, for callers watching from another thread
+    load_progress: Arc<LoadProgress>,
+    /// Stops computations over all origins (discovery, streaming compute) when cancelled
+    cancel: CancellationToken,
     serialization_format: SerializationFormat,
+    /// Whether JSON caches are indented, see [`GraphBuilder::pretty_json`]
+    pretty_json: bool,
+    /// If set, only these origins are computed and loaded
+    origin_list: Option<OriginList>,
+    /// Modification time of the origins cache when this process last saved it, to tell
+    /// its own writes from those of other processes
+    cache_saved_at: Mutex<Option<SystemTime>>,
 } 
 
-impl <G> Graph<G>
+/// Options of a [`Graph`], see [`Graph::builder`]
+pub struct GraphBuilder<G> {
+    data_path: PathBuf,
+    cache_dir: Option<PathBuf>,
+    serialization_format: SerializationFormat,
+    pretty_json: bool,
+    use_node_type_index: bool,
+    load_progress: Option<Arc<LoadProgress>>,
+    cancel: Option<CancellationToken>,
+    filter: OriginFilter,
+    origin_list: Option<OriginList>,
+    graph: PhantomData<fn() -> G>,
+}
+
+impl<G> GraphBuilder<G>
 where
-    G: SwhLabeledForwardGraph 
-    + SwhGraphWithProperties<
-        Maps: properties::Maps,
-        Timestamps: properties::Timestamps,
-        Persons: properties::Persons,
-        Contents: properties::Contents,
-        Strings: properties::Strings,
-        LabelNames: properties::LabelNames,
-    > + Send + Sync,
-{  
-    pub fn new<P: Into<PathBuf>>(graph_path: P, graph: G) -> Self {
-        Self::with_serialization_format(graph_path, graph, SerializationFormat::Json)
+    G: GraphBackend + Send + Sync,
+{
+    /// Format of the origins cache (JSON by default)
+    pub fn serialization(mut self, format: SerializationFormat) -> Self {
+        self.serialization_format = format;
+        self
     }
-    
-    pub fn with_serialization_format<P: Into<PathBuf>>(
-        graph_path: P, 
-        graph: G, 
-        format: SerializationFormat
-    ) -> Self {
-        let base_path: PathBuf = graph_path.into();
-
-        let mut origins_cache_file = base_path.clone();
-        let extension = match format {
+
+    /// Indent JSON caches, one field per line, for humans reading them. Compact by default,
+    /// pretty caches being about three times larger.
+    pub fn pretty_json(mut self, pretty: bool) -> Self {
+        self.pretty_json = pretty;
+        self
+    }
+
+    /// Keep the cache and its sidecar files in `dir` instead of next to the data path
+    pub fn cache_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Persist the ids of origin nodes in the cache directory the first time they are
+    /// discovered, so later runs skip the full node scan
+    pub fn node_type_index(mut self, enabled: bool) -> Self {
+        self.use_node_type_index = enabled;
+        self
+    }
+
+    /// Report origins loading to `progress`, e.g. to continue the graph loading progress
+    pub fn load_progress(mut self, progress: Arc<LoadProgress>) -> Self {
+        self.load_progress = Some(progress);
+        self
+    }
+
+    /// Stop computations over all origins when `cancel` gets cancelled, e.g. on shutdown
+    pub fn cancellation_token(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Which origins are kept, both when computing origins and when loading them from the
+    /// cache, and which of them are listed by `/origins` (see
+    /// [`Graph::get_eligible_origin_ids`]). By default, origins with a snapshot are kept,
+    /// and those with commits and a latest commit date listed.
+    pub fn filter(mut self, filter: OriginFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Only keep the listed origins, both when computing origins and when loading them
+    /// from the cache
+    pub fn origin_list(mut self, list: OriginList) -> Self {
+        self.origin_list = Some(list);
+        self
+    }
+
+    pub fn build(self, graph: G) -> Graph<G> {
+        let cache_dir = self.cache_dir.unwrap_or_else(|| {
+            self.data_path
+                .parent()
+                .map(PathBuf::from)
+                .unwrap_or_default()
+        });
+        let origins_cache_file = cache_dir.join(match self.serialization_format {
             SerializationFormat::Json => "origins.json",
-            SerializationFormat::Bincode => "origins.bin",
-        };
-        origins_cache_file.set_file_name(extension);
+            SerializationFormat::Compact => "origins.bin",
+        });
+        let eligible_origins_file = cache_dir.join(self.filter.eligibility.file_name());
 
         Graph {
             graph: Arc::new(graph),
-            base_path,
+            cache_dir,
             origins_cache_file,
             origins: None,
-            serialization_format: format,
+            eligible_origins_file,
+            eligible_origins: None,
+            filter: self.filter,
+            use_node_type_index: self.use_node_type_index,
+            unsaved_origins: AtomicUsize::new(0),
+            quarantined_origins: 0,
+            complete_origins: AtomicUsize::new(0),
+            metrics_generation: AtomicU64::new(0),
+            origins_generation: 0,
+            date_index: Mutex::new(None),
+            metric_digests: Mutex::new(None),
+            forge_facets: Default::default(),
+            url_duplicates: Mutex::new(None),
+            #[cfg(feature = "sql")]
+            origin_table: Mutex::new(None),
+            load_progress: self.load_progress.unwrap_or_default(),
+            cancel: self.cancel.unwrap_or_default(),
+            serialization_format: self.serialization_format,
+            pretty_json: self.pretty_json,
+            origin_list: self.origin_list,
+            cache_saved_at: Mutex::new(None),
         }
     }
-    
+}
+
+impl <G> Graph<G>
+where
+    G: GraphBackend + Send + Sync,
+{  
+    /// Start configuring a graph whose cache files live next to `data_path`
+    /// (e.g. `./data` puts them in `./`)
+    pub fn builder<P: Into<PathBuf>>(data_path: P) -> GraphBuilder<G> {
+        GraphBuilder {
+            data_path: data_path.into(),
+            cache_dir: None,
+            serialization_format: SerializationFormat::Json,
+            pretty_json: false,
+            use_node_type_index: false,
+            load_progress: None,
+            cancel: None,
+            filter: OriginFilter::default(),
+            origin_list: None,
+            graph: PhantomData,
+        }
+    }
+
+    /// Progress of loading (or computing) the origins, updated as they get loaded
+    pub fn load_progress(&self) -> Arc<LoadProgress> {
+        self.load_progress.clone()
+    }
+
+    /// Path of the file `name` in the cache directory, for analyses persisted alongside
+    /// the origins cache
+    pub fn cache_path(&self, name: &str) -> PathBuf {
+        self.cache_dir.join(name)
+    }
+
+    /// Get ids of all origin nodes of the graph, using the node-type index if enabled
+    fn origin_node_ids(&self) -> Vec<NodeId> {
+        if self.use_node_type_index {
+            let index_file = self.cache_dir.join(format!("nodes_{}.txt", NodeType::Origin.to_str()));
+            match load_or_build_node_type_index(self.graph.as_ref(), NodeType::Origin, &index_file) {
+                Ok(ids) => return ids,
+                Err(e) => warn!("Failed to use node type index {:?}: {}", index_file, e),
+            }
+        }
+        filter_by_node_type(self.graph.as_ref(), NodeType::Origin)
+    }
+
     /// Get graph statistics
     pub fn stats(&self) -> (usize, usize) {
         (self.graph.num_nodes(), self.graph.num_arcs().try_into().unwrap())
     }
     
     
+    /// Estimated memory used by the graph statistics, origins and eligible ids in memory
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let (nodes, arcs) = self.stats();
+        let (origins, origins_bytes) = self.origins.as_ref().map_or((0, 0), |origins| {
+            (origins.len(), origins.capacity() * std::mem::size_of::<Origin<G>>())
+        });
+        MemoryUsage {
+            rss_bytes: resident_set_size(),
+            nodes,
+            arcs,
+            origins,
+            origins_bytes,
+            urls_bytes: self.par_origins_iter().map(Origin::heap_size).sum(),
+            eligible_origins_bytes: self
+                .eligible_origins
+                .as_ref()
+                .map_or(0, |ids| ids.capacity() * std::mem::size_of::<NodeId>()),
+        }
+    }
+
     /// Get origins, automatically loading if not already loaded
     /// Returns a reference to the Vec of Origin objects
-    #[allow(dead_code)]
-    pub fn get_origins(&mut self) -> Result<&Vec<Origin<G>>, std::io::Error> {
+    pub fn get_origins(&mut self) -> Result<&Vec<Origin<G>>, GraphError> {
         if self.origins.is_none() {
             self.load_or_compute_origins();
         }
-        Ok(self.origins.as_ref().unwrap())
+        self.origins.as_ref().ok_or(GraphError::Cancelled)
     }
     
-     pub fn get_origins_mut(&mut self) -> Result<&mut Vec<Origin<G>>, std::io::Error> {
+     pub fn get_origins_mut(&mut self) -> Result<&mut Vec<Origin<G>>, GraphError> {
         if self.origins.is_none() {
            self.load_or_compute_origins();
         }
-        Ok(self.origins.as_mut().unwrap())
+        self.origins.as_mut().ok_or(GraphError::Cancelled)
     }
     
+    /// Shared handle on the underlying compressed graph
+    pub fn inner_graph(&self) -> Arc<G> {
+        self.graph.clone()
+    }
+
+    /// Position of the origin with the given id in the loaded origins.
+    ///
+    /// Origins are computed from node ids in increasing order and saved in that
+    /// order, so they are kept sorted by id.
+    fn origin_position(&self, id: usize) -> Option<usize> {
+        self.origins.as_ref()?.binary_search_by_key(&id, |o| o.id).ok()
+    }
+
+    /// Origins currently in memory, without loading them if they are not yet
+    pub fn loaded_origins(&self) -> &[Origin<G>] {
+        self.origins.as_deref().unwrap_or_default()
+    }
+
+    /// Iterate over the loaded origins by increasing id, read-only
+    pub fn origins_iter(&self) -> impl ExactSizeIterator<Item = &Origin<G>> + DoubleEndedIterator + '_ {
+        self.loaded_origins().iter()
+    }
+
+    /// Parallel counterpart of `origins_iter`
+    pub fn par_origins_iter(&self) -> SliceIter<'_, Origin<G>> {
+        self.loaded_origins().par_iter()
+    }
+
+    /// Drop the URLs of the loaded origins, read from the graph again when needed, e.g. to
+    /// free memory. The next caches are written without them. Returns the bytes freed.
+    pub fn drop_urls(&mut self) -> usize {
+        let origins = self.origins.as_deref_mut().unwrap_or_default();
+        let before: usize = origins.iter().map(Origin::heap_size).sum();
+        origins.iter_mut().for_each(Origin::forget_url);
+        before - origins.iter().map(Origin::heap_size).sum::<usize>()
+    }
+
+    /// Get the origin with the given id among the loaded origins, checking that it has a
+    /// graph to compute its metrics on
+    pub fn origin(&self, id: usize) -> Result<&Origin<G>, GraphError> {
+        let origin = self.find_origin(id).ok_or(GraphError::OriginNotFound(id))?;
+        origin.get_graph()?;
+        Ok(origin)
+    }
+
+    /// Get the origin with the given id, without loading the origins if they are not yet
+    pub fn find_origin(&self, id: usize) -> Option<&Origin<G>> {
+        let position = self.origin_position(id)?;
+        self.origins.as_ref().map(|origins| &origins[position])
+    }
+
+    /// Copy of the origin with the given id, detached from the origins list so its metrics
+    /// can be computed without borrowing `self`. With `force`, cached metrics are dropped.
+    pub fn detached_origin(&self, id: usize, force: bool) -> Option<Origin<G>> {
+        let origin = self.find_origin(id)?;
+        if force {
+            let mut fresh = Origin::new(id, self.graph.clone());
+            fresh.set_url(origin.cached_url());
+            Some(fresh)
+        } else {
+            Some(Origin::from_data(origin.to_data(), self.graph.clone()))
+        }
+    }
+
+    /// Replace the stored origin having the same id as `origin`, typically after its
+    /// metrics were computed on a [detached copy](Self::detached_origin).
+    ///
+    /// Returns `false` if there is no such origin.
+    pub fn store_origin(&mut self, origin: Origin<G>) -> bool {
+        let Some(position) = self.origin_position(origin.id) else {
+            return false;
+        };
+        if let Some(origins) = self.origins.as_mut() {
+            let completed = origin.has_computed_metrics() as usize;
+            let replaced = std::mem::replace(&mut origins[position], origin);
+            let uncompleted = replaced.has_computed_metrics() as usize;
+            self.complete_origins.fetch_add(completed, Ordering::Relaxed);
+            self.complete_origins.fetch_sub(uncompleted, Ordering::Relaxed);
+        }
+        self.mark_origins_modified(1);
+        true
+    }
+
+    /// Ids of all loaded origins
+    pub fn origin_ids(&self) -> Vec<usize> {
+        self.origins
+            .as_ref()
+            .map_or_else(Vec::new, |origins| origins.iter().map(|o| o.id).collect())
+    }
+
+    /// Ids of the loaded origins missing some metric, never computed or whose computation
+    /// was cancelled
+    pub fn stale_origin_ids(&self) -> Vec<usize> {
+        self.origins.as_ref().map_or_else(Vec::new, |origins| {
+            origins.iter().filter(|o| !o.has_computed_metrics()).map(|o| o.id).collect()
+        })
+    }
+
+    /// Run `f` on the origin with the given id, or return `None` if there is no such origin
+    /// or the origins are not loaded.
+    ///
+    /// Metrics computed by `f` are kept in memory and the origin is counted as unsaved,
+    /// see [`Self::write_back`].
+    pub fn with_origin<T>(&self, id: usize, f: impl FnOnce(&Origin<G>) -> T) -> Option<T> {
+        let origin = self.find_origin(id)?;
+        let missing_before = origin.missing_metrics();
+        let complete_before = origin.has_computed_metrics();
+        let result = f(origin);
+        if origin.missing_metrics() < missing_before {
+            self.mark_origins_modified(1);
+        }
+        if !complete_before && origin.has_computed_metrics() {
+            self.mark_origins_completed(1);
+        }
+        Some(result)
+    }
+
+    /// Record that `count` origins got new metrics which are not in the cache file yet
+    pub fn mark_origins_modified(&self, count: usize) {
+        self.unsaved_origins.fetch_add(count, Ordering::Relaxed);
+        self.metrics_generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Ids of the loaded origins whose known latest commit date is between `from` and `to`
+    /// (seconds since the epoch, inclusive), sorted by id. Nothing is computed.
+    ///
+    /// Answered from an index of the origins sorted by date, rebuilt by the first query
+    /// after metrics changed rather than scanning every origin on each query.
+    pub fn committed_between(&self, from: i64, to: i64) -> Vec<NodeId> {
+        let index = self.date_index();
+        let start = index.partition_point(|&(date, _)| date < from);
+        let end = index.partition_point(|&(date, _)| date <= to).max(start);
+        let mut ids: Vec<NodeId> = index[start..end].iter().map(|&(_, id)| id).collect();
+        ids.par_sort_unstable();
+        ids
+    }
+
+    /// Up to `limit` loaded origins with their known latest commit date, most recent first
+    /// (ties by decreasing id), starting right after the `(date, id)` cursor if given:
+    /// keyset pagination, each page costing a binary search of the date index of
+    /// [`Self::committed_between`] whatever its depth. Nothing is computed.
+    pub fn recently_committed(&self, after: Option<(i64, NodeId)>, limit: usize) -> Vec<(i64, NodeId)> {
+        let index = self.date_index();
+        let end = after.map_or(index.len(), |cursor| index.partition_point(|&entry| entry < cursor));
+        index[..end].iter().rev().take(limit).copied().collect()
+    }
+
+    /// t-digests of the known metrics of the loaded origins, to estimate their quantiles.
+    /// Nothing is computed; like the date index of [`Self::committed_between`], they are
+    /// rebuilt by the first call after metrics changed.
+    pub fn metric_digests(&self) -> Arc<MetricDigests> {
+        self.memoized_metrics(&self.metric_digests, || MetricDigests::build(self.loaded_origins()))
+    }
+
+    /// Number of loaded origins of each forge (host of their URL) by bucket of `metric`
+    /// (see [`metric_bucket`]), forges with the most origins first. Nothing is computed;
+    /// rebuilt by the first call after metrics changed, like [`Self::metric_digests`].
+    pub fn forge_facets(&self, metric: Metric) -> Result<Arc<ForgeFacets>, GraphError> {
+        let position = FACET_METRICS.iter().position(|&m| m == metric).unwrap();
+        let generation = self.metrics_generation.load(Ordering::Relaxed);
+        self.memoized(&self.forge_facets[position], generation, || ForgeFacets::build(self.loaded_origins(), metric))
+    }
+
+    /// Loaded origins whose URLs have the same [`canonical_url`], e.g. mirrors or a
+    /// repository archived over both HTTPS and SSH, which count once per URL otherwise.
+    /// Nothing is computed; URLs not depending on metrics, only rebuilt by the first call
+    /// after the origins were replaced (loaded, reloaded or quarantined).
+    pub fn url_duplicates(&self) -> Result<Arc<UrlDuplicates>, GraphError> {
+        self.memoized(&self.url_duplicates, self.origins_generation, || UrlDuplicates::build(self.loaded_origins()))
+    }
+
+    /// Loaded origins as Arrow record batches with the columns of
+    /// [`crate::origin_table::schema`], which `POST /query` runs on. Nothing is computed;
+    /// rebuilt by the first call after metrics changed, like [`Self::metric_digests`], so
+    /// that queries share the batches rather than each copying every origin.
+    #[cfg(feature = "sql")]
+    pub fn origin_table(&self) -> Result<Arc<Vec<RecordBatch>>, GraphError> {
+        let generation = self.metrics_generation.load(Ordering::Relaxed);
+        self.memoized(&self.origin_table, generation, || {
+            crate::origin_table::record_batches(self.loaded_origins()).map_err(GraphError::serialization)
+        })
+    }
+
+    /// Index of [`Self::committed_between`], rebuilt if metrics changed since it was built
+    fn date_index(&self) -> Arc<Vec<(i64, NodeId)>> {
+        self.memoized_metrics(&self.date_index, || {
+            let mut entries: Vec<(i64, NodeId)> = self
+                .loaded_origins()
+                .par_iter()
+                .filter_map(|origin| Some((origin.known_metric(Metric::LatestCommitDate)?, origin.id)))
+                .collect();
+            entries.par_sort_unstable();
+            entries
+        })
+    }
+
+    /// Value kept in `memo`, built again by `build` if metrics changed since it was
+    fn memoized_metrics<T>(&self, memo: &Memo<T>, build: impl FnOnce() -> T) -> Arc<T> {
+        let generation = self.metrics_generation.load(Ordering::Relaxed);
+        let Ok(value) = self.memoized(memo, generation, || Ok::<_, Infallible>(build()));
+        value
+    }
+
+    /// Value kept in `memo`, built again by `build` unless it was at `generation`. Nothing
+    /// is kept if `build` fails.
+    fn memoized<T, E>(&self, memo: &Memo<T>, generation: u64, build: impl FnOnce() -> Result<T, E>) -> Result<Arc<T>, E> {
+        let mut memo = memo.lock().unwrap();
+        if let Some((built_at, value)) = memo.as_ref() {
+            if *built_at == generation {
+                return Ok(value.clone());
+            }
+        }
+        let value = Arc::new(build()?);
+        // Not kept before the origins are loaded, which doesn't bump the generation
+        if self.origins.is_some() {
+            *memo = Some((generation, value.clone()));
+        }
+        Ok(value)
+    }
+
+    /// Record that the metrics of `count` more loaded origins are now all known, computed
+    /// through a shared reference
+    pub fn mark_origins_completed(&self, count: usize) {
+        self.complete_origins.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Count the loaded origins whose metrics are all known again, after the origins were
+    /// replaced or most of their metrics computed
+    fn recount_complete_origins(&self) {
+        let complete = self.par_origins_iter().filter(|origin| origin.has_computed_metrics()).count();
+        self.complete_origins.store(complete, Ordering::Relaxed);
+    }
+
+    /// Why the origin node `id` is not eligible, without computing anything: `None` if it
+    /// is. Loaded origins are diagnosed from their known metrics, see [`Origin::exclusion`].
+    pub fn diagnose(&self, id: usize) -> Result<Option<Exclusion>, GraphError> {
+        if id >= self.graph.num_nodes() || self.graph.node_type(id) != NodeType::Origin {
+            return Err(GraphError::OriginNotFound(id));
+        }
+        match self.find_origin(id) {
+            Some(origin) => Ok(origin.exclusion(&self.filter.eligibility)),
+            // Origins without latest snapshot are not even loaded
+            None if self.graph.latest_snapshot(id).is_none() => Ok(Some(Exclusion::NoSnapshot)),
+            None => Ok(Some(Exclusion::Filtered)),
+        }
+    }
+
+    /// Which origins are listed by `/origins`
+    pub fn eligibility(&self) -> Eligibility {
+        self.filter.eligibility
+    }
+
+    /// Ids of the origins listed by `/origins`, `None` until built by
+    /// [`Self::get_eligible_origin_ids`]
+    pub fn eligible_origin_ids(&self) -> Option<&[NodeId]> {
+        self.eligible_origins.as_deref()
+    }
+
+    /// Whether origin `id` is in the list served by `/origins`, `None` until it is built
+    pub fn is_listed(&self, id: usize) -> Option<bool> {
+        self.eligible_origins.as_ref().map(|ids| ids.binary_search(&id).is_ok())
+    }
+
+    /// Number of loaded origins, of those whose metrics are all known, and of the eligible
+    /// ones if listed yet, without going through the origins
+    pub fn origin_counts(&self) -> OriginCounts {
+        let total = self.loaded_origins().len();
+        OriginCounts {
+            total,
+            complete: self.complete_origins.load(Ordering::Relaxed).min(total),
+            eligible: self.eligible_origins.as_ref().map(Vec::len),
+            quarantined: self.quarantined_origins,
+        }
+    }
+
+    /// Save the origins cache if some metrics were computed since it was last saved.
+    ///
+    /// Returns the number of origins whose new metrics got persisted.
+    pub fn write_back(&mut self) -> Result<usize, GraphError> {
+        let unsaved = self.unsaved_origins.swap(0, Ordering::Relaxed);
+        if unsaved > 0 {
+            if let Err(e) = self.save_origins_to_file() {
+                self.mark_origins_modified(unsaved);
+                return Err(e);
+            }
+        }
+        Ok(unsaved)
+    }
+
+    /// Get the ids of the eligible origins, by default those having commits and a latest
+    /// commit date (see [`OriginFilter::eligibility`]).
+    ///
+    /// The list is persisted next to the origins cache and reused as long as it is
+    /// newer than the cache file; otherwise it is recomputed from the origins
+    /// (computing missing metrics on the way) and written back.
+    pub fn get_eligible_origin_ids(&mut self) -> Result<&Vec<NodeId>, GraphError> {
+        if self.eligible_origins.is_none() && self.eligible_origins_file_is_fresh() {
+            info!("Loading eligible origins from: {:?}", self.eligible_origins_file);
+            match read_node_ids(&self.eligible_origins_file) {
+                Ok(ids) => self.eligible_origins = Some(ids),
+                Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                    warn!("Eligible origins {:?} are corrupted ({}), recomputing them", self.eligible_origins_file, e);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if self.eligible_origins.is_none() {
+            let ids = self.compute_eligible_origin_ids()?;
+            if let Err(e) = write_node_ids(&self.eligible_origins_file, &ids) {
+                error!("Failed to save eligible origins: {}", e);
+            }
+            self.eligible_origins = Some(ids);
+        }
+        Ok(self.eligible_origins.as_ref().unwrap())
+    }
+
+    /// Whether the persisted eligible origins list was written after the origins cache
+    fn eligible_origins_file_is_fresh(&self) -> bool {
+        let modified = |path: &PathBuf| fs::metadata(path).and_then(|m| m.modified());
+        match (modified(&self.eligible_origins_file), modified(&self.origins_cache_file)) {
+            (Ok(eligible), Ok(cache)) => eligible >= cache,
+            _ => false,
+        }
+    }
+
+    fn compute_eligible_origin_ids(&mut self) -> Result<Vec<NodeId>, GraphError> {
+        let progress = self.load_progress.clone();
+        let eligibility = self.filter.eligibility;
+        let origins = self.get_origins_mut()?;
+        info!("Filtering {} origins by commit count...", origins.len());
+        progress.set_total(origins.len());
+
+        let pb = Arc::new(ProgressBar::new(origins.len() as u64));
+        pb.set_style(ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({percent}%) | ETA: {eta} | Rate: {per_sec}")
+            .unwrap()
+            .progress_chars("█▉▊▋▌▍▎▏  "));
+        pb.set_message("Filtering origins");
+
+        // Eligibility may compute missing metrics, which then need to be written back
+        let modified = AtomicUsize::new(0);
+        let counter = Progress::new().with(pb.clone()).with(progress);
+        let ids: Vec<NodeId> = origins
+            .par_iter()
+            .map_init(|| counter.batch(), |batch, origin| {
+                batch.inc();
+                let missing_before = origin.missing_metrics();
+                let eligible = skip_over_budget(origin.is_eligible(&eligibility), false);
+                if origin.missing_metrics() < missing_before {
+                    modified.fetch_add(1, Ordering::Relaxed);
+                }
+                eligible.map(|eligible| eligible.then_some(origin.id))
+            })
+            .filter_map(Result::transpose)
+            .collect::<Result<_, GraphError>>()?;
+        self.mark_origins_modified(modified.into_inner());
+        self.recount_complete_origins();
+
+        pb.finish_with_message("Origin filtering completed!");
+        info!("Found {} eligible origins ({:?})", ids.len(), eligibility);
+        Ok(ids)
+    }
+
     // Private helper methods
     fn load_or_compute_origins(&mut self)  {
+        // Any previously derived list refers to the origins being replaced
+        self.eligible_origins = None;
         if fs::metadata(&self.origins_cache_file).is_ok() {
-            println!("Loading origins from cache ({:?}): {:?}", 
+            self.load_progress.set_stage(LoadStage::LoadingOrigins);
+            info!("Loading origins from cache ({:?}): {:?}", 
                      self.serialization_format, self.origins_cache_file);
             match self.load_origins_from_file() {
                 Ok(()) => {
-                    println!("Successfully loaded {} origins from cache", 
+                    info!("Successfully loaded {} origins from cache", 
                              self.origins.as_ref().map_or(0, |o| o.len()));
                 }
                 Err(e) => {
-                    eprintln!("Failed to load origins from cache: {}. Recomputing...", e);
+                    warn!("Failed to load origins from cache: {}. Recomputing...", e);
                     // Delete the corrupted cache file
                     let _ = fs::remove_file(&self.origins_cache_file);
                     // Recompute origins
-                    self.origins = Some(self.compute_origins());
-                     if let Err(e) = self.save_origins_to_file() {
-                eprintln!("Failed to save origins to cache: {}", e);
-            }
+                    let origins = self.compute_origins();
+                    self.set_origins(origins);
+                    if self.origins.is_some() {
+                        if let Err(e) = self.save_origins_to_file() {
+                            error!("Failed to save origins to cache: {}", e);
+                        }
+                    }
                 }
             }
         } else {
-            println!("Computing origins and caching to ({:?}): {:?}", 
+            info!("Computing origins and caching to ({:?}): {:?}", 
                      self.serialization_format, self.origins_cache_file);
-            self.origins= Some(self.compute_origins());
-            if let Err(e) = self.save_origins_to_file() {
-                eprintln!("Failed to save origins to cache: {}", e);
+            let origins = self.compute_origins();
+            self.set_origins(origins);
+            if self.origins.is_some() {
+                if let Err(e) = self.save_origins_to_file() {
+                    error!("Failed to save origins to cache: {}", e);
+                }
             }
         }
+        self.recount_complete_origins();
     }
     
-    fn load_origins_from_file(&mut self) -> Result<(), std::io::Error> {
-        let file = File::open(&self.origins_cache_file)?;
-        let reader = BufReader::new(file);
-        
+    fn load_origins_from_file(&mut self) -> Result<(), GraphError> {
         // Deserialize the Origin objects (without graph reference)
-        let origins_data: Vec<OriginData> = match self.serialization_format {
-            SerializationFormat::Json => {
-                serde_json::from_reader(reader)
-                    .map_err(|e| {
-                        eprintln!("Error deserializing JSON: {}", e);
-                        std::io::Error::new(std::io::ErrorKind::InvalidData, e)
-                    })?
-            }
-            SerializationFormat::Bincode => {
-                bincode::deserialize_from(reader)
-                    .map_err(|e| {
-                        eprintln!("Error deserializing Bincode: {}", e);
-                        std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Bincode error: {}", e))
-                    })?
-            }
-        };
-        
+        let origins_data = read_origins_cache(&self.origins_cache_file, self.serialization_format)?;
+        let origins_data = self.quarantine_mismatched(origins_data)?;
+
         //map to Origin<G> by setting the graph reference
-        let origins: Vec<Origin<G>> = origins_data.into_iter()
+        let mut origins: Vec<Origin<G>> = origins_data.into_iter()
             .map(|data| Origin::from_data(data, self.graph.clone()))
             .collect();
-        self.origins = Some(origins);
+        if self.filter.drops_loaded() || self.origin_list.is_some() {
+            let total = origins.len();
+            origins = self.kept(origins)?;
+            info!("Kept {} origins out of {} by origin filter or list", origins.len(), total);
+        }
+        self.set_origins(Some(origins));
         Ok(())
     }
 
+    /// Replace the loaded origins, so that what is derived from them is rebuilt
+    fn set_origins(&mut self, origins: Option<Vec<Origin<G>>>) {
+        self.origins = origins;
+        self.origins_generation += 1;
+    }
+
+    /// Set aside the records of a cache computed against another graph export whose ids
+    /// are not origins of this graph, writing them to [`QUARANTINE_FILE`] (replaced on each
+    /// load) instead of computing nonsense metrics for whatever node has their id now
+    fn quarantine_mismatched(&mut self, origins_data: Vec<OriginData>) -> Result<Vec<OriginData>, GraphError> {
+        let num_nodes = self.graph.num_nodes();
+        let (origins_data, mismatched): (Vec<OriginData>, Vec<OriginData>) = origins_data
+            .into_par_iter()
+            .partition(|data| data.id < num_nodes && self.graph.node_type(data.id) == NodeType::Origin);
+        self.quarantined_origins = mismatched.len();
+        let path = self.cache_path(QUARANTINE_FILE);
+        if mismatched.is_empty() {
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+            return Ok(origins_data);
+        }
+        let mut writer = BufWriter::new(File::create(&path)?);
+        for data in &mismatched {
+            serde_json::to_writer(&mut writer, data).map_err(GraphError::serialization)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        warn!(
+            "{} of {} cached origins are not origins of this graph (cache of another export?), set aside in {:?}",
+            mismatched.len(),
+            mismatched.len() + origins_data.len(),
+            path
+        );
+        Ok(origins_data)
+    }
+
+    /// Whether `origin` is in the origin list, if any, and passes the origin filter as far
+    /// as its known metrics tell
+    fn keeps(&self, origin: &Origin<G>) -> Result<bool, GraphError> {
+        let listed = self.origin_list.as_ref().is_none_or(|list| list.contains(self.graph.as_ref(), origin.id));
+        Ok(listed
+            && !(self.filter.require_snapshot && origin.lacks_snapshot())
+            && (!self.filter.filters_urls() || self.filter.keeps_url(origin.get_url()?.as_deref())))
+    }
+
+    /// Those of `origins` which are [kept](Self::keeps)
+    fn kept(&self, origins: Vec<Origin<G>>) -> Result<Vec<Origin<G>>, GraphError> {
+        origins
+            .into_par_iter()
+            .map(|origin| Ok(self.keeps(&origin)?.then_some(origin)))
+            .filter_map(Result::transpose)
+            .collect()
+    }
+
+    /// Whether a newly discovered origin is computed: same as [`Self::keeps`], its latest
+    /// snapshot being looked up if the filter requires one
+    fn admits_new(&self, origin: &Origin<G>) -> Result<bool, GraphError> {
+        if self.filter.require_snapshot && origin.get_latest_snapshot()?.is_none() {
+            return Ok(false);
+        }
+        self.keeps(origin)
+    }
+
  pub fn filter_n_first_origins(&mut self, max_size: usize) {
     if let Some(origins) = &mut self.origins {
         if origins.len() > max_size {
             origins.truncate(max_size);
+            self.eligible_origins = None;
+            self.recount_complete_origins();
         }
     }
 }
 
     
-    pub fn save_origins_to_file(&self) -> Result<(), std::io::Error> {
-        let file = File::create(&self.origins_cache_file)?;
-        let writer = BufWriter::new(file);
-        
-        // Convert Origins to OriginData for serialization
+    pub fn save_origins_to_file(&self) -> Result<(), GraphError> {
+        // Convert Origins to OriginData for serialization, one at a time
         if let Some(origins) = &self.origins {
-            let origins_data: Vec<OriginData> = origins.iter()
-                .map(|origin| OriginData {
-                    id: origin.id,
-                    url: origin.url.clone(),
-                    latest_commit_date: origin.latest_commit_date,
-                    number_of_commits: origin.number_of_commits,
-                    number_of_commiters: origin.number_of_commiters,
-                })
-                .collect();
-            
-            // Serialize the origins data using the chosen format
-            match self.serialization_format {
-                SerializationFormat::Json => {
-                    serde_json::to_writer_pretty(writer, &origins_data)
-                        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
-                }
-                SerializationFormat::Bincode => {
-                    bincode::serialize_into(writer, &origins_data)
-                        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
-                }
+            let mut writer = CacheWriter::create(&self.origins_cache_file, self.serialization_format, self.pretty_json)?;
+            for origin in origins {
+                writer.push(&origin.to_data())?;
             }
-            
-        } else {
-            Ok(())
+            writer.finish()?;
+            *self.cache_saved_at.lock().unwrap() = fs::metadata(&self.origins_cache_file).and_then(|m| m.modified()).ok();
+        }
+        Ok(())
+    }
+
+    /// Path of the origins cache file
+    pub fn origins_cache_file(&self) -> &Path {
+        &self.origins_cache_file
+    }
+
+    /// When the origins cache was last written, by this process or another one
+    pub fn cache_modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.origins_cache_file).and_then(|m| m.modified()).ok()
+    }
+
+    /// Origins whose metrics changed since the cache was last written
+    pub fn unsaved_origins(&self) -> usize {
+        self.unsaved_origins.load(Ordering::Relaxed)
+    }
+
+    pub fn serialization_format(&self) -> SerializationFormat {
+        self.serialization_format
+    }
+
+    /// Whether the origins cache file was last written by [`Self::save_origins_to_file`]
+    pub fn saved_origins_cache(&self) -> bool {
+        let modified = fs::metadata(&self.origins_cache_file).and_then(|m| m.modified()).ok();
+        modified.is_some() && *self.cache_saved_at.lock().unwrap() == modified
+    }
+
+    /// Replace the origins by `origins_data`, e.g. read from a cache regenerated by an
+    /// offline run, then list the eligible origins again. The data is checked to be made of
+    /// origins of this graph sorted by id first, the current origins being kept otherwise.
+    ///
+    /// Metrics computed since the cache was last saved are dropped. Returns the number of
+    /// origins kept by the URL filter and origin list.
+    pub fn replace_origins(&mut self, origins_data: Vec<OriginData>) -> Result<usize, GraphError> {
+        let invalid = |reason: String| GraphError::CacheCorrupted { path: self.origins_cache_file.clone(), reason };
+        let num_nodes = self.graph.num_nodes();
+        if let Some(data) = origins_data.iter().find(|data| data.id >= num_nodes || self.graph.node_type(data.id) != NodeType::Origin) {
+            return Err(invalid(format!("node {} is not an origin of the graph", data.id)));
+        }
+        if let Some(pair) = origins_data.windows(2).find(|pair| pair[0].id >= pair[1].id) {
+            return Err(invalid(format!("origins are not sorted by id ({} before {})", pair[0].id, pair[1].id)));
         }
+        let mut origins: Vec<Origin<G>> = origins_data
+            .into_par_iter()
+            .map(|data| Origin::from_data(data, self.graph.clone()))
+            .collect();
+        if self.filter.drops_loaded() || self.origin_list.is_some() {
+            origins = self.kept(origins)?;
+        }
+        let count = origins.len();
+        self.set_origins(Some(origins));
+        self.eligible_origins = None;
+        self.unsaved_origins.store(0, Ordering::Relaxed);
+        self.metrics_generation.fetch_add(1, Ordering::Relaxed);
+        self.recount_complete_origins();
+        self.get_eligible_origin_ids()?;
+        Ok(count)
     }
     
     /// Save n random origins to file instead of all origins
     /// Useful for testing and reducing file sizes
-    pub fn save_n_random_origins_to_file(&self, n: usize) -> Result<(), std::io::Error> {
+    pub fn save_n_random_origins_to_file(&self, n: usize) -> Result<(), GraphError> {
+        self.save_sample_to_file(SampleStrategy::Uniform(n)).map(|_| ())
+    }
+
+    /// Pick loaded origins according to `strategy`, e.g. to build balanced research
+    /// corpora. Groups are ordered by stratum (commit-count buckets ascending, forges by
+    /// decreasing population), origins by id.
+    ///
+    /// Commit counts which are not known yet are computed and memoized.
+    pub fn sample<'a>(&'a self, strategy: SampleStrategy) -> Result<Vec<SampleGroup<'a, G>>, GraphError> {
+        let origins = self.loaded_origins();
+        let mut rng = rand::rng();
+        let mut pick = |population: &[&'a Origin<G>], n: usize| -> Vec<&'a Origin<G>> {
+            let mut picked: Vec<&Origin<G>> = population.choose_multiple(&mut rng, n).copied().collect();
+            picked.sort_unstable_by_key(|origin| origin.id);
+            picked
+        };
+        match strategy {
+            SampleStrategy::Uniform(n) => {
+                let population: Vec<&Origin<G>> = origins.iter().collect();
+                Ok(vec![SampleGroup { stratum: "all".to_string(), population: origins.len(), origins: pick(&population, n) }])
+            }
+            SampleStrategy::Stratified { stratum, per_stratum } => {
+                let keys: Vec<(u32, String)> = origins.par_iter().map(|origin| stratum.key(origin)).collect::<Result<_, _>>()?;
+                let mut strata: HashMap<(u32, String), Vec<&Origin<G>>> = HashMap::new();
+                for (key, origin) in keys.into_iter().zip(origins) {
+                    strata.entry(key).or_default().push(origin);
+                }
+                let mut strata: Vec<_> = strata.into_iter().collect();
+                strata.sort_unstable_by(|((a_rank, a), a_origins), ((b_rank, b), b_origins)| {
+                    (a_rank, b_origins.len(), a).cmp(&(b_rank, a_origins.len(), b))
+                });
+                Ok(strata
+                    .into_iter()
+                    .map(|((_, label), population)| SampleGroup {
+                        stratum: label,
+                        population: population.len(),
+                        origins: pick(&population, per_stratum),
+                    })
+                    .collect())
+            }
+        }
+    }
+
+    /// Save the origins picked by [`Self::sample`] next to the origins cache, in a file
+    /// named after `strategy` (e.g. `origins_random_1000.bin`), which can be loaded as a
+    /// cache of its own. Returns the groups picked.
+    pub fn save_sample_to_file(&self, strategy: SampleStrategy) -> Result<Vec<SampleGroup<'_, G>>, GraphError> {
         let mut cache_file = self.origins_cache_file.clone();
         
-        // Modify filename to include the number of origins
+        // Modify filename to include the strategy
         let base_name = cache_file.file_stem()
             .and_then(|s| s.to_str())
             .unwrap_or("origins");
@@ -216,52 +935,107 @@ where
             .and_then(|s| s.to_str())
             .unwrap_or("bin");
         
-        let new_filename = format!("{}_random_{}.{}", base_name, n, extension);
+        let new_filename = format!("{}_{}.{}", base_name, strategy.file_suffix(), extension);
         cache_file.set_file_name(new_filename);
-        
-        let file = File::create(&cache_file)?;
-        let writer = BufWriter::new(file);
-        
-        // Convert Origins to OriginData for serialization
-        if let Some(origins) = &self.origins {
-            // Select n random origins
-            let mut rng = rand::thread_rng();
-            let selected_origins: Vec<&Origin<G>> = origins
-                .choose_multiple(&mut rng, n.min(origins.len()))
-                .collect();
-            
-            let origins_data: Vec<OriginData> = selected_origins.iter()
-                .map(|origin| OriginData {
-                    id: origin.id,
-                    url: origin.url.clone(),
-                    latest_commit_date: origin.latest_commit_date,
-                    number_of_commits: origin.number_of_commits,
-                    number_of_commiters: origin.number_of_commiters,
+
+        let sample = self.sample(strategy)?;
+        // Origins must stay sorted by id in a cache
+        let mut selected_origins: Vec<&Origin<G>> = sample.iter().flat_map(|group| group.origins.iter().copied()).collect();
+        selected_origins.sort_unstable_by_key(|origin| origin.id);
+
+        info!("Saving {} sampled origins out of {} total to: {:?}", 
+                 selected_origins.len(), self.loaded_origins().len(), cache_file);
+
+        let mut writer = CacheWriter::create(&cache_file, self.serialization_format, self.pretty_json)?;
+        for origin in selected_origins {
+            writer.push(&origin.to_data())?;
+        }
+        writer.finish()?;
+        Ok(sample)
+    }
+    
+    /// Compute the metrics of every origin of the graph `chunk_size` origins at a time and
+    /// write them to the origins cache as they are produced, without ever holding all
+    /// origins in memory. The resulting cache (and eligibility list) is the same as the one
+    /// written by a full in-memory computation.
+    ///
+    /// Origins are not kept loaded afterwards. Each record is also published to `export`
+    /// as soon as its chunk is computed, and progress reported to `sink` along with the
+    /// progress bar. Returns the number of origins written.
+    pub fn stream_compute_origins_to_file(
+        &mut self,
+        chunk_size: usize,
+        export: Option<&OriginExport>,
+        sink: Option<Arc<dyn ProgressSink>>,
+    ) -> Result<usize, GraphError> {
+        let origin_ids = self.origin_node_ids();
+        let chunk_size = chunk_size.max(1);
+
+        let pb = ProgressBar::new(origin_ids.len() as u64);
+        pb.set_style(ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} ({percent}%) | ETA: {eta_precise} | Rate: {per_sec}")
+            .unwrap()
+            .progress_chars("█▉▊▋▌▍▎▏  "));
+
+        let mut writer = CacheWriter::create(&self.origins_cache_file, self.serialization_format, self.pretty_json)?;
+        let mut counter = Progress::new().with(Arc::new(pb.clone()));
+        if let Some(sink) = sink {
+            counter = counter.with(sink);
+        }
+
+        let mut eligible = Vec::new();
+        for chunk in origin_ids.chunks(chunk_size) {
+            if self.cancel.is_cancelled() {
+                pb.abandon_with_message("Origins computation cancelled");
+                writer.discard();
+                return Err(GraphError::Cancelled);
+            }
+            let chunk_data: Vec<OriginData> = chunk.par_iter()
+                .map_init(|| counter.batch(), |batch, &id| {
+                    let origin = Origin::new(id, self.graph.clone());
+                    batch.inc();
+                    // Same filter as compute_origins
+                    if !self.admits_new(&origin)? {
+                        return Ok(None);
+                    }
+                    skip_over_budget(origin.compute_metrics_cancellable(Metrics::ALL, &self.cancel), true)?;
+                    Ok(Some(origin.to_data()))
                 })
-                .collect();
-            
-            println!("Saving {} random origins out of {} total to: {:?}", 
-                     origins_data.len(), origins.len(), cache_file);
-            
-            // Serialize the origins data using the chosen format
-            match self.serialization_format {
-                SerializationFormat::Json => {
-                    serde_json::to_writer_pretty(writer, &origins_data)
-                        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+                .filter_map(Result::transpose)
+                .collect::<Result<_, GraphError>>()?;
+            for data in &chunk_data {
+                if self.filter.eligibility.admits(data.number_of_commits, data.latest_commit_date) {
+                    eligible.push(data.id);
                 }
-                SerializationFormat::Bincode => {
-                    bincode::serialize_into(writer, &origins_data)
-                        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+                writer.push(data)?;
+                if let Some(export) = export {
+                    export.publish(data);
                 }
             }
-            
-        } else {
-            Ok(())
         }
+        if self.cancel.is_cancelled() {
+            pb.abandon_with_message("Origins computation cancelled");
+            writer.discard();
+            return Err(GraphError::Cancelled);
+        }
+        let written = writer.finish()?;
+        pb.finish_with_message("Origins computed");
+
+        // Written after the cache so it is considered fresh
+        write_node_ids(&self.eligible_origins_file, &eligible)?;
+        self.set_origins(None);
+        self.eligible_origins = Some(eligible);
+        self.unsaved_origins.store(0, Ordering::Relaxed);
+        info!("Saved {} origins to: {:?}", written, self.origins_cache_file);
+        Ok(written)
     }
-    
-    fn compute_origins(&self) -> Vec<Origin<G>> {
-        let origin_ids = filter_by_node_type(&self.graph, NodeType::Origin);
+
+    /// Discover the origins having a latest snapshot, or `None` if cancelled meanwhile or if
+    /// one of them could not be read (logged)
+    fn compute_origins(&self) -> Option<Vec<Origin<G>>> {
+        self.load_progress.set_stage(LoadStage::ComputingOrigins);
+        let origin_ids = self.origin_node_ids();
+        self.load_progress.set_total(origin_ids.len());
         
         // Create progress bar
         let pb = Arc::new(ProgressBar::new(origin_ids.len() as u64));
@@ -271,26 +1045,863 @@ where
             .progress_chars("#>-"));
         pb.set_message("Computing origins");
         
+        let counter = Progress::new().with(pb.clone()).with(self.load_progress.clone());
         let origins: Vec<Origin<G>> = origin_ids.par_iter()
-            .filter_map(|&id| {
-                let mut origin = Origin::new(id, self.graph.clone());
-                pb.inc(1);
+            .map_init(|| counter.batch(), |batch, &id| {
+                if self.cancel.is_cancelled() {
+                    return Ok(None);
+                }
+                let origin = Origin::new(id, self.graph.clone());
+                batch.inc();
                 
-                // Filter out origins that don't have a latest snapshot
-                if origin.get_latest_snapshot().is_some() {
-                    Some(origin)
-                } else {
-                    None
+                // Filter out origins that don't have a latest snapshot (unless allowed) or whose URL is filtered out
+                Ok(self.admits_new(&origin)?.then_some(origin))
+            })
+            .filter_map(Result::transpose)
+            .collect::<Result<_, GraphError>>()
+            .inspect_err(|e| {
+                pb.abandon_with_message("Origins computation failed");
+                error!("Failed to compute origins: {}", e);
+            })
+            .ok()?;
+
+        if self.cancel.is_cancelled() {
+            pb.abandon_with_message("Origins computation cancelled");
+            return None;
+        }
+        pb.finish_with_message("Origins computed! Check logs for count with snapshots");
+        info!("Found {} origins with snapshots out of {} total", origins.len(), origin_ids.len());
+        if let Some(list) = &self.origin_list {
+            info!("Kept {} of the {} listed origins, the others are missing or have no snapshot", origins.len(), list.len());
+        }
+        Some(origins)
+    }
+
+
+}
+
+impl<G> Graph<G>
+where
+    G: GraphBackend + Send + Sync,
+{
+    /// Write the nodes reachable from the given origins (through any of their visits), and
+    /// the arcs between them, as a standalone dataset in `output_dir`:
+    ///
+    /// - `nodes.csv`: `id,swhid`, ids being numbered from 0 in the order of the graph
+    /// - `edges.csv`: `src,dst` pairs of these ids
+    /// - `origins.csv`: `id,url` of the selected origins
+    ///
+    /// Returns the number of nodes and arcs written.
+    pub fn extract_subgraph(
+        &self,
+        origin_ids: &[NodeId],
+        output_dir: &Path,
+        cancel: &CancellationToken,
+    ) -> Result<(usize, usize), GraphError> {
+        let graph = self.graph.as_ref();
+        for &origin in origin_ids {
+            if origin >= graph.num_nodes() || graph.node_type(origin) != NodeType::Origin {
+                return Err(GraphError::NodeNotFound { node: origin, expected: NodeType::Origin });
+            }
+        }
+        let mut nodes = Vec::new();
+        let end = bfs(graph, origin_ids.iter().copied(), &TraversalOptions::new().cancel(cancel), |node, _| {
+            nodes.push(node);
+            Visit::Continue
+        });
+        if end.is_cancelled() {
+            return Err(GraphError::Cancelled);
+        }
+        nodes.par_sort_unstable();
+        let local_id = |node: NodeId| nodes.binary_search(&node).ok();
+
+        fs::create_dir_all(output_dir)?;
+        let mut writer = BufWriter::new(File::create(output_dir.join("nodes.csv"))?);
+        writeln!(writer, "id,swhid")?;
+        for (id, &node) in nodes.iter().enumerate() {
+            writeln!(writer, "{},{}", id, graph.swhid(node))?;
+        }
+        writer.flush()?;
+
+        let mut arcs = 0;
+        let mut writer = BufWriter::new(File::create(output_dir.join("edges.csv"))?);
+        writeln!(writer, "src,dst")?;
+        for (src, &node) in nodes.iter().enumerate() {
+            // Every successor was reached by the traversal, hence has an id
+            for dst in graph.successors(node).filter_map(local_id) {
+                writeln!(writer, "{},{}", src, dst)?;
+                arcs += 1;
+            }
+        }
+        writer.flush()?;
+
+        let mut writer = BufWriter::new(File::create(output_dir.join("origins.csv"))?);
+        writeln!(writer, "id,url")?;
+        for &origin in origin_ids {
+            let url = graph.message(origin).map(|url| String::from_utf8_lossy(&url).replace('"', "\"\""));
+            writeln!(writer, "{},\"{}\"", local_id(origin).unwrap_or_default(), url.unwrap_or_default())?;
+        }
+        writer.flush()?;
+        Ok((nodes.len(), arcs))
+    }
+
+    /// Write the metrics of the loaded origins as of `as_of` (see [`Origin::metrics_as_of`])
+    /// to `path`, as CSV `origin_id,latest_commit_date,commits,committers` lines in origin
+    /// order, empty fields standing for unknown values.
+    ///
+    /// Returns the number of origins written, or [`GraphError::Cancelled`].
+    pub fn write_metrics_as_of(
+        &self,
+        as_of: i64,
+        path: &Path,
+        progress: &Progress,
+        cancel: &CancellationToken,
+    ) -> Result<usize, GraphError> {
+        let field = |value: Option<String>| value.unwrap_or_default();
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "origin_id,latest_commit_date,commits,committers")?;
+        for chunk in self.loaded_origins().chunks(AS_OF_CHUNK_SIZE) {
+            let metrics: Vec<_> = chunk
+                .par_iter()
+                .map_init(|| progress.batch(), |batch, origin| {
+                    batch.inc();
+                    (origin.id, skip_over_budget(origin.metrics_as_of(as_of, cancel), Some(AsOfMetrics::default())))
+                })
+                .collect();
+            for (origin, metrics) in metrics {
+                let metrics = metrics?.ok_or(GraphError::Cancelled)?;
+                writeln!(
+                    writer,
+                    "{},{},{},{}",
+                    origin,
+                    field(metrics.latest_commit_date.map(|date| date.to_string())),
+                    field(metrics.commit_count.map(|count| count.to_string())),
+                    field(metrics.committer_count.map(|count| count.to_string())),
+                )?;
+            }
+        }
+        writer.flush()?;
+        Ok(self.loaded_origins().len())
+    }
+}
+
+/// Origins whose as-of metrics are held in memory at a time by
+/// [`Graph::write_metrics_as_of`], so that the output is in origin order
+const AS_OF_CHUNK_SIZE: usize = 100_000;
+
+/// Value derived from the origins, with the generation of their metrics or of the
+/// origins themselves it was built at, see [`Graph::memoized`]
+type Memo<T> = Mutex<Option<(u64, Arc<T>)>>;
+
+/// Metrics origins can be bucketed by, see [`metric_bucket`]
+const FACET_METRICS: [Metric; 3] = [Metric::LatestCommitDate, Metric::CommitCount, Metric::CommitterCount];
+
+/// Rank of the bucket of a metric `value` (to order buckets) and its label: the year of
+/// latest commit dates, power-of-two buckets of counts (`0`, `1`, `2-3`, `4-7`, ...),
+/// `unknown` last for metrics not computed yet
+pub fn metric_bucket(metric: Metric, value: Option<i64>) -> (u32, String) {
+    match (metric, value) {
+        (_, None) => (u32::MAX, "unknown".to_string()),
+        (Metric::LatestCommitDate, Some(date)) => match DateTime::from_timestamp(date, 0) {
+            Some(date) => (date.year().max(0) as u32, date.year().to_string()),
+            None => (u32::MAX - 1, "invalid".to_string()),
+        },
+        (_, Some(count)) if count <= 0 => (0, "0".to_string()),
+        (_, Some(count)) => {
+            let bucket = count.ilog2();
+            let low = 1i64 << bucket;
+            let label = if low == 1 { "1".to_string() } else { format!("{}-{}", low, 2 * low - 1) };
+            (1 + bucket, label)
+        }
+    }
+}
+
+/// Origins of a forge, see [`ForgeFacets`]
+pub struct ForgeFacet {
+    /// Host of the origin URLs, e.g. `github.com`, `unknown` for origins without URL
+    pub forge: String,
+    pub total: usize,
+    /// Label of each non-empty bucket and its number of origins, in bucket order
+    pub buckets: Vec<(String, usize)>,
+}
+
+/// Number of loaded origins of each forge by bucket of a metric, see
+/// [`Graph::forge_facets`]
+pub struct ForgeFacets {
+    pub metric: Metric,
+    /// Forges with the most origins first
+    pub forges: Vec<ForgeFacet>,
+}
+
+impl ForgeFacets {
+    /// Origins whose keys are held in memory at a time while counting
+    const CHUNK: usize = 1 << 20;
+
+    fn build<G>(origins: &[Origin<G>], metric: Metric) -> Result<Self, GraphError>
+    where
+        G: GraphBackend + Send + Sync,
+    {
+        let mut counts: HashMap<String, HashMap<(u32, String), usize>> = HashMap::new();
+        for chunk in origins.chunks(Self::CHUNK) {
+            let keys: Vec<(String, (u32, String))> = chunk
+                .par_iter()
+                .map(|origin| {
+                    let forge = origin.get_url()?.as_deref().and_then(host).unwrap_or("unknown").to_string();
+                    Ok((forge, metric_bucket(metric, origin.known_metric(metric))))
+                })
+                .collect::<Result<_, GraphError>>()?;
+            for (forge, bucket) in keys {
+                *counts.entry(forge).or_default().entry(bucket).or_default() += 1;
+            }
+        }
+        let mut forges: Vec<ForgeFacet> = counts
+            .into_iter()
+            .map(|(forge, buckets)| {
+                let mut buckets: Vec<((u32, String), usize)> = buckets.into_iter().collect();
+                buckets.sort_unstable();
+                ForgeFacet {
+                    forge,
+                    total: buckets.iter().map(|(_, count)| count).sum(),
+                    buckets: buckets.into_iter().map(|((_, label), count)| (label, count)).collect(),
                 }
             })
             .collect();
-            
-        pb.finish_with_message("Origins computed! Check logs for count with snapshots");
-        println!("Found {} origins with snapshots out of {} total", origins.len(), origin_ids.len());
-        origins
+        forges.sort_unstable_by(|a, b| b.total.cmp(&a.total).then_with(|| a.forge.cmp(&b.forge)));
+        Ok(ForgeFacets { metric, forges })
+    }
+}
+
+/// Origins sharing a canonical URL, see [`Graph::url_duplicates`]
+pub struct UrlDuplicates {
+    /// Canonical URL of each group and the ids of its origins, sorted, the largest groups
+    /// first
+    pub groups: Vec<(String, Vec<NodeId>)>,
+}
+
+impl UrlDuplicates {
+    fn build<G>(origins: &[Origin<G>]) -> Result<Self, GraphError>
+    where
+        G: GraphBackend + Send + Sync,
+    {
+        // Only a hash of the canonical URL of every origin is held at once, the URLs being
+        // compared again for those whose hash is shared
+        let hash = |url: &str| {
+            let mut hasher = DefaultHasher::new();
+            url.hash(&mut hasher);
+            hasher.finish()
+        };
+        let mut hashes: Vec<(u64, usize)> = origins
+            .par_iter()
+            .enumerate()
+            .filter_map(|(index, origin)| {
+                origin.get_url().map(|url| Some((hash(&canonical_url(&url?)), index))).transpose()
+            })
+            .collect::<Result<_, GraphError>>()?;
+        hashes.par_sort_unstable();
+        let mut groups: HashMap<String, Vec<NodeId>> = HashMap::new();
+        let mut start = 0;
+        while start < hashes.len() {
+            let end = start + hashes[start..].iter().take_while(|(h, _)| *h == hashes[start].0).count();
+            if end - start > 1 {
+                for &(_, index) in &hashes[start..end] {
+                    if let Some(url) = origins[index].get_url()? {
+                        groups.entry(canonical_url(&url)).or_default().push(origins[index].id);
+                    }
+                }
+            }
+            start = end;
+        }
+        let mut groups: Vec<(String, Vec<NodeId>)> = groups
+            .into_iter()
+            .filter(|(_, ids)| ids.len() > 1)
+            .map(|(url, mut ids)| {
+                ids.sort_unstable();
+                (url, ids)
+            })
+            .collect();
+        groups.sort_unstable_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(&b.0)));
+        Ok(UrlDuplicates { groups })
+    }
+
+    /// Number of origins in a group, all of them but one being duplicates
+    pub fn origin_count(&self) -> usize {
+        self.groups.iter().map(|(_, ids)| ids.len()).sum()
+    }
+}
+
+/// How [`Graph::sample`] picks origins
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SampleStrategy {
+    /// This many origins uniformly at random
+    Uniform(usize),
+    /// Up to `per_stratum` origins uniformly at random in each stratum, so that rare
+    /// strata are as represented as common ones
+    Stratified { stratum: Stratum, per_stratum: usize },
+}
+
+impl SampleStrategy {
+    fn file_suffix(&self) -> String {
+        match self {
+            SampleStrategy::Uniform(n) => format!("random_{}", n),
+            SampleStrategy::Stratified { stratum: Stratum::CommitCount, per_stratum } => format!("commit_count_{}", per_stratum),
+            SampleStrategy::Stratified { stratum: Stratum::Forge, per_stratum } => format!("forge_{}", per_stratum),
+        }
+    }
+}
+
+/// Partition of the origins for [`SampleStrategy::Stratified`]
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stratum {
+    /// Power-of-two buckets of commit count: `0`, `1`, `2-3`, `4-7`, ...
+    CommitCount,
+    /// Host of the origin URL, e.g. `github.com`
+    Forge,
+}
+
+impl Stratum {
+    /// Rank of the stratum of `origin` (to order strata) and its label
+    fn key<G: GraphBackend + Send + Sync>(&self, origin: &Origin<G>) -> Result<(u32, String), GraphError> {
+        Ok(match self {
+            Stratum::CommitCount => {
+                let commits = origin.metric(Metric::CommitCount, ComputePolicy::ComputeIfMissing);
+                metric_bucket(Metric::CommitCount, skip_over_budget(commits, None)?)
+            }
+            Stratum::Forge => (0, origin.get_url()?.as_deref().and_then(host).unwrap_or("unknown").to_string()),
+        })
+    }
+}
+
+/// Origins picked in one stratum by [`Graph::sample`]
+pub struct SampleGroup<'a, G>
+where
+    G: GraphBackend + Send + Sync,
+{
+    pub stratum: String,
+    /// Number of loaded origins in the stratum
+    pub population: usize,
+    pub origins: Vec<&'a Origin<G>>,
+}
+
+/// Loaded origins by category, see [`Graph::origin_counts`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OriginCounts {
+    pub total: usize,
+    /// Origins whose latest commit date, commit count and committer count are known
+    pub complete: usize,
+    /// Origins served by `/origins`, `None` until they are listed
+    pub eligible: Option<usize>,
+    /// Cache records which were not origins of the graph, see [`QUARANTINE_FILE`]
+    pub quarantined: usize,
+}
+
+impl OriginCounts {
+    /// JSON representation served by `/origins/count`. Complete origins which are not
+    /// eligible fail the [`Eligibility`] of the graph.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "total": self.total,
+            "complete": self.complete,
+            "incomplete": self.total - self.complete,
+            "eligible": self.eligible,
+            "ineligible": self.eligible.map(|eligible| self.complete.saturating_sub(eligible)),
+            "quarantined": self.quarantined,
+        })
+    }
+}
+
+/// Which visits of an origin count for [`Graph::origins_reaching`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reach {
+    /// The node was archived by any visit of the origin
+    AnyVisit,
+    /// The node is reachable from the latest snapshot of the origin, like the metrics
+    LatestSnapshot,
+}
+
+/// Features walking arcs backward, for graphs loaded with their transposed graph
+impl<G> Graph<G>
+where
+    G: BackwardCapable + Send + Sync,
+{
+    /// Up to `limit` origins from which `node` is reachable according to `reach`, in
+    /// breadth-first order. `node` must be of type `expected`.
+    ///
+    /// Returns whether the list is complete, `false` if `limit` was hit.
+    pub fn origins_reaching(
+        &self,
+        node: NodeId,
+        expected: NodeType,
+        reach: Reach,
+        limit: usize,
+        cancel: &CancellationToken,
+    ) -> Result<(Vec<NodeId>, bool), GraphError> {
+        if node >= self.graph.num_nodes() || self.graph.node_type(node) != expected {
+            return Err(GraphError::NodeNotFound { node, expected });
+        }
+        let mut origins = Vec::new();
+        // Only gathered for `LatestSnapshot`, which needs the whole walk: the latest
+        // snapshot of an origin may be found after the origin itself
+        let mut snapshots = HashSet::new();
+        let end = bfs_backward(self.graph.as_ref(), [node], &TraversalOptions::new().cancel(cancel), |pred, _| {
+            match (self.graph.node_type(pred), reach) {
+                (NodeType::Origin, Reach::AnyVisit) if origins.len() == limit => return Visit::Stop,
+                (NodeType::Origin, _) => origins.push(pred),
+                (NodeType::Snapshot, Reach::LatestSnapshot) => {
+                    snapshots.insert(pred);
+                }
+                _ => {}
+            }
+            Visit::Continue
+        });
+        if end.is_cancelled() {
+            return Err(GraphError::Cancelled);
+        }
+        let mut complete = end != TraversalEnd::Stopped;
+        if reach == Reach::LatestSnapshot {
+            origins.retain(|&origin| {
+                self.graph
+                    .latest_snapshot(origin)
+                    .is_some_and(|(snapshot, _)| snapshots.contains(&snapshot))
+            });
+            if origins.len() > limit {
+                origins.truncate(limit);
+                complete = false;
+            }
+        }
+        Ok((origins, complete))
     }
+}
 
+/// Differences between two snapshots, see [`Graph::diff_snapshots`]
+pub struct SnapshotDiff {
+    /// Branches only in the second snapshot
+    pub added: Vec<Branch>,
+    /// Branches only in the first snapshot
+    pub removed: Vec<Branch>,
+    /// Branches in both snapshots with different targets, as (name, old target, new target)
+    pub changed: Vec<(Vec<u8>, NodeId, NodeId)>,
+    /// Revisions reachable from the second snapshot but not from the first, in
+    /// breadth-first order
+    pub new_revisions: Vec<NodeId>,
+}
 
+/// Features reading branch names, for graphs loaded with the label names
+impl<G> Graph<G>
+where
+    G: LabelCapable + Send + Sync,
+{
+    /// Compare the branches and the commit histories of snapshots `a` and `b`, e.g. two
+    /// visits of the same origin
+    pub fn diff_snapshots(&self, a: NodeId, b: NodeId, cancel: &CancellationToken) -> Result<SnapshotDiff, GraphError> {
+        for node in [a, b] {
+            if node >= self.graph.num_nodes() || self.graph.node_type(node) != NodeType::Snapshot {
+                return Err(GraphError::NodeNotFound { node, expected: NodeType::Snapshot });
+            }
+        }
+        let (old, new) = (snapshot_branches(self.graph.as_ref(), a), snapshot_branches(self.graph.as_ref(), b));
+
+        // Both lists are sorted by name, merge them
+        let (mut added, mut removed, mut changed) = (Vec::new(), Vec::new(), Vec::new());
+        let (mut old_iter, mut new_iter) = (old.iter().peekable(), new.iter().peekable());
+        loop {
+            match (old_iter.peek(), new_iter.peek()) {
+                (None, None) => break,
+                (Some(_), None) => removed.extend(old_iter.by_ref().cloned()),
+                (None, Some(_)) => added.extend(new_iter.by_ref().cloned()),
+                (Some(old_branch), Some(new_branch)) => {
+                    match old_branch.name.cmp(&new_branch.name) {
+                        std::cmp::Ordering::Less => removed.extend(old_iter.next().cloned()),
+                        std::cmp::Ordering::Greater => added.extend(new_iter.next().cloned()),
+                        std::cmp::Ordering::Equal => {
+                            if old_branch.target != new_branch.target {
+                                changed.push((old_branch.name.clone(), old_branch.target, new_branch.target));
+                            }
+                            old_iter.next();
+                            new_iter.next();
+                        }
+                    }
+                }
+            }
+        }
+
+        let old_history = self.history(old.iter().map(|branch| branch.target), cancel, |_| true)?;
+        let old_history: HashSet<NodeId> = old_history.into_iter().collect();
+        let new_revisions = self.history(new.iter().map(|branch| branch.target), cancel, |revision| {
+            !old_history.contains(&revision)
+        })?;
+        Ok(SnapshotDiff { added, removed, changed, new_revisions })
+    }
+
+    /// Revisions reachable from `targets` through revisions and releases only, for which
+    /// `keep` returns `true`
+    fn history(
+        &self,
+        targets: impl Iterator<Item = NodeId>,
+        cancel: &CancellationToken,
+        keep: impl Fn(NodeId) -> bool,
+    ) -> Result<Vec<NodeId>, GraphError> {
+        const HISTORY: [NodeType; 2] = [NodeType::Release, NodeType::Revision];
+        let graph = self.graph.as_ref();
+        let start = targets.filter(|&target| HISTORY.contains(&graph.node_type(target)));
+        let mut revisions = Vec::new();
+        let options = TraversalOptions::new().node_types(&HISTORY).cancel(cancel);
+        let end = bfs(graph, start, &options, |node, _| {
+            if graph.node_type(node) == NodeType::Revision && keep(node) {
+                revisions.push(node);
+            }
+            Visit::Continue
+        });
+        if end.is_cancelled() {
+            return Err(GraphError::Cancelled);
+        }
+        Ok(revisions)
+    }
 }
 
+/// Cache records whose ids are not origins of the graph, one JSON [`OriginData`] per line,
+/// in the cache directory, see [`Graph::origin_counts`]
+pub const QUARANTINE_FILE: &str = "quarantined_origins.jsonl";
+
+/// First bytes of compact caches: the number of origins follows as a
+/// little-endian `u64`, then chunks of [`CACHE_CHUNK_ORIGINS`] (or fewer, for the last
+/// one) CBOR-encoded [`OriginData`]s, each prefixed with its number of origins and of
+/// bytes as little-endian `u64`s, so that chunks are decoded in parallel. CBOR keeps the
+/// name of each field, so that metrics added to [`OriginData`] need no new layout. Caches
+/// not starting with it are read as a fixed-width `Vec<LegacyOriginData>`, the first layout.
+const COMPACT_CACHE_MAGIC: &[u8; 8] = b"SWHORIGC";
 
+/// Version of the [`OriginData`] layout written to caches, the magic of the compact layout
+pub fn cache_schema() -> &'static str {
+    std::str::from_utf8(COMPACT_CACHE_MAGIC).unwrap_or_default()
+}
+
+/// Origins per chunk of compact caches: enough for each to be worth a rayon task, few
+/// enough for a multi-GB cache to keep every core busy
+const CACHE_CHUNK_ORIGINS: usize = 65_536;
+
+/// Read the origins of a cache file without any graph, e.g. the cache of another export
+pub fn read_origins_cache(path: &Path, format: SerializationFormat) -> Result<Vec<OriginData>, GraphError> {
+    let reader = BufReader::new(File::open(path)?);
+    read_origins_data(reader, format).map_err(|reason| GraphError::CacheCorrupted {
+        path: path.to_path_buf(),
+        reason,
+    })
+}
+
+/// Read the origins of a cache, in the compact or the legacy bincode layout for compact
+/// caches
+fn read_origins_data(mut reader: impl Read, format: SerializationFormat) -> Result<Vec<OriginData>, String> {
+    match format {
+        SerializationFormat::Json => {
+            serde_json::from_reader(reader).map_err(|e| format!("JSON error: {}", e))
+        }
+        SerializationFormat::Compact => {
+            let mut header = [0u8; 8];
+            reader.read_exact(&mut header).map_err(|e| format!("Compact cache error: {}", e))?;
+            if &header == COMPACT_CACHE_MAGIC {
+                read_chunked_origins(reader)
+            } else {
+                // The legacy layout starts right away with the length of the sequence
+                let legacy: Vec<LegacyOriginData> = bincode::deserialize_from((&header[..]).chain(reader))
+                    .map_err(|e| format!("Bincode error (legacy layout): {}", e))?;
+                Ok(legacy.into_iter().map(OriginData::from).collect())
+            }
+        }
+    }
+}
+
+/// Little-endian `u64` of the compact layout
+fn read_u64(reader: &mut impl Read) -> Result<u64, String> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes).map_err(|e| format!("Compact cache error: {}", e))?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Read the count and chunks of origins following the magic of a compact cache. Chunks
+/// are read one after the other, then decoded in parallel.
+fn read_chunked_origins(mut reader: impl Read) -> Result<Vec<OriginData>, String> {
+    let count = read_u64(&mut reader)? as usize;
+    let mut chunks = Vec::new();
+    let mut read = 0;
+    while read < count {
+        let origins = read_u64(&mut reader)? as usize;
+        let length = read_u64(&mut reader)?;
+        // Each origin takes at least a byte, don't trust a corrupted count either
+        if origins as u64 > length {
+            return Err(format!("Compact cache error: chunk of {} bytes for {} origins", length, origins));
+        }
+        // Don't trust a corrupted length for the allocation
+        let mut bytes = Vec::new();
+        (&mut reader).take(length).read_to_end(&mut bytes).map_err(|e| format!("Compact cache error: {}", e))?;
+        if bytes.len() as u64 != length {
+            return Err(format!("Compact cache error: chunk of {} bytes truncated to {}", length, bytes.len()));
+        }
+        read += origins;
+        chunks.push((origins, bytes));
+    }
+    let chunks: Vec<Vec<OriginData>> = chunks
+        .into_par_iter()
+        .map(|(origins, bytes)| -> Result<Vec<OriginData>, String> {
+            let mut slice = bytes.as_slice();
+            (0..origins)
+                .map(|_| ciborium::from_reader(&mut slice).map_err(|e| format!("CBOR error: {}", e)))
+                .collect()
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(chunks.into_iter().flatten().collect())
+}
+
+/// Writer of the origins cache, one origin at a time. JSON caches are written as an array
+/// whose elements are serialized as they come, so that they never need to be all in memory.
+///
+/// The cache is written to `<path>.tmp` and renamed to `path` by [`CacheWriter::finish`], so
+/// that a process killed while writing never leaves a truncated cache behind, and watchers
+/// of the cache only ever see complete ones.
+struct CacheWriter {
+    writer: BufWriter<File>,
+    path: PathBuf,
+    tmp_path: PathBuf,
+    format: SerializationFormat,
+    /// Indent JSON, each origin starting on its own line
+    pretty: bool,
+    count: usize,
+    /// Encoded origins not written yet, see [`CACHE_CHUNK_ORIGINS`]
+    chunk: Vec<u8>,
+    chunk_origins: usize,
+}
+
+impl CacheWriter {
+    fn create(path: &Path, format: SerializationFormat, pretty: bool) -> Result<Self, GraphError> {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        match format {
+            SerializationFormat::Json => writer.write_all(b"[")?,
+            SerializationFormat::Compact => {
+                writer.write_all(COMPACT_CACHE_MAGIC)?;
+                // Number of origins, patched in `finish`
+                writer.write_all(&0u64.to_le_bytes())?;
+            }
+        }
+        Ok(CacheWriter {
+            writer,
+            path: path.to_path_buf(),
+            tmp_path,
+            format,
+            pretty,
+            count: 0,
+            chunk: Vec::new(),
+            chunk_origins: 0,
+        })
+    }
+
+    fn push(&mut self, data: &OriginData) -> Result<(), GraphError> {
+        match self.format {
+            SerializationFormat::Json => {
+                if self.count > 0 {
+                    self.writer.write_all(b",")?;
+                }
+                if self.pretty {
+                    self.writer.write_all(b"\n")?;
+                    serde_json::to_writer_pretty(&mut self.writer, data).map_err(GraphError::serialization)?;
+                } else {
+                    serde_json::to_writer(&mut self.writer, data).map_err(GraphError::serialization)?;
+                }
+            }
+            SerializationFormat::Compact => {
+                ciborium::into_writer(data, &mut self.chunk).map_err(GraphError::serialization)?;
+                self.chunk_origins += 1;
+                if self.chunk_origins == CACHE_CHUNK_ORIGINS {
+                    self.write_chunk()?;
+                }
+            }
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    fn write_chunk(&mut self) -> Result<(), GraphError> {
+        if self.chunk_origins > 0 {
+            self.writer.write_all(&(self.chunk_origins as u64).to_le_bytes())?;
+            self.writer.write_all(&(self.chunk.len() as u64).to_le_bytes())?;
+            self.writer.write_all(&self.chunk)?;
+            self.chunk.clear();
+            self.chunk_origins = 0;
+        }
+        Ok(())
+    }
+
+    /// Complete the cache and move it to its path, returning the number of origins written
+    fn finish(mut self) -> Result<usize, GraphError> {
+        match self.format {
+            SerializationFormat::Json => {
+                let end: &[u8] = if self.pretty { b"\n]\n" } else { b"]" };
+                self.writer.write_all(end)?;
+            }
+            SerializationFormat::Compact => self.write_chunk()?,
+        }
+        let mut file = self.writer.into_inner().map_err(|e| e.into_error())?;
+        if matches!(self.format, SerializationFormat::Compact) {
+            file.seek(SeekFrom::Start(COMPACT_CACHE_MAGIC.len() as u64))?;
+            file.write_all(&(self.count as u64).to_le_bytes())?;
+        }
+        file.sync_all()?;
+        fs::rename(&self.tmp_path, &self.path)?;
+        Ok(self.count)
+    }
+
+    /// Give up writing the cache, leaving the previous one in place
+    fn discard(self) {
+        drop(self.writer);
+        let _ = fs::remove_file(&self.tmp_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_graph::InMemoryGraphBuilder;
+    use crate::origin::{Head, MetricValue};
+
+    /// Empty directory of its own for `test`, under the system temporary directory
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("swh-origins-{}-{}", std::process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// `count` origins, some of them with optional metrics and columns
+    fn origins(count: usize) -> Vec<OriginData> {
+        (0..count)
+            .map(|id| {
+                let mut data = OriginData::from(LegacyOriginData {
+                    id,
+                    url: (id % 3 != 0).then(|| format!("https://example.org/{}.git", id)),
+                    latest_commit_date: Some(1_700_000_000 + id as i64),
+                    number_of_commits: (id % 5 != 0).then_some(id),
+                    number_of_commiters: Some(1 + id % 7),
+                });
+                if id % 2 == 0 {
+                    data.head = Some(Head { snapshot: id + 1, revision: Some(id + 2) });
+                    data.columns.insert("stars".to_string(), MetricValue::Int(id as i64));
+                }
+                data
+            })
+            .collect()
+    }
+
+    fn write_cache(path: &Path, format: SerializationFormat, pretty: bool, origins: &[OriginData]) -> usize {
+        let mut writer = CacheWriter::create(path, format, pretty).unwrap();
+        for data in origins {
+            writer.push(data).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    fn round_trip(format: SerializationFormat, pretty: bool, count: usize) {
+        let dir = temp_dir(&format!("{:?}-{}-{}", format, pretty, count));
+        let path = dir.join("origins");
+        let written = origins(count);
+        assert_eq!(write_cache(&path, format, pretty, &written), count);
+        assert!(!dir.join("origins.tmp").exists());
+        let read = read_origins_cache(&path, format).unwrap();
+        assert_eq!(serde_json::to_value(&read).unwrap(), serde_json::to_value(&written).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn json_round_trip() {
+        round_trip(SerializationFormat::Json, false, 0);
+        round_trip(SerializationFormat::Json, false, 100);
+        round_trip(SerializationFormat::Json, true, 100);
+    }
+
+    #[test]
+    fn compact_round_trip() {
+        round_trip(SerializationFormat::Compact, false, 0);
+        round_trip(SerializationFormat::Compact, false, 100);
+        // The last chunk is partial
+        round_trip(SerializationFormat::Compact, false, CACHE_CHUNK_ORIGINS + 10);
+    }
+
+    #[test]
+    fn legacy_bincode_cache() {
+        let dir = temp_dir("legacy");
+        let path = dir.join("origins.bin");
+        // Fields of `LegacyOriginData`, in order
+        let legacy = vec![
+            (3usize, Some("https://example.org/a.git".to_string()), Some(1_700_000_000i64), Some(12usize), Some(2usize)),
+            (7, None, None, None, None),
+        ];
+        fs::write(&path, bincode::serialize(&legacy).unwrap()).unwrap();
+        let read = read_origins_cache(&path, SerializationFormat::Compact).unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!((read[0].id, read[0].url.as_deref(), read[0].number_of_commits), (3, Some("https://example.org/a.git"), Some(12)));
+        assert_eq!((read[1].id, read[1].latest_commit_date, read[1].number_of_commiters), (7, None, None));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn truncated_compact_cache() {
+        let dir = temp_dir("truncated");
+        let path = dir.join("origins.bin");
+        write_cache(&path, SerializationFormat::Compact, false, &origins(CACHE_CHUNK_ORIGINS + 10));
+        let bytes = fs::read(&path).unwrap();
+        // In the magic, the count, a chunk header, and the middle and end of each chunk
+        let first_chunk = 32 + u64::from_le_bytes(bytes[24..32].try_into().unwrap()) as usize;
+        for length in [4, 12, 20, 1000, first_chunk - 1, first_chunk + 20, bytes.len() - 1] {
+            fs::write(&path, &bytes[..length]).unwrap();
+            let result = read_origins_cache(&path, SerializationFormat::Compact);
+            assert!(matches!(result, Err(GraphError::CacheCorrupted { .. })), "truncated to {} bytes", length);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn discarded_cache_keeps_the_previous_one() {
+        let dir = temp_dir("discarded");
+        let path = dir.join("origins.json");
+        write_cache(&path, SerializationFormat::Json, false, &origins(3));
+        let mut writer = CacheWriter::create(&path, SerializationFormat::Json, false).unwrap();
+        writer.push(&origins(1)[0]).unwrap();
+        writer.discard();
+        assert!(!dir.join("origins.json.tmp").exists());
+        assert_eq!(read_origins_cache(&path, SerializationFormat::Json).unwrap().len(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn url_duplicates_survive_metric_changes() {
+        let dir = temp_dir("duplicates");
+        let mut builder = InMemoryGraphBuilder::default();
+        let urls = ["https://example.org/a.git", "git@example.org:a", "https://example.org/b"];
+        let ids: Vec<NodeId> = urls.iter().map(|url| builder.origin(url)).collect();
+        let mut graph = Graph::builder(dir.join("graph")).cache_dir(&dir).build(builder.build());
+        let data = |ids: &[NodeId]| -> Vec<OriginData> {
+            ids.iter()
+                .map(|&id| OriginData::from(LegacyOriginData { id, url: Some(urls[id].to_string()), latest_commit_date: None, number_of_commits: None, number_of_commiters: None }))
+                .collect()
+        };
+        graph.replace_origins(data(&ids)).unwrap();
+
+        let duplicates = graph.url_duplicates().unwrap();
+        assert_eq!(duplicates.groups, [("example.org/a".to_string(), vec![ids[0], ids[1]])]);
+        // URLs don't depend on metrics
+        graph.mark_origins_modified(1);
+        assert!(Arc::ptr_eq(&duplicates, &graph.url_duplicates().unwrap()));
+
+        graph.replace_origins(data(&ids[1..])).unwrap();
+        assert!(graph.url_duplicates().unwrap().groups.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn format_names() {
+        assert!(matches!("compact".parse(), Ok(SerializationFormat::Compact)));
+        assert!(matches!("bincode".parse(), Ok(SerializationFormat::Compact)));
+        assert!(matches!("json".parse(), Ok(SerializationFormat::Json)));
+        assert!("cbor".parse::<SerializationFormat>().is_err());
+    }
+}
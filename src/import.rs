@@ -0,0 +1,268 @@
+use chrono::DateTime;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use swh_graph::{NodeType, SWHID};
+
+use crate::backend::GraphBackend;
+use crate::error::GraphError;
+use crate::graph::Graph;
+use crate::origin::MetricValue;
+use crate::parallel::*;
+
+/// Metrics computed by another pipeline (e.g. a license scanner), one row per origin, to
+/// be stored in the dynamic columns of the origins by [`import_columns`]
+pub struct MetricsTable {
+    /// Names of the imported columns, all but the key column
+    pub columns: Vec<String>,
+    /// Key of each row, an origin SWHID (`swh:1:ori:...`) or URL, and its value in each
+    /// column, `None` for empty or null cells
+    pub rows: Vec<(String, Vec<Option<MetricValue>>)>,
+}
+
+impl MetricsTable {
+    /// Read `path`, a Parquet file if its extension is `.parquet` and a CSV file with a
+    /// header line otherwise, the origins being identified by the column `key`
+    pub fn read(path: &Path, key: &str) -> Result<Self, GraphError> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("parquet") => read_parquet(path, key),
+            _ => read_csv(path, key),
+        }
+    }
+}
+
+fn invalid(path: &Path, reason: String) -> GraphError {
+    GraphError::InvalidMetricsTable { path: path.to_path_buf(), reason }
+}
+
+/// Position of `key` in `columns` and the names of the other columns
+fn split_key(path: &Path, columns: Vec<String>, key: &str) -> Result<(usize, Vec<String>), GraphError> {
+    let position = columns
+        .iter()
+        .position(|column| column == key)
+        .ok_or_else(|| invalid(path, format!("no key column {:?}", key)))?;
+    let mut columns = columns;
+    columns.remove(position);
+    Ok((position, columns))
+}
+
+/// Fields of a CSV record (RFC 4180: fields with commas, quotes or line breaks are
+/// quoted, quotes doubled), `None` if `record` ends within a quoted field
+fn parse_csv_record(record: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = record.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return None;
+    }
+    fields.push(field);
+    Some(fields)
+}
+
+/// Value of a CSV cell: an integer, a float, an RFC 3339 datetime or else text
+fn parse_cell(cell: &str) -> Option<MetricValue> {
+    let cell = cell.trim();
+    if cell.is_empty() {
+        return None;
+    }
+    Some(if let Ok(value) = cell.parse::<i64>() {
+        MetricValue::Int(value)
+    } else if let Ok(value) = cell.parse::<f64>() {
+        MetricValue::Float(value)
+    } else if let Ok(datetime) = DateTime::parse_from_rfc3339(cell) {
+        MetricValue::Timestamp(datetime.timestamp())
+    } else {
+        MetricValue::Text(cell.to_string())
+    })
+}
+
+fn read_csv(path: &Path, key: &str) -> Result<MetricsTable, GraphError> {
+    let mut lines = BufReader::new(File::open(path)?).lines().enumerate();
+    // Records span several lines when a quoted field has line breaks
+    let mut next_record = || -> Result<Option<(usize, Vec<String>)>, GraphError> {
+        let Some((index, line)) = lines.next() else {
+            return Ok(None);
+        };
+        let mut record = line?;
+        loop {
+            if let Some(fields) = parse_csv_record(&record) {
+                return Ok(Some((index + 1, fields)));
+            }
+            match lines.next() {
+                Some((_, line)) => {
+                    record.push('\n');
+                    record.push_str(&line?);
+                }
+                None => return Err(invalid(path, format!("line {}: unterminated quoted field", index + 1))),
+            }
+        }
+    };
+    let Some((_, header)) = next_record()? else {
+        return Err(invalid(path, "no header line".to_string()));
+    };
+    let header: Vec<String> = header.into_iter().map(|column| column.trim().to_string()).collect();
+    let width = header.len();
+    let (key_position, columns) = split_key(path, header, key)?;
+    let mut rows = Vec::new();
+    while let Some((line, mut fields)) = next_record()? {
+        if fields.len() == 1 && fields[0].trim().is_empty() {
+            continue;
+        }
+        if fields.len() != width {
+            return Err(invalid(path, format!("line {}: {} fields, expected {}", line, fields.len(), width)));
+        }
+        let key = fields.remove(key_position).trim().to_string();
+        rows.push((key, fields.iter().map(|cell| parse_cell(cell)).collect()));
+    }
+    Ok(MetricsTable { columns, rows })
+}
+
+#[cfg(feature = "parquet")]
+fn read_parquet(path: &Path, key: &str) -> Result<MetricsTable, GraphError> {
+    use arrow_array::RecordBatchReader;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)
+        .and_then(|builder| builder.build())
+        .map_err(|e| invalid(path, e.to_string()))?;
+    let header: Vec<String> = reader.schema().fields().iter().map(|field| field.name().clone()).collect();
+    let (key_position, columns) = split_key(path, header, key)?;
+    let mut rows = Vec::new();
+    for batch in reader {
+        let batch = batch.map_err(|e| invalid(path, e.to_string()))?;
+        let mut arrays = batch.columns().to_vec();
+        let keys = arrays.remove(key_position);
+        for row in 0..batch.num_rows() {
+            let key = match arrow_value(&keys, row).map_err(|e| invalid(path, e))? {
+                Some(MetricValue::Text(key)) => key,
+                None => continue,
+                Some(_) => return Err(invalid(path, format!("key column {:?} is not a string column", key))),
+            };
+            let values = arrays
+                .iter()
+                .map(|array| arrow_value(array, row))
+                .collect::<Result<_, _>>()
+                .map_err(|e| invalid(path, e))?;
+            rows.push((key, values));
+        }
+    }
+    Ok(MetricsTable { columns, rows })
+}
+
+#[cfg(not(feature = "parquet"))]
+fn read_parquet(path: &Path, _key: &str) -> Result<MetricsTable, GraphError> {
+    Err(invalid(path, "Parquet files need swh-test to be built with `--features parquet`".to_string()))
+}
+
+/// Value of a Parquet cell, for integer, float, boolean (0 or 1), string, timestamp and
+/// date columns
+#[cfg(feature = "parquet")]
+fn arrow_value(array: &arrow_array::ArrayRef, row: usize) -> Result<Option<MetricValue>, String> {
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::*;
+    use arrow_array::Array;
+    use arrow_schema::{DataType, TimeUnit};
+
+    if array.is_null(row) {
+        return Ok(None);
+    }
+    Ok(Some(match array.data_type() {
+        DataType::Int8 => MetricValue::Int(array.as_primitive::<Int8Type>().value(row).into()),
+        DataType::Int16 => MetricValue::Int(array.as_primitive::<Int16Type>().value(row).into()),
+        DataType::Int32 => MetricValue::Int(array.as_primitive::<Int32Type>().value(row).into()),
+        DataType::Int64 => MetricValue::Int(array.as_primitive::<Int64Type>().value(row)),
+        DataType::UInt8 => MetricValue::Int(array.as_primitive::<UInt8Type>().value(row).into()),
+        DataType::UInt16 => MetricValue::Int(array.as_primitive::<UInt16Type>().value(row).into()),
+        DataType::UInt32 => MetricValue::Int(array.as_primitive::<UInt32Type>().value(row).into()),
+        DataType::UInt64 => {
+            let value = array.as_primitive::<UInt64Type>().value(row);
+            MetricValue::Int(i64::try_from(value).map_err(|_| format!("{} overflows a 64-bit signed integer", value))?)
+        }
+        DataType::Float32 => MetricValue::Float(array.as_primitive::<Float32Type>().value(row).into()),
+        DataType::Float64 => MetricValue::Float(array.as_primitive::<Float64Type>().value(row)),
+        DataType::Boolean => MetricValue::Int(array.as_boolean().value(row).into()),
+        DataType::Utf8 => MetricValue::Text(array.as_string::<i32>().value(row).to_string()),
+        DataType::LargeUtf8 => MetricValue::Text(array.as_string::<i64>().value(row).to_string()),
+        DataType::Timestamp(TimeUnit::Second, _) => MetricValue::Timestamp(array.as_primitive::<TimestampSecondType>().value(row)),
+        DataType::Timestamp(TimeUnit::Millisecond, _) => {
+            MetricValue::Timestamp(array.as_primitive::<TimestampMillisecondType>().value(row).div_euclid(1_000))
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, _) => {
+            MetricValue::Timestamp(array.as_primitive::<TimestampMicrosecondType>().value(row).div_euclid(1_000_000))
+        }
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+            MetricValue::Timestamp(array.as_primitive::<TimestampNanosecondType>().value(row).div_euclid(1_000_000_000))
+        }
+        DataType::Date32 => MetricValue::Timestamp(i64::from(array.as_primitive::<Date32Type>().value(row)) * 86_400),
+        other => return Err(format!("unsupported column type {}", other)),
+    }))
+}
+
+/// Rows of a [`MetricsTable`] stored by [`import_columns`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ImportSummary {
+    /// Rows whose values were stored in the columns of their origin
+    pub imported: usize,
+    /// Rows whose key is not an origin of the graph, or of an origin which is not loaded
+    pub not_found: usize,
+    /// Rows whose key is neither an origin SWHID nor a URL
+    pub invalid: usize,
+}
+
+/// Store the values of each row of `table` in the dynamic columns of its origin, the
+/// columns being named after those of the table. Empty cells leave the column of the
+/// origin as it was. The origins are matched through their SWHID, the hash of their URL,
+/// so the table doesn't depend on the node ids of this graph export.
+pub fn import_columns<G>(graph: &Graph<G>, table: &MetricsTable) -> ImportSummary
+where
+    G: GraphBackend + Send + Sync,
+{
+    let inner = graph.inner_graph();
+    let outcomes: Vec<Option<bool>> = table
+        .rows
+        .par_iter()
+        .map(|(key, values)| {
+            let swhid = if key.starts_with("swh:") {
+                match key.parse::<SWHID>() {
+                    Ok(swhid) if swhid.node_type == NodeType::Origin => swhid,
+                    _ => return None,
+                }
+            } else {
+                SWHID::from_origin_url(key)
+            };
+            let stored = inner.node_id(&swhid).and_then(|id| {
+                graph.with_origin(id, |origin| {
+                    for (name, value) in table.columns.iter().zip(values) {
+                        if let Some(value) = value {
+                            origin.set_column(name.clone(), value.clone());
+                        }
+                    }
+                })
+            });
+            Some(stored.is_some())
+        })
+        .collect();
+    let mut summary = ImportSummary::default();
+    for outcome in outcomes {
+        match outcome {
+            Some(true) => summary.imported += 1,
+            Some(false) => summary.not_found += 1,
+            None => summary.invalid += 1,
+        }
+    }
+    graph.mark_origins_modified(summary.imported);
+    summary
+}
@@ -0,0 +1,344 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use crate::error::GraphError;
+use crate::progress::ProgressSink;
+use crate::utils::write_atomically;
+
+/// Job history kept by the server in its cache directory, see [`JobRegistry::persistent`]
+pub const JOBS_FILE: &str = "jobs.json";
+
+/// Most failed origins a job lists individually, the others being only counted
+const MAX_REPORTED_FAILURES: usize = 1000;
+
+/// Why an origin of a job could not be processed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// Not an origin of the served graph
+    NotFound,
+    /// Computing its metrics went wrong, e.g. a worker panicked on corrupted graph data
+    ComputeFailed,
+}
+
+/// An origin of a job which could not be processed
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Failure {
+    pub origin_id: usize,
+    pub status: FailureKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// A batch of origin computations triggered through the admin API
+pub struct Job {
+    pub id: u64,
+    /// What the job does, e.g. "recompute"
+    pub kind: String,
+    /// Number of origins the job has to process
+    pub total: usize,
+    /// What the job was submitted with, e.g. the origins to recompute
+    params: Value,
+    completed: AtomicUsize,
+    not_found: AtomicUsize,
+    compute_failed: AtomicUsize,
+    /// The first [`MAX_REPORTED_FAILURES`] origins which could not be processed
+    failures: Mutex<Vec<Failure>>,
+    created_at: DateTime<Utc>,
+    finished_at: Mutex<Option<DateTime<Utc>>>,
+    /// Cancelled through `DELETE /jobs/:id`; stops feeding and computing the job's origins
+    cancel: CancellationToken,
+    /// Still running when the server stopped, restored from the history as finished
+    interrupted: bool,
+}
+
+impl Job {
+    /// Record that one more origin of this job was processed
+    pub fn complete_one(&self) {
+        self.complete(1);
+    }
+
+    /// Record that `count` more origins of this job were processed
+    pub fn complete(&self, count: usize) {
+        let completed = self.completed.fetch_add(count, Ordering::Relaxed) + count;
+        if completed >= self.total {
+            self.finish();
+        }
+    }
+
+    /// Record that an origin of this job was processed but failed, so that the job goes
+    /// on with the others and reports it
+    pub fn fail(&self, failure: Failure) {
+        let counter = match failure.status {
+            FailureKind::NotFound => &self.not_found,
+            FailureKind::ComputeFailed => &self.compute_failed,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        {
+            let mut failures = self.failures.lock().unwrap();
+            if failures.len() < MAX_REPORTED_FAILURES {
+                failures.push(failure);
+            }
+        }
+        self.complete_one();
+    }
+
+    fn finish(&self) {
+        let mut finished_at = self.finished_at.lock().unwrap();
+        if finished_at.is_none() {
+            *finished_at = Some(Utc::now());
+        }
+    }
+
+    /// Stop the job. Returns `false` if it had already finished.
+    pub fn cancel(&self) -> bool {
+        let mut finished_at = self.finished_at.lock().unwrap();
+        if finished_at.is_some() {
+            return false;
+        }
+        self.cancel.cancel();
+        *finished_at = Some(Utc::now());
+        true
+    }
+
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    pub fn completed(&self) -> usize {
+        self.completed.load(Ordering::Relaxed)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished_at.lock().unwrap().is_some()
+    }
+
+    fn status(&self, finished_at: Option<DateTime<Utc>>) -> &'static str {
+        if self.cancel.is_cancelled() {
+            "cancelled"
+        } else if self.interrupted {
+            "interrupted"
+        } else if finished_at.is_some() {
+            "completed"
+        } else {
+            "running"
+        }
+    }
+
+    /// JSON representation served by the `/jobs` endpoints
+    pub fn to_json(&self) -> Value {
+        let finished_at = *self.finished_at.lock().unwrap();
+        json!({
+            "job_id": self.id,
+            "kind": self.kind,
+            "status": self.status(finished_at),
+            "params": self.params,
+            "total": self.total,
+            "completed": self.completed(),
+            "succeeded": self.succeeded(),
+            "not_found": self.not_found.load(Ordering::Relaxed),
+            "compute_failed": self.compute_failed.load(Ordering::Relaxed),
+            "failures": *self.failures.lock().unwrap(),
+            "created_at": self.created_at.to_rfc3339(),
+            "finished_at": finished_at.map(|t| t.to_rfc3339()),
+        })
+    }
+
+    /// Origins processed without failure
+    fn succeeded(&self) -> usize {
+        self.completed()
+            .saturating_sub(self.not_found.load(Ordering::Relaxed))
+            .saturating_sub(self.compute_failed.load(Ordering::Relaxed))
+    }
+
+    fn to_record(&self) -> JobRecord {
+        let finished_at = *self.finished_at.lock().unwrap();
+        JobRecord {
+            id: self.id,
+            kind: self.kind.clone(),
+            status: self.status(finished_at).to_string(),
+            params: self.params.clone(),
+            total: self.total,
+            completed: self.completed(),
+            not_found: self.not_found.load(Ordering::Relaxed),
+            compute_failed: self.compute_failed.load(Ordering::Relaxed),
+            failures: self.failures.lock().unwrap().clone(),
+            created_at: self.created_at.to_rfc3339(),
+            finished_at: finished_at.map(|t| t.to_rfc3339()),
+        }
+    }
+
+    /// Job saved as `record` by a previous run of the server. Jobs which were running then
+    /// are not resumed but marked as interrupted, finished at `restarted_at`.
+    fn from_record(record: JobRecord, restarted_at: DateTime<Utc>) -> Result<Self, String> {
+        let parse = |date: &str| {
+            DateTime::parse_from_rfc3339(date)
+                .map(|date| date.with_timezone(&Utc))
+                .map_err(|e| format!("job {}: invalid date {:?}: {}", record.id, date, e))
+        };
+        let created_at = parse(&record.created_at)?;
+        let finished_at = record.finished_at.as_deref().map(parse).transpose()?;
+        let cancel = CancellationToken::new();
+        if record.status == "cancelled" {
+            cancel.cancel();
+        }
+        Ok(Job {
+            id: record.id,
+            kind: record.kind,
+            total: record.total,
+            params: record.params,
+            completed: AtomicUsize::new(record.completed),
+            not_found: AtomicUsize::new(record.not_found),
+            compute_failed: AtomicUsize::new(record.compute_failed),
+            failures: Mutex::new(record.failures),
+            created_at,
+            finished_at: Mutex::new(Some(finished_at.unwrap_or(restarted_at))),
+            cancel,
+            interrupted: record.status == "interrupted" || finished_at.is_none(),
+        })
+    }
+}
+
+/// A [`Job`] as saved in the [`JOBS_FILE`], dates being RFC 3339 strings
+#[derive(Serialize, Deserialize)]
+struct JobRecord {
+    id: u64,
+    kind: String,
+    status: String,
+    #[serde(default)]
+    params: Value,
+    total: usize,
+    completed: usize,
+    #[serde(default)]
+    not_found: usize,
+    #[serde(default)]
+    compute_failed: usize,
+    #[serde(default)]
+    failures: Vec<Failure>,
+    created_at: String,
+    finished_at: Option<String>,
+}
+
+/// Lets a job count the origins processed for it through a [`Progress`](crate::progress::Progress)
+impl ProgressSink for Job {
+    fn add(&self, count: usize) {
+        self.complete(count);
+    }
+}
+
+/// Registry of all jobs submitted since the server started, or since the history was
+/// started if it is [persistent](Self::persistent)
+#[derive(Default)]
+pub struct JobRegistry {
+    next_id: AtomicU64,
+    jobs: Mutex<BTreeMap<u64, Arc<Job>>>,
+    /// Where the history is saved, if anywhere
+    path: Option<PathBuf>,
+    /// Last history written, not to rewrite it unchanged
+    saved: Mutex<String>,
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry saving its jobs to `path` (see [`Self::save`]), starting with the ones
+    /// saved there by a previous run. An unreadable history is set aside with a warning
+    /// rather than preventing the server from starting.
+    pub fn persistent(path: PathBuf) -> Result<Self, GraphError> {
+        let registry = JobRegistry { path: Some(path.clone()), ..Self::default() };
+        if !path.exists() {
+            return Ok(registry);
+        }
+        let saved = fs::read_to_string(&path)?;
+        let restarted_at = Utc::now();
+        let jobs = serde_json::from_str::<Vec<JobRecord>>(&saved)
+            .map_err(|e| e.to_string())
+            .and_then(|records| {
+                records.into_iter().map(|record| Job::from_record(record, restarted_at)).collect::<Result<Vec<_>, _>>()
+            });
+        match jobs {
+            Ok(jobs) => {
+                let mut registered = registry.jobs.lock().unwrap();
+                for job in jobs {
+                    registry.next_id.fetch_max(job.id, Ordering::Relaxed);
+                    registered.insert(job.id, Arc::new(job));
+                }
+            }
+            Err(e) => {
+                let mut aside = path.clone().into_os_string();
+                aside.push(".unreadable");
+                warn!("Ignoring unreadable job history {:?} (moved to {:?}): {}", path, aside, e);
+                fs::rename(&path, &aside)?;
+            }
+        }
+        Ok(registry)
+    }
+
+    /// Register a new job processing `total` origins, submitted with `params`
+    pub fn create(&self, kind: &str, total: usize, params: Value) -> Arc<Job> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let job = Arc::new(Job {
+            id,
+            kind: kind.to_string(),
+            total,
+            params,
+            completed: AtomicUsize::new(0),
+            not_found: AtomicUsize::new(0),
+            compute_failed: AtomicUsize::new(0),
+            failures: Mutex::new(Vec::new()),
+            created_at: Utc::now(),
+            finished_at: Mutex::new(None),
+            cancel: CancellationToken::new(),
+            interrupted: false,
+        });
+        if total == 0 {
+            job.finish();
+        }
+        self.jobs.lock().unwrap().insert(id, job.clone());
+        self.save_or_warn();
+        job
+    }
+
+    /// Write the jobs, with their progress, to the history file if the registry is
+    /// persistent and they changed since the last save. Returns whether it was written.
+    pub fn save(&self) -> Result<bool, GraphError> {
+        let Some(path) = &self.path else {
+            return Ok(false);
+        };
+        let records: Vec<JobRecord> = self.list().iter().map(|job| job.to_record()).collect();
+        let history = serde_json::to_string_pretty(&records).map_err(GraphError::serialization)?;
+        let mut saved = self.saved.lock().unwrap();
+        if *saved == history {
+            return Ok(false);
+        }
+        write_atomically(path, history.as_bytes())?;
+        *saved = history;
+        Ok(true)
+    }
+
+    /// [`Self::save`], only logging failures: losing the history must not fail a job
+    pub fn save_or_warn(&self) {
+        if let Err(e) = self.save() {
+            warn!("Failed to save the job history: {}", e);
+        }
+    }
+
+    pub fn get(&self, id: u64) -> Option<Arc<Job>> {
+        self.jobs.lock().unwrap().get(&id).cloned()
+    }
+
+    /// All jobs, oldest first
+    pub fn list(&self) -> Vec<Arc<Job>> {
+        self.jobs.lock().unwrap().values().cloned().collect()
+    }
+}
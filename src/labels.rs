@@ -0,0 +1,111 @@
+use std::borrow::Cow;
+use swh_graph::graph::NodeId;
+use swh_graph::labels::Permission;
+use swh_graph::NodeType;
+
+use crate::backend::{ArcLabel, LabelCapable};
+
+/// Branch of a snapshot, decoded from the label of a `snp -> *` arc
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Branch {
+    /// Raw name, e.g. `refs/heads/main`, not necessarily UTF-8
+    pub name: Vec<u8>,
+    pub target: NodeId,
+}
+
+impl Branch {
+    pub fn name_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.name)
+    }
+}
+
+/// Entry of a directory, decoded from the label of a `dir -> *` arc
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirEntry {
+    /// Raw file name, not necessarily UTF-8
+    pub name: Vec<u8>,
+    /// `None` in graph exports without permissions
+    pub permission: Option<Permission>,
+    pub target: NodeId,
+}
+
+/// Visit of an origin, decoded from the label of an `ori -> snp` arc
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OriginVisit {
+    pub snapshot: NodeId,
+    /// Seconds since the epoch
+    pub timestamp: u64,
+    /// Whether the visit was complete, partial visits may miss branches
+    pub full: bool,
+}
+
+/// Branches of a snapshot, sorted by name
+pub fn snapshot_branches<G: LabelCapable>(graph: &G, snapshot: NodeId) -> Vec<Branch> {
+    let mut branches: Vec<Branch> = graph
+        .labeled_arcs(snapshot)
+        .into_iter()
+        .flat_map(|(target, labels)| {
+            labels.into_iter().filter_map(move |label| match label {
+                ArcLabel::Branch { name } => Some(Branch { name, target }),
+                _ => None,
+            })
+        })
+        .collect();
+    branches.sort_unstable();
+    branches
+}
+
+/// Entries of a directory, sorted by name
+pub fn directory_entries<G: LabelCapable>(graph: &G, directory: NodeId) -> Vec<DirEntry> {
+    let mut entries: Vec<DirEntry> = graph
+        .labeled_arcs(directory)
+        .into_iter()
+        .flat_map(|(target, labels)| {
+            labels.into_iter().filter_map(move |label| match label {
+                ArcLabel::DirEntry { name, permission } => Some(DirEntry { name, permission, target }),
+                _ => None,
+            })
+        })
+        .collect();
+    entries.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    entries
+}
+
+/// Entry at `path` (`/`-separated names, e.g. `src/main.rs`) under the directory `root`,
+/// following one directory entry per name; `None` if there is none or `path` has no name
+pub fn path_entry<G: LabelCapable>(graph: &G, root: NodeId, path: &str) -> Option<DirEntry> {
+    let mut entry: Option<DirEntry> = None;
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        let directory = match &entry {
+            None => root,
+            Some(parent) if graph.node_type(parent.target) == NodeType::Directory => parent.target,
+            Some(_) => return None,
+        };
+        let found = graph.labeled_arcs(directory).into_iter().find_map(|(target, labels)| {
+            labels.into_iter().find_map(|label| match label {
+                ArcLabel::DirEntry { name: entry_name, permission } if entry_name == name.as_bytes() => {
+                    Some(DirEntry { name: entry_name, permission, target })
+                }
+                _ => None,
+            })
+        });
+        entry = Some(found?);
+    }
+    entry
+}
+
+/// Visits of an origin, oldest first
+pub fn origin_visits<G: LabelCapable>(graph: &G, origin: NodeId) -> Vec<OriginVisit> {
+    let mut visits: Vec<OriginVisit> = graph
+        .labeled_arcs(origin)
+        .into_iter()
+        .flat_map(|(snapshot, labels)| {
+            labels.into_iter().filter_map(move |label| match label {
+                ArcLabel::Visit { timestamp, full } => Some(OriginVisit { snapshot, timestamp, full }),
+                _ => None,
+            })
+        })
+        .collect();
+    visits.sort_unstable_by_key(|visit| (visit.timestamp, visit.snapshot));
+    visits
+}
@@ -0,0 +1,60 @@
+//! Origins of a Software Heritage graph export and their metrics, computed by walking the
+//! graph and kept in an origins cache. This library is shared by the `swh-test` and
+//! `swh-server` binaries, and by other Rust code embedding it. With `--features python`,
+//! it is also the `swh_origins` Python module.
+
+pub mod backend;
+pub mod cli;
+#[cfg(feature = "compute")]
+pub mod contributions;
+#[cfg(feature = "server")]
+pub mod dataset_manager;
+pub mod datasets;
+pub mod error;
+pub mod export;
+#[cfg(feature = "flight")]
+pub mod flight;
+#[cfg(feature = "server")]
+pub mod freeze;
+pub mod graph;
+pub mod import;
+#[cfg(feature = "server")]
+pub mod jobs;
+pub mod labels;
+pub mod logging;
+pub mod memory;
+pub mod memory_graph;
+pub mod node_list;
+pub mod origin;
+pub mod origin_list;
+#[cfg(any(feature = "flight", feature = "sql"))]
+pub mod origin_table;
+pub mod parallel;
+pub mod progress;
+#[cfg(feature = "compute")]
+pub mod provenance;
+pub mod pushgateway;
+#[cfg(feature = "python")]
+mod python;
+pub mod quantiles;
+#[cfg(feature = "server")]
+pub mod query;
+#[cfg(feature = "server")]
+pub mod redis;
+#[cfg(feature = "server")]
+pub mod request_metrics;
+#[cfg(feature = "server")]
+pub mod schedule;
+pub mod scratch;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "sql")]
+pub mod sql;
+#[cfg(feature = "compute")]
+pub mod stats;
+#[cfg(feature = "server")]
+pub mod tags;
+pub mod urls;
+pub mod utils;
+#[cfg(feature = "server")]
+pub mod worker;
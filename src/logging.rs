@@ -0,0 +1,165 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::EnvFilter;
+
+use crate::error::GraphError;
+
+/// Shape of the log lines
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, with the event and span fields as keys, for Loki or ELK
+    Json,
+}
+
+/// When [`LogFile`] starts a new file
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogRotation {
+    /// Every day, suffixing the files with the date (`swh.log.2024-05-17`)
+    Daily,
+    /// Every hour, suffixing the files with the date and hour
+    Hourly,
+    /// Once the file reaches the maximum size, renaming the older ones `swh.log.1`,
+    /// `swh.log.2`, ...
+    Size,
+    /// Never, appending to a single file
+    Never,
+}
+
+/// File the logs are written to instead of the standard output, so long runs on remote
+/// machines keep their logs without shell redirection
+#[derive(Clone, Debug)]
+pub struct LogFile {
+    pub path: PathBuf,
+    pub rotation: LogRotation,
+    /// Size in bytes after which [`LogRotation::Size`] rotates
+    pub max_size: u64,
+    /// Files kept, the current one included; older ones are deleted
+    pub max_files: usize,
+}
+
+impl LogFile {
+    fn writer(&self) -> Result<Box<dyn Write + Send>, GraphError> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("invalid log file {:?}", self.path));
+        let name = self.path.file_name().ok_or_else(invalid)?.to_string_lossy().into_owned();
+        let directory = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let rotation = match self.rotation {
+            LogRotation::Daily => Rotation::DAILY,
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Size => return Ok(Box::new(SizeRotatingFile::open(&self.path, self.max_size, self.max_files)?)),
+            LogRotation::Never => Rotation::NEVER,
+        };
+        let appender = RollingFileAppender::builder()
+            .rotation(rotation)
+            .filename_prefix(name)
+            .max_log_files(self.max_files.max(1))
+            .build(directory)
+            .map_err(io::Error::other)?;
+        Ok(Box::new(appender))
+    }
+}
+
+/// Check `RUST_LOG`-style directives, e.g. `info,swh_server::server=debug,swh_graph=warn`
+pub fn parse_log_filter(directives: &str) -> Result<String, String> {
+    EnvFilter::try_new(directives).map(|_| directives.to_string()).map_err(|e| e.to_string())
+}
+
+/// Verbosity without `--log-filter` nor `RUST_LOG`: the progress of the library, warnings
+/// of its dependencies
+const DEFAULT_LOG_FILTER: &str = "warn,swh_origins=info";
+
+/// Install the global subscriber. Logs according to `directives` (see
+/// [`parse_log_filter`]) if given, otherwise to `RUST_LOG` ([`DEFAULT_LOG_FILTER`] if
+/// unset), to the standard output or to `file`.
+///
+/// Logs written to a file go through a background thread, which flushes them when the
+/// returned guard is dropped: keep it until the end of `main`.
+pub fn init(format: LogFormat, directives: Option<&str>, file: Option<&LogFile>) -> Result<Option<WorkerGuard>, GraphError> {
+    let filter = match directives {
+        Some(directives) => EnvFilter::try_new(directives).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER)),
+    };
+    let (writer, guard) = match file {
+        Some(file) => {
+            let (writer, guard) = tracing_appender::non_blocking(file.writer()?);
+            (Some(writer), Some(guard))
+        }
+        None => (None, None),
+    };
+    let builder = tracing_subscriber::fmt().with_env_filter(filter).with_ansi(writer.is_none());
+    // `BoxMakeWriter` erases whether logs go to the standard output or to the file
+    let builder = builder.with_writer(match writer {
+        Some(writer) => tracing_subscriber::fmt::writer::BoxMakeWriter::new(writer),
+        None => tracing_subscriber::fmt::writer::BoxMakeWriter::new(io::stdout),
+    });
+    match format {
+        LogFormat::Text => builder.init(),
+        // Flattened so that `status` or `origin_id` are top-level keys, the request span
+        // giving its fields to every event logged while serving it
+        LogFormat::Json => builder.json().flatten_event(true).with_current_span(true).with_span_list(false).init(),
+    }
+    Ok(guard)
+}
+
+/// Log file renamed `<path>.1` once it reaches `max_size` bytes, the previous `<path>.1`
+/// becoming `<path>.2` and so on up to `max_files - 1`
+struct SizeRotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl SizeRotatingFile {
+    fn open(path: &Path, max_size: u64, max_files: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(SizeRotatingFile { path: path.to_path_buf(), max_size, max_files, file, size })
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        for index in (1..self.max_files.saturating_sub(1)).rev() {
+            let older = self.rotated(index);
+            if older.exists() {
+                fs::rename(older, self.rotated(index + 1))?;
+            }
+        }
+        if self.max_files > 1 {
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+        self.file = File::create(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for SizeRotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
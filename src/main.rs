@@ -1,35 +1,393 @@
-use crate::graph::{Graph, SerializationFormat};
+use swh_origins::cli::Command;
+use swh_origins::contributions::{write_contributions, PersonTotals, PERSON_COMMITS_FILE};
+use swh_origins::datasets::diff_origins;
+use swh_origins::export::{ExportFormat, OriginExport};
+use swh_origins::logging::{self, parse_log_filter, LogFile, LogFormat, LogRotation};
+use swh_origins::node_list::{write_node_list, NodeListFormat};
+use swh_origins::import::{import_columns, MetricsTable};
+use swh_origins::graph::{read_origins_cache, Graph, SampleStrategy, SerializationFormat, Stratum};
+use swh_origins::error::GraphError;
+use swh_origins::origin_list::OriginList;
+use swh_origins::origin::{enable_snapshot_memo, parse_as_of, set_origin_node_budget, set_slow_origin_threshold, skip_over_budget, snapshot_memo_hits, ComputePolicy, Eligibility, OriginFilter};
+use swh_origins::provenance::{assign_clusters, assign_dedup, RevisionOrigins, SharedContents, REVISION_ORIGINS_FILE, SHARED_CONTENTS_FILE};
+use swh_origins::stats::{GraphStats, GRAPH_STATS_FILE};
+use swh_origins::urls::{UrlFilter, UrlPattern};
+use swh_origins::utils::set_visit_throttle;
+use tokio_util::sync::CancellationToken;
+use swh_origins::progress::{load_stage, LoadProgress, LoadStage, Progress, ProgressSink};
+use swh_origins::pushgateway::Pushgateway;
 use rayon::prelude::*;
-use swh_graph::{graph::SwhUnidirectionalGraph, mph::DynMphf};
+use swh_graph::{graph::{NodeId, SwhUnidirectionalGraph}, mph::DynMphf};
 use std::path::PathBuf;
-use indicatif::{ProgressBar, ProgressStyle};
-use std::sync::{Arc, Mutex};
-use std::collections::HashSet;
-use std::time::{Duration, Instant};
-use chrono;
+use indicatif::ProgressBar;
+use std::sync::Arc;
+use std::time::Duration;
+use clap::Parser;
 
-mod graph;
-mod origin;
-mod utils;
+
+/// CLI arguments for the origin metrics computation
+#[derive(Parser, Debug)]
+#[command(name = "swh-test", author, version, about, long_about = None)]
+struct ComputeArgs {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Compute origins in chunks written straight to the cache instead of loading them all
+    /// in memory, for machines which can't hold every origin at once
+    #[arg(long)]
+    streaming: bool,
+
+    /// Also publish the computed origin records to this file or named pipe as they are
+    /// produced, e.g. read by a NATS client or by `psql`. With the `postgres` feature, a
+    /// `postgres://` connection string upserts them directly; with the `kafka` feature,
+    /// `kafka://<brokers>/<topic>` publishes them to a Kafka topic
+    #[arg(long)]
+    export_stream: Option<PathBuf>,
+
+    /// Format of `--export-stream`: JSON lines, or batches of upserts in a `psql` script
+    #[arg(long, value_enum, default_value = "json")]
+    export_format: ExportFormat,
+
+    /// Number of records written at a time to `--export-stream`
+    #[arg(long, default_value = "1000")]
+    export_batch_size: usize,
+
+    /// Table upserted with `--export-format postgres`, created if missing
+    #[arg(long, default_value = "origins")]
+    export_table: String,
+
+    /// Push the progress of the origins computation (processed count, rate, failures) to
+    /// this Prometheus Pushgateway, e.g. `http://localhost:9091`
+    #[arg(long)]
+    pushgateway: Option<String>,
+
+    /// Job name of the metrics pushed to `--pushgateway`
+    #[arg(long, default_value = "swh-test")]
+    pushgateway_job: String,
+
+    /// Seconds between two pushes to `--pushgateway`
+    #[arg(long, default_value = "60")]
+    pushgateway_interval: u64,
+
+    /// Number of origins held in memory at a time in streaming mode
+    #[arg(long, default_value = "1000000")]
+    chunk_size: usize,
+
+    /// Also compute the structure of each origin (node counts by type, arcs, max
+    /// out-degree), which takes another traversal of every origin
+    #[arg(long)]
+    structure: bool,
+
+    /// Also compute when each origin's commits were made in their committers' local time
+    /// (hour of the day) and from how many timezones, which takes another traversal
+    #[arg(long)]
+    working_hours: bool,
+
+    /// Also compute how bursty each origin's weekly commit counts are, which takes another
+    /// traversal
+    #[arg(long)]
+    burstiness: bool,
+
+    /// Also count the commits reachable from the `HEAD` branch of each origin's latest
+    /// snapshot, which takes another (shorter) traversal
+    #[arg(long)]
+    default_branch: bool,
+
+    /// Also detect the ecosystems of each origin (`rust`, `javascript`, `java`, `python`,
+    /// `go`) from the manifest files at the root of its head revision
+    #[arg(long)]
+    ecosystems: bool,
+
+    /// Store the columns of this CSV (with a header line) or Parquet file, computed by
+    /// another pipeline, in the dynamic columns of the origins and save the cache, to be
+    /// served by the API next to the metrics
+    #[arg(long)]
+    import_metrics: Option<PathBuf>,
+
+    /// Column of the `--import-metrics` file identifying the origins, by SWHID or URL
+    #[arg(long, default_value = "swhid", requires = "import_metrics")]
+    import_key: String,
+
+    /// Also build the revision to origins multimap, see `provenance.rs`
+    #[arg(long)]
+    revision_origins: bool,
+
+    /// Cluster origins sharing a root revision, from the multimap built with
+    /// `--revision-origins` or already in the cache
+    #[arg(long)]
+    clusters: bool,
+
+    /// Also compute the share of each origin's contents found in other origins, from the
+    /// contents of the whole corpus (`shared_contents.bin` in the cache directory, built
+    /// unless it is there), which takes two traversals of every origin down to contents
+    #[arg(long)]
+    dedup: bool,
+
+    /// Log origins whose metrics take longer than this many milliseconds (0 disables)
+    #[arg(long, default_value = "10000")]
+    slow_origin_ms: u64,
+
+    /// Leave unknown the metrics of origins whose traversal would visit more than this
+    /// many nodes (0 disables)
+    #[arg(long, default_value = "0")]
+    max_nodes_per_origin: usize,
+
+    /// Reuse the commit and committer counts of a snapshot for the other origins whose
+    /// latest snapshot it is, keeping those of up to this many snapshots (0 disables)
+    #[arg(long, default_value = "0")]
+    snapshot_memo: usize,
+
+    /// Visit at most this many graph nodes per second over all threads (0 disables), to
+    /// keep the page cache of a machine shared with other graph users from being thrashed
+    #[arg(long, default_value = "0")]
+    max_visits_per_second: u64,
+
+    /// Also write the committer to origin bipartite graph, weighted by commits, to this CSV
+    /// file, or Parquet file if its extension is `.parquet`, see `contributions.rs`
+    #[arg(long)]
+    contributions: Option<PathBuf>,
+
+    /// Also add up the commits of each person across all origins, saved in the cache
+    /// directory for the server's `/persons/top`
+    #[arg(long)]
+    person_commits: bool,
+
+    /// Also write the origins listed by `/origins` (kept by the URL filters, origin list
+    /// and eligibility criteria) to this file, to feed them to other graph pipelines
+    #[arg(long)]
+    export_nodes: Option<PathBuf>,
+
+    /// Layout of `--export-nodes`
+    #[arg(long, value_enum, default_value = "node-ids", requires = "export_nodes")]
+    node_list_format: NodeListFormat,
+
+    /// Also write the metrics of every origin as of this date (`YYYY-MM-DD`, RFC 3339 or
+    /// seconds since the epoch) to `--as-of-output`, ignoring later revisions
+    #[arg(long, value_parser = parse_as_of, requires = "as_of_output")]
+    as_of: Option<i64>,
+
+    /// CSV file written by `--as-of`
+    #[arg(long, requires = "as_of")]
+    as_of_output: Option<PathBuf>,
+
+    /// Also save a sample of this many origins (per stratum with `--stratify-by`) next
+    /// to the origins cache
+    #[arg(long)]
+    sample: Option<usize>,
+
+    /// Pick `--sample` origins in each stratum instead of among all origins
+    #[arg(long, value_enum, requires = "sample")]
+    stratify_by: Option<Stratum>,
+
+    /// Only compute origins whose URL matches one of these glob patterns (repeatable),
+    /// e.g. `github.com/*`. Patterns without `/` match the host, e.g. `*.gitlab.io`.
+    #[arg(long)]
+    allow_url: Vec<UrlPattern>,
+
+    /// Skip origins whose URL matches one of these glob patterns (repeatable), e.g.
+    /// `*.googlesource.com`, even if allowed
+    #[arg(long)]
+    block_url: Vec<UrlPattern>,
+
+    /// Only compute origins hosted on one of these forges (repeatable), e.g. `github.com`
+    #[arg(long)]
+    forge: Vec<String>,
+
+    /// Also compute origins without snapshot, which have no metric
+    #[arg(long)]
+    allow_no_snapshot: bool,
+
+    /// Only compute the origins listed in this file, one node id, origin SWHID or URL per
+    /// line, e.g. to reproduce a published dataset
+    #[arg(long)]
+    origins_file: Option<PathBuf>,
+
+    /// Fewest commits an origin needs to be listed by `/origins`
+    #[arg(long, default_value = "1")]
+    min_commits: u32,
+
+    /// Also list origins none of whose head revisions has a committer date in `/origins`
+    #[arg(long)]
+    allow_undated: bool,
+
+    /// Instead of computing anything, compare the origins caches of two graph exports and
+    /// print the new, disappeared and changed origins as JSON
+    #[arg(long, num_args = 2, value_names = ["OLD_CACHE", "NEW_CACHE"])]
+    diff_caches: Option<Vec<PathBuf>>,
+
+    /// Maximum number of origins of each kind listed by `--diff-caches`
+    #[arg(long, default_value = "1000")]
+    diff_limit: usize,
+
+    /// Instead of computing anything, write the sub-graph reachable from `--origin-ids` to
+    /// this directory as CSV node and edge lists, see `Graph::extract_subgraph`
+    #[arg(long, requires = "origin_ids")]
+    extract_subgraph: Option<PathBuf>,
+
+    /// Origins extracted by `--extract-subgraph`, comma-separated node ids
+    #[arg(long, value_delimiter = ',')]
+    origin_ids: Vec<usize>,
+
+    /// Instead of computing anything, print the node-type and out-degree statistics of
+    /// the graph as JSON (also saved in the cache directory)
+    #[arg(long)]
+    graph_stats: bool,
+
+    /// Format of the logs
+    #[arg(long, value_enum, default_value = "text")]
+    log_format: LogFormat,
+
+    /// Log verbosity per module as `RUST_LOG`-style directives, e.g.
+    /// `info,swh_test::graph=debug,swh_graph=warn`, overriding `RUST_LOG`
+    #[arg(long, value_parser = parse_log_filter)]
+    log_filter: Option<String>,
+
+    /// Write the logs to this file instead of the standard output, rotated according to
+    /// `--log-rotation`
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+
+    /// When to start a new log file
+    #[arg(long, value_enum, default_value = "daily")]
+    log_rotation: LogRotation,
+
+    /// Size in MB after which `--log-rotation size` starts a new log file
+    #[arg(long, default_value = "100")]
+    log_max_size: u64,
+
+    /// Log files kept, older ones being deleted
+    #[arg(long, default_value = "7")]
+    log_max_files: usize,
+}
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = ComputeArgs::parse();
+    if let Some(command) = &args.command {
+        command.run::<ComputeArgs>()?;
+        return Ok(());
+    }
+    let log_file = args.log_file.clone().map(|path| LogFile {
+        path,
+        rotation: args.log_rotation,
+        max_size: args.log_max_size * 1024 * 1024,
+        max_files: args.log_max_files,
+    });
+    let _log_guard = logging::init(args.log_format, args.log_filter.as_deref(), log_file.as_ref())?;
+    set_slow_origin_threshold(Duration::from_millis(args.slow_origin_ms));
+    set_origin_node_budget(args.max_nodes_per_origin);
+    if args.snapshot_memo > 0 {
+        enable_snapshot_memo(args.snapshot_memo);
+    }
+    if args.max_visits_per_second > 0 {
+        set_visit_throttle(args.max_visits_per_second);
+    }
+    if let Some(caches) = &args.diff_caches {
+        let old = read_origins_cache(&caches[0], SerializationFormat::Compact)?;
+        let new = read_origins_cache(&caches[1], SerializationFormat::Compact)?;
+        let diff = diff_origins(&old, &new);
+        println!("{}", serde_json::to_string_pretty(&diff.to_json(args.diff_limit))?);
+        return Ok(());
+    }
     let graph_path = "/mnt/graph_temp/graph";
     let _base_path: PathBuf = graph_path.into();
       // Get origins (will automatically load if not cached)
 
     //#swh_graph::graph::load_full::<swh_graph::mph::DynMphf>(&base_path).unwrap()
-    let internal_graph = SwhUnidirectionalGraph::new(graph_path)?.load_all_properties::<DynMphf>()?.load_labels()?;
+    let progress = LoadProgress::default();
+    let graph = load_stage(&progress, LoadStage::LoadingGraph, || SwhUnidirectionalGraph::new(graph_path))?;
+    let graph = graph.init_properties();
+    let graph = load_stage(&progress, LoadStage::LoadingProperties("maps"), || graph.load_properties(|p| p.load_maps::<DynMphf>()))?;
+    let graph = load_stage(&progress, LoadStage::LoadingProperties("timestamps"), || graph.load_properties(|p| p.load_timestamps()))?;
+    let graph = load_stage(&progress, LoadStage::LoadingProperties("persons"), || graph.load_properties(|p| p.load_persons()))?;
+    let graph = load_stage(&progress, LoadStage::LoadingProperties("contents"), || graph.load_properties(|p| p.load_contents()))?;
+    let graph = load_stage(&progress, LoadStage::LoadingProperties("strings"), || graph.load_properties(|p| p.load_strings()))?;
+    let graph = load_stage(&progress, LoadStage::LoadingProperties("label names"), || graph.load_properties(|p| p.load_label_names()))?;
+    let internal_graph = load_stage(&progress, LoadStage::LoadingLabels, || graph.load_labels())?;
     
 
-    // Option 2: Use Bincode serialization (faster, more compact)
-    let mut graph = Graph::with_serialization_format(
-        "./data",
-        internal_graph,
-        SerializationFormat::Bincode,
-    );
+    // Compact cache, much faster to load than JSON
+    let mut builder = Graph::builder("./data")
+        .serialization(SerializationFormat::Compact)
+        .node_type_index(true)
+        .filter(OriginFilter {
+            require_snapshot: !args.allow_no_snapshot,
+            urls: UrlFilter { allow: args.allow_url.clone(), block: args.block_url.clone() },
+            forges: args.forge.clone(),
+            eligibility: Eligibility { min_commits: args.min_commits, require_commit_date: !args.allow_undated },
+        });
+    if let Some(path) = &args.origins_file {
+        builder = builder.origin_list(OriginList::read(path)?);
+    }
+    let mut graph = builder.build(internal_graph);
+
+    if let Some(output_dir) = &args.extract_subgraph {
+        let (nodes, arcs) = graph.extract_subgraph(&args.origin_ids, output_dir, &CancellationToken::new())?;
+        println!("✅ Extracted {} nodes and {} arcs from {} origins to {:?}", nodes, arcs, args.origin_ids.len(), output_dir);
+        return Ok(());
+    }
+
+    if args.graph_stats {
+        let pb = Arc::new(ProgressBar::new(graph.stats().0 as u64));
+        pb.set_message("Scanning nodes");
+        let counter = Progress::new().with(pb.clone());
+        let in_degree: Option<fn(NodeId) -> usize> = None;
+        let stats = GraphStats::load_or_compute(
+            graph.inner_graph().as_ref(),
+            &graph.cache_path(GRAPH_STATS_FILE),
+            in_degree,
+            &counter,
+            &CancellationToken::new(),
+        )?;
+        pb.finish_and_clear();
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    if let Some(path) = &args.import_metrics {
+        let table = MetricsTable::read(path, &args.import_key)?;
+        println!("Importing {} columns of {} rows from {:?}", table.columns.len(), table.rows.len(), path);
+        graph.get_origins_mut()?;
+        let summary = import_columns(&graph, &table);
+        graph.save_origins_to_file()?;
+        println!(
+            "✅ Imported the columns of {} origins ({} not in the graph or not loaded, {} invalid keys)",
+            summary.imported, summary.not_found, summary.invalid
+        );
+        return Ok(());
+    }
+
+    let export = args
+        .export_stream
+        .as_deref()
+        .map(|path| OriginExport::create(path, args.export_format, &args.export_table, args.export_batch_size))
+        .transpose()?;
+    let gateway = args
+        .pushgateway
+        .as_deref()
+        .map(|url| Pushgateway::new(url, &args.pushgateway_job).map(Arc::new))
+        .transpose()?;
+    if let Some(gateway) = &gateway {
+        gateway.spawn(Duration::from_secs(args.pushgateway_interval.max(1)));
+    }
+    if args.streaming {
+        println!("Streaming origin computation in chunks of {}", args.chunk_size);
+        let sink = gateway.clone().map(|gateway| gateway as Arc<dyn ProgressSink>);
+        let written = graph.stream_compute_origins_to_file(args.chunk_size, export.as_ref(), sink)?;
+        if let Some(export) = &export {
+            println!("✅ Published {} origin records", export.finish()?);
+        }
+        if let Some(gateway) = &gateway {
+            gateway.finish();
+        }
+        println!("✅ All {} origin statistics computed and saved successfully!", written);
+        return Ok(());
+    }
     
     let origins = graph.get_origins_mut()?;
     println!("Number of origins to process: {}", origins.len());
+    if let Some(gateway) = &gateway {
+        gateway.set_total(origins.len());
+    }
 
     //graph.save_n_random_origins_to_file(30000000).ok();
     //graph.filter_n_first_origins(10000000);
@@ -86,16 +444,155 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     pb.set_message("Processing origins");
     
     //origins.par_iter_mut().take(1000).for_each(|o| {
-    origins.par_iter_mut().for_each(|o| {
-        o.compute_data();
-        pb.inc(1);
-    });
+    let mut counter = Progress::new().with(pb.clone());
+    if let Some(gateway) = &gateway {
+        counter = counter.with(gateway.clone());
+    }
+    origins.par_iter().try_for_each_init(|| counter.batch(), |batch, o| -> Result<(), GraphError> {
+        skip_over_budget(o.compute_data(), ())?;
+        if args.structure {
+            skip_over_budget(o.structure(ComputePolicy::ComputeIfMissing), None)?;
+        }
+        if args.working_hours {
+            skip_over_budget(o.working_hours(ComputePolicy::ComputeIfMissing), None)?;
+        }
+        if args.burstiness {
+            skip_over_budget(o.burstiness(ComputePolicy::ComputeIfMissing), None)?;
+        }
+        if args.default_branch {
+            skip_over_budget(o.default_branch_commits(ComputePolicy::ComputeIfMissing), None)?;
+        }
+        if args.ecosystems {
+            o.ecosystems(ComputePolicy::ComputeIfMissing)?;
+        }
+        if let Some(gateway) = &gateway {
+            if !o.has_computed_metrics() {
+                gateway.add_failures(1);
+            }
+        }
+        if let Some(export) = &export {
+            export.publish(&o.to_data());
+        }
+        batch.inc();
+        Ok(())
+    })?;
+    if let Some(export) = &export {
+        println!("✅ Published {} origin records", export.finish()?);
+    }
     
     // pb.finish_with_message("✅ All origin statistics computed successfully!");
 
+    if let Some(hits) = snapshot_memo_hits() {
+        println!("✅ Reused the traversal of a shared snapshot for {} origins", hits);
+    }
     graph.save_origins_to_file()?;
     println!("✅ All origin statistics computed and saved successfully!");
 
+    let backend = graph.inner_graph();
+    // Persist the /origins eligibility filter now that every metric is known
+    let eligible = graph.get_eligible_origin_ids()?;
+    println!("✅ Saved {} eligible origins", eligible.len());
+    if let Some(path) = &args.export_nodes {
+        let written = write_node_list(backend.as_ref(), eligible, path, args.node_list_format)?;
+        println!("✅ Wrote {} origin nodes to {:?}", written, path);
+    }
+    let mut multimap = None;
+    if args.revision_origins {
+        println!("\nBuilding the revision to origins multimap...");
+        let pb = Arc::new(ProgressBar::new(graph.loaded_origins().len() as u64));
+        let counter = Progress::new().with(pb.clone());
+        if let Some(built) = RevisionOrigins::build(&graph, &counter, &CancellationToken::new())? {
+            pb.finish();
+            let path = graph.cache_path(REVISION_ORIGINS_FILE);
+            built.save(&path)?;
+            println!("✅ Saved {} revisions in {} origins to {:?}", built.revision_count(), built.pair_count(), path);
+            multimap = Some(built);
+        }
+    }
+
+    if let Some(path) = &args.contributions {
+        println!("\nWriting the committer to origin graph...");
+        let pb = Arc::new(ProgressBar::new(graph.loaded_origins().len() as u64));
+        let counter = Progress::new().with(pb.clone());
+        let edges = write_contributions(&graph, path, &counter, &CancellationToken::new())?;
+        pb.finish();
+        println!("✅ Saved {} committer to origin edges to {:?}", edges, path);
+    }
+
+    if args.person_commits {
+        println!("\nAdding up the commits of each person...");
+        let pb = Arc::new(ProgressBar::new(graph.loaded_origins().len() as u64));
+        let counter = Progress::new().with(pb.clone());
+        if let Some(totals) = PersonTotals::build(&graph, &counter, &CancellationToken::new())? {
+            pb.finish();
+            let path = graph.cache_path(PERSON_COMMITS_FILE);
+            totals.save(&path)?;
+            println!("✅ Saved the commits of {} persons to {:?}", totals.len(), path);
+        }
+    }
+
+    if let (Some(as_of), Some(path)) = (args.as_of, &args.as_of_output) {
+        println!("\nComputing metrics as of {}...", as_of);
+        let pb = Arc::new(ProgressBar::new(graph.loaded_origins().len() as u64));
+        let counter = Progress::new().with(pb.clone());
+        let written = graph.write_metrics_as_of(as_of, path, &counter, &CancellationToken::new())?;
+        pb.finish();
+        println!("✅ Saved the metrics of {} origins as of {} to {:?}", written, as_of, path);
+    }
+
+    if let Some(n) = args.sample {
+        let strategy = match args.stratify_by {
+            Some(stratum) => SampleStrategy::Stratified { stratum, per_stratum: n },
+            None => SampleStrategy::Uniform(n),
+        };
+        for group in graph.save_sample_to_file(strategy)? {
+            println!("  {}: {} of {} origins", group.stratum, group.origins.len(), group.population);
+        }
+    }
+
+    if args.clusters {
+        let multimap = match multimap {
+            Some(multimap) => multimap,
+            None => RevisionOrigins::load(&graph.cache_path(REVISION_ORIGINS_FILE))?,
+        };
+        let clusters = assign_clusters(&mut graph, &multimap)?;
+        graph.save_origins_to_file()?;
+        println!("✅ Assigned origins to {} clusters", clusters);
+    }
+
+    if args.dedup {
+        let path = graph.cache_path(SHARED_CONTENTS_FILE);
+        let shared = if path.exists() {
+            Some(SharedContents::load(&path)?)
+        } else {
+            println!("\nFinding the contents shared by origins...");
+            let pb = Arc::new(ProgressBar::new(graph.loaded_origins().len() as u64));
+            let counter = Progress::new().with(pb.clone());
+            let built = SharedContents::build(&graph, &counter, &CancellationToken::new())?;
+            pb.finish();
+            if let Some(built) = &built {
+                built.save(&path)?;
+                println!("✅ Saved {} shared contents to {:?}", built.len(), path);
+            }
+            built
+        };
+        if let Some(shared) = shared {
+            println!("\nCounting the shared contents of each origin...");
+            let pb = Arc::new(ProgressBar::new(graph.loaded_origins().len() as u64));
+            let counter = Progress::new().with(pb.clone());
+            if let Some(computed) = assign_dedup(&graph, &shared, &counter, &CancellationToken::new())? {
+                pb.finish();
+                graph.save_origins_to_file()?;
+                println!("✅ Computed the deduplication ratio of {} origins", computed);
+            }
+        }
+    }
+
+    println!("Memory: {}", graph.memory_usage());
+    if let Some(gateway) = &gateway {
+        gateway.finish();
+    }
+
     
 
     Ok(())
@@ -1,9 +1,4 @@
-mod graph;
-mod origin;
-mod server;
-mod utils;
-
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    server::create_server().await
+    Ok(swh_origins::server::create_server().await?)
 }
@@ -0,0 +1,136 @@
+use serde_json::{json, Value};
+use std::fmt;
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Estimated memory footprint of the server's structures, for sizing machines.
+///
+/// Structure sizes are estimates from lengths and capacities, allocator overhead not
+/// included. The compressed graph itself is memory-mapped and only shows up in the RSS.
+pub struct MemoryUsage {
+    /// Resident set size of the process, `None` where `/proc` is not available
+    pub rss_bytes: Option<u64>,
+    pub nodes: usize,
+    pub arcs: usize,
+    /// Number of origins in memory
+    pub origins: usize,
+    /// The origins vector itself
+    pub origins_bytes: usize,
+    /// URLs owned by the origins
+    pub urls_bytes: usize,
+    /// Ids of the origins served by `/origins`, if computed
+    pub eligible_origins_bytes: usize,
+}
+
+/// Resident set size of this process in bytes, read from `/proc/self/status` (Linux only)
+pub fn resident_set_size() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Shedding stops once the RSS is back under this share of the limit, so that it doesn't
+/// flap around the limit
+const RELIEF_RATIO: f64 = 0.9;
+
+/// Whether the process uses more memory than it is allowed to, to degrade gracefully
+/// (rejecting heavy requests, pausing background work) before the OOM killer steps in
+pub struct MemoryPressure {
+    /// RSS limit in bytes, 0 disables shedding
+    limit_bytes: u64,
+    shedding: AtomicBool,
+}
+
+impl MemoryPressure {
+    pub fn new(limit_bytes: u64) -> Self {
+        MemoryPressure { limit_bytes, shedding: AtomicBool::new(false) }
+    }
+
+    pub fn limit_bytes(&self) -> u64 {
+        self.limit_bytes
+    }
+
+    pub fn is_shedding(&self) -> bool {
+        self.shedding.load(Ordering::Relaxed)
+    }
+
+    /// Compare the RSS with the limit. Returns whether shedding started (`Some(true)`) or
+    /// stopped (`Some(false)`) with this check, along with the RSS.
+    pub fn check(&self) -> (Option<bool>, Option<u64>) {
+        let rss = resident_set_size();
+        let Some(bytes) = rss.filter(|_| self.limit_bytes > 0) else {
+            return (None, rss);
+        };
+        let shedding = self.is_shedding();
+        let changed = if !shedding && bytes > self.limit_bytes {
+            Some(true)
+        } else if shedding && (bytes as f64) < self.limit_bytes as f64 * RELIEF_RATIO {
+            Some(false)
+        } else {
+            None
+        };
+        if let Some(shedding) = changed {
+            self.shedding.store(shedding, Ordering::Relaxed);
+        }
+        (changed, rss)
+    }
+}
+
+impl MemoryUsage {
+    /// Sum of the estimated structure sizes
+    pub fn estimated_bytes(&self) -> usize {
+        self.origins_bytes + self.urls_bytes + self.eligible_origins_bytes
+    }
+
+    /// JSON representation served by `/admin/memory`
+    pub fn to_json(&self) -> Value {
+        json!({
+            "rss_bytes": self.rss_bytes,
+            "graph": {
+                "nodes": self.nodes,
+                "arcs": self.arcs,
+            },
+            "origins": {
+                "count": self.origins,
+                "vector_bytes": self.origins_bytes,
+                "urls_bytes": self.urls_bytes,
+                "bytes_per_origin": (self.origins_bytes + self.urls_bytes).checked_div(self.origins),
+            },
+            "eligible_origins_bytes": self.eligible_origins_bytes,
+            "estimated_bytes": self.estimated_bytes(),
+        })
+    }
+}
+
+impl fmt::Display for MemoryUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.rss_bytes {
+            Some(rss) => write!(f, "RSS {}", human_bytes(rss as usize))?,
+            None => write!(f, "RSS unknown")?,
+        }
+        write!(
+            f,
+            ", {} origins (vector {}, URLs {}), eligible ids {}",
+            self.origins,
+            human_bytes(self.origins_bytes),
+            human_bytes(self.urls_bytes),
+            human_bytes(self.eligible_origins_bytes),
+        )
+    }
+}
+
+fn human_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use swh_graph::graph::NodeId;
+use swh_graph::{NodeType, SWHID};
+
+use crate::backend::{ArcLabel, BackwardCapable, GraphBackend, LabelCapable};
+
+#[derive(Clone)]
+struct Node {
+    node_type: NodeType,
+    successors: Vec<NodeId>,
+    predecessors: Vec<NodeId>,
+    committer_timestamp: Option<i64>,
+    committer_timestamp_offset: Option<i16>,
+    committer_id: Option<u32>,
+    message: Option<Vec<u8>>,
+}
+
+/// Small graph held in memory, to exercise origin metrics without a graph export.
+///
+/// Built with [`InMemoryGraphBuilder`]; node ids are assigned in creation order.
+#[derive(Clone, Default)]
+pub struct InMemoryGraph {
+    nodes: Vec<Node>,
+    /// Full visits of each origin: snapshot and visit timestamp
+    visits: HashMap<NodeId, Vec<(NodeId, u64)>>,
+    /// Named branches of each snapshot
+    branches: HashMap<NodeId, Vec<(Vec<u8>, NodeId)>>,
+}
+
+impl GraphBackend for InMemoryGraph {
+    fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn num_arcs(&self) -> u64 {
+        self.nodes.iter().map(|node| node.successors.len() as u64).sum()
+    }
+
+    fn successors(&self, node: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        self.nodes[node].successors.iter().copied()
+    }
+
+    fn node_type(&self, node: NodeId) -> NodeType {
+        self.nodes[node].node_type
+    }
+
+    /// Synthetic SWHID whose hash is the big-endian node id
+    fn swhid(&self, node: NodeId) -> SWHID {
+        let mut hash = [0u8; 20];
+        hash[12..].copy_from_slice(&(node as u64).to_be_bytes());
+        SWHID {
+            namespace_version: 1,
+            node_type: self.nodes[node].node_type,
+            hash,
+        }
+    }
+
+    /// Node whose [synthetic SWHID](Self::swhid) this is
+    fn node_id(&self, swhid: &SWHID) -> Option<NodeId> {
+        let node = u64::from_be_bytes(swhid.hash[12..].try_into().unwrap());
+        let node = usize::try_from(node).ok().filter(|&node| node < self.nodes.len())?;
+        (self.swhid(node) == *swhid).then_some(node)
+    }
+
+    fn committer_timestamp(&self, node: NodeId) -> Option<i64> {
+        self.nodes[node].committer_timestamp
+    }
+
+    fn committer_timestamp_offset(&self, node: NodeId) -> Option<i16> {
+        self.nodes[node].committer_timestamp_offset
+    }
+
+    fn committer_id(&self, node: NodeId) -> Option<u32> {
+        self.nodes[node].committer_id
+    }
+
+    fn message(&self, node: NodeId) -> Option<Vec<u8>> {
+        self.nodes[node].message.clone()
+    }
+
+    fn latest_snapshot(&self, origin: NodeId) -> Option<(NodeId, u64)> {
+        self.visits
+            .get(&origin)?
+            .iter()
+            .copied()
+            .max_by_key(|&(_, timestamp)| timestamp)
+    }
+}
+
+impl BackwardCapable for InMemoryGraph {
+    fn predecessors(&self, node: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        self.nodes[node].predecessors.iter().copied()
+    }
+}
+
+/// Only branches and visits are labeled
+impl LabelCapable for InMemoryGraph {
+    fn labeled_arcs(&self, node: NodeId) -> Vec<(NodeId, Vec<ArcLabel>)> {
+        let branches = self.branches.get(&node).map(Vec::as_slice).unwrap_or_default();
+        let visits = self.visits.get(&node).map(Vec::as_slice).unwrap_or_default();
+        let mut arcs: Vec<(NodeId, Vec<ArcLabel>)> = Vec::new();
+        for succ in self.successors(node) {
+            // A successor appears once per arc to it, only label the first one
+            if arcs.iter().any(|&(known, _)| known == succ) {
+                continue;
+            }
+            let mut labels: Vec<ArcLabel> = branches
+                .iter()
+                .filter(|&&(_, target)| target == succ)
+                .map(|(name, _)| ArcLabel::Branch { name: name.clone() })
+                .collect();
+            labels.extend(
+                visits
+                    .iter()
+                    .filter(|&&(snapshot, _)| snapshot == succ)
+                    .map(|&(_, timestamp)| ArcLabel::Visit { timestamp, full: true }),
+            );
+            arcs.push((succ, labels));
+        }
+        arcs
+    }
+}
+
+/// Builder of [`InMemoryGraph`]s:
+///
+/// ```ignore
+/// let mut builder = InMemoryGraphBuilder::default();
+/// let origin = builder.origin("https://example.org/repo.git");
+/// let snapshot = builder.node(NodeType::Snapshot);
+/// let revision = builder.revision(1_700_000_000, 42);
+/// builder.visit(origin, snapshot, 1_700_000_100);
+/// builder.branch(snapshot, "refs/heads/main", revision);
+/// let graph = builder.build();
+/// ```
+#[derive(Default)]
+pub struct InMemoryGraphBuilder {
+    graph: InMemoryGraph,
+}
+
+impl InMemoryGraphBuilder {
+    /// Add a node without any property
+    pub fn node(&mut self, node_type: NodeType) -> NodeId {
+        self.graph.nodes.push(Node {
+            node_type,
+            successors: Vec::new(),
+            predecessors: Vec::new(),
+            committer_timestamp: None,
+            committer_timestamp_offset: None,
+            committer_id: None,
+            message: None,
+        });
+        self.graph.nodes.len() - 1
+    }
+
+    pub fn origin(&mut self, url: &str) -> NodeId {
+        let id = self.node(NodeType::Origin);
+        self.graph.nodes[id].message = Some(url.as_bytes().to_vec());
+        id
+    }
+
+    /// Add a revision committed at `timestamp` (seconds since the epoch) by `committer`
+    pub fn revision(&mut self, timestamp: i64, committer: u32) -> NodeId {
+        let id = self.node(NodeType::Revision);
+        self.graph.nodes[id].committer_timestamp = Some(timestamp);
+        self.graph.nodes[id].committer_id = Some(committer);
+        id
+    }
+
+    /// Set the timezone of the commit date of `revision`, in minutes east of UTC
+    pub fn timezone(&mut self, revision: NodeId, offset: i16) -> &mut Self {
+        self.graph.nodes[revision].committer_timestamp_offset = Some(offset);
+        self
+    }
+
+    pub fn arc(&mut self, src: NodeId, dst: NodeId) -> &mut Self {
+        self.graph.nodes[src].successors.push(dst);
+        self.graph.nodes[dst].predecessors.push(src);
+        self
+    }
+
+    /// Record a full visit of `origin` which produced `snapshot` at `timestamp`
+    pub fn visit(&mut self, origin: NodeId, snapshot: NodeId, timestamp: u64) -> &mut Self {
+        self.arc(origin, snapshot);
+        self.graph.visits.entry(origin).or_default().push((snapshot, timestamp));
+        self
+    }
+
+    /// Add a branch of `snapshot` named `name` pointing to `target`
+    pub fn branch(&mut self, snapshot: NodeId, name: &str, target: NodeId) -> &mut Self {
+        self.arc(snapshot, target);
+        self.graph.branches.entry(snapshot).or_default().push((name.as_bytes().to_vec(), target));
+        self
+    }
+
+    pub fn build(self) -> InMemoryGraph {
+        self.graph
+    }
+}
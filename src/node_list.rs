@@ -0,0 +1,40 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use swh_graph::graph::NodeId;
+
+use crate::backend::GraphBackend;
+use crate::error::GraphError;
+
+/// Layout of the node lists written by [`write_node_list`], to feed a subset of origins
+/// to other swh-graph pipelines
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeListFormat {
+    /// One node id per line, as read back by `--origins-file`. Node ids are those of
+    /// this graph export only.
+    NodeIds,
+    /// Big-endian `u64` node ids, like the node property arrays of swh-graph
+    Binary,
+    /// One SWHID per line (`swh:1:ori:...`), valid across graph exports and also read
+    /// back by `--origins-file`
+    Swhids,
+}
+
+/// Write `nodes` to `path` in `format`, returning how many were written
+pub fn write_node_list<G: GraphBackend>(
+    graph: &G,
+    nodes: &[NodeId],
+    path: &Path,
+    format: NodeListFormat,
+) -> Result<usize, GraphError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for &node in nodes {
+        match format {
+            NodeListFormat::NodeIds => writeln!(writer, "{}", node)?,
+            NodeListFormat::Binary => writer.write_all(&(node as u64).to_be_bytes())?,
+            NodeListFormat::Swhids => writeln!(writer, "{}", graph.swhid(node))?,
+        }
+    }
+    writer.flush()?;
+    Ok(nodes.len())
+}
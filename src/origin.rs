@@ -1,19 +1,359 @@
+use chrono::{DateTime, NaiveDate, NaiveTime};
 use serde::{Deserialize, Serialize};
-use swh_graph::properties::{self, Contents, LabelNames, Maps, Persons, Timestamps};
-use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 use swh_graph::NodeType;
-use swh_graph::graph::{NodeId, SwhLabeledForwardGraph , SwhGraphWithProperties};
+use swh_graph::graph::NodeId;
+
+use tokio_util::sync::CancellationToken;
+use tracing::{debug_span, field, warn};
+
+use crate::backend::{GraphBackend, LabelCapable};
+use crate::error::GraphError;
+use crate::labels::{directory_entries, path_entry, snapshot_branches, DirEntry};
+use crate::scratch::with_scratch;
+use crate::urls::{host, UrlFilter};
+use crate::utils::{bfs, TraversalEnd, TraversalOptions, Visit};
 
 /// Serializable data for Origin (without graph reference)
-#[derive(Serialize, Deserialize)]
+///
+/// `None` means the metric was not computed; counts saturate at `u32::MAX`. Caches name the
+/// fields of each origin, so that metrics can be added as fields with `#[serde(default)]`,
+/// origins of older caches reading as not having them computed.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct OriginData {
     pub id: usize,
     pub url: Option<String>,
-    pub latest_commit_date: Option<usize>,
+    /// Seconds since the epoch
+    pub latest_commit_date: Option<i64>,
+    pub number_of_commits: Option<u32>,
+    pub number_of_commiters: Option<u32>,
+    /// Cluster of origins sharing history with this one, see `provenance::assign_clusters`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<usize>,
+    /// Only computed on demand, see [`Origin::structure`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structure: Option<Structure>,
+    /// Only computed on demand, see [`Origin::working_hours`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub working_hours: Option<WorkingHours>,
+    /// Computed along with the latest commit date, see [`Origin::head`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub head: Option<Head>,
+    /// Computed along with the commit count, see [`Origin::history`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<History>,
+    /// Computed along with the commit count, see [`Origin::parents`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parents: Option<Parents>,
+    /// Only computed on demand, see [`Origin::burstiness`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub burstiness: Option<Burstiness>,
+    /// Only computed on demand, see [`Origin::default_branch_commits`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number_of_commits_default_branch: Option<u32>,
+    /// Computed along with the latest commit date, see [`Origin::latest_committer`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest_committer: Option<u32>,
+    /// Only computed with `--dedup`, see [`Origin::dedup`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dedup: Option<Dedup>,
+    /// Metrics without a field of their own, by name, see [`Origin::column`]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub columns: BTreeMap<String, MetricValue>,
+}
+
+/// Layout of [`OriginData`] in the bincode caches of the first layout, before compact caches
+#[derive(Deserialize)]
+pub struct LegacyOriginData {
+    pub id: usize,
+    pub url: Option<String>,
+    pub latest_commit_date: Option<i64>,
     pub number_of_commits: Option<usize>,
     pub number_of_commiters: Option<usize>,
 }
 
+impl From<LegacyOriginData> for OriginData {
+    fn from(data: LegacyOriginData) -> Self {
+        OriginData {
+            id: data.id,
+            url: data.url,
+            latest_commit_date: data.latest_commit_date,
+            number_of_commits: data.number_of_commits.map(saturating_count),
+            number_of_commiters: data.number_of_commiters.map(saturating_count),
+            cluster: None,
+            structure: None,
+            working_hours: None,
+            head: None,
+            history: None,
+            parents: None,
+            burstiness: None,
+            number_of_commits_default_branch: None,
+            latest_committer: None,
+            dedup: None,
+            columns: BTreeMap::new(),
+        }
+    }
+}
+
+fn saturating_count(count: usize) -> u32 {
+    u32::try_from(count).unwrap_or(u32::MAX)
+}
+
+/// Shape of the sub-graph reachable from the latest snapshot of an origin (the snapshot
+/// included), to spot anomalous or machine-generated repositories. Counts saturate at
+/// `u32::MAX`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Structure {
+    pub contents: u32,
+    pub directories: u32,
+    pub revisions: u32,
+    pub releases: u32,
+    pub snapshots: u32,
+    pub arcs: u64,
+    /// Largest number of successors of a node, e.g. of a huge flat directory
+    pub max_out_degree: u32,
+}
+
+impl Structure {
+    fn count(&mut self, node_type: NodeType) {
+        let count = match node_type {
+            NodeType::Content => &mut self.contents,
+            NodeType::Directory => &mut self.directories,
+            NodeType::Revision => &mut self.revisions,
+            NodeType::Release => &mut self.releases,
+            NodeType::Snapshot => &mut self.snapshots,
+            NodeType::Origin => return,
+        };
+        *count = count.saturating_add(1);
+    }
+}
+
+/// When the commits of the latest snapshot sub-graph of an origin were made, in the local
+/// time of their committers, and how many timezones they were made from, to study globally
+/// distributed projects. Revisions without committer date or timezone are not counted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkingHours {
+    /// Commits by local hour of the day, from 0 to 23
+    pub hours: [u32; 24],
+    /// Number of distinct timezone offsets
+    pub timezones: u32,
+    /// Westernmost timezone offset, in minutes east of UTC
+    pub min_offset: Option<i16>,
+    /// Easternmost timezone offset, in minutes east of UTC
+    pub max_offset: Option<i16>,
+}
+
+impl WorkingHours {
+    fn count(&mut self, timestamp: i64, offset: i16) {
+        let local = timestamp + i64::from(offset) * 60;
+        let hour = local.rem_euclid(86_400) / 3_600;
+        self.hours[hour as usize] = self.hours[hour as usize].saturating_add(1);
+        self.min_offset = Some(self.min_offset.map_or(offset, |min| min.min(offset)));
+        self.max_offset = Some(self.max_offset.map_or(offset, |max| max.max(offset)));
+    }
+
+    /// Commits counted
+    pub fn commits(&self) -> u64 {
+        self.hours.iter().map(|&count| u64::from(count)).sum()
+    }
+
+    /// Minutes between the westernmost and easternmost timezones, 0 without commit
+    pub fn spread_minutes(&self) -> i32 {
+        match (self.min_offset, self.max_offset) {
+            (Some(min), Some(max)) => i32::from(max) - i32::from(min),
+            _ => 0,
+        }
+    }
+}
+
+/// Contents of the latest snapshot sub-graph of an origin also found in other origins, a
+/// proxy for how much of it is vendored or copied code. Counts saturate at `u32::MAX`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dedup {
+    pub contents: u32,
+    /// Contents in the latest snapshot of at least one other origin
+    pub shared: u32,
+}
+
+impl Dedup {
+    /// Fraction of the contents shared with other origins, `None` without content
+    pub fn ratio(&self) -> Option<f64> {
+        (self.contents > 0).then(|| f64::from(self.shared) / f64::from(self.contents))
+    }
+}
+
+/// Seconds in a week, weeks starting on Thursday 1970-01-01 like the epoch
+const WEEK: i64 = 7 * 24 * 3600;
+
+/// How bursty the commits of the latest snapshot sub-graph of an origin are, over the
+/// weeks from its first to its last commit: bulk-imported mirrors land most of their
+/// commits in a few weeks, organically developed repositories spread them. Revisions
+/// without committer date are not counted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Burstiness {
+    pub commits: u32,
+    /// Weeks from the first to the last commit, both included
+    pub weeks: u32,
+    /// Sum of the squared commit counts of the weeks
+    pub sum_of_squares: u64,
+}
+
+impl Burstiness {
+    fn from_weekly_counts(weekly: &HashMap<i64, u32>) -> Self {
+        let (Some(first), Some(last)) = (weekly.keys().min(), weekly.keys().max()) else {
+            return Burstiness::default();
+        };
+        Burstiness {
+            commits: weekly.values().fold(0u32, |total, &count| total.saturating_add(count)),
+            weeks: u32::try_from(last - first + 1).unwrap_or(u32::MAX),
+            sum_of_squares: weekly.values().map(|&count| u64::from(count).pow(2)).sum(),
+        }
+    }
+
+    /// Variance over mean of the weekly commit counts: about 1 for commits made at random,
+    /// higher for bursts, `None` without commit
+    pub fn score(&self) -> Option<f64> {
+        if self.commits == 0 {
+            return None;
+        }
+        let weeks = f64::from(self.weeks);
+        let mean = f64::from(self.commits) / weeks;
+        let variance = self.sum_of_squares as f64 / weeks - mean * mean;
+        Some(variance.max(0.0) / mean)
+    }
+}
+
+/// Latest snapshot of an origin and the most recently committed of its head revisions, the
+/// one giving the latest commit date, as node ids of the graph export the cache belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Head {
+    pub snapshot: NodeId,
+    /// `None` if no head revision has a committer date
+    pub revision: Option<NodeId>,
+}
+
+/// Root revisions (without parent revision) of the latest snapshot sub-graph of an origin.
+/// More than one means disconnected histories, which often come from squashed imports,
+/// orphan branches or force pushes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct History {
+    /// Saturates at `u32::MAX`
+    pub roots: u32,
+}
+
+impl History {
+    fn count<G: GraphBackend>(&mut self, graph: &G, revision: NodeId) {
+        if !graph.successors(revision).any(|node| graph.node_type(node) == NodeType::Revision) {
+            self.roots = self.roots.saturating_add(1);
+        }
+    }
+
+    /// Whether the origin has several disconnected histories
+    pub fn has_multiple_roots(&self) -> bool {
+        self.roots > 1
+    }
+}
+
+/// Parent revisions of the revisions of the latest snapshot sub-graph of an origin: many
+/// merges point to a merge-based workflow, close to one parent per commit to a rebase-based
+/// one. Counts saturate.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Parents {
+    pub revisions: u32,
+    /// Parent revisions over all the revisions
+    pub total: u64,
+    /// Largest number of parent revisions of a revision, e.g. of an octopus merge
+    pub max: u32,
+}
+
+impl Parents {
+    fn count<G: GraphBackend>(&mut self, graph: &G, revision: NodeId) {
+        let parents = graph.successors(revision).filter(|&node| graph.node_type(node) == NodeType::Revision).count();
+        self.revisions = self.revisions.saturating_add(1);
+        self.total = self.total.saturating_add(parents as u64);
+        self.max = self.max.max(saturating_count(parents));
+    }
+
+    /// Average number of parent revisions per revision, `None` without revision
+    pub fn average(&self) -> Option<f64> {
+        (self.revisions > 0).then(|| self.total as f64 / f64::from(self.revisions))
+    }
+}
+
+/// Value of a metric stored in the dynamic columns of an origin, see [`Origin::column`]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricValue {
+    Int(i64),
+    Float(f64),
+    /// Seconds since the epoch
+    Timestamp(i64),
+    Text(String),
+    /// Counts per bucket, the buckets being up to the metric
+    Histogram(Vec<u64>),
+}
+
+impl MetricValue {
+    /// Bytes allocated by this value outside of its own enum
+    pub fn heap_size(&self) -> usize {
+        match self {
+            MetricValue::Text(text) => text.capacity(),
+            MetricValue::Histogram(counts) => counts.capacity() * std::mem::size_of::<u64>(),
+            MetricValue::Int(_) | MetricValue::Float(_) | MetricValue::Timestamp(_) => 0,
+        }
+    }
+}
+
+/// Metrics of an origin computed as of a cutoff date, see [`Origin::metrics_as_of`].
+/// `None` if the origin has no snapshot.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct AsOfMetrics {
+    /// Latest committer date up to the cutoff
+    pub latest_commit_date: Option<i64>,
+    pub commit_count: Option<u32>,
+    pub committer_count: Option<u32>,
+}
+
+/// Cutoff of [`Origin::metrics_as_of`] from a date (`2020-01-01`, midnight UTC), an
+/// RFC 3339 datetime or seconds since the epoch
+pub fn parse_as_of(value: &str) -> Result<i64, String> {
+    if let Ok(timestamp) = value.parse::<i64>() {
+        return Ok(timestamp);
+    }
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Ok(datetime.timestamp());
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|date| date.and_time(NaiveTime::MIN).and_utc().timestamp())
+        .map_err(|_| format!("invalid date {:?}, expected YYYY-MM-DD, RFC 3339 or seconds since the epoch", value))
+}
+
+/// Inclusive `from,to` range of latest commit dates, each bound in the formats of
+/// [`parse_as_of`]. A `to` date (`2020-12-31`) includes its whole day; an empty bound
+/// (`2020-01-01,`) leaves that end of the range open.
+pub fn parse_date_range(value: &str) -> Result<(i64, i64), String> {
+    let (from, to) = value
+        .split_once(',')
+        .ok_or_else(|| format!("invalid date range {:?}, expected FROM,TO", value))?;
+    let (from, to) = (from.trim(), to.trim());
+    let end = match to {
+        "" => i64::MAX,
+        to if NaiveDate::parse_from_str(to, "%Y-%m-%d").is_ok() => parse_as_of(to)? + 24 * 3600 - 1,
+        to => parse_as_of(to)?,
+    };
+    let start = match from {
+        "" => i64::MIN,
+        from => parse_as_of(from)?,
+    };
+    if start > end {
+        return Err(format!("invalid date range {:?}, {} is after {}", value, from, to));
+    }
+    Ok((start, end))
+}
+
 
 //    type Maps: properties::MaybeMaps;
 //     type Timestamps: properties::MaybeTimestamps;
@@ -22,90 +362,487 @@ pub struct OriginData {
 //     type Strings: properties::MaybeStrings;
 //     type LabelNames: properties::MaybeLabelNames;
 /// Represents an origin node in the Software Heritage graph
-#[derive(Serialize, Deserialize)]
+///
+/// Metrics are memoized in [`OnceLock`]s, so they can be computed through a shared
+/// reference (e.g. from rayon workers or while holding a read lock). An empty slot
+/// means the metric was not computed yet.
 pub struct Origin<G>
 where
-    G: SwhLabeledForwardGraph 
-    + SwhGraphWithProperties<
-        Maps: properties::Maps,
-        Timestamps: properties::Timestamps,
-        Persons: properties::Persons,
-        Contents: properties::Contents,
-        Strings: properties::Strings,
-        LabelNames: properties::LabelNames,
-    > + Send + Sync,
+    G: GraphBackend + Send + Sync,
 {
     /// Internal node ID of the origin
     pub id: usize,
     /// Reference-counted pointer to the graph containing this origin
-    #[serde(skip)]
     pub graph: Option<Arc<G>>,
-    pub url: Option<String>,
-    pub latest_commit_date: Option<usize>,
-    pub number_of_commits: Option<usize>,
-    pub number_of_commiters: Option<usize>,
+    url: OnceLock<Option<String>>,
+    latest_commit_date: OnceLock<Option<i64>>,
+    number_of_commits: OnceLock<Option<u32>>,
+    number_of_commiters: OnceLock<Option<u32>>,
+    /// Smallest id of the origins sharing history with this one, if clustered
+    cluster: Option<usize>,
+    structure: OnceLock<Option<Structure>>,
+    working_hours: OnceLock<Option<WorkingHours>>,
+    head: OnceLock<Option<Head>>,
+    history: OnceLock<Option<History>>,
+    parents: OnceLock<Option<Parents>>,
+    burstiness: OnceLock<Option<Burstiness>>,
+    number_of_commits_default_branch: OnceLock<Option<u32>>,
+    latest_committer: OnceLock<Option<u32>>,
+    dedup: OnceLock<Option<Dedup>>,
+    /// Metrics without a field of their own: experimental ones can be added there
+    /// without changing the cache layout
+    columns: Mutex<BTreeMap<String, MetricValue>>,
+}
+
+/// Selection of metrics for [`Origin::compute_metrics`]
+#[derive(Clone, Copy, Debug)]
+pub struct Metrics {
+    pub latest_commit_date: bool,
+    pub commit_count: bool,
+    pub committer_count: bool,
+}
+
+impl Metrics {
+    pub const ALL: Metrics = Metrics { latest_commit_date: true, commit_count: true, committer_count: true };
+    pub const LATEST_COMMIT_DATE: Metrics = Metrics { latest_commit_date: true, commit_count: false, committer_count: false };
+    pub const COMMIT_COUNT: Metrics = Metrics { latest_commit_date: false, commit_count: true, committer_count: false };
+    pub const COMMITTER_COUNT: Metrics = Metrics { latest_commit_date: false, commit_count: false, committer_count: true };
+}
+
+/// One of the metrics of an origin, named as in the cache (e.g. `commit_count`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    LatestCommitDate,
+    CommitCount,
+    CommitterCount,
+}
+
+impl From<Metric> for Metrics {
+    fn from(metric: Metric) -> Self {
+        match metric {
+            Metric::LatestCommitDate => Metrics::LATEST_COMMIT_DATE,
+            Metric::CommitCount => Metrics::COMMIT_COUNT,
+            Metric::CommitterCount => Metrics::COMMITTER_COUNT,
+        }
+    }
+}
+
+/// Why an origin is not listed by `/origins`, see [`Origin::exclusion`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Exclusion {
+    /// Not loaded: dropped by the URL filter or the origin list
+    Filtered,
+    /// No full visit, so no latest snapshot to compute metrics from
+    NoSnapshot,
+    /// Metrics not computed yet, or their computation was cancelled
+    NotComputed,
+    /// The latest snapshot reaches no revision
+    NoRevisions,
+    /// The latest snapshot reaches fewer revisions than [`Eligibility::min_commits`]
+    TooFewCommits,
+    /// None of the head revisions of the latest snapshot has a committer date
+    NoTimestamps,
+}
+
+impl Exclusion {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Exclusion::Filtered => "filtered",
+            Exclusion::NoSnapshot => "no_snapshot",
+            Exclusion::NotComputed => "not_computed",
+            Exclusion::NoRevisions => "no_revisions",
+            Exclusion::TooFewCommits => "too_few_commits",
+            Exclusion::NoTimestamps => "no_timestamps",
+        }
+    }
+
+    /// Explanation for humans
+    pub fn description(&self) -> &'static str {
+        match self {
+            Exclusion::Filtered => "not kept by the URL allow/block lists or the origins file",
+            Exclusion::NoSnapshot => "the origin has no full visit, hence no latest snapshot",
+            Exclusion::NotComputed => "the metrics of the origin were not computed yet, or their computation was cancelled",
+            Exclusion::NoRevisions => "the latest snapshot of the origin reaches no revision",
+            Exclusion::TooFewCommits => "the latest snapshot of the origin reaches fewer revisions than required",
+            Exclusion::NoTimestamps => "no head revision of the latest snapshot has a committer date",
+        }
+    }
+}
+
+/// What makes an origin eligible, i.e. listed by `/origins`. By default, having at least
+/// one commit and a latest commit date.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Eligibility {
+    /// Fewest commits in the latest snapshot sub-graph
+    pub min_commits: u32,
+    /// Whether some head revision of the latest snapshot must have a committer date
+    pub require_commit_date: bool,
+}
+
+impl Default for Eligibility {
+    fn default() -> Self {
+        Eligibility { min_commits: 1, require_commit_date: true }
+    }
+}
+
+impl Eligibility {
+    /// Whether an origin with these metrics is eligible, unknown metrics counting as
+    /// missing
+    pub fn admits(&self, commits: Option<u32>, latest_commit_date: Option<i64>) -> bool {
+        commits.unwrap_or(0) >= self.min_commits && (!self.require_commit_date || latest_commit_date.is_some())
+    }
+
+    /// Name of the file listing the eligible origins in the cache directory, one per
+    /// definition so that lists made with different ones are not mixed up
+    pub fn file_name(&self) -> String {
+        if *self == Eligibility::default() {
+            return "origins_eligible.txt".to_string();
+        }
+        let undated = if self.require_commit_date { "" } else { "_undated" };
+        format!("origins_eligible_min{}{}.txt", self.min_commits, undated)
+    }
+}
+
+/// Which origins are kept and which of them are listed, the same way whether they are
+/// computed or loaded from the cache, by both binaries: origins failing the structural and
+/// URL criteria are not kept at all, the [`Eligibility`] deciding which of the others
+/// `/origins` lists.
+#[derive(Clone, Debug)]
+pub struct OriginFilter {
+    /// Whether origins without snapshot are dropped (only known of cached origins once
+    /// their metrics are)
+    pub require_snapshot: bool,
+    pub urls: UrlFilter,
+    /// If not empty, only origins hosted on one of these forges (URL hosts, e.g.
+    /// `github.com`, compared case-insensitively) are kept
+    pub forges: Vec<String>,
+    pub eligibility: Eligibility,
+}
+
+impl Default for OriginFilter {
+    fn default() -> Self {
+        OriginFilter {
+            require_snapshot: true,
+            urls: UrlFilter::default(),
+            forges: Vec::new(),
+            eligibility: Eligibility::default(),
+        }
+    }
+}
+
+impl OriginFilter {
+    /// Whether origins are filtered by URL or forge
+    pub fn filters_urls(&self) -> bool {
+        !self.urls.is_empty() || !self.forges.is_empty()
+    }
+
+    /// Whether some origins of a cache may be dropped when loading it
+    pub fn drops_loaded(&self) -> bool {
+        self.require_snapshot || self.filters_urls()
+    }
+
+    /// Whether an origin with this URL is kept. Origins without URL are only kept without
+    /// URL allowlist nor forges.
+    pub fn keeps_url(&self, url: Option<&str>) -> bool {
+        let forge = self.forges.is_empty()
+            || url.and_then(host).is_some_and(|host| self.forges.iter().any(|forge| forge.eq_ignore_ascii_case(host)));
+        forge && self.urls.keeps(url)
+    }
+}
+
+/// How [`Origin::metric`] treats a metric which may not be known yet
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComputePolicy {
+    /// Only return a memoized (or cached on disk) value, never traverse the graph
+    CachedOnly,
+    /// Compute and memoize the metric if it is not known yet
+    ComputeIfMissing,
+    /// Compute the metric again from the graph, even if it is known. The memoized value
+    /// is left untouched: replace the origin (see `Graph::detached_origin`) to update it.
+    ForceRecompute,
+}
+
+/// Origins whose metrics take longer than this many milliseconds to compute are logged,
+/// 0 disables the log
+static SLOW_ORIGIN_THRESHOLD_MS: AtomicU64 = AtomicU64::new(10_000);
+
+/// Log the origins whose metrics take longer than `threshold` to compute (never if zero),
+/// to spot pathological repositories
+pub fn set_slow_origin_threshold(threshold: Duration) {
+    SLOW_ORIGIN_THRESHOLD_MS.store(threshold.as_millis() as u64, Ordering::Relaxed);
+}
+
+/// Largest number of nodes a traversal computing metrics of one origin visits, 0 for no
+/// limit
+static ORIGIN_NODE_BUDGET: AtomicUsize = AtomicUsize::new(0);
+
+/// Give up the metrics whose traversal of an origin would visit more than `max_nodes`
+/// nodes (never if zero): they fail with [`GraphError::TraversalBudgetExceeded`] and stay
+/// unknown, instead of tying up a worker on a pathological repository
+pub fn set_origin_node_budget(max_nodes: usize) {
+    ORIGIN_NODE_BUDGET.store(max_nodes, Ordering::Relaxed);
+}
+
+/// Options of a traversal computing metrics, bounded by the node budget
+fn budgeted(options: TraversalOptions<'_>) -> TraversalOptions<'_> {
+    match ORIGIN_NODE_BUDGET.load(Ordering::Relaxed) {
+        0 => options,
+        budget => options.max_nodes(budget),
+    }
+}
+
+/// `result` of a computation over many origins, or `unknown` if the origin is over the
+/// node budget: its metrics stay unknown without failing the whole computation
+pub fn skip_over_budget<T>(result: Result<T, GraphError>, unknown: T) -> Result<T, GraphError> {
+    match result {
+        Err(GraphError::TraversalBudgetExceeded { .. }) => Ok(unknown),
+        result => result,
+    }
+}
+
+/// Results of the traversal of a snapshot, see [`enable_snapshot_memo`]
+#[derive(Clone, Copy)]
+struct SnapshotTraversal {
+    commits: u32,
+    committers: u32,
+    history: History,
+    parents: Parents,
+}
+
+/// Shards of the snapshot memo, locked separately so that workers rarely wait on each other
+const SNAPSHOT_MEMO_SHARDS: usize = 64;
+
+/// Traversal results by snapshot, shared by the origins whose latest snapshot it is
+struct SnapshotMemo {
+    shard_capacity: usize,
+    shards: Vec<Mutex<HashMap<NodeId, SnapshotTraversal>>>,
+    hits: AtomicU64,
+}
+
+impl SnapshotMemo {
+    fn shard(&self, snapshot: NodeId) -> std::sync::MutexGuard<'_, HashMap<NodeId, SnapshotTraversal>> {
+        self.shards[snapshot % SNAPSHOT_MEMO_SHARDS].lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn get(&self, snapshot: NodeId) -> Option<SnapshotTraversal> {
+        let found = self.shard(snapshot).get(&snapshot).copied();
+        if found.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        found
+    }
+
+    /// Keep `traversal` unless the shard of `snapshot` is full
+    fn insert(&self, snapshot: NodeId, traversal: SnapshotTraversal) {
+        let mut shard = self.shard(snapshot);
+        if shard.len() < self.shard_capacity {
+            shard.insert(snapshot, traversal);
+        }
+    }
+}
+
+static SNAPSHOT_MEMO: OnceLock<SnapshotMemo> = OnceLock::new();
+
+/// Reuse the commit and committer counts computed for a snapshot for every origin whose
+/// latest snapshot it is (mirrors, forks never pushed to), keeping up to `capacity`
+/// snapshots. Only the first call has an effect.
+pub fn enable_snapshot_memo(capacity: usize) {
+    let _ = SNAPSHOT_MEMO.get_or_init(|| SnapshotMemo {
+        shard_capacity: capacity.div_ceil(SNAPSHOT_MEMO_SHARDS),
+        shards: (0..SNAPSHOT_MEMO_SHARDS).map(|_| Mutex::default()).collect(),
+        hits: AtomicU64::new(0),
+    });
+}
+
+/// Number of snapshot traversals saved by the memo, `None` if it is not enabled
+pub fn snapshot_memo_hits() -> Option<u64> {
+    SNAPSHOT_MEMO.get().map(|memo| memo.hits.load(Ordering::Relaxed))
+}
+
+/// Latest committer date among `revisions`, with the revision having it
+fn latest_revision<G: GraphBackend>(graph: &G, revisions: &[NodeId]) -> Option<(i64, NodeId)> {
+    revisions
+        .iter()
+        .filter_map(|&rev| graph.committer_timestamp(rev).map(|date| (date, rev)))
+        .max()
+}
+
+/// Slot holding `value` if it is known, empty otherwise
+fn memo<T>(value: Option<T>) -> OnceLock<Option<T>> {
+    let slot = OnceLock::new();
+    if let Some(value) = value {
+        let _ = slot.set(Some(value));
+    }
+    slot
+}
+
+/// Value of the optional metric held by `slot` under `policy`, memoizing what `compute`
+/// finds unless it fails
+fn memoized<T: Copy>(
+    slot: &OnceLock<Option<T>>,
+    policy: ComputePolicy,
+    compute: impl FnOnce() -> Result<Option<T>, GraphError>,
+) -> Result<Option<T>, GraphError> {
+    match policy {
+        ComputePolicy::CachedOnly => Ok(slot.get().copied().flatten()),
+        ComputePolicy::ComputeIfMissing => match slot.get() {
+            Some(&value) => Ok(value),
+            None => {
+                let value = compute()?;
+                Ok(*slot.get_or_init(|| value))
+            }
+        },
+        ComputePolicy::ForceRecompute => compute(),
+    }
 }
 
 impl<G> Origin<G>
 where
-    G: SwhLabeledForwardGraph 
-    + SwhGraphWithProperties<
-        Maps: properties::Maps,
-        Timestamps: properties::Timestamps,
-        Persons: properties::Persons,
-        Contents: properties::Contents,
-        Strings: properties::Strings,
-        LabelNames: properties::LabelNames,
-    > + Send + Sync,
+    G: GraphBackend + Send + Sync,
 {
     /// Create a new Origin from a node ID and graph reference
     pub fn new(id: usize, graph: Arc<G>) -> Self {
         Origin {
-            id: id,
+            id,
             graph: Some(graph),
-            latest_commit_date: None,
-            number_of_commits: None,
-            number_of_commiters: None,
-            url:None
+            latest_commit_date: OnceLock::new(),
+            number_of_commits: OnceLock::new(),
+            number_of_commiters: OnceLock::new(),
+            url: OnceLock::new(),
+            cluster: None,
+            structure: OnceLock::new(),
+            working_hours: OnceLock::new(),
+            head: OnceLock::new(),
+            history: OnceLock::new(),
+            parents: OnceLock::new(),
+            burstiness: OnceLock::new(),
+            number_of_commits_default_branch: OnceLock::new(),
+            latest_committer: OnceLock::new(),
+            dedup: OnceLock::new(),
+            columns: Mutex::default(),
         }
     }
 
     /// Set the graph reference (used after deserialization)
-    #[allow(dead_code)]
     pub fn set_graph(&mut self, graph: Arc<G>) {
         self.graph = Some(graph);
     }
 
-    pub fn get_graph(&self) -> Arc<G> {
-        return self.graph.as_ref().unwrap().clone();
+    /// Graph containing this origin, which metric computations walk. Fails with
+    /// [`GraphError::GraphNotAttached`] for origins deserialized and never given one.
+    pub fn get_graph(&self) -> Result<Arc<G>, GraphError> {
+        self.graph.clone().ok_or(GraphError::GraphNotAttached(self.id))
     }
 
     /// Convert Origin to OriginData (without graph reference)
-    #[allow(dead_code)]
-    pub fn to_data(&mut self) -> OriginData {
+    pub fn to_data(&self) -> OriginData {
         OriginData {
             id: self.id,
-            latest_commit_date: self.latest_commit_date,
-            number_of_commits: self.number_of_commits,
-            number_of_commiters: self.number_of_commiters,
-            url:  self.url.clone(),
+            latest_commit_date: self.latest_commit_date.get().copied().flatten(),
+            number_of_commits: self.number_of_commits.get().copied().flatten(),
+            number_of_commiters: self.number_of_commiters.get().copied().flatten(),
+            url: self.cached_url(),
+            cluster: self.cluster,
+            structure: self.structure.get().copied().flatten(),
+            working_hours: self.working_hours.get().copied().flatten(),
+            head: self.head.get().copied().flatten(),
+            history: self.history.get().copied().flatten(),
+            parents: self.parents.get().copied().flatten(),
+            burstiness: self.burstiness.get().copied().flatten(),
+            number_of_commits_default_branch: self.number_of_commits_default_branch.get().copied().flatten(),
+            latest_committer: self.latest_committer.get().copied().flatten(),
+            dedup: self.dedup.get().copied().flatten(),
+            columns: self.columns(),
         }
     }
 
-    pub fn get_url(&mut self) -> Option<String> {
-        let binding = self.get_graph();
-        let props = binding.properties();
+    /// URL of the origin, read from the graph unless already known
+    pub fn get_url(&self) -> Result<Option<String>, GraphError> {
+        if let Some(url) = self.url.get() {
+            return Ok(url.clone());
+        }
+        let binding = self.get_graph()?;
+        let props = binding.as_ref();
 
         // Verify this is actually an origin node
-        if props.node_type(self.id) != NodeType::Origin {
-            return None;
+        let url = if props.node_type(self.id) != NodeType::Origin {
+            None
+        } else {
+            // For origin nodes, the URL is stored in the message field
+            props
+                .message(self.id)
+                .and_then(|bytes| String::from_utf8(bytes.to_vec()).ok())
+        };
+        Ok(self.url.get_or_init(|| url).clone())
+    }
+
+    /// URL if already known, without reading it from the graph
+    pub fn cached_url(&self) -> Option<String> {
+        self.known_url().map(str::to_string)
+    }
+
+    /// URL if already known, borrowed
+    pub fn known_url(&self) -> Option<&str> {
+        self.url.get().and_then(|url| url.as_deref())
+    }
+
+    /// Bytes allocated by this origin outside of its own struct, i.e. its URL
+    pub fn heap_size(&self) -> usize {
+        let url = self.url.get().and_then(|url| url.as_ref()).map_or(0, |url| url.capacity());
+        let columns: usize = self
+            .lock_columns()
+            .iter()
+            .map(|(name, value)| name.capacity() + value.heap_size())
+            .sum();
+        url + columns
+    }
+
+    fn lock_columns(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, MetricValue>> {
+        self.columns.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Value of the dynamic column `name`, `None` if it was never set
+    pub fn column(&self, name: &str) -> Option<MetricValue> {
+        self.lock_columns().get(name).cloned()
+    }
+
+    /// Whether `ecosystem` is among the known [`Self::ecosystems`], without computing them
+    pub fn has_ecosystem(&self, ecosystem: &str) -> bool {
+        match self.lock_columns().get(ECOSYSTEMS_COLUMN) {
+            Some(MetricValue::Text(names)) => names.split(',').any(|name| name == ecosystem),
+            _ => false,
         }
+    }
+
+    /// Set the dynamic column `name`, replacing its previous value. Written to the
+    /// cache along with the other metrics, so that new metrics need no new layout.
+    pub fn set_column(&self, name: impl Into<String>, value: MetricValue) {
+        self.lock_columns().insert(name.into(), value);
+    }
+
+    /// All dynamic columns, by name
+    pub fn columns(&self) -> BTreeMap<String, MetricValue> {
+        self.lock_columns().clone()
+    }
+
+    /// Drop the URL to free its memory, it is read from the graph again when needed
+    pub fn forget_url(&mut self) {
+        self.url = OnceLock::new();
+    }
 
-        // For origin nodes, the URL is stored in the message field
-        self.url= props
-            .message(self.id)
-            .and_then(|bytes| String::from_utf8(bytes.to_vec()).ok());
-        return self.url.clone();
+    /// Set the URL, e.g. to carry it over to a copy of this origin
+    pub fn set_url(&mut self, url: Option<String>) {
+        self.url = memo(url);
+    }
+
+    /// Cluster of origins sharing history with this one, see `provenance::assign_clusters`
+    pub fn cluster(&self) -> Option<usize> {
+        self.cluster
+    }
+
+    pub fn set_cluster(&mut self, cluster: Option<usize>) {
+        self.cluster = cluster;
     }
 
     /// Create Origin from OriginData and graph reference
@@ -113,20 +850,27 @@ where
         Origin {
             id: data.id,
             graph: Some(graph),
-            latest_commit_date: data.latest_commit_date,
-            number_of_commits: data.number_of_commits,
-            number_of_commiters: data.number_of_commiters,
-            url: data.url,
+            latest_commit_date: memo(data.latest_commit_date),
+            number_of_commits: memo(data.number_of_commits),
+            number_of_commiters: memo(data.number_of_commiters),
+            url: memo(data.url),
+            cluster: data.cluster,
+            structure: memo(data.structure),
+            working_hours: memo(data.working_hours),
+            head: memo(data.head),
+            history: memo(data.history),
+            parents: memo(data.parents),
+            burstiness: memo(data.burstiness),
+            number_of_commits_default_branch: memo(data.number_of_commits_default_branch),
+            latest_committer: memo(data.latest_committer),
+            dedup: memo(data.dedup),
+            columns: Mutex::new(data.columns),
         }
     }
 
-    pub fn compute_data(&mut self) {
-        // Compute latest commit date
-        self.get_latest_commit_date();
-        // Compute total number of commits
-        self.total_commit_latest_snp();
-        // Compute total number of commiters
-        self.total_commiter_latest_snp();
+    pub fn compute_data(&self) -> Result<(), GraphError> {
+        // Compute latest commit date, total number of commits and of commiters
+        self.compute_metrics(Metrics::ALL)
         // Compute URL
         //self.get_url();
     }
@@ -138,167 +882,832 @@ where
 
 
     /// Get the SWHID string for this origin
-    pub fn swhid(&self) -> String {
-        let graph = self.get_graph();
-        let props = graph.properties();
-        props.swhid(self.id).to_string()
+    pub fn swhid(&self) -> Result<String, GraphError> {
+        let graph = self.get_graph()?;
+        let props = graph.as_ref();
+        Ok(props.swhid(self.id).to_string())
     }
 
-    pub fn get_latest_snapshot(& self) -> Option<(NodeId, u64)> {
-        let graph = self.get_graph();
-        let props = graph.properties();
+    pub fn get_latest_snapshot(& self) -> Result<Option<(NodeId, u64)>, GraphError> {
+        let graph = self.get_graph()?;
+        let props = graph.as_ref();
         if props.node_type(self.id) != NodeType::Origin {
-            return None;
+            return Ok(None);
+        }
+        Ok(graph.latest_snapshot(self.id))
+    }
+
+    /// Value of `metric`, computed or not according to `policy`. Dates are in seconds
+    /// since the epoch.
+    ///
+    /// This is the single entry point to read a metric, so that every caller (endpoints,
+    /// workers, precomputation) gets the same memoization behavior. Only fails if the
+    /// metric has to be computed and no graph is attached.
+    pub fn metric(&self, metric: Metric, policy: ComputePolicy) -> Result<Option<i64>, GraphError> {
+        match policy {
+            ComputePolicy::CachedOnly => {}
+            ComputePolicy::ComputeIfMissing => self.compute_metrics(metric.into())?,
+            ComputePolicy::ForceRecompute => {
+                return Origin::new(self.id, self.get_graph()?).metric(metric, ComputePolicy::ComputeIfMissing);
+            }
+        }
+        Ok(self.known_metric(metric))
+    }
+
+    /// Value of `metric` if already known, which never needs the graph
+    pub fn known_metric(&self, metric: Metric) -> Option<i64> {
+        match metric {
+            Metric::LatestCommitDate => self.latest_commit_date.get().copied().flatten(),
+            Metric::CommitCount => self.number_of_commits.get().copied().flatten().map(i64::from),
+            Metric::CommitterCount => self.number_of_commiters.get().copied().flatten().map(i64::from),
         }
-        return swh_graph_stdlib::find_latest_snp(graph.as_ref(), self.id)
-            .ok()
-            .flatten();
-    }
-
-    pub fn total_commit_latest_snp(&mut self) -> Option<usize> {
-        if self.number_of_commits.is_none() {
-            let snapshot = self.get_latest_snapshot()?;
-            let snapshot_id = snapshot.0;
-            let graph = self.get_graph();
-            let count = swh_graph_stdlib::iter_nodes(&graph, &[snapshot_id])
-                .filter(|&node| graph.properties().node_type(node) == NodeType::Revision)
-                .count();
-
-
-            self.number_of_commits = count.into()
-        }
-        return self.number_of_commits;
-    }
-
-    pub fn total_commit_latest_snp_read_only(& self) -> Option<usize> {
-        if self.number_of_commits.is_none() {
-            let snapshot = self.get_latest_snapshot()?;
-            let snapshot_id = snapshot.0;
-            let graph = self.get_graph();
-            let count = swh_graph_stdlib::iter_nodes(&graph, &[snapshot_id])
-                .filter(|&node| graph.properties().node_type(node) == NodeType::Revision)
-                .count();
-            return    count.into()
-        }
-        return self.number_of_commits;
-           
-        
-        
-    }
-
-    pub fn total_commiter_latest_snp(&mut self) -> Option<usize> {
-        //Check wether the value is not computed yet
-        let graph = self.get_graph();
-        if self.number_of_commiters.is_none() {
-            let snapshot = self.get_latest_snapshot()?;
-
-            let snapshot_id = snapshot.0;
-            let count = swh_graph_stdlib::iter_nodes(&graph, &[snapshot_id])
-                .filter(|&node| graph.properties().node_type(node) == NodeType::Revision)
-                .filter_map(|rev| graph.properties().committer_id(rev).map(|ts| ts as u64))
-                .collect::<std::collections::HashSet<u64>>()
-                .len();
-
-            self.number_of_commiters = count.into();
-        }
-        return self.number_of_commiters;
-    }
-
-    pub fn get_latest_commit_date(&mut self) -> Option<usize> {
-        let graph = self.get_graph();
-        if self.latest_commit_date.is_none() {
-            let revisions = self.get_all_latest_snapshots_revisions();
-            let mut max_date: Option<usize> = None;
-            for rev in revisions {
-                let props = graph.properties();
-                let commit_date = props.committer_timestamp(rev);
-                if let Some(date) = commit_date {
-                    // Convert timestamp to usize safely
-                    let date_usize = date as usize;
-                    if let Some(max) = max_date {
-                        if date_usize > max {
-                            max_date = Some(date_usize);
+    }
+
+    /// Compute the `wanted` metrics which are not known yet, sharing the work between them:
+    /// the latest snapshot is looked up once, and commits and committers are counted in
+    /// a single traversal of its sub-graph (so asking for one of them fills in both).
+    pub fn compute_metrics(&self, wanted: Metrics) -> Result<(), GraphError> {
+        self.compute_metrics_cancellable(wanted, &CancellationToken::new()).map(|_| ())
+    }
+
+    /// Same as [`Self::compute_metrics`], giving up as soon as `cancel` is cancelled.
+    ///
+    /// Returns `false` if cancelled; metrics whose computation was interrupted stay unknown,
+    /// as do those of origins over the node budget (see [`set_origin_node_budget`]).
+    pub fn compute_metrics_cancellable(&self, wanted: Metrics, cancel: &CancellationToken) -> Result<bool, GraphError> {
+        if cancel.is_cancelled() {
+            return Ok(false);
+        }
+        let date = wanted.latest_commit_date && self.latest_commit_date.get().is_none();
+        let traverse = (wanted.commit_count && self.number_of_commits.get().is_none())
+            || (wanted.committer_count && self.number_of_commiters.get().is_none());
+        if !date && !traverse {
+            return Ok(true);
+        }
+
+        let Some((snapshot_id, _)) = self.get_latest_snapshot()? else {
+            if date {
+                let _ = self.latest_commit_date.set(None);
+                let _ = self.head.set(None);
+                let _ = self.latest_committer.set(None);
+            }
+            if traverse {
+                let _ = self.number_of_commits.set(None);
+                let _ = self.number_of_commiters.set(None);
+                let _ = self.history.set(None);
+                let _ = self.parents.set(None);
+            }
+            return Ok(true);
+        };
+
+        let span = debug_span!("origin_metrics", origin = self.id, visited = field::Empty, elapsed_ms = field::Empty);
+        let _entered = span.enter();
+        let start = Instant::now();
+        let mut visited = 0usize;
+
+        let graph = self.get_graph()?;
+        let props = graph.as_ref();
+        let finished = with_scratch(|scratch| {
+            if date {
+                //max commit date over the latest snapshot head revisions
+                collect_snapshot_revisions(props, snapshot_id, &mut scratch.revisions);
+                let latest = latest_revision(props, &scratch.revisions);
+                let _ = self.latest_commit_date.set(latest.map(|(date, _)| date));
+                let _ = self.head.set(Some(Head { snapshot: snapshot_id, revision: latest.map(|(_, rev)| rev) }));
+                let _ = self.latest_committer.set(latest.and_then(|(_, rev)| props.committer_id(rev)));
+            }
+            if traverse {
+                let memo = SNAPSHOT_MEMO.get();
+                if let Some(traversal) = memo.and_then(|memo| memo.get(snapshot_id)) {
+                    self.set_traversal(traversal);
+                    return Ok(true);
+                }
+                let mut commits = 0;
+                let mut history = History::default();
+                let mut parents = Parents::default();
+                let committers = &mut scratch.committers;
+                let end = bfs(props, [snapshot_id], &budgeted(TraversalOptions::new().cancel(cancel)), |node, _| {
+                    visited += 1;
+                    if props.node_type(node) == NodeType::Revision {
+                        commits += 1;
+                        history.count(props, node);
+                        parents.count(props, node);
+                        if let Some(committer) = props.committer_id(node) {
+                            committers.insert(committer as u64);
                         }
-                    } else {
-                        max_date = Some(date_usize);
                     }
+                    Visit::Continue
+                });
+                if self.within_budget(end)?.is_cancelled() {
+                    return Ok(false);
+                }
+                let traversal = SnapshotTraversal {
+                    commits: saturating_count(commits),
+                    committers: saturating_count(committers.len()),
+                    history,
+                    parents,
+                };
+                if let Some(memo) = memo {
+                    memo.insert(snapshot_id, traversal);
                 }
+                self.set_traversal(traversal);
             }
-            self.latest_commit_date = max_date;
-        }
-        //iterate over get_all_latest_snapshots_revisions and get the max commit date
-        return self.latest_commit_date;
-    }
-
-    pub fn get_latest_commit_date_read_only(& self) -> Option<usize> {
-        let graph = self.get_graph();
-        if self.latest_commit_date.is_none() {
-            let revisions = self.get_all_latest_snapshots_revisions();
-            let mut max_date: Option<usize> = None;
-            for rev in revisions {
-                let props = graph.properties();
-                let commit_date = props.committer_timestamp(rev);
-                if let Some(date) = commit_date {
-                    if let Some(max) = max_date {
-                        if date > max.try_into().unwrap() {
-                            max_date = Some(date.try_into().unwrap());
+            Ok(true)
+        });
+
+        let elapsed = start.elapsed();
+        span.record("visited", visited);
+        span.record("elapsed_ms", elapsed.as_millis() as u64);
+        let threshold = SLOW_ORIGIN_THRESHOLD_MS.load(Ordering::Relaxed);
+        if threshold > 0 && elapsed.as_millis() as u64 >= threshold {
+            warn!(
+                origin = self.id,
+                visited,
+                elapsed_ms = elapsed.as_millis() as u64,
+                "Slow origin: metrics took {:.1}s over {} nodes",
+                elapsed.as_secs_f64(),
+                visited
+            );
+        }
+        finished
+    }
+
+    /// Fail with [`GraphError::TraversalBudgetExceeded`] if a traversal of the origin
+    /// ended for want of node budget
+    fn within_budget(&self, end: TraversalEnd) -> Result<TraversalEnd, GraphError> {
+        if end != TraversalEnd::BudgetExhausted {
+            return Ok(end);
+        }
+        let max_nodes = ORIGIN_NODE_BUDGET.load(Ordering::Relaxed);
+        warn!(origin = self.id, max_nodes, "Origin over the node budget: its metrics are left unknown");
+        Err(GraphError::TraversalBudgetExceeded { origin: self.id, max_nodes })
+    }
+
+    fn set_traversal(&self, traversal: SnapshotTraversal) {
+        let _ = self.number_of_commits.set(Some(traversal.commits));
+        let _ = self.number_of_commiters.set(Some(traversal.committers));
+        let _ = self.history.set(Some(traversal.history));
+        let _ = self.parents.set(Some(traversal.parents));
+    }
+
+    /// Commit count, committer count and latest commit date of the latest snapshot
+    /// sub-graph, ignoring revisions committed after `as_of` (seconds since the epoch) or
+    /// without committer date, for longitudinal studies on a single graph export.
+    ///
+    /// Revisions rewritten or deleted since `as_of` are missed, only the latest snapshot
+    /// being walked. Nothing is memoized. Returns `None` if cancelled meanwhile.
+    pub fn metrics_as_of(&self, as_of: i64, cancel: &CancellationToken) -> Result<Option<AsOfMetrics>, GraphError> {
+        let Some((snapshot_id, _)) = self.get_latest_snapshot()? else {
+            return Ok(Some(AsOfMetrics::default()));
+        };
+        let graph = self.get_graph()?;
+        let props = graph.as_ref();
+        with_scratch(|scratch| {
+            let mut commits = 0;
+            let mut latest = None;
+            let committers = &mut scratch.committers;
+            // Later revisions are still walked through, their parents may be older
+            let end = bfs(props, [snapshot_id], &budgeted(TraversalOptions::new().cancel(cancel)), |node, _| {
+                if props.node_type(node) == NodeType::Revision {
+                    if let Some(timestamp) = props.committer_timestamp(node).filter(|&timestamp| timestamp <= as_of) {
+                        commits += 1;
+                        latest = latest.max(Some(timestamp));
+                        if let Some(committer) = props.committer_id(node) {
+                            committers.insert(committer as u64);
                         }
-                    } else {
-                        max_date = Some(date.try_into().unwrap());
                     }
                 }
-                return max_date;
+                Visit::Continue
+            });
+            Ok((!self.within_budget(end)?.is_cancelled()).then(|| AsOfMetrics {
+                latest_commit_date: latest,
+                commit_count: Some(saturating_count(commits)),
+                committer_count: Some(saturating_count(committers.len())),
+            }))
+        })
+    }
+
+    /// Shape of the latest snapshot sub-graph, `None` if the origin has no snapshot (or
+    /// if not known yet and `policy` is [`ComputePolicy::CachedOnly`]).
+    ///
+    /// Not part of [`Self::compute_metrics`]: it is optional, and walks the whole closure
+    /// on its own.
+    pub fn structure(&self, policy: ComputePolicy) -> Result<Option<Structure>, GraphError> {
+        memoized(&self.structure, policy, || self.compute_structure())
+    }
+
+    /// Whether [`Self::structure`] is known without computing it
+    pub fn has_structure(&self) -> bool {
+        self.structure.get().is_some()
+    }
+
+    fn compute_structure(&self) -> Result<Option<Structure>, GraphError> {
+        let Some((snapshot, _)) = self.get_latest_snapshot()? else {
+            return Ok(None);
+        };
+        let graph = self.get_graph()?;
+        let props = graph.as_ref();
+        let mut structure = Structure::default();
+        let end = bfs(props, [snapshot], &budgeted(TraversalOptions::new()), |node, _| {
+            structure.count(props.node_type(node));
+            let out_degree = props.successors(node).count();
+            structure.arcs += out_degree as u64;
+            structure.max_out_degree = structure.max_out_degree.max(saturating_count(out_degree));
+            Visit::Continue
+        });
+        self.within_budget(end)?;
+        Ok(Some(structure))
+    }
+
+    /// Commit hours and timezones of the latest snapshot sub-graph, `None` if the origin
+    /// has no snapshot (or if not known yet and `policy` is [`ComputePolicy::CachedOnly`]).
+    ///
+    /// Optional like [`Self::structure`], and walks the whole closure on its own.
+    pub fn working_hours(&self, policy: ComputePolicy) -> Result<Option<WorkingHours>, GraphError> {
+        memoized(&self.working_hours, policy, || self.compute_working_hours())
+    }
+
+    /// Whether [`Self::working_hours`] is known without computing it
+    pub fn has_working_hours(&self) -> bool {
+        self.working_hours.get().is_some()
+    }
+
+    fn compute_working_hours(&self) -> Result<Option<WorkingHours>, GraphError> {
+        let Some((snapshot, _)) = self.get_latest_snapshot()? else {
+            return Ok(None);
+        };
+        let graph = self.get_graph()?;
+        let props = graph.as_ref();
+        let mut working_hours = WorkingHours::default();
+        let mut offsets = HashSet::new();
+        let end = bfs(props, [snapshot], &budgeted(TraversalOptions::new()), |node, _| {
+            if props.node_type(node) == NodeType::Revision {
+                let timestamp = props.committer_timestamp(node);
+                if let (Some(timestamp), Some(offset)) = (timestamp, props.committer_timestamp_offset(node)) {
+                    working_hours.count(timestamp, offset);
+                    offsets.insert(offset);
+                }
+            }
+            Visit::Continue
+        });
+        self.within_budget(end)?;
+        working_hours.timezones = saturating_count(offsets.len());
+        Ok(Some(working_hours))
+    }
+
+    /// Weekly commit burstiness of the latest snapshot sub-graph, `None` if the origin has no
+    /// snapshot (or if not known yet and `policy` is [`ComputePolicy::CachedOnly`]).
+    ///
+    /// Optional like [`Self::structure`], and walks the whole closure on its own.
+    pub fn burstiness(&self, policy: ComputePolicy) -> Result<Option<Burstiness>, GraphError> {
+        memoized(&self.burstiness, policy, || self.compute_burstiness())
+    }
+
+    /// Whether [`Self::burstiness`] is known without computing it
+    pub fn has_burstiness(&self) -> bool {
+        self.burstiness.get().is_some()
+    }
+
+    fn compute_burstiness(&self) -> Result<Option<Burstiness>, GraphError> {
+        let Some((snapshot, _)) = self.get_latest_snapshot()? else {
+            return Ok(None);
+        };
+        let graph = self.get_graph()?;
+        let props = graph.as_ref();
+        let mut weekly = HashMap::new();
+        let end = bfs(props, [snapshot], &budgeted(TraversalOptions::new()), |node, _| {
+            if props.node_type(node) == NodeType::Revision {
+                if let Some(timestamp) = props.committer_timestamp(node) {
+                    let count: &mut u32 = weekly.entry(timestamp.div_euclid(WEEK)).or_default();
+                    *count = count.saturating_add(1);
+                }
+            }
+            Visit::Continue
+        });
+        self.within_budget(end)?;
+        Ok(Some(Burstiness::from_weekly_counts(&weekly)))
+    }
+
+    /// Contents shared with other origins, `None` if the origin has no snapshot or if not
+    /// computed yet: it takes the contents of the whole corpus, see [`Self::compute_dedup`]
+    pub fn dedup(&self) -> Option<Dedup> {
+        self.dedup.get().copied().flatten()
+    }
+
+    /// Count the contents of the latest snapshot sub-graph and those of them which are
+    /// `shared`, i.e. found in more than one origin (see `provenance::SharedContents`),
+    /// unless already known. Returns `None` if cancelled meanwhile.
+    pub fn compute_dedup(
+        &self,
+        shared: impl Fn(NodeId) -> bool,
+        cancel: &CancellationToken,
+    ) -> Result<Option<Option<Dedup>>, GraphError> {
+        if let Some(&dedup) = self.dedup.get() {
+            return Ok(Some(dedup));
+        }
+        let Some((snapshot, _)) = self.get_latest_snapshot()? else {
+            return Ok(Some(*self.dedup.get_or_init(|| None)));
+        };
+        let graph = self.get_graph()?;
+        let props = graph.as_ref();
+        let mut dedup = Dedup::default();
+        let end = bfs(props, [snapshot], &budgeted(TraversalOptions::new().cancel(cancel)), |node, _| {
+            if props.node_type(node) == NodeType::Content {
+                dedup.contents = dedup.contents.saturating_add(1);
+                if shared(node) {
+                    dedup.shared = dedup.shared.saturating_add(1);
+                }
             }
+            Visit::Continue
+        });
+        if self.within_budget(end)?.is_cancelled() {
+            return Ok(None);
         }
-        //iterate over get_all_latest_snapshots_revisions and get the max commit date
-        return self.latest_commit_date;
+        Ok(Some(*self.dedup.get_or_init(|| Some(dedup))))
+    }
+
+    /// Latest snapshot and head revision, `None` if the origin has no snapshot (or if not
+    /// known yet and `policy` is [`ComputePolicy::CachedOnly`]).
+    ///
+    /// Filled in by [`Self::compute_metrics`] along with the latest commit date; computed on
+    /// its own for origins loaded from caches written before heads were kept.
+    pub fn head(&self, policy: ComputePolicy) -> Result<Option<Head>, GraphError> {
+        memoized(&self.head, policy, || self.compute_head())
+    }
+
+    /// Whether [`Self::head`] is known without computing it
+    pub fn has_head(&self) -> bool {
+        self.head.get().is_some()
+    }
+
+    fn compute_head(&self) -> Result<Option<Head>, GraphError> {
+        let Some((snapshot, _)) = self.get_latest_snapshot()? else {
+            return Ok(None);
+        };
+        let graph = self.get_graph()?;
+        with_scratch(|scratch| {
+            collect_snapshot_revisions(graph.as_ref(), snapshot, &mut scratch.revisions);
+            let latest = latest_revision(graph.as_ref(), &scratch.revisions);
+            Ok(Some(Head { snapshot, revision: latest.map(|(_, rev)| rev) }))
+        })
+    }
+
+    /// Root revisions of the latest snapshot sub-graph, `None` if the origin has no snapshot
+    /// (or if not known yet and `policy` is [`ComputePolicy::CachedOnly`]).
+    ///
+    /// Filled in by [`Self::compute_metrics`] along with the commit count; computed on its
+    /// own for origins loaded from caches written before histories were kept.
+    pub fn history(&self, policy: ComputePolicy) -> Result<Option<History>, GraphError> {
+        memoized(&self.history, policy, || self.compute_history())
+    }
+
+    /// Whether [`Self::history`] is known without computing it
+    pub fn has_history(&self) -> bool {
+        self.history.get().is_some()
+    }
+
+    fn compute_history(&self) -> Result<Option<History>, GraphError> {
+        let Some((snapshot, _)) = self.get_latest_snapshot()? else {
+            return Ok(None);
+        };
+        let graph = self.get_graph()?;
+        let props = graph.as_ref();
+        let mut history = History::default();
+        let end = bfs(props, [snapshot], &budgeted(TraversalOptions::new()), |node, _| {
+            if props.node_type(node) == NodeType::Revision {
+                history.count(props, node);
+            }
+            Visit::Continue
+        });
+        self.within_budget(end)?;
+        Ok(Some(history))
+    }
+
+    /// Average and largest number of parent revisions per revision of the latest snapshot
+    /// sub-graph, `None` if the origin has no snapshot (or if not known yet and `policy` is
+    /// [`ComputePolicy::CachedOnly`]).
+    ///
+    /// Filled in by [`Self::compute_metrics`] like [`Self::history`].
+    pub fn parents(&self, policy: ComputePolicy) -> Result<Option<Parents>, GraphError> {
+        memoized(&self.parents, policy, || self.compute_parents())
+    }
+
+    /// Whether [`Self::parents`] is known without computing it
+    pub fn has_parents(&self) -> bool {
+        self.parents.get().is_some()
+    }
+
+    fn compute_parents(&self) -> Result<Option<Parents>, GraphError> {
+        let Some((snapshot, _)) = self.get_latest_snapshot()? else {
+            return Ok(None);
+        };
+        let graph = self.get_graph()?;
+        let props = graph.as_ref();
+        let mut parents = Parents::default();
+        let end = bfs(props, [snapshot], &budgeted(TraversalOptions::new()), |node, _| {
+            if props.node_type(node) == NodeType::Revision {
+                parents.count(props, node);
+            }
+            Visit::Continue
+        });
+        self.within_budget(end)?;
+        Ok(Some(parents))
+    }
+
+    /// Person id of the committer of the head revision giving the latest commit date, `None`
+    /// if it is not known (or if not known yet and `policy` is [`ComputePolicy::CachedOnly`]).
+    ///
+    /// Ids are the graph's pseudonymized person ids, stable within a graph export only.
+    /// Filled in by [`Self::compute_metrics`] along with the latest commit date; found from
+    /// [`Self::head`] for origins loaded from caches written before they were kept.
+    pub fn latest_committer(&self, policy: ComputePolicy) -> Result<Option<u32>, GraphError> {
+        memoized(&self.latest_committer, policy, || {
+            let Some(revision) = self.head(policy)?.and_then(|head| head.revision) else {
+                return Ok(None);
+            };
+            Ok(self.get_graph()?.committer_id(revision))
+        })
+    }
+
+    /// Whether [`Self::latest_committer`] is known without computing it
+    pub fn has_latest_committer(&self) -> bool {
+        self.latest_committer.get().is_some()
+    }
+
+    /// Number of metrics which have not been computed (or loaded from the cache) yet
+    pub fn missing_metrics(&self) -> usize {
+        [
+            self.latest_commit_date.get().is_none(),
+            self.number_of_commits.get().is_none(),
+            self.number_of_commiters.get().is_none(),
+            self.url.get().is_none(),
+        ]
+        .iter()
+        .filter(|&&missing| missing)
+        .count()
+    }
+
+    /// Whether the origin is known to have no snapshot, from its commit count
+    pub fn lacks_snapshot(&self) -> bool {
+        matches!(self.number_of_commits.get(), Some(None))
+    }
+
+    /// Whether the latest commit date, commit count and committer count are all known
+    pub fn has_computed_metrics(&self) -> bool {
+        self.latest_commit_date.get().is_some()
+            && self.number_of_commits.get().is_some()
+            && self.number_of_commiters.get().is_some()
+    }
+
+    /// Whether all the commits of the latest snapshot sub-graph were made by a single
+    /// committer, e.g. a personal one-off repository, `None` if the committer count is not
+    /// known
+    pub fn single_committer(&self) -> Option<bool> {
+        self.number_of_commiters.get().copied().flatten().map(|committers| committers == 1)
+    }
+
+    /// Whether the latest snapshot sub-graph has a single commit, `None` if the commit count
+    /// is not known
+    pub fn single_commit(&self) -> Option<bool> {
+        self.number_of_commits.get().copied().flatten().map(|commits| commits == 1)
+    }
+
+    /// Why this origin is not eligible under `eligibility`, from its known metrics only:
+    /// `None` if it is, or [`Exclusion::NotComputed`] if that depends on metrics not
+    /// computed yet
+    pub fn exclusion(&self, eligibility: &Eligibility) -> Option<Exclusion> {
+        match self.number_of_commits.get() {
+            None => return Some(Exclusion::NotComputed),
+            Some(None) => return Some(Exclusion::NoSnapshot),
+            Some(&Some(commits)) if commits < eligibility.min_commits => {
+                return Some(if commits == 0 { Exclusion::NoRevisions } else { Exclusion::TooFewCommits });
+            }
+            Some(Some(_)) => {}
+        }
+        if !eligibility.require_commit_date {
+            return None;
+        }
+        match self.latest_commit_date.get() {
+            None => Some(Exclusion::NotComputed),
+            Some(None) => Some(Exclusion::NoTimestamps),
+            Some(Some(_)) => None,
+        }
+    }
+
+    /// Whether this origin should be listed by `/origins` under `eligibility`. Missing
+    /// metrics are computed and memoized.
+    pub fn is_eligible(&self, eligibility: &Eligibility) -> Result<bool, GraphError> {
+        // Widened from the `u32` count
+        let commits = self.metric(Metric::CommitCount, ComputePolicy::ComputeIfMissing)?.map(|commits| commits as u32);
+        let latest_commit_date = self.metric(Metric::LatestCommitDate, ComputePolicy::ComputeIfMissing)?;
+        Ok(eligibility.admits(commits, latest_commit_date))
     }
 
     //Get all head revision of the latest snapshots
-    pub fn get_all_latest_snapshots_revisions(& self) -> Vec<NodeId> {
-        // Return empty vector if there's no latest snapshot    
-        let latest_snapshots = match self.get_latest_snapshot() {
-            Some(snapshot) => snapshot,
-            None => return Vec::new(),
+    pub fn get_all_latest_snapshots_revisions(& self) -> Result<Vec<NodeId>, GraphError> {
+        let mut revisions = Vec::new();
+        // Nothing to add if there's no latest snapshot
+        if let Some((snapshot_id, _)) = self.get_latest_snapshot()? {
+            collect_snapshot_revisions(self.get_graph()?.as_ref(), snapshot_id, &mut revisions);
+        }
+        Ok(revisions)
+    }
+}
+
+/// Push the head revisions of `snapshot_id` to `revisions`, following releases
+fn collect_snapshot_revisions<G: GraphBackend>(props: &G, snapshot_id: NodeId, revisions: &mut Vec<NodeId>) {
+    let heads = TraversalOptions::new()
+        .node_types(&[NodeType::Release, NodeType::Revision])
+        .max_depth(2);
+    bfs(props, [snapshot_id], &heads, |node, _| {
+        if props.node_type(node) == NodeType::Revision {
+            // A head, not its parents
+            revisions.push(node);
+            Visit::Skip
+        } else {
+            Visit::Continue
+        }
+    });
+}
+
+/// Name of the snapshot branch pointing to the default branch, its alias being resolved in
+/// graph exports
+const HEAD_BRANCH: &[u8] = b"HEAD";
+
+/// Manifest files telling the ecosystem of an origin when at the root of its head
+/// revision's tree, see [`Origin::ecosystems`]
+pub const ECOSYSTEM_MANIFESTS: [(&str, &str); 5] = [
+    ("Cargo.toml", "rust"),
+    ("package.json", "javascript"),
+    ("pom.xml", "java"),
+    ("setup.py", "python"),
+    ("go.mod", "go"),
+];
+
+/// Dynamic column storing [`Origin::ecosystems`], comma-separated
+pub const ECOSYSTEMS_COLUMN: &str = "ecosystems";
+
+/// Metrics reading branch names, for graphs loaded with the label names
+impl<G> Origin<G>
+where
+    G: LabelCapable + Send + Sync,
+{
+    /// Commits reachable from the `HEAD` branch of the latest snapshot, unlike the commit
+    /// count which is inflated by long-lived branches and tags. `None` if the origin has no
+    /// snapshot or its latest snapshot has no `HEAD` branch (or if not known yet and
+    /// `policy` is [`ComputePolicy::CachedOnly`]).
+    ///
+    /// Optional like [`Self::structure`], and walks the history on its own.
+    pub fn default_branch_commits(&self, policy: ComputePolicy) -> Result<Option<u32>, GraphError> {
+        memoized(&self.number_of_commits_default_branch, policy, || self.compute_default_branch_commits())
+    }
+
+    /// Whether [`Self::default_branch_commits`] is known without computing it
+    pub fn has_default_branch_commits(&self) -> bool {
+        self.number_of_commits_default_branch.get().is_some()
+    }
+
+    /// Entry at `path` (e.g. `Cargo.toml` or `src/main.rs`) in the tree of the head
+    /// revision (see [`Self::head`]), following directory entry names; `None` if there is
+    /// none, or if the origin has no snapshot or no dated head revision (or its head is not
+    /// known yet and `policy` is [`ComputePolicy::CachedOnly`])
+    pub fn head_path(&self, path: &str, policy: ComputePolicy) -> Result<Option<DirEntry>, GraphError> {
+        let Some(revision) = self.head(policy)?.and_then(|head| head.revision) else {
+            return Ok(None);
         };
-        
-        let graph = self.get_graph();
-        let mut revisions: Vec<NodeId> = Vec::new();
-        for succ in graph.successors(latest_snapshots.0) {
-            let node_type = graph.properties().node_type(succ);
-            if node_type == NodeType::Revision {
-                revisions.push(succ);
-            } else if node_type == NodeType::Release {
-                //get all revisions linked to this release
-                for rel_succ in graph.successors(succ) {
-                    let rel_node_type = graph.properties().node_type(rel_succ);
-                    if rel_node_type == NodeType::Revision {
-                        revisions.push(rel_succ);
-                    }
-                }
-            } 
-            
+        let graph = self.get_graph()?;
+        let props = graph.as_ref();
+        let root = props.successors(revision).find(|&node| props.node_type(node) == NodeType::Directory);
+        Ok(root.and_then(|root| path_entry(props, root, path)))
+    }
+
+    /// Ecosystems of the origin (`rust`, `javascript`, ...), sorted, from the manifest
+    /// files of [`ECOSYSTEM_MANIFESTS`] at the root of the tree of its head revision.
+    /// `None` if the origin has no snapshot or no dated head revision (or if not known yet
+    /// and `policy` is [`ComputePolicy::CachedOnly`]).
+    ///
+    /// Stored in the [`ECOSYSTEMS_COLUMN`] dynamic column, written to the cache like the
+    /// other columns.
+    pub fn ecosystems(&self, policy: ComputePolicy) -> Result<Option<Vec<String>>, GraphError> {
+        if policy != ComputePolicy::ForceRecompute {
+            if let Some(MetricValue::Text(names)) = self.column(ECOSYSTEMS_COLUMN) {
+                return Ok(Some(names.split(',').filter(|name| !name.is_empty()).map(String::from).collect()));
+            }
+            if policy == ComputePolicy::CachedOnly {
+                return Ok(None);
+            }
         }
-        return revisions;
+        let Some(ecosystems) = self.compute_ecosystems()? else {
+            return Ok(None);
+        };
+        self.set_column(ECOSYSTEMS_COLUMN, MetricValue::Text(ecosystems.join(",")));
+        Ok(Some(ecosystems))
+    }
+
+    fn compute_ecosystems(&self) -> Result<Option<Vec<String>>, GraphError> {
+        let Some(revision) = self.head(ComputePolicy::ComputeIfMissing)?.and_then(|head| head.revision) else {
+            return Ok(None);
+        };
+        let graph = self.get_graph()?;
+        let props = graph.as_ref();
+        let Some(root) = props.successors(revision).find(|&node| props.node_type(node) == NodeType::Directory) else {
+            return Ok(None);
+        };
+        let mut ecosystems: Vec<String> = directory_entries(props, root)
+            .iter()
+            .filter(|entry| props.node_type(entry.target) == NodeType::Content)
+            .filter_map(|entry| {
+                ECOSYSTEM_MANIFESTS
+                    .iter()
+                    .find(|(manifest, _)| manifest.as_bytes() == entry.name.as_slice())
+                    .map(|(_, ecosystem)| ecosystem.to_string())
+            })
+            .collect();
+        ecosystems.sort_unstable();
+        ecosystems.dedup();
+        Ok(Some(ecosystems))
+    }
+
+    fn compute_default_branch_commits(&self) -> Result<Option<u32>, GraphError> {
+        let Some((snapshot, _)) = self.get_latest_snapshot()? else {
+            return Ok(None);
+        };
+        let graph = self.get_graph()?;
+        let props = graph.as_ref();
+        let Some(head) = snapshot_branches(props, snapshot).into_iter().find(|branch| branch.name == HEAD_BRANCH) else {
+            return Ok(None);
+        };
+        let mut commits = 0;
+        // Releases lead to the revision they tag, directories are never needed
+        let history = budgeted(TraversalOptions::new().node_types(&[NodeType::Release, NodeType::Revision]));
+        let end = bfs(props, [head.target], &history, |node, _| {
+            if props.node_type(node) == NodeType::Revision {
+                commits += 1;
+            }
+            Visit::Continue
+        });
+        self.within_budget(end)?;
+        Ok(Some(saturating_count(commits)))
     }
 }
 
 impl<G> std::fmt::Debug for Origin<G>
 where
-    G: SwhLabeledForwardGraph 
-    + SwhGraphWithProperties<
-        Maps: properties::Maps,
-        Timestamps: properties::Timestamps,
-        Persons: properties::Persons,
-        Contents: properties::Contents,
-        Strings: properties::Strings,
-        LabelNames: properties::LabelNames,
-    > + Send + Sync,
+    G: GraphBackend + Send + Sync,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Origin")
             .field("id", &self.id)
-            .field("url", &self.url)
+            .field("url", &self.cached_url())
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_graph::{InMemoryGraph, InMemoryGraphBuilder};
+
+    /// Origin (node 0) whose latest snapshot has a merged `HEAD` history and an orphan
+    /// branch, an older visit seeing only part of the history:
+    ///
+    /// ```text
+    /// r3 (3000, committer 3) -> r2 (2000, committer 2) -> r1 (1000, committer 1)
+    ///                        -> r4 (2500, committer 1) -> r1
+    /// r5 (1500, committer 4)
+    /// ```
+    fn merged_history() -> InMemoryGraph {
+        let mut builder = InMemoryGraphBuilder::default();
+        let origin = builder.origin("https://example.org/repo.git");
+        let old_snapshot = builder.node(NodeType::Snapshot);
+        let snapshot = builder.node(NodeType::Snapshot);
+        let r1 = builder.revision(1000, 1);
+        let r2 = builder.revision(2000, 2);
+        let r3 = builder.revision(3000, 3);
+        let r4 = builder.revision(2500, 1);
+        let r5 = builder.revision(1500, 4);
+        builder.arc(r2, r1).arc(r4, r1).arc(r3, r2).arc(r3, r4);
+        builder.visit(origin, old_snapshot, 100).branch(old_snapshot, "HEAD", r2);
+        builder.visit(origin, snapshot, 200).branch(snapshot, "HEAD", r3);
+        builder.branch(snapshot, "refs/heads/orphan", r5);
+        builder.build()
+    }
+
+    #[test]
+    fn metrics_of_the_latest_snapshot() {
+        let origin = Origin::new(0, Arc::new(merged_history()));
+        assert_eq!(origin.metric(Metric::CommitCount, ComputePolicy::CachedOnly).unwrap(), None);
+        assert_eq!(origin.metric(Metric::CommitCount, ComputePolicy::ComputeIfMissing).unwrap(), Some(5));
+        assert_eq!(origin.metric(Metric::CommitterCount, ComputePolicy::CachedOnly).unwrap(), Some(4));
+        assert_eq!(origin.metric(Metric::LatestCommitDate, ComputePolicy::ComputeIfMissing).unwrap(), Some(3000));
+        assert_eq!(origin.head(ComputePolicy::CachedOnly).unwrap(), Some(Head { snapshot: 2, revision: Some(5) }));
+        assert_eq!(origin.latest_committer(ComputePolicy::CachedOnly).unwrap(), Some(3));
+        assert_eq!(origin.metric(Metric::CommitCount, ComputePolicy::ForceRecompute).unwrap(), Some(5));
+    }
+
+    #[test]
+    fn latest_commit_date_over_all_heads() {
+        let mut builder = InMemoryGraphBuilder::default();
+        let origin = builder.origin("https://example.org/repo.git");
+        let snapshot = builder.node(NodeType::Snapshot);
+        let main = builder.revision(1000, 1);
+        let feature = builder.revision(2000, 2);
+        builder.visit(origin, snapshot, 100).branch(snapshot, "refs/heads/main", main);
+        builder.branch(snapshot, "refs/heads/feature", feature);
+        let origin = Origin::new(origin, Arc::new(builder.build()));
+        assert_eq!(origin.metric(Metric::LatestCommitDate, ComputePolicy::ComputeIfMissing).unwrap(), Some(2000));
+        assert_eq!(origin.head(ComputePolicy::CachedOnly).unwrap().and_then(|head| head.revision), Some(feature));
+    }
+
+    #[test]
+    fn history_and_parents() {
+        let origin = Origin::new(0, Arc::new(merged_history()));
+        // Computed on their own, before the other metrics
+        let history = origin.history(ComputePolicy::ComputeIfMissing).unwrap().unwrap();
+        assert_eq!(history.roots, 2);
+        assert!(history.has_multiple_roots());
+        let parents = origin.parents(ComputePolicy::ComputeIfMissing).unwrap().unwrap();
+        assert_eq!(parents, Parents { revisions: 5, total: 4, max: 2 });
+        assert_eq!(parents.average(), Some(0.8));
+        assert_eq!(origin.default_branch_commits(ComputePolicy::ComputeIfMissing).unwrap(), Some(4));
+    }
+
+    #[test]
+    fn origin_without_visit() {
+        let mut builder = InMemoryGraphBuilder::default();
+        let origin = builder.origin("https://example.org/empty.git");
+        let origin = Origin::new(origin, Arc::new(builder.build()));
+        assert_eq!(origin.metric(Metric::CommitCount, ComputePolicy::ComputeIfMissing).unwrap(), None);
+        assert_eq!(origin.metric(Metric::LatestCommitDate, ComputePolicy::ComputeIfMissing).unwrap(), None);
+        assert!(origin.has_computed_metrics());
+        assert_eq!(origin.history(ComputePolicy::CachedOnly).unwrap(), None);
+        assert_eq!(origin.parents(ComputePolicy::ComputeIfMissing).unwrap(), None);
+        assert!(origin.lacks_snapshot());
+    }
+
+    #[test]
+    fn over_node_budget() {
+        // The budget itself is process-wide, so only its outcome is checked here
+        let origin = Origin::new(0, Arc::new(merged_history()));
+        assert_eq!(origin.within_budget(TraversalEnd::Complete).unwrap(), TraversalEnd::Complete);
+        let over = origin.within_budget(TraversalEnd::BudgetExhausted);
+        assert!(matches!(over, Err(GraphError::TraversalBudgetExceeded { origin: 0, .. })));
+        assert_eq!(skip_over_budget(over.map(|_| Some(1)), None).unwrap(), None);
+        // Other failures still fail
+        assert!(matches!(skip_over_budget(Err(GraphError::Cancelled), ()), Err(GraphError::Cancelled)));
+    }
+
+    #[test]
+    fn origin_without_graph() {
+        let mut origin = Origin::new(0, Arc::new(merged_history()));
+        origin.compute_metrics(Metrics::ALL).unwrap();
+        origin.graph = None;
+        // Known metrics are still served, only computing needs the graph
+        assert_eq!(origin.metric(Metric::CommitCount, ComputePolicy::CachedOnly).unwrap(), Some(5));
+        assert_eq!(origin.metric(Metric::CommitCount, ComputePolicy::ComputeIfMissing).unwrap(), Some(5));
+        assert!(matches!(
+            origin.metric(Metric::CommitCount, ComputePolicy::ForceRecompute),
+            Err(GraphError::GraphNotAttached(0))
+        ));
+        assert!(matches!(origin.structure(ComputePolicy::ComputeIfMissing), Err(GraphError::GraphNotAttached(0))));
+        assert_eq!(origin.structure(ComputePolicy::CachedOnly).unwrap(), None);
+        assert!(matches!(origin.get_url(), Err(GraphError::GraphNotAttached(0))));
+        origin.set_url(Some("https://example.org/repo.git".to_string()));
+        assert_eq!(origin.get_url().unwrap().as_deref(), Some("https://example.org/repo.git"));
+    }
+
+    #[test]
+    fn as_of() {
+        assert_eq!(parse_as_of("1600000000"), Ok(1_600_000_000));
+        assert_eq!(parse_as_of("-86400"), Ok(-86_400));
+        assert_eq!(parse_as_of("2020-01-01"), Ok(1_577_836_800));
+        assert_eq!(parse_as_of("2020-01-01T00:00:00Z"), Ok(1_577_836_800));
+        assert_eq!(parse_as_of("2020-01-01T02:00:00+02:00"), Ok(1_577_836_800));
+        for invalid in ["", "yesterday", "2020-13-01", "2021-02-29", "2020-01-01 00:00:00", "01/01/2020"] {
+            assert!(parse_as_of(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn date_range() {
+        assert_eq!(parse_date_range("2020-01-01,2020-12-31"), Ok((1_577_836_800, 1_609_459_199)));
+        assert_eq!(parse_date_range(" 2020-01-01 , 2020-01-01 "), Ok((1_577_836_800, 1_577_923_199)));
+        // Only a `to` date is extended to the end of its day
+        assert_eq!(parse_date_range("1577836800,1577836800"), Ok((1_577_836_800, 1_577_836_800)));
+        assert_eq!(parse_date_range("2020-01-01,2020-01-01T12:00:00Z"), Ok((1_577_836_800, 1_577_880_000)));
+
+        assert_eq!(parse_date_range("2020-01-01,"), Ok((1_577_836_800, i64::MAX)));
+        assert_eq!(parse_date_range(",2020-12-31"), Ok((i64::MIN, 1_609_459_199)));
+        assert_eq!(parse_date_range(","), Ok((i64::MIN, i64::MAX)));
+
+        assert_eq!(
+            parse_date_range("2020-12-31,2020-01-01"),
+            Err("invalid date range \"2020-12-31,2020-01-01\", 2020-12-31 is after 2020-01-01".to_string())
+        );
+        assert!(parse_date_range("2020-01-01T23:59:59Z,2020-01-01").is_ok());
+        assert!(parse_date_range("2020-01-02T00:00:00Z,2020-01-01").is_err());
+        for invalid in ["", "2020-01-01", "2020-01-01;2020-12-31", "2020-02-30,2020-12-31", "2020-01-01,soon"] {
+            assert!(parse_date_range(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::collections::HashSet;
+use std::fs::read_to_string;
+use std::path::Path;
+use swh_graph::graph::NodeId;
+use swh_graph::{NodeType, SWHID};
+
+use crate::backend::GraphBackend;
+use crate::error::GraphError;
+
+/// Origins to restrict processing to, e.g. those of a published dataset to reproduce.
+///
+/// Read from a file with one origin per line, given by node id (`12345`), SWHID
+/// (`swh:1:ori:...`) or URL (`https://github.com/...`). Blank lines and lines starting
+/// with `#` are skipped. URLs are matched through their SWHID, the hash of the URL, so
+/// they stay valid across graph exports unlike node ids.
+#[derive(Clone, Debug, Default)]
+pub struct OriginList {
+    ids: HashSet<NodeId>,
+    swhids: HashSet<SWHID>,
+}
+
+impl OriginList {
+    pub fn read(path: &Path) -> Result<Self, GraphError> {
+        let mut list = OriginList::default();
+        for (index, line) in read_to_string(path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |reason: String| GraphError::InvalidOriginList {
+                path: path.to_path_buf(),
+                line: index + 1,
+                reason,
+            };
+            if let Ok(id) = line.parse::<NodeId>() {
+                list.ids.insert(id);
+            } else if line.starts_with("swh:") {
+                let swhid: SWHID = line.parse().map_err(|e| invalid(format!("{}", e)))?;
+                if swhid.node_type != NodeType::Origin {
+                    return Err(invalid(format!("{} is not an origin SWHID", line)));
+                }
+                list.swhids.insert(swhid);
+            } else {
+                list.swhids.insert(SWHID::from_origin_url(line));
+            }
+        }
+        Ok(list)
+    }
+
+    /// Number of distinct entries, origins listed twice in different forms counting twice
+    pub fn len(&self) -> usize {
+        self.ids.len() + self.swhids.len()
+    }
+
+    /// Whether no origin is listed
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty() && self.swhids.is_empty()
+    }
+
+    /// Whether the origin node `origin` of `graph` is listed
+    pub fn contains<G: GraphBackend>(&self, graph: &G, origin: NodeId) -> bool {
+        self.ids.contains(&origin) || (!self.swhids.is_empty() && self.swhids.contains(&graph.swhid(origin)))
+    }
+}
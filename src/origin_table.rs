@@ -0,0 +1,66 @@
+use arrow_array::builder::{StringBuilder, TimestampSecondBuilder, UInt32Builder, UInt64Builder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef, TimeUnit};
+use rayon::prelude::*;
+use std::sync::Arc;
+
+use crate::backend::GraphBackend;
+use crate::origin::{Metric, Origin};
+
+/// Origins per record batch
+pub const BATCH_ROWS: usize = 65_536;
+
+/// Columns of the origin table, as served by Arrow Flight and queried by `POST /query`:
+/// one row per loaded origin, metrics not computed yet being null
+pub fn schema() -> Schema {
+    Schema::new(vec![
+        Field::new("id", DataType::UInt64, false),
+        Field::new("url", DataType::Utf8, true),
+        Field::new("latest_commit_date", DataType::Timestamp(TimeUnit::Second, Some("UTC".into())), true),
+        Field::new("commit_count", DataType::UInt32, true),
+        Field::new("committer_count", DataType::UInt32, true),
+        Field::new("cluster", DataType::UInt64, true),
+    ])
+}
+
+/// Record batch of `origins` with the columns of [`schema`], read from what is in memory:
+/// nothing is computed
+pub fn record_batch<G>(schema: SchemaRef, origins: &[Origin<G>]) -> Result<RecordBatch, ArrowError>
+where
+    G: GraphBackend + Send + Sync,
+{
+    let mut ids = UInt64Builder::with_capacity(origins.len());
+    let mut urls = StringBuilder::new();
+    let mut dates = TimestampSecondBuilder::with_capacity(origins.len()).with_timezone("UTC");
+    let mut commits = UInt32Builder::with_capacity(origins.len());
+    let mut committers = UInt32Builder::with_capacity(origins.len());
+    let mut clusters = UInt64Builder::with_capacity(origins.len());
+    // Counts fit in 32 bits, see `OriginData`
+    let count = |origin: &Origin<G>, metric| origin.known_metric(metric).map(|count| count as u32);
+    for origin in origins {
+        ids.append_value(origin.id() as u64);
+        urls.append_option(origin.known_url());
+        dates.append_option(origin.known_metric(Metric::LatestCommitDate));
+        commits.append_option(count(origin, Metric::CommitCount));
+        committers.append_option(count(origin, Metric::CommitterCount));
+        clusters.append_option(origin.cluster().map(|cluster| cluster as u64));
+    }
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(ids.finish()),
+        Arc::new(urls.finish()),
+        Arc::new(dates.finish()),
+        Arc::new(commits.finish()),
+        Arc::new(committers.finish()),
+        Arc::new(clusters.finish()),
+    ];
+    RecordBatch::try_new(schema, columns)
+}
+
+/// Record batches of `origins`, [`BATCH_ROWS`] rows each but the last, built in parallel
+pub fn record_batches<G>(origins: &[Origin<G>]) -> Result<Vec<RecordBatch>, ArrowError>
+where
+    G: GraphBackend + Send + Sync,
+{
+    let schema = Arc::new(schema());
+    origins.par_chunks(BATCH_ROWS).map(|chunk| record_batch(schema.clone(), chunk)).collect()
+}
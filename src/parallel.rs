@@ -0,0 +1,59 @@
+//! Parallel iterators of the library modules: rayon's with the `compute` feature, plain
+//! iterators under the same names otherwise, so that the library builds without rayon
+//! (computing origins on a single thread).
+
+#[cfg(feature = "compute")]
+pub use rayon::prelude::*;
+
+#[cfg(not(feature = "compute"))]
+pub use sequential::*;
+
+/// What `par_iter` returns on a slice
+#[cfg(feature = "compute")]
+pub type SliceIter<'a, T> = rayon::slice::Iter<'a, T>;
+#[cfg(not(feature = "compute"))]
+pub type SliceIter<'a, T> = std::slice::Iter<'a, T>;
+
+#[cfg(not(feature = "compute"))]
+mod sequential {
+    /// `into_par_iter`, i.e. `into_iter`
+    pub trait IntoParallelIterator: IntoIterator + Sized {
+        fn into_par_iter(self) -> Self::IntoIter {
+            self.into_iter()
+        }
+    }
+
+    impl<I: IntoIterator> IntoParallelIterator for I {}
+
+    /// `par_iter`, i.e. `iter`
+    pub trait IntoParallelRefIterator<T> {
+        fn par_iter(&self) -> std::slice::Iter<'_, T>;
+    }
+
+    impl<T> IntoParallelRefIterator<T> for [T] {
+        fn par_iter(&self) -> std::slice::Iter<'_, T> {
+            self.iter()
+        }
+    }
+
+    /// `par_sort_unstable`, i.e. `sort_unstable`
+    pub trait ParallelSliceMut<T> {
+        fn par_sort_unstable(&mut self);
+    }
+
+    impl<T: Ord> ParallelSliceMut<T> for [T] {
+        fn par_sort_unstable(&mut self) {
+            self.sort_unstable()
+        }
+    }
+
+    /// Rayon's `map_init`: `init` is called once, its value given to each call of `f`
+    pub trait ParallelIterator: Iterator + Sized {
+        fn map_init<T, R>(self, init: impl FnOnce() -> T, mut f: impl FnMut(&mut T, Self::Item) -> R) -> impl Iterator<Item = R> {
+            let mut state = init();
+            self.map(move |item| f(&mut state, item))
+        }
+    }
+
+    impl<I: Iterator> ParallelIterator for I {}
+}
@@ -0,0 +1,349 @@
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+#[cfg(feature = "compute")]
+pub use indicatif::{ProgressBar, ProgressStyle};
+#[cfg(not(feature = "compute"))]
+pub use quiet::{ProgressBar, ProgressStyle};
+
+/// What the server is doing before it can answer origin queries
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoadStage {
+    /// Mapping the compressed graph
+    LoadingGraph,
+    /// Mapping the given property family
+    LoadingProperties(&'static str),
+    /// Mapping the edge labels
+    LoadingLabels,
+    /// Reading the origins cache
+    LoadingOrigins,
+    /// No usable cache: discovering the origins of the graph
+    ComputingOrigins,
+    /// Building the list of origins served by `/origins`
+    FilteringOrigins,
+    Ready,
+    Failed(String),
+}
+
+impl LoadStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LoadStage::LoadingGraph => "loading_graph",
+            LoadStage::LoadingProperties(_) => "loading_properties",
+            LoadStage::LoadingLabels => "loading_labels",
+            LoadStage::LoadingOrigins => "loading_origins",
+            LoadStage::ComputingOrigins => "computing_origins",
+            LoadStage::FilteringOrigins => "filtering_origins",
+            LoadStage::Ready => "ready",
+            LoadStage::Failed(_) => "failed",
+        }
+    }
+}
+
+/// Progress of the origins loading, shared between the loader, `/readyz` and `/progress`
+pub struct LoadProgress {
+    stage: Mutex<LoadStage>,
+    done: AtomicUsize,
+    /// Number of items of the current stage, 0 if unknown
+    total: AtomicUsize,
+    started: Instant,
+    /// When the current stage was entered, or its items started being counted
+    stage_started: Mutex<Instant>,
+    /// Stages gone through, with how long each took
+    history: Mutex<Vec<(LoadStage, Duration)>>,
+}
+
+impl Default for LoadProgress {
+    fn default() -> Self {
+        LoadProgress {
+            stage: Mutex::new(LoadStage::LoadingGraph),
+            done: AtomicUsize::new(0),
+            total: AtomicUsize::new(0),
+            started: Instant::now(),
+            stage_started: Mutex::new(Instant::now()),
+            history: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl LoadProgress {
+    /// Enter `stage`, with an unknown number of items to process
+    pub fn set_stage(&self, stage: LoadStage) {
+        let mut current = self.stage.lock().unwrap();
+        let previous = std::mem::replace(&mut *current, stage);
+        let elapsed = self.stage_started.lock().unwrap().elapsed();
+        self.history.lock().unwrap().push((previous, elapsed));
+        self.set_total(0);
+    }
+
+    /// Start counting `total` items processed by the current stage
+    pub fn set_total(&self, total: usize) {
+        *self.stage_started.lock().unwrap() = Instant::now();
+        self.done.store(0, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    /// Record that `count` more items of the current stage were processed
+    pub fn inc(&self, count: usize) {
+        self.done.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn stage(&self) -> LoadStage {
+        self.stage.lock().unwrap().clone()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        *self.stage.lock().unwrap() == LoadStage::Ready
+    }
+
+    /// JSON representation served by `/readyz`
+    pub fn to_json(&self) -> Value {
+        let stage = self.stage();
+        let mut value = json!({
+            "status": match stage {
+                LoadStage::Ready => "ready",
+                LoadStage::Failed(_) => "failed",
+                _ => "loading",
+            },
+            "stage": stage.as_str(),
+        });
+        match &stage {
+            LoadStage::LoadingProperties(family) => value["properties"] = json!(family),
+            LoadStage::Failed(error) => value["error"] = json!(error),
+            _ => {}
+        }
+        let total = self.total.load(Ordering::Relaxed);
+        if total > 0 && stage != LoadStage::Ready {
+            value["progress"] = json!({
+                "done": self.done.load(Ordering::Relaxed),
+                "total": total,
+            });
+        }
+        value
+    }
+
+    /// JSON representation served by `/progress`: the stages gone through with their
+    /// durations, then the current one with its items processed, rate and ETA when their
+    /// number is known
+    pub fn progress_json(&self) -> Value {
+        // Stage first, so that it is consistent with the history
+        let stage = self.stage.lock().unwrap();
+        let stages: Vec<Value> = self
+            .history
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(stage, duration)| {
+                let mut value = stage_json(stage);
+                value["duration_secs"] = json!(duration.as_secs_f64());
+                value
+            })
+            .collect();
+        let elapsed = self.stage_started.lock().unwrap().elapsed().as_secs_f64();
+        let mut current = stage_json(&stage);
+        current["elapsed_secs"] = json!(elapsed);
+        let total = self.total.load(Ordering::Relaxed);
+        if total > 0 && *stage != LoadStage::Ready {
+            let done = self.done.load(Ordering::Relaxed);
+            let rate = if elapsed > 0.0 { done as f64 / elapsed } else { 0.0 };
+            current["done"] = json!(done);
+            current["total"] = json!(total);
+            current["percent"] = json!(100.0 * done as f64 / total as f64);
+            current["rate"] = json!(rate);
+            current["eta_secs"] = json!((rate > 0.0).then(|| total.saturating_sub(done) as f64 / rate));
+        }
+        json!({
+            "status": match *stage {
+                LoadStage::Ready => "ready",
+                LoadStage::Failed(_) => "failed",
+                _ => "loading",
+            },
+            "elapsed_secs": self.started.elapsed().as_secs_f64(),
+            "stages": stages,
+            "current": current,
+        })
+    }
+}
+
+/// Name of a stage, with its property family or error
+fn stage_json(stage: &LoadStage) -> Value {
+    let mut value = json!({ "stage": stage.as_str() });
+    match stage {
+        LoadStage::LoadingProperties(family) => value["properties"] = json!(family),
+        LoadStage::Failed(error) => value["error"] = json!(error),
+        _ => {}
+    }
+    value
+}
+
+impl ProgressSink for LoadProgress {
+    fn add(&self, count: usize) {
+        self.inc(count);
+    }
+}
+
+/// Anything counting processed items: progress bars, the loading progress, jobs
+pub trait ProgressSink: Send + Sync {
+    fn add(&self, count: usize);
+}
+
+impl ProgressSink for ProgressBar {
+    fn add(&self, count: usize) {
+        self.inc(count as u64);
+    }
+}
+
+/// Number of items a [`ProgressBatch`] accumulates before reporting them
+const BATCH_SIZE: usize = 1024;
+
+/// Longest time a [`ProgressBatch`] keeps items before reporting them
+const BATCH_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Progress of a parallel loop, reported to several sinks.
+///
+/// Workers count items in their own [`ProgressBatch`] and only touch the shared sinks every
+/// [`BATCH_SIZE`] items or [`BATCH_INTERVAL`], instead of contending on them for each item:
+///
+/// ```ignore
+/// let progress = Progress::new().with(Arc::new(pb.clone())).with(load_progress.clone());
+/// items.par_iter().for_each_init(|| progress.batch(), |batch, item| {
+///     process(item);
+///     batch.inc();
+/// });
+/// ```
+#[derive(Default)]
+pub struct Progress {
+    sinks: Vec<Arc<dyn ProgressSink>>,
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also report progress to `sink`
+    pub fn with(mut self, sink: Arc<dyn ProgressSink>) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    /// New per-worker counter, reporting what is left when dropped
+    pub fn batch(&self) -> ProgressBatch<'_> {
+        ProgressBatch {
+            progress: self,
+            pending: 0,
+            last_report: Instant::now(),
+        }
+    }
+
+    fn add(&self, count: usize) {
+        for sink in &self.sinks {
+            sink.add(count);
+        }
+    }
+}
+
+/// Items processed by one worker and not reported yet, see [`Progress`]
+pub struct ProgressBatch<'a> {
+    progress: &'a Progress,
+    pending: usize,
+    last_report: Instant,
+}
+
+impl ProgressBatch<'_> {
+    /// Record that one more item was processed
+    pub fn inc(&mut self) {
+        self.pending += 1;
+        if self.pending >= BATCH_SIZE || self.last_report.elapsed() >= BATCH_INTERVAL {
+            self.report();
+        }
+    }
+
+    fn report(&mut self) {
+        if self.pending > 0 {
+            self.progress.add(self.pending);
+            self.pending = 0;
+        }
+        self.last_report = Instant::now();
+    }
+}
+
+impl Drop for ProgressBatch<'_> {
+    fn drop(&mut self) {
+        self.report();
+    }
+}
+
+/// Run one graph loading step as `stage`, logging how long it took, and mark loading as
+/// failed if it does not succeed
+pub fn load_stage<T, E: Display>(
+    progress: &LoadProgress,
+    stage: LoadStage,
+    step: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    let what = match &stage {
+        LoadStage::LoadingProperties(family) => format!("{} properties", family),
+        LoadStage::LoadingLabels => "labels".to_string(),
+        _ => "graph".to_string(),
+    };
+    info!("Loading {}...", what);
+    let start = Instant::now();
+    progress.set_stage(stage);
+    match step() {
+        Ok(loaded) => {
+            info!("Loaded {} in {:.1}s", what, start.elapsed().as_secs_f64());
+            Ok(loaded)
+        }
+        Err(e) => {
+            progress.set_stage(LoadStage::Failed(format!("Failed to load {}: {}", what, e)));
+            Err(e)
+        }
+    }
+}
+
+/// Progress bars drawing nothing, standing in for indicatif's without the `compute`
+/// feature
+#[cfg(not(feature = "compute"))]
+mod quiet {
+    use std::borrow::Cow;
+    use std::convert::Infallible;
+
+    #[derive(Clone)]
+    pub struct ProgressBar;
+
+    impl ProgressBar {
+        pub fn new(_len: u64) -> Self {
+            ProgressBar
+        }
+
+        pub fn inc(&self, _delta: u64) {}
+
+        pub fn set_style(&self, _style: ProgressStyle) {}
+
+        pub fn set_message(&self, _message: impl Into<Cow<'static, str>>) {}
+
+        pub fn finish_with_message(&self, _message: impl Into<Cow<'static, str>>) {}
+
+        pub fn abandon_with_message(&self, _message: impl Into<Cow<'static, str>>) {}
+    }
+
+    pub struct ProgressStyle;
+
+    impl ProgressStyle {
+        pub fn default_bar() -> Self {
+            ProgressStyle
+        }
+
+        pub fn template(self, _template: &str) -> Result<Self, Infallible> {
+            Ok(self)
+        }
+
+        pub fn progress_chars(self, _chars: &str) -> Self {
+            self
+        }
+    }
+}
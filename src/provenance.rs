@@ -0,0 +1,373 @@
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use swh_graph::graph::NodeId;
+use swh_graph::NodeType;
+use tokio_util::sync::CancellationToken;
+
+use crate::backend::GraphBackend;
+use crate::error::GraphError;
+use crate::graph::Graph;
+use crate::origin::skip_over_budget;
+use crate::progress::Progress;
+use crate::utils::{bfs, TraversalOptions, Visit};
+
+/// Name of the multimap file in the cache directory
+pub const REVISION_ORIGINS_FILE: &str = "revision_origins.bin";
+
+/// First bytes of the multimap file. The number of revisions and of (revision, origin)
+/// pairs follow as little-endian `u64`s, then the three arrays of [`RevisionOrigins`].
+const MAGIC: &[u8; 8] = b"SWHREVO1";
+
+/// Mapping from each revision to the origins whose latest snapshot contains it, built
+/// from forward traversals so it needs no transposed graph.
+///
+/// Stored in compressed sparse row form: the origins of `revisions[i]` are
+/// `origins[offsets[i]..offsets[i + 1]]`, sorted, and `revisions` is sorted for binary
+/// search. The file is the same three arrays of little-endian `u64`s.
+pub struct RevisionOrigins {
+    revisions: Vec<NodeId>,
+    offsets: Vec<u64>,
+    origins: Vec<NodeId>,
+}
+
+impl RevisionOrigins {
+    /// Traverse the latest snapshot of every loaded origin and gather, for each revision
+    /// found, the origins containing it. Returns `None` if cancelled meanwhile.
+    pub fn build<G>(graph: &Graph<G>, progress: &Progress, cancel: &CancellationToken) -> Result<Option<Self>, GraphError>
+    where
+        G: GraphBackend + Send + Sync,
+    {
+        let mut pairs: Vec<(NodeId, NodeId)> = graph
+            .loaded_origins()
+            .par_iter()
+            .map_init(|| progress.batch(), |batch, origin| {
+                batch.inc();
+                let mut pairs = Vec::new();
+                if cancel.is_cancelled() {
+                    return Ok(pairs);
+                }
+                let Some((snapshot, _)) = origin.get_latest_snapshot()? else {
+                    return Ok(pairs);
+                };
+                let inner = origin.get_graph()?;
+                bfs(inner.as_ref(), [snapshot], &TraversalOptions::new().cancel(cancel), |node, _| {
+                    if inner.node_type(node) == NodeType::Revision {
+                        pairs.push((node, origin.id));
+                    }
+                    Visit::Continue
+                });
+                Ok(pairs)
+            })
+            .flat_map_iter(|pairs: Result<Vec<_>, GraphError>| {
+                // Flattened as they come rather than collected per origin first
+                let (pairs, failure) = match pairs {
+                    Ok(pairs) => (pairs, None),
+                    Err(e) => (Vec::new(), Some(Err(e))),
+                };
+                pairs.into_iter().map(Ok).chain(failure)
+            })
+            .collect::<Result<_, GraphError>>()?;
+        if cancel.is_cancelled() {
+            return Ok(None);
+        }
+        pairs.par_sort_unstable();
+        pairs.dedup();
+
+        let mut revisions = Vec::new();
+        let mut offsets = Vec::new();
+        let mut origins = Vec::with_capacity(pairs.len());
+        for (revision, origin) in pairs {
+            if revisions.last() != Some(&revision) {
+                revisions.push(revision);
+                offsets.push(origins.len() as u64);
+            }
+            origins.push(origin);
+        }
+        offsets.push(origins.len() as u64);
+        Ok(Some(RevisionOrigins { revisions, offsets, origins }))
+    }
+
+    /// Origins whose latest snapshot contains `revision`, empty if it is in none
+    pub fn origins_of(&self, revision: NodeId) -> &[NodeId] {
+        match self.revisions.binary_search(&revision) {
+            Ok(i) => &self.origins[self.offsets[i] as usize..self.offsets[i + 1] as usize],
+            Err(_) => &[],
+        }
+    }
+
+    /// Revisions, each with the origins containing it
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &[NodeId])> + '_ {
+        self.revisions.iter().enumerate().map(|(i, &revision)| {
+            (revision, &self.origins[self.offsets[i] as usize..self.offsets[i + 1] as usize])
+        })
+    }
+
+    /// Number of distinct revisions found in at least one origin
+    pub fn revision_count(&self) -> usize {
+        self.revisions.len()
+    }
+
+    /// Number of (revision, origin) pairs
+    pub fn pair_count(&self) -> usize {
+        self.origins.len()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), GraphError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(self.revisions.len() as u64).to_le_bytes())?;
+        writer.write_all(&(self.origins.len() as u64).to_le_bytes())?;
+        for &revision in &self.revisions {
+            writer.write_all(&(revision as u64).to_le_bytes())?;
+        }
+        for &offset in &self.offsets {
+            writer.write_all(&offset.to_le_bytes())?;
+        }
+        for &origin in &self.origins {
+            writer.write_all(&(origin as u64).to_le_bytes())?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, GraphError> {
+        let corrupted = |reason: &str| GraphError::CacheCorrupted {
+            path: path.to_path_buf(),
+            reason: reason.to_string(),
+        };
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(corrupted("not a revision to origins multimap"));
+        }
+        let revision_count = read_u64(&mut reader)? as usize;
+        let pair_count = read_u64(&mut reader)? as usize;
+        let revisions = read_u64s(&mut reader, revision_count)?;
+        let offsets = read_u64s(&mut reader, revision_count + 1)?;
+        let origins = read_u64s(&mut reader, pair_count)?;
+        if offsets.last() != Some(&(pair_count as u64)) || offsets.windows(2).any(|w| w[0] > w[1]) {
+            return Err(corrupted("inconsistent offsets"));
+        }
+        Ok(RevisionOrigins {
+            revisions: revisions.into_iter().map(|id| id as NodeId).collect(),
+            offsets,
+            origins: origins.into_iter().map(|id| id as NodeId).collect(),
+        })
+    }
+}
+
+/// Name of the shared contents file in the cache directory
+pub const SHARED_CONTENTS_FILE: &str = "shared_contents.bin";
+
+/// First bytes of the shared contents file. The number of nodes of the graph follows as a
+/// little-endian `u64`, then the words of the bitset of [`SharedContents`].
+const SHARED_CONTENTS_MAGIC: &[u8; 8] = b"SWHSHRC1";
+
+/// Contents found in the latest snapshot of more than one loaded origin, to tell how much
+/// of each origin is vendored or copied code, see [`assign_dedup`].
+///
+/// A bitset over all the nodes of the graph, i.e. an eighth of a byte per node: it is
+/// built with two of them, one marking the contents already found in some origin.
+pub struct SharedContents {
+    num_nodes: usize,
+    bits: Vec<u64>,
+}
+
+impl SharedContents {
+    /// Traverse the latest snapshot of every loaded origin, marking the contents found in
+    /// two of them or more. Returns `None` if cancelled meanwhile.
+    pub fn build<G>(graph: &Graph<G>, progress: &Progress, cancel: &CancellationToken) -> Result<Option<Self>, GraphError>
+    where
+        G: GraphBackend + Send + Sync,
+    {
+        let num_nodes = graph.inner_graph().num_nodes();
+        let words = num_nodes.div_ceil(64);
+        let seen: Vec<AtomicU64> = (0..words).map(|_| AtomicU64::new(0)).collect();
+        let shared: Vec<AtomicU64> = (0..words).map(|_| AtomicU64::new(0)).collect();
+        graph.par_origins_iter().try_for_each_init(|| progress.batch(), |batch, origin| -> Result<(), GraphError> {
+            batch.inc();
+            if cancel.is_cancelled() {
+                return Ok(());
+            }
+            let Some((snapshot, _)) = origin.get_latest_snapshot()? else {
+                return Ok(());
+            };
+            let inner = origin.get_graph()?;
+            // Each content is visited once per origin, so finding it seen means another
+            // origin has it
+            bfs(inner.as_ref(), [snapshot], &TraversalOptions::new().cancel(cancel), |node, _| {
+                if inner.node_type(node) == NodeType::Content {
+                    let (word, bit) = (node / 64, 1 << (node % 64));
+                    if seen[word].fetch_or(bit, Ordering::Relaxed) & bit != 0 {
+                        shared[word].fetch_or(bit, Ordering::Relaxed);
+                    }
+                }
+                Visit::Continue
+            });
+            Ok(())
+        })?;
+        if cancel.is_cancelled() {
+            return Ok(None);
+        }
+        Ok(Some(SharedContents { num_nodes, bits: shared.into_iter().map(AtomicU64::into_inner).collect() }))
+    }
+
+    /// Whether `content` is in the latest snapshot of more than one origin
+    pub fn contains(&self, content: NodeId) -> bool {
+        content < self.num_nodes && self.bits[content / 64] & (1 << (content % 64)) != 0
+    }
+
+    /// Number of contents in more than one origin
+    pub fn len(&self) -> usize {
+        self.bits.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|&word| word == 0)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), GraphError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(SHARED_CONTENTS_MAGIC)?;
+        writer.write_all(&(self.num_nodes as u64).to_le_bytes())?;
+        for &word in &self.bits {
+            writer.write_all(&word.to_le_bytes())?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, GraphError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != SHARED_CONTENTS_MAGIC {
+            return Err(GraphError::CacheCorrupted {
+                path: path.to_path_buf(),
+                reason: "not a shared contents file".to_string(),
+            });
+        }
+        let num_nodes = read_u64(&mut reader)? as usize;
+        let bits = read_u64s(&mut reader, num_nodes.div_ceil(64))?;
+        Ok(SharedContents { num_nodes, bits })
+    }
+}
+
+/// Count, for each loaded origin, the contents of its latest snapshot and those of them
+/// in `shared`, see [`Origin::compute_dedup`](crate::origin::Origin::compute_dedup).
+/// Returns the number of origins whose ratio was computed, `None` if cancelled meanwhile.
+pub fn assign_dedup<G>(
+    graph: &Graph<G>,
+    shared: &SharedContents,
+    progress: &Progress,
+    cancel: &CancellationToken,
+) -> Result<Option<usize>, GraphError>
+where
+    G: GraphBackend + Send + Sync,
+{
+    let computed = graph
+        .loaded_origins()
+        .par_iter()
+        .map_init(|| progress.batch(), |batch, origin| {
+            batch.inc();
+            let known = origin.dedup().is_some();
+            // Origins over the node budget are left out like cancelled ones
+            let dedup = skip_over_budget(origin.compute_dedup(|node| shared.contains(node), cancel), None)?;
+            Ok(usize::from(!known && dedup.is_some()))
+        })
+        .sum::<Result<usize, GraphError>>()?;
+    if cancel.is_cancelled() {
+        return Ok(None);
+    }
+    graph.mark_origins_modified(computed);
+    Ok(Some(computed))
+}
+
+/// Group the loaded origins into clusters of mirrors and forks: the connected components
+/// of the "shares a root revision" relation, root revisions being those without parent.
+///
+/// Each origin gets the smallest origin id of its cluster as cluster id; origins without
+/// any revision get none. Returns the number of clusters.
+pub fn assign_clusters<G>(graph: &mut Graph<G>, multimap: &RevisionOrigins) -> Result<usize, GraphError>
+where
+    G: GraphBackend + Send + Sync,
+{
+    let inner = graph.inner_graph();
+    let ids: Vec<NodeId> = graph.get_origins()?.iter().map(|origin| origin.id).collect();
+    let position = |id: NodeId| ids.binary_search(&id).ok();
+
+    // Union-find over origin positions, the representative being the smallest position
+    // (hence the smallest id, origins being sorted by id)
+    let mut parent: Vec<usize> = (0..ids.len()).collect();
+    let mut has_revision = vec![false; ids.len()];
+    for (revision, origins) in multimap.iter() {
+        let positions = origins.iter().filter_map(|&origin| position(origin));
+        let is_root = inner
+            .successors(revision)
+            .all(|succ| inner.node_type(succ) != NodeType::Revision);
+        let mut first = None;
+        for pos in positions {
+            has_revision[pos] = true;
+            if !is_root {
+                continue;
+            }
+            match first {
+                None => first = Some(pos),
+                Some(first) => union(&mut parent, first, pos),
+            }
+        }
+    }
+
+    let mut clusters = 0;
+    let origins = graph.get_origins_mut()?;
+    for pos in 0..origins.len() {
+        let cluster = has_revision[pos].then(|| find(&mut parent, pos));
+        if cluster == Some(pos) {
+            clusters += 1;
+        }
+        origins[pos].set_cluster(cluster.map(|representative| ids[representative]));
+    }
+    let count = origins.len();
+    graph.mark_origins_modified(count);
+    Ok(clusters)
+}
+
+fn find(parent: &mut [usize], mut node: usize) -> usize {
+    let mut root = node;
+    while parent[root] != root {
+        root = parent[root];
+    }
+    // Path compression
+    while parent[node] != root {
+        let next = parent[node];
+        parent[node] = root;
+        node = next;
+    }
+    root
+}
+
+fn union(parent: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parent, a), find(parent, b));
+    if a != b {
+        parent[a.max(b)] = a.min(b);
+    }
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64, GraphError> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_u64s(reader: &mut impl Read, count: usize) -> Result<Vec<u64>, GraphError> {
+    // Don't trust a corrupted count for the allocation
+    let mut values = Vec::with_capacity(count.min(1 << 24));
+    for _ in 0..count {
+        values.push(read_u64(reader)?);
+    }
+    Ok(values)
+}
@@ -0,0 +1,156 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+use crate::error::GraphError;
+use crate::progress::ProgressSink;
+
+/// Longest time a push may take before it is given up
+const PUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Prometheus Pushgateway receiving the progress of a batch run, so that runs lasting
+/// hours unattended can be monitored and alerted on.
+///
+/// Counts the items reported to it as a [`ProgressSink`] and pushes them, along with the
+/// rate and failures, every interval from a background thread (see [`Self::spawn`]).
+/// Pushes replace the metrics of the job (HTTP `PUT`); failing pushes are logged and
+/// never stop the run. Only plain `http://` gateways are supported.
+pub struct Pushgateway {
+    address: String,
+    host: String,
+    path: String,
+    started: Instant,
+    started_at: u64,
+    processed: AtomicUsize,
+    failures: AtomicUsize,
+    total: AtomicUsize,
+    done: AtomicBool,
+    /// Wakes the pushing thread up early when the run is done
+    stop: (Mutex<bool>, Condvar),
+}
+
+impl Pushgateway {
+    /// Gateway at `url` (e.g. `http://localhost:9091`), the metrics being grouped under
+    /// `job`
+    pub fn new(url: &str, job: &str) -> Result<Self, GraphError> {
+        let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidInput, format!("Pushgateway URL {:?}: {}", url, reason));
+        let rest = url.strip_prefix("http://").ok_or_else(|| invalid("only http:// is supported"))?;
+        let (authority, prefix) = rest.split_once('/').map_or((rest, ""), |(authority, prefix)| (authority, prefix));
+        if authority.is_empty() {
+            return Err(invalid("missing host").into());
+        }
+        if job.is_empty() || !job.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.') {
+            return Err(invalid("the job name must be made of letters, digits, '_', '-' and '.'").into());
+        }
+        let address = if authority.contains(':') { authority.to_string() } else { format!("{}:9091", authority) };
+        let prefix = prefix.trim_end_matches('/');
+        let path = if prefix.is_empty() { format!("/metrics/job/{}", job) } else { format!("/{}/metrics/job/{}", prefix, job) };
+        Ok(Pushgateway {
+            address,
+            host: authority.to_string(),
+            path,
+            started: Instant::now(),
+            started_at: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs()),
+            processed: AtomicUsize::new(0),
+            failures: AtomicUsize::new(0),
+            total: AtomicUsize::new(0),
+            done: AtomicBool::new(false),
+            stop: (Mutex::new(false), Condvar::new()),
+        })
+    }
+
+    /// Number of items the run is expected to process, 0 if unknown
+    pub fn set_total(&self, total: usize) {
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    /// Record items which failed to be processed
+    pub fn add_failures(&self, count: usize) {
+        self.failures.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Push every `interval` until [`Self::finish`] is called
+    pub fn spawn(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let gateway = self.clone();
+        thread::spawn(move || {
+            let (stopped, wake) = &gateway.stop;
+            let mut stopped = stopped.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            while !*stopped {
+                gateway.push_or_warn();
+                stopped = wake.wait_timeout(stopped, interval).unwrap_or_else(|poisoned| poisoned.into_inner()).0;
+            }
+        })
+    }
+
+    /// Mark the run as done, stop the pushing thread and push the final values
+    pub fn finish(&self) {
+        self.done.store(true, Ordering::Relaxed);
+        *self.stop.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = true;
+        self.stop.1.notify_all();
+        self.push_or_warn();
+    }
+
+    fn push_or_warn(&self) {
+        if let Err(e) = self.push() {
+            warn!("Failed to push metrics to the Pushgateway at {}: {}", self.address, e);
+        }
+    }
+
+    fn push(&self) -> io::Result<()> {
+        let body = self.render();
+        let address = self
+            .address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address"))?;
+        let mut stream = TcpStream::connect_timeout(&address, PUSH_TIMEOUT)?;
+        stream.set_read_timeout(Some(PUSH_TIMEOUT))?;
+        stream.set_write_timeout(Some(PUSH_TIMEOUT))?;
+        write!(
+            stream,
+            "PUT {} HTTP/1.1\r\nHost: {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.path,
+            self.host,
+            body.len(),
+            body
+        )?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        let status = response.split_whitespace().nth(1).unwrap_or_default();
+        if status.starts_with('2') {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!("HTTP status {:?}", status)))
+        }
+    }
+
+    /// Metrics in the Prometheus text exposition format
+    fn render(&self) -> String {
+        let processed = self.processed.load(Ordering::Relaxed);
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 { processed as f64 / elapsed } else { 0.0 };
+        let metrics = [
+            ("swh_batch_processed_total", "counter", "Items processed so far", processed as f64),
+            ("swh_batch_failures_total", "counter", "Items which failed to be processed", self.failures.load(Ordering::Relaxed) as f64),
+            ("swh_batch_items", "gauge", "Items to process, 0 if unknown", self.total.load(Ordering::Relaxed) as f64),
+            ("swh_batch_rate", "gauge", "Items processed per second since the start", rate),
+            ("swh_batch_start_time_seconds", "gauge", "Start of the run, seconds since the epoch", self.started_at as f64),
+            ("swh_batch_done", "gauge", "Whether the run is over", if self.done.load(Ordering::Relaxed) { 1.0 } else { 0.0 }),
+        ];
+        let mut body = String::new();
+        for (name, kind, help, value) in metrics {
+            body.push_str(&format!("# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n"));
+        }
+        body
+    }
+}
+
+impl ProgressSink for Pushgateway {
+    fn add(&self, count: usize) {
+        self.processed.fetch_add(count, Ordering::Relaxed);
+    }
+}
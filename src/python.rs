@@ -0,0 +1,205 @@
+//! `swh_origins` Python module, built with `--features python`: graph loading, origins
+//! cache access and per-origin metrics, for notebooks which would otherwise go through
+//! the HTTP API for everything.
+
+use pyo3::exceptions::{PyOSError, PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use std::path::PathBuf;
+use swh_graph::graph::SwhUnidirectionalGraph;
+use swh_graph::mph::DynMphf;
+
+use crate::backend::GraphBackend;
+use crate::error::GraphError;
+use crate::graph::{read_origins_cache, Graph, SerializationFormat};
+use crate::origin::{Metrics, OriginData};
+
+impl From<GraphError> for PyErr {
+    fn from(e: GraphError) -> Self {
+        match e {
+            GraphError::Io(e) => PyOSError::new_err(e.to_string()),
+            GraphError::OriginNotFound(_) => PyValueError::new_err(e.to_string()),
+            e => PyRuntimeError::new_err(e.to_string()),
+        }
+    }
+}
+
+/// What the bindings use of a [`Graph`], object safe so that [`PyGraph`] doesn't have to
+/// name the type of the loaded graph
+trait Origins: Send + Sync {
+    fn stats(&self) -> (usize, usize);
+    fn load(&mut self) -> Result<usize, GraphError>;
+    fn origin_ids(&self) -> Vec<usize>;
+    fn eligible_origin_ids(&mut self) -> Result<Vec<usize>, GraphError>;
+    fn origin(&self, id: usize, compute: bool) -> Result<Option<OriginData>, GraphError>;
+    fn write_back(&mut self) -> Result<usize, GraphError>;
+}
+
+impl<G> Origins for Graph<G>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    fn stats(&self) -> (usize, usize) {
+        Graph::stats(self)
+    }
+
+    fn load(&mut self) -> Result<usize, GraphError> {
+        self.get_origins().map(Vec::len)
+    }
+
+    fn origin_ids(&self) -> Vec<usize> {
+        Graph::origin_ids(self)
+    }
+
+    fn eligible_origin_ids(&mut self) -> Result<Vec<usize>, GraphError> {
+        self.get_eligible_origin_ids().cloned()
+    }
+
+    fn origin(&self, id: usize, compute: bool) -> Result<Option<OriginData>, GraphError> {
+        self.with_origin(id, |origin| {
+            if compute {
+                origin.get_url()?;
+                origin.compute_metrics(Metrics::ALL)?;
+            }
+            Ok(origin.to_data())
+        })
+        .transpose()
+    }
+
+    fn write_back(&mut self) -> Result<usize, GraphError> {
+        Graph::write_back(self)
+    }
+}
+
+/// Compressed graph and its origins, loaded from the origins cache (or computed and
+/// cached on first use)
+#[pyclass(name = "Graph")]
+struct PyGraph {
+    inner: Box<dyn Origins>,
+}
+
+#[pymethods]
+impl PyGraph {
+    /// Load the graph at `path` (its basename, e.g. `/data/graph`) with the properties
+    /// metrics need. The origins cache lives in `cache_dir`, next to the graph by default.
+    #[new]
+    #[pyo3(signature = (path, cache_dir = None))]
+    fn new(py: Python<'_>, path: PathBuf, cache_dir: Option<PathBuf>) -> PyResult<Self> {
+        let inner = py.allow_threads(|| -> Result<Box<dyn Origins>, GraphError> {
+            let graph = SwhUnidirectionalGraph::new(&path)?
+                .init_properties()
+                .load_properties(|p| p.load_maps::<DynMphf>())?
+                .load_properties(|p| p.load_timestamps())?
+                .load_properties(|p| p.load_persons())?
+                .load_properties(|p| p.load_strings())?
+                .load_labels()?;
+            let mut builder = Graph::builder(&path).serialization(SerializationFormat::Compact);
+            if let Some(dir) = cache_dir {
+                builder = builder.cache_dir(dir);
+            }
+            Ok(Box::new(builder.build(graph)))
+        })?;
+        Ok(PyGraph { inner })
+    }
+
+    #[getter]
+    fn num_nodes(&self) -> usize {
+        self.inner.stats().0
+    }
+
+    #[getter]
+    fn num_arcs(&self) -> usize {
+        self.inner.stats().1
+    }
+
+    /// Load the origins, returning how many there are
+    fn load_origins(&mut self, py: Python<'_>) -> PyResult<usize> {
+        Ok(py.allow_threads(|| self.inner.load())?)
+    }
+
+    /// Ids of the loaded origins
+    fn origin_ids(&self) -> Vec<usize> {
+        self.inner.origin_ids()
+    }
+
+    /// Ids of the origins having commits and a latest commit date, as served by
+    /// `/origins`
+    fn eligible_origin_ids(&mut self, py: Python<'_>) -> PyResult<Vec<usize>> {
+        Ok(py.allow_threads(|| self.inner.eligible_origin_ids())?)
+    }
+
+    /// Origin `id` with its metrics, computing the missing ones unless `compute` is false
+    #[pyo3(signature = (id, compute = true))]
+    fn origin(&self, py: Python<'_>, id: usize, compute: bool) -> PyResult<PyOrigin> {
+        let data = py.allow_threads(|| self.inner.origin(id, compute))?.ok_or(GraphError::OriginNotFound(id))?;
+        Ok(PyOrigin::from(data))
+    }
+
+    /// Save the metrics computed since the origins cache was loaded, returning the number
+    /// of origins saved
+    fn save(&mut self, py: Python<'_>) -> PyResult<usize> {
+        Ok(py.allow_threads(|| self.inner.write_back())?)
+    }
+}
+
+/// An origin and its metrics, `None` if not computed
+#[pyclass(name = "Origin", frozen, get_all)]
+struct PyOrigin {
+    id: usize,
+    url: Option<String>,
+    /// Seconds since the epoch
+    latest_commit_date: Option<i64>,
+    commit_count: Option<u32>,
+    committer_count: Option<u32>,
+    cluster: Option<usize>,
+}
+
+impl From<OriginData> for PyOrigin {
+    fn from(data: OriginData) -> Self {
+        PyOrigin {
+            id: data.id,
+            url: data.url,
+            latest_commit_date: data.latest_commit_date,
+            commit_count: data.number_of_commits,
+            committer_count: data.number_of_commiters,
+            cluster: data.cluster,
+        }
+    }
+}
+
+#[pymethods]
+impl PyOrigin {
+    fn __repr__(&self) -> String {
+        format!(
+            "Origin(id={}, url={}, latest_commit_date={}, commit_count={}, committer_count={}, cluster={})",
+            self.id,
+            optional(self.url.as_ref().map(|url| format!("{:?}", url))),
+            optional(self.latest_commit_date),
+            optional(self.commit_count),
+            optional(self.committer_count),
+            optional(self.cluster)
+        )
+    }
+}
+
+/// Python representation of an optional value
+fn optional(value: Option<impl ToString>) -> String {
+    value.map_or("None".to_string(), |value| value.to_string())
+}
+
+/// Read the origins of a cache file without loading any graph; `format` is `"compact"`
+/// (formerly `"bincode"`, still accepted) or `"json"`
+#[pyfunction]
+#[pyo3(signature = (path, format = "compact"))]
+fn read_cache(py: Python<'_>, path: PathBuf, format: &str) -> PyResult<Vec<PyOrigin>> {
+    let format: SerializationFormat = format.parse().map_err(PyValueError::new_err)?;
+    let origins = py.allow_threads(|| read_origins_cache(&path, format))?;
+    Ok(origins.into_iter().map(PyOrigin::from).collect())
+}
+
+#[pymodule]
+fn swh_origins(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyGraph>()?;
+    module.add_class::<PyOrigin>()?;
+    module.add_function(wrap_pyfunction!(read_cache, module)?)?;
+    Ok(())
+}
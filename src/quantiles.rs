@@ -0,0 +1,72 @@
+use tdigest::TDigest;
+
+use crate::backend::GraphBackend;
+use crate::origin::{Metric, Origin};
+
+/// Centroids kept by each digest: quantiles are within a fraction of a percent of the
+/// exact ones, for a few kilobytes per metric
+const DIGEST_SIZE: usize = 200;
+
+/// Origins whose values are collected before being merged into the digests, bounding
+/// the memory used while building them
+const CHUNK: usize = 1 << 20;
+
+const METRICS: [Metric; 3] = [Metric::LatestCommitDate, Metric::CommitCount, Metric::CommitterCount];
+
+/// t-digest of each metric over the loaded origins, estimating its quantiles without
+/// going through the origins again, see [`crate::graph::Graph::metric_digests`]
+pub struct MetricDigests {
+    /// In the order of [`METRICS`]
+    digests: [TDigest; 3],
+}
+
+impl MetricDigests {
+    /// Digests of the metrics already known of `origins`, nothing being computed
+    pub fn build<G>(origins: &[Origin<G>]) -> Self
+    where
+        G: GraphBackend + Send + Sync,
+    {
+        let mut digests = METRICS.map(|_| TDigest::new_with_size(DIGEST_SIZE));
+        for chunk in origins.chunks(CHUNK) {
+            for (digest, metric) in digests.iter_mut().zip(METRICS) {
+                let values: Vec<f64> = chunk
+                    .iter()
+                    .filter_map(|origin| origin.known_metric(metric))
+                    .map(|value| value as f64)
+                    .collect();
+                if !values.is_empty() {
+                    *digest = digest.merge_unsorted(values);
+                }
+            }
+        }
+        MetricDigests { digests }
+    }
+
+    fn digest(&self, metric: Metric) -> &TDigest {
+        let position = METRICS.iter().position(|&m| m == metric).unwrap();
+        &self.digests[position]
+    }
+
+    /// Number of origins whose `metric` is known
+    pub fn count(&self, metric: Metric) -> usize {
+        self.digest(metric).count() as usize
+    }
+
+    /// Estimated `q` quantile (between 0 and 1) of `metric`, `None` if it is not known of
+    /// any origin
+    pub fn quantile(&self, metric: Metric, q: f64) -> Option<f64> {
+        let digest = self.digest(metric);
+        (!digest.is_empty()).then(|| digest.estimate_quantile(q))
+    }
+}
+
+/// Comma separated quantiles, e.g. `0.5,0.9,0.99`, each between 0 and 1
+pub fn parse_quantiles(value: &str) -> Result<Vec<f64>, String> {
+    value
+        .split(',')
+        .map(|q| match q.trim().parse::<f64>() {
+            Ok(q) if (0.0..=1.0).contains(&q) => Ok(q),
+            _ => Err(format!("invalid quantile {:?}, expected a number between 0 and 1", q)),
+        })
+        .collect()
+}
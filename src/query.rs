@@ -0,0 +1,606 @@
+use rayon::prelude::*;
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use tokio_util::sync::CancellationToken;
+
+use crate::backend::GraphBackend;
+use crate::origin::{parse_as_of, Metric, Origin};
+use crate::urls::wildcard_match;
+
+/// Rows returned at most by a query, whatever its `LIMIT`
+pub const MAX_QUERY_ROWS: usize = 100_000;
+
+/// Column of the `origins` table, the metrics in memory (`NULL` if not computed)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Column {
+    Id,
+    Url,
+    /// Seconds since the epoch
+    LatestCommitDate,
+    CommitCount,
+    CommitterCount,
+    Cluster,
+}
+
+const COLUMNS: [Column; 6] = [
+    Column::Id,
+    Column::Url,
+    Column::LatestCommitDate,
+    Column::CommitCount,
+    Column::CommitterCount,
+    Column::Cluster,
+];
+
+impl Column {
+    fn name(self) -> &'static str {
+        match self {
+            Column::Id => "id",
+            Column::Url => "url",
+            Column::LatestCommitDate => "latest_commit_date",
+            Column::CommitCount => "commit_count",
+            Column::CommitterCount => "committer_count",
+            Column::Cluster => "cluster",
+        }
+    }
+
+    fn named(name: &str) -> Option<Self> {
+        COLUMNS.into_iter().find(|column| column.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Value of the column for `origin`, borrowing its URL
+    fn value<G>(self, origin: &Origin<G>) -> Cell<'_>
+    where
+        G: GraphBackend + Send + Sync,
+    {
+        let int = |value: Option<i64>| value.map_or(Cell::Null, Cell::Int);
+        match self {
+            Column::Id => Cell::Int(origin.id() as i64),
+            Column::Url => origin.known_url().map_or(Cell::Null, |url| Cell::Text(Cow::Borrowed(url))),
+            Column::LatestCommitDate => int(origin.known_metric(Metric::LatestCommitDate)),
+            Column::CommitCount => int(origin.known_metric(Metric::CommitCount)),
+            Column::CommitterCount => int(origin.known_metric(Metric::CommitterCount)),
+            Column::Cluster => int(origin.cluster().map(|cluster| cluster as i64)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Cell<'a> {
+    Null,
+    Int(i64),
+    Text(Cow<'a, str>),
+}
+
+impl Cell<'_> {
+    fn to_json(&self) -> Value {
+        match self {
+            Cell::Null => Value::Null,
+            Cell::Int(value) => json!(value),
+            Cell::Text(value) => json!(value),
+        }
+    }
+
+    /// Order of `ORDER BY`, `NULL` coming after any value
+    fn order(&self, other: &Cell<'_>) -> Ordering {
+        match (self, other) {
+            (Cell::Int(a), Cell::Int(b)) => a.cmp(b),
+            (Cell::Text(a), Cell::Text(b)) => a.cmp(b),
+            (Cell::Null, Cell::Null) => Ordering::Equal,
+            (Cell::Null, _) => Ordering::Greater,
+            (_, Cell::Null) => Ordering::Less,
+            // Columns have a single type
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::Equal => ordering.is_eq(),
+            Comparison::NotEqual => ordering.is_ne(),
+            Comparison::Less => ordering.is_lt(),
+            Comparison::LessOrEqual => ordering.is_le(),
+            Comparison::Greater => ordering.is_gt(),
+            Comparison::GreaterOrEqual => ordering.is_ge(),
+        }
+    }
+}
+
+/// Condition of a `WHERE` clause, in SQL's three-valued logic: comparing `NULL` is
+/// unknown (`None`) and unknown rows are filtered out
+#[derive(Clone, Debug)]
+enum Condition {
+    Compare(Column, Comparison, Cell<'static>),
+    IsNull { column: Column, negated: bool },
+    Like { column: Column, pattern: String, negated: bool },
+    Not(Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+impl Condition {
+    fn eval<G>(&self, origin: &Origin<G>) -> Option<bool>
+    where
+        G: GraphBackend + Send + Sync,
+    {
+        match self {
+            Condition::Compare(column, comparison, literal) => match column.value(origin) {
+                Cell::Null => None,
+                value => Some(comparison.holds(value.order(literal))),
+            },
+            Condition::IsNull { column, negated } => Some((column.value(origin) == Cell::Null) != *negated),
+            Condition::Like { column, pattern, negated } => match column.value(origin) {
+                Cell::Text(value) => Some(wildcard_match(pattern.as_bytes(), value.as_bytes(), b'%', b'_') != *negated),
+                _ => None,
+            },
+            Condition::Not(condition) => condition.eval(origin).map(|holds| !holds),
+            Condition::And(left, right) => match left.eval(origin) {
+                Some(false) => Some(false),
+                left => match (left, right.eval(origin)) {
+                    (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                },
+            },
+            Condition::Or(left, right) => match left.eval(origin) {
+                Some(true) => Some(true),
+                left => match (left, right.eval(origin)) {
+                    (_, Some(true)) => Some(true),
+                    (Some(false), Some(false)) => Some(false),
+                    _ => None,
+                },
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Selection {
+    Columns(Vec<Column>),
+    Count,
+}
+
+/// Ad-hoc query over the origins, in a subset of SQL:
+///
+/// ```sql
+/// SELECT url, commit_count FROM origins
+/// WHERE committer_count > 50 AND url LIKE 'https://github.com/%'
+/// ORDER BY commit_count DESC LIMIT 100
+/// ```
+///
+/// A single `origins` table with the columns `id`, `url`, `latest_commit_date` (seconds
+/// since the epoch, compared with numbers or dates such as `'2024-01-01'`),
+/// `commit_count`, `committer_count` and `cluster`. `SELECT` takes `*`, column names or
+/// `COUNT(*)`; `WHERE` comparisons (`=`, `<>`, `!=`, `<`, `<=`, `>`, `>=`), `IS [NOT] NULL`
+/// and `[NOT] LIKE` (on `url`) combined with `AND`, `OR`, `NOT` and parentheses.
+///
+/// No joins, grouping nor other aggregates: the server built with the `sql` feature runs
+/// queries with DataFusion instead, see `sql::run`.
+#[derive(Clone, Debug)]
+pub struct Query {
+    selection: Selection,
+    filter: Option<Condition>,
+    /// Columns and whether they are sorted in descending order
+    order_by: Vec<(Column, bool)>,
+    limit: Option<usize>,
+}
+
+impl Query {
+    /// Run the query over `origins`, returning the selected columns and rows, or `None`
+    /// if cancelled. At most [`MAX_QUERY_ROWS`] rows are returned, `truncated` telling
+    /// whether there were more. Conditions and sorting read the origins in place, only
+    /// the returned rows being copied.
+    pub fn run<G>(&self, origins: &[Origin<G>], cancel: &CancellationToken) -> Option<Value>
+    where
+        G: GraphBackend + Send + Sync,
+    {
+        let matching: Result<Vec<&Origin<G>>, ()> = origins
+            .par_iter()
+            .filter_map(|origin| {
+                if cancel.is_cancelled() {
+                    return Some(Err(()));
+                }
+                self.filter.as_ref().is_none_or(|filter| filter.eval(origin) == Some(true)).then_some(Ok(origin))
+            })
+            .collect();
+        let mut matching = matching.ok()?;
+        let columns = match &self.selection {
+            Selection::Count => {
+                return Some(json!({"columns": ["count"], "rows": [[matching.len()]], "truncated": false}));
+            }
+            Selection::Columns(columns) => columns,
+        };
+        if !self.order_by.is_empty() {
+            matching.par_sort_by(|a, b| {
+                self.order_by.iter().fold(Ordering::Equal, |ordering, &(column, descending)| {
+                    ordering.then_with(|| {
+                        let ordering = column.value(a).order(&column.value(b));
+                        if descending { ordering.reverse() } else { ordering }
+                    })
+                })
+            });
+        }
+        let limit = self.limit.unwrap_or(usize::MAX);
+        let truncated = matching.len() > MAX_QUERY_ROWS && limit > MAX_QUERY_ROWS;
+        let rows: Vec<Value> = matching
+            .iter()
+            .take(limit.min(MAX_QUERY_ROWS))
+            .map(|origin| Value::Array(columns.iter().map(|column| column.value(origin).to_json()).collect()))
+            .collect();
+        Some(json!({
+            "columns": columns.iter().map(|column| column.name()).collect::<Vec<_>>(),
+            "rows": rows,
+            "truncated": truncated,
+        }))
+    }
+}
+
+impl std::str::FromStr for Query {
+    type Err = String;
+
+    fn from_str(sql: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { tokens: tokenize(sql)?, position: 0 };
+        let query = parser.query()?;
+        match parser.peek() {
+            None => Ok(query),
+            token => Err(format!("unexpected {}", describe(token))),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Number(i64),
+    Text(String),
+    Symbol(&'static str),
+}
+
+/// Symbols, the longest first so that `<=` is not read as `<`
+const SYMBOLS: [&str; 12] = ["<=", ">=", "<>", "!=", "=", "<", ">", "(", ")", ",", "*", ";"];
+
+fn tokenize(sql: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = sql.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let negative = c == '-' && rest[1..].starts_with(|c: char| c.is_ascii_digit());
+        if c.is_ascii_digit() || negative {
+            let end = rest[1..].find(|c: char| !c.is_ascii_digit()).map_or(rest.len(), |end| end + 1);
+            let number = rest[..end].parse().map_err(|_| format!("number {} is out of range", &rest[..end]))?;
+            tokens.push(Token::Number(number));
+            rest = &rest[end..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let end = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+            tokens.push(Token::Word(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else if c == '\'' {
+            // Quotes are escaped by doubling them
+            let mut text = String::new();
+            let mut chars = rest[1..].char_indices();
+            let end = loop {
+                match chars.next() {
+                    Some((i, '\'')) if rest[i + 2..].starts_with('\'') => {
+                        text.push('\'');
+                        chars.next();
+                    }
+                    Some((i, '\'')) => break i + 2,
+                    Some((_, c)) => text.push(c),
+                    None => return Err("unterminated string".to_string()),
+                }
+            };
+            tokens.push(Token::Text(text));
+            rest = &rest[end..];
+        } else if let Some(symbol) = SYMBOLS.into_iter().find(|symbol| rest.starts_with(symbol)) {
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        } else {
+            return Err(format!("unexpected character {:?}", c));
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+fn describe(token: Option<&Token>) -> String {
+    match token {
+        None => "end of query".to_string(),
+        Some(Token::Word(word)) => format!("{:?}", word),
+        Some(Token::Number(number)) => number.to_string(),
+        Some(Token::Text(text)) => format!("'{}'", text),
+        Some(Token::Symbol(symbol)) => format!("{:?}", symbol),
+    }
+}
+
+/// Recursive descent parser of [`Query`]
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// Consume `keyword` (case insensitive) if it comes next
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword));
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn symbol(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Symbol(found)) if *found == symbol);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        if self.keyword(keyword) {
+            Ok(())
+        } else {
+            Err(format!("expected {}, found {}", keyword, describe(self.peek())))
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), String> {
+        if self.symbol(symbol) {
+            Ok(())
+        } else {
+            Err(format!("expected {:?}, found {}", symbol, describe(self.peek())))
+        }
+    }
+
+    fn query(&mut self) -> Result<Query, String> {
+        self.expect_keyword("SELECT")?;
+        let selection = if self.symbol("*") {
+            Selection::Columns(COLUMNS.to_vec())
+        } else if self.keyword("COUNT") {
+            self.expect_symbol("(")?;
+            self.expect_symbol("*")?;
+            self.expect_symbol(")")?;
+            Selection::Count
+        } else {
+            let mut columns = vec![self.column()?];
+            while self.symbol(",") {
+                columns.push(self.column()?);
+            }
+            Selection::Columns(columns)
+        };
+        self.expect_keyword("FROM")?;
+        self.expect_keyword("origins")?;
+        let filter = if self.keyword("WHERE") { Some(self.or()?) } else { None };
+        let mut order_by = Vec::new();
+        if self.keyword("ORDER") {
+            self.expect_keyword("BY")?;
+            loop {
+                let column = self.column()?;
+                let descending = self.keyword("DESC");
+                if !descending {
+                    self.keyword("ASC");
+                }
+                order_by.push((column, descending));
+                if !self.symbol(",") {
+                    break;
+                }
+            }
+        }
+        let limit = if self.keyword("LIMIT") {
+            match self.next() {
+                Some(Token::Number(limit)) if limit >= 0 => Some(limit as usize),
+                token => return Err(format!("expected a row count after LIMIT, found {}", describe(token.as_ref()))),
+            }
+        } else {
+            None
+        };
+        self.symbol(";");
+        Ok(Query { selection, filter, order_by, limit })
+    }
+
+    fn column(&mut self) -> Result<Column, String> {
+        match self.next() {
+            Some(Token::Word(name)) => Column::named(&name).ok_or_else(|| {
+                let names: Vec<_> = COLUMNS.iter().map(|column| column.name()).collect();
+                format!("unknown column {:?}, expected one of {}", name, names.join(", "))
+            }),
+            token => Err(format!("expected a column, found {}", describe(token.as_ref()))),
+        }
+    }
+
+    fn or(&mut self) -> Result<Condition, String> {
+        let mut condition = self.and()?;
+        while self.keyword("OR") {
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
+        }
+        Ok(condition)
+    }
+
+    fn and(&mut self) -> Result<Condition, String> {
+        let mut condition = self.not()?;
+        while self.keyword("AND") {
+            condition = Condition::And(Box::new(condition), Box::new(self.not()?));
+        }
+        Ok(condition)
+    }
+
+    fn not(&mut self) -> Result<Condition, String> {
+        if self.keyword("NOT") {
+            return Ok(Condition::Not(Box::new(self.not()?)));
+        }
+        if self.symbol("(") {
+            let condition = self.or()?;
+            self.expect_symbol(")")?;
+            return Ok(condition);
+        }
+        let column = self.column()?;
+        if self.keyword("IS") {
+            let negated = self.keyword("NOT");
+            self.expect_keyword("NULL")?;
+            return Ok(Condition::IsNull { column, negated });
+        }
+        let negated = self.keyword("NOT");
+        if negated || self.keyword("LIKE") {
+            if negated {
+                self.expect_keyword("LIKE")?;
+            }
+            return match (column, self.next()) {
+                (Column::Url, Some(Token::Text(pattern))) => Ok(Condition::Like { column, pattern, negated }),
+                (Column::Url, token) => Err(format!("expected a pattern after LIKE, found {}", describe(token.as_ref()))),
+                _ => Err(format!("LIKE only applies to url, not {}", column.name())),
+            };
+        }
+        let comparison = match self.next() {
+            Some(Token::Symbol("=")) => Comparison::Equal,
+            Some(Token::Symbol("<>" | "!=")) => Comparison::NotEqual,
+            Some(Token::Symbol("<")) => Comparison::Less,
+            Some(Token::Symbol("<=")) => Comparison::LessOrEqual,
+            Some(Token::Symbol(">")) => Comparison::Greater,
+            Some(Token::Symbol(">=")) => Comparison::GreaterOrEqual,
+            token => return Err(format!("expected a comparison after {}, found {}", column.name(), describe(token.as_ref()))),
+        };
+        let literal = match (column, self.next()) {
+            (Column::Url, Some(Token::Text(text))) => Cell::Text(Cow::Owned(text)),
+            (Column::LatestCommitDate, Some(Token::Text(date))) => Cell::Int(parse_as_of(&date)?),
+            (Column::Url, _) => return Err("url is compared with strings".to_string()),
+            (_, Some(Token::Number(number))) => Cell::Int(number),
+            (_, Some(Token::Word(word))) if word.eq_ignore_ascii_case("NULL") => {
+                return Err(format!("comparing with NULL is never true, use {} IS NULL", column.name()))
+            }
+            (_, token) => return Err(format!("expected a number after the comparison, found {}", describe(token.as_ref()))),
+        };
+        Ok(Condition::Compare(column, comparison, literal))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_graph::InMemoryGraph;
+    use crate::origin::{LegacyOriginData, OriginData};
+    use std::sync::Arc;
+
+    fn origin(id: usize, url: Option<&str>, commits: Option<usize>) -> Origin<InMemoryGraph> {
+        let data = OriginData::from(LegacyOriginData {
+            id,
+            url: url.map(str::to_string),
+            latest_commit_date: Some(1_700_000_000),
+            number_of_commits: commits,
+            number_of_commiters: commits.map(|commits| commits.min(3)),
+        });
+        Origin::from_data(data, Arc::new(InMemoryGraph::default()))
+    }
+
+    fn filter(sql: &str) -> Condition {
+        let query: Query = sql.parse().unwrap();
+        query.filter.unwrap()
+    }
+
+    fn run(sql: &str, origins: Vec<Origin<InMemoryGraph>>) -> Value {
+        let query: Query = sql.parse().unwrap();
+        query.run(&origins, &CancellationToken::new()).unwrap()
+    }
+
+    #[test]
+    fn tokens() {
+        let tokens = tokenize("select url,commit_count FROM origins WHERE id>=-5 AND url LIKE 'it''s %';").unwrap();
+        assert_eq!(
+            tokens,
+            [
+                Token::Word("select".to_string()),
+                Token::Word("url".to_string()),
+                Token::Symbol(","),
+                Token::Word("commit_count".to_string()),
+                Token::Word("FROM".to_string()),
+                Token::Word("origins".to_string()),
+                Token::Word("WHERE".to_string()),
+                Token::Word("id".to_string()),
+                Token::Symbol(">="),
+                Token::Number(-5),
+                Token::Word("AND".to_string()),
+                Token::Word("url".to_string()),
+                Token::Word("LIKE".to_string()),
+                Token::Text("it's %".to_string()),
+                Token::Symbol(";"),
+            ]
+        );
+        assert_eq!(tokenize("a <> b != c").unwrap()[1], Token::Symbol("<>"));
+        assert!(tokenize("'unterminated").is_err());
+        assert!(tokenize("id = 99999999999999999999").is_err());
+        assert!(tokenize("id # 1").is_err());
+    }
+
+    #[test]
+    fn where_precedence() {
+        let first = origin(1, Some("https://example.org/a"), Some(5));
+        let second = origin(2, None, Some(500));
+        // AND binds tighter than OR
+        let condition = filter("SELECT * FROM origins WHERE id = 1 OR id = 2 AND commit_count > 100");
+        assert_eq!((condition.eval(&first), condition.eval(&second)), (Some(true), Some(true)));
+        let condition = filter("SELECT * FROM origins WHERE (id = 1 OR id = 2) AND commit_count > 100");
+        assert_eq!((condition.eval(&first), condition.eval(&second)), (Some(false), Some(true)));
+        // NOT binds tighter than AND
+        let condition = filter("SELECT * FROM origins WHERE NOT id = 1 AND commit_count > 100");
+        assert_eq!((condition.eval(&first), condition.eval(&second)), (Some(false), Some(true)));
+        // Comparing NULL is unknown, unless the other side decides
+        let condition = filter("SELECT * FROM origins WHERE url LIKE '%example%' OR id = 2");
+        assert_eq!((condition.eval(&first), condition.eval(&second)), (Some(true), Some(true)));
+        let condition = filter("SELECT * FROM origins WHERE url NOT LIKE '%example%'");
+        assert_eq!((condition.eval(&first), condition.eval(&second)), (Some(false), None));
+        let condition = filter("SELECT * FROM origins WHERE url IS NOT NULL");
+        assert_eq!((condition.eval(&first), condition.eval(&second)), (Some(true), Some(false)));
+        let condition = filter("SELECT * FROM origins WHERE latest_commit_date < '2024-01-01'");
+        assert_eq!(condition.eval(&first), Some(true));
+    }
+
+    #[test]
+    fn order_by_and_limit() {
+        let query: Query = "SELECT id FROM origins ORDER BY commit_count DESC, id ASC LIMIT 2".parse().unwrap();
+        assert_eq!(query.order_by, [(Column::CommitCount, true), (Column::Id, false)]);
+        assert_eq!(query.limit, Some(2));
+        let origins = || vec![origin(1, None, Some(5)), origin(2, None, None), origin(3, None, Some(50)), origin(4, None, Some(5))];
+        let result = run("SELECT id, commit_count FROM origins ORDER BY commit_count DESC, id LIMIT 3", origins());
+        // NULL first in descending order, last in ascending order, like Postgres
+        assert_eq!(result, json!({"columns": ["id", "commit_count"], "rows": [[2, null], [3, 50], [1, 5]], "truncated": false}));
+        let result = run("select id from origins order by commit_count", origins());
+        assert_eq!(result["rows"], json!([[1], [4], [3], [2]]));
+        let result = run("SELECT COUNT(*) FROM origins WHERE commit_count >= 5", origins());
+        assert_eq!(result["rows"], json!([[3]]));
+    }
+
+    #[test]
+    fn errors() {
+        let error = |sql: &str| sql.parse::<Query>().unwrap_err();
+        assert!(error("SELECT nope FROM origins").starts_with("unknown column \"nope\""));
+        assert_eq!(error("SELECT * FROM repositories"), "expected origins, found \"repositories\"");
+        assert_eq!(error("SELECT * FROM origins WHERE commit_count LIKE 'a%'"), "LIKE only applies to url, not commit_count");
+        assert_eq!(error("SELECT * FROM origins WHERE url = 1"), "url is compared with strings");
+        assert_eq!(error("SELECT * FROM origins WHERE cluster = NULL"), "comparing with NULL is never true, use cluster IS NULL");
+        assert_eq!(error("SELECT * FROM origins WHERE (id = 1"), "expected \")\", found end of query");
+        assert_eq!(error("SELECT * FROM origins LIMIT -1"), "expected a row count after LIMIT, found -1");
+        assert_eq!(error("SELECT * FROM origins ORDER id"), "expected BY, found \"id\"");
+        assert_eq!(error("SELECT * FROM origins id"), "unexpected \"id\"");
+        assert_eq!(error("SELECT COUNT(id) FROM origins"), "expected \"*\", found \"id\"");
+    }
+}
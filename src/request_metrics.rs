@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds of the latency histogram buckets, in seconds
+const BUCKETS: [f64; 12] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+/// Latency of the requests served by the server, by route (e.g. `/origins/:id/url`), in
+/// the Prometheus text exposition format served by `/metrics`.
+///
+/// Requests taking longer than the slow request threshold are counted apart, the server
+/// logging them with their parameters.
+pub struct RequestMetrics {
+    routes: Mutex<BTreeMap<String, RouteLatency>>,
+    /// Never slow if zero
+    slow_threshold: Duration,
+}
+
+/// Requests served by one route
+#[derive(Default)]
+struct RouteLatency {
+    /// Requests by latency bucket, not cumulative, the last one above all [`BUCKETS`]
+    buckets: [u64; BUCKETS.len() + 1],
+    count: u64,
+    /// Total latency, in seconds
+    sum: f64,
+    /// Requests answered with a 5xx status
+    errors: u64,
+    slow: u64,
+}
+
+impl RequestMetrics {
+    pub fn new(slow_threshold: Duration) -> Self {
+        RequestMetrics {
+            routes: Mutex::new(BTreeMap::new()),
+            slow_threshold,
+        }
+    }
+
+    /// Whether a request which took `elapsed` should be logged as slow
+    pub fn is_slow(&self, elapsed: Duration) -> bool {
+        !self.slow_threshold.is_zero() && elapsed >= self.slow_threshold
+    }
+
+    /// Record a request to `route` which took `elapsed`, `error` if it failed on the
+    /// server side
+    pub fn record(&self, route: &str, elapsed: Duration, error: bool) {
+        let seconds = elapsed.as_secs_f64();
+        let bucket = BUCKETS.iter().position(|&bound| seconds <= bound).unwrap_or(BUCKETS.len());
+        let slow = self.is_slow(elapsed);
+        let mut routes = self.routes.lock().unwrap();
+        // Routes are few, only allocate the first time each one is seen
+        if !routes.contains_key(route) {
+            routes.insert(route.to_string(), RouteLatency::default());
+        }
+        let latency = routes.get_mut(route).unwrap();
+        latency.buckets[bucket] += 1;
+        latency.count += 1;
+        latency.sum += seconds;
+        latency.errors += error as u64;
+        latency.slow += slow as u64;
+    }
+
+    /// Metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let routes = self.routes.lock().unwrap();
+        let mut body = String::new();
+        let name = "swh_server_request_duration_seconds";
+        body.push_str(&format!("# HELP {name} Time to serve requests, by route\n# TYPE {name} histogram\n"));
+        for (route, latency) in routes.iter() {
+            let mut cumulative = 0;
+            for (bound, count) in BUCKETS.iter().zip(&latency.buckets) {
+                cumulative += count;
+                body.push_str(&format!("{name}_bucket{{route=\"{route}\",le=\"{bound}\"}} {cumulative}\n"));
+            }
+            body.push_str(&format!("{name}_bucket{{route=\"{route}\",le=\"+Inf\"}} {}\n", latency.count));
+            body.push_str(&format!("{name}_sum{{route=\"{route}\"}} {}\n", latency.sum));
+            body.push_str(&format!("{name}_count{{route=\"{route}\"}} {}\n", latency.count));
+        }
+        type Counter = (&'static str, &'static str, fn(&RouteLatency) -> u64);
+        let counters: [Counter; 2] = [
+            ("swh_server_request_errors_total", "Requests answered with a 5xx status, by route", |latency| latency.errors),
+            ("swh_server_slow_requests_total", "Requests slower than the slow request threshold, by route", |latency| latency.slow),
+        ];
+        for (name, help, value) in counters {
+            body.push_str(&format!("# HELP {name} {help}\n# TYPE {name} counter\n"));
+            for (route, latency) in routes.iter() {
+                body.push_str(&format!("{name}{{route=\"{route}\"}} {}\n", value(latency)));
+            }
+        }
+        body
+    }
+}
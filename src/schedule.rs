@@ -0,0 +1,168 @@
+use chrono::{DateTime, Datelike, Duration, DurationRound, Timelike, Utc};
+
+/// Longest time [`Schedule::next_after`] looks ahead, beyond which a schedule never fires
+/// (e.g. `0 0 31 2 *`)
+const MAX_LOOKAHEAD_MINUTES: i64 = 366 * 24 * 60 * 4;
+
+/// Cron expression telling when to run a periodic task, in UTC.
+///
+/// The usual five fields `minute hour day-of-month month day-of-week`, each `*`, a value,
+/// a range `a-b`, a step `*/n` or `a-b/n`, or a comma-separated list of those. Days of
+/// the week go from 0 (Sunday) to 6, 7 being Sunday too. As in cron, when both day fields
+/// are restricted (neither starts with `*`) a day matching either is enough.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Schedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// Whether the day fields start with `*` (`*` or `*/n`), to combine them like cron does
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl Schedule {
+    pub fn as_str(&self) -> &str {
+        &self.expression
+    }
+
+    /// First time strictly after `after` (at the minute) matching the schedule
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after.duration_trunc(Duration::minutes(1)).ok()? + Duration::minutes(1);
+        (0..MAX_LOOKAHEAD_MINUTES)
+            .map(|minutes| start + Duration::minutes(minutes))
+            .find(|time| self.matches(time))
+    }
+
+    fn matches(&self, time: &DateTime<Utc>) -> bool {
+        let day_of_month = has(self.days_of_month, time.day());
+        let day_of_week = has(self.days_of_week, time.weekday().num_days_from_sunday());
+        let day = match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        };
+        day && has(self.minutes, time.minute()) && has(self.hours, time.hour()) && has(self.months, time.month())
+    }
+}
+
+impl std::str::FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days_of_month, months, days_of_week] = fields[..] else {
+            return Err(format!("{:?}: expected 5 fields (minute hour day-of-month month day-of-week)", expression));
+        };
+        let mut days_of_week_set = parse_field(days_of_week, 0, 7)?;
+        // Sunday is both 0 and 7
+        if has(days_of_week_set, 7) {
+            days_of_week_set |= 1;
+        }
+        Ok(Schedule {
+            expression: expression.to_string(),
+            minutes: parse_field(minutes, 0, 59)?,
+            hours: parse_field(hours, 0, 23)?,
+            days_of_month: parse_field(days_of_month, 1, 31)?,
+            months: parse_field(months, 1, 12)?,
+            days_of_week: days_of_week_set,
+            any_day_of_month: days_of_month.starts_with('*'),
+            any_day_of_week: days_of_week.starts_with('*'),
+        })
+    }
+}
+
+fn has(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+/// Bit set of the values in `min..=max` selected by a cron field
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut set = 0;
+    for part in field.split(',') {
+        let invalid = || format!("invalid cron field {:?} (values go from {} to {})", part, min, max);
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, Some(step.parse::<u32>().ok().filter(|&step| step > 0).ok_or_else(invalid)?)),
+            None => (part, None),
+        };
+        let (first, last) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((first, last)) => (
+                    first.parse().map_err(|_| invalid())?,
+                    last.parse().map_err(|_| invalid())?,
+                ),
+                None => {
+                    let value = range.parse().map_err(|_| invalid())?;
+                    // `a/n` goes from a to the maximum, every n
+                    (value, if step.is_some() { max } else { value })
+                }
+            },
+        };
+        if first < min || last > max || first > last {
+            return Err(invalid());
+        }
+        for value in (first..=last).step_by(step.unwrap_or(1) as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn next(expression: &str, after: &str) -> Option<String> {
+        let schedule: Schedule = expression.parse().unwrap();
+        let after = DateTime::parse_from_rfc3339(after).unwrap().with_timezone(&Utc);
+        schedule.next_after(after).map(|time| time.to_rfc3339())
+    }
+
+    #[test]
+    fn steps() {
+        assert_eq!(next("*/15 * * * *", "2024-01-01T10:07:30Z").as_deref(), Some("2024-01-01T10:15:00+00:00"));
+        assert_eq!(next("*/15 * * * *", "2024-01-01T10:15:00Z").as_deref(), Some("2024-01-01T10:30:00+00:00"));
+        assert_eq!(next("5/20 * * * *", "2024-01-01T10:46:00Z").as_deref(), Some("2024-01-01T11:05:00+00:00"));
+    }
+
+    #[test]
+    fn ranges_and_lists() {
+        // 2024-01-06 is a Saturday
+        assert_eq!(next("0 9-17 * * 1-5", "2024-01-06T12:00:00Z").as_deref(), Some("2024-01-08T09:00:00+00:00"));
+        assert_eq!(next("0 9-17 * * 1-5", "2024-01-08T17:30:00Z").as_deref(), Some("2024-01-09T09:00:00+00:00"));
+        assert_eq!(next("0,30 8,20 * * *", "2024-01-01T08:10:00Z").as_deref(), Some("2024-01-01T08:30:00+00:00"));
+        assert_eq!(next("0,30 8,20 * * *", "2024-01-01T08:30:00Z").as_deref(), Some("2024-01-01T20:00:00+00:00"));
+    }
+
+    #[test]
+    fn sunday_is_0_and_7() {
+        // 2024-01-01 is a Monday
+        assert_eq!(next("0 0 * * 7", "2024-01-01T00:00:00Z").as_deref(), Some("2024-01-07T00:00:00+00:00"));
+        assert_eq!(next("0 0 * * 0", "2024-01-01T00:00:00Z").as_deref(), Some("2024-01-07T00:00:00+00:00"));
+        assert_eq!(next("0 0 * * 5-7", "2024-01-06T12:00:00Z").as_deref(), Some("2024-01-07T00:00:00+00:00"));
+    }
+
+    #[test]
+    fn day_fields() {
+        // Both restricted: the 13th or a Friday
+        assert_eq!(next("0 0 13 * 5", "2024-01-06T00:00:00Z").as_deref(), Some("2024-01-12T00:00:00+00:00"));
+        assert_eq!(next("0 0 13 * 5", "2024-01-12T00:00:00Z").as_deref(), Some("2024-01-13T00:00:00+00:00"));
+        // A day of the month starting with `*` is not a restriction: odd days which are Mondays
+        assert_eq!(next("0 0 */2 * 1", "2024-01-02T00:00:00Z").as_deref(), Some("2024-01-15T00:00:00+00:00"));
+        assert_eq!(next("0 0 1 * */2", "2024-01-02T00:00:00Z").as_deref(), Some("2024-02-01T00:00:00+00:00"));
+    }
+
+    #[test]
+    fn never_fires() {
+        assert_eq!(next("0 0 31 2 *", "2024-01-01T00:00:00Z"), None);
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        for expression in ["* * * *", "60 * * * *", "* 24 * * *", "* * 0 * *", "* * * 13 *", "* * * * 8", "*/0 * * * *", "5-1 * * * *", "a * * * *"] {
+            assert!(expression.parse::<Schedule>().is_err(), "{:?}", expression);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use swh_graph::graph::NodeId;
+
+use crate::utils::Traversal;
+
+/// Buffers grown past this many entries are dropped instead of cleared, so that one
+/// huge origin doesn't make every following (usually tiny) traversal pay for clearing
+/// a huge table
+pub const MAX_RETAINED_CAPACITY: usize = 1 << 20;
+
+/// Per-thread buffers reused across origin computations.
+///
+/// Fields are public so callers can borrow them independently. The traversal buffers are
+/// kept apart (see [`with_traversal`]), so that e.g. `committers` can be filled from
+/// within a traversal callback.
+#[derive(Default)]
+pub struct Scratch {
+    /// Revisions found so far; cleared before use
+    pub revisions: Vec<NodeId>,
+    /// Distinct committer ids found so far; cleared before use
+    pub committers: HashSet<u64>,
+}
+
+impl Scratch {
+    /// Empty `revisions` and `committers`, keeping their capacity unless it got too large
+    pub fn reset(&mut self) {
+        if self.revisions.capacity() > MAX_RETAINED_CAPACITY {
+            self.revisions = Vec::new();
+        } else {
+            self.revisions.clear();
+        }
+        reset_set(&mut self.committers);
+    }
+}
+
+pub fn reset_set<T>(set: &mut HashSet<T>) {
+    if set.capacity() > MAX_RETAINED_CAPACITY {
+        *set = HashSet::new();
+    } else {
+        set.clear();
+    }
+}
+
+thread_local! {
+    static SCRATCH: RefCell<Scratch> = RefCell::new(Scratch::default());
+    static TRAVERSAL: RefCell<Traversal> = RefCell::new(Traversal::default());
+}
+
+/// Run `f` with this thread's scratch buffers, reset beforehand.
+///
+/// Nested calls (e.g. a metric computed from within another one) get fresh buffers
+/// rather than panicking on the already borrowed ones.
+pub fn with_scratch<T>(f: impl FnOnce(&mut Scratch) -> T) -> T {
+    SCRATCH.with(|scratch| match scratch.try_borrow_mut() {
+        Ok(mut scratch) => {
+            scratch.reset();
+            f(&mut scratch)
+        }
+        Err(_) => f(&mut Scratch::default()),
+    })
+}
+
+/// Run `f` with this thread's traversal buffers, used by [`crate::utils::bfs`] and co.
+///
+/// Nested calls (e.g. a traversal started from within another one's callback) get fresh
+/// buffers, like [`with_scratch`].
+pub fn with_traversal<T>(f: impl FnOnce(&mut Traversal) -> T) -> T {
+    TRAVERSAL.with(|traversal| match traversal.try_borrow_mut() {
+        Ok(mut traversal) => f(&mut traversal),
+        Err(_) => f(&mut Traversal::default()),
+    })
+}
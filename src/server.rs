@@ -1,44 +1,94 @@
 use axum::{
     body::Body,
-    extract::{Path, State},
-    http::{Request, StatusCode},
+    extract::{FromRef, MatchedPath, Path, Query, State},
+    http::{header::{CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_TYPE, RETRY_AFTER}, HeaderName, HeaderValue, Request, StatusCode},
     middleware::{self, Next},
-    response::{Json, Response},
-    routing::get,
+    response::{IntoResponse, Json, Response},
+    routing::{delete, get, post},
     Router,
 };
 use axum::body::to_bytes;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, Instant};
 use rayon::prelude::*;
-use swh_graph::{graph::{SwhGraphWithProperties, SwhLabeledForwardGraph, SwhUnidirectionalGraph}, mph::DynMphf, properties};
-use tokio::sync::RwLock;
+use swh_graph::{graph::{NodeId, SwhBidirectionalGraph, SwhUnidirectionalGraph}, mph::DynMphf, NodeType, SWHID};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::{oneshot, RwLock};
+use tokio_util::sync::CancellationToken;
 use tower_http::cors::CorsLayer;
-use tower_http::trace::TraceLayer;
-use tracing::{info, error, debug};
-use tracing_subscriber::fmt::init;
-use clap::Parser;
+use tracing::{info, info_span, error, debug, warn, Instrument};
+use notify::event::{EventKind, ModifyKind};
+use notify::{RecursiveMode, Watcher};
+use clap::{Parser, ValueEnum};
 use indicatif::{ProgressBar, ProgressStyle};
 use std::sync::Arc as StdArc;
-use crate::graph::{Graph, SerializationFormat};
+use crate::backend::{BackwardCapable, ContentCapable, GraphBackend, LabelCapable};
+use crate::cli::Command;
+use crate::error::GraphError;
+use crate::contributions::{PersonTotals, PERSON_COMMITS_FILE};
+use crate::dataset_manager::{DatasetConfig, DatasetManager};
+use crate::datasets::diff_origins;
+use crate::freeze::{FrozenDatasets, GraphExport, CODE_VERSION, FROZEN_DIR};
+#[cfg(feature = "flight")]
+use crate::flight::OriginsFlight;
+use crate::graph::{cache_schema, read_origins_cache, Graph, Reach, SerializationFormat};
+use crate::jobs::{Job, JobRegistry, JOBS_FILE};
+use crate::labels::Branch;
+use crate::logging::{self, parse_log_filter, LogFile, LogFormat, LogRotation};
+use crate::memory::{resident_set_size, MemoryPressure};
+use crate::origin_list::OriginList;
+use crate::origin::{enable_snapshot_memo, parse_as_of, parse_date_range, set_origin_node_budget, set_slow_origin_threshold, skip_over_budget, ComputePolicy, Eligibility, Metric, OriginData, OriginFilter, ECOSYSTEM_MANIFESTS};
+use crate::progress::{load_stage, LoadProgress, LoadStage, Progress};
+use crate::provenance::{RevisionOrigins, REVISION_ORIGINS_FILE};
+use crate::quantiles::parse_quantiles;
+#[cfg(not(feature = "sql"))]
+use crate::query::Query as SqlQuery;
+use crate::redis::{RedisCache, MAX_CACHED_RESPONSE};
+use crate::request_metrics::RequestMetrics;
+use crate::schedule::Schedule;
+use crate::stats::{GraphStats, GRAPH_STATS_FILE};
+use crate::tags::{TagStore, TAGS_FILE};
+use crate::urls::{canonical_url, UrlFilter, UrlPattern};
+use crate::utils::set_visit_throttle;
+use crate::worker::WorkerPool;
 
 /// CLI arguments for the SWH Graph API server
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[derive(Parser, Clone, Debug)]
+#[command(name = "swh-server", author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct ServerArgs {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Port to bind the server to
     #[arg(short, long, default_value = "5000")]
     pub port: u16,
 
     /// Path to the graph data directory
-    #[arg(short, long)]
-    pub graph_path: String,
+    #[arg(short, long, required_unless_present = "datasets")]
+    pub graph_path: Option<String>,
+
+    /// JSON file declaring named datasets, each with its own graph path, data path, cache
+    /// directory and Redis, served side by side under `/graphs/<name>` instead of the
+    /// graph at `--graph-path`. The other options apply to all of them.
+    #[arg(long, conflicts_with = "graph_path")]
+    pub datasets: Option<PathBuf>,
 
     /// Path to store cached data
     #[arg(short, long, default_value = "./data")]
     pub data_path: String,
 
+    /// Directory for the origins cache and its sidecar files (defaults to the data path's parent)
+    #[arg(long)]
+    pub cache_dir: Option<String>,
+
     /// Host to bind the server to
     #[arg(long, default_value = "127.0.0.1")]
     pub host: String,
@@ -46,62 +96,878 @@ pub struct ServerArgs {
     /// Enable debug mode to log all HTTP requests
     #[arg(short, long)]
     pub log: bool,
+
+    /// Format of the logs; in JSON, requests are logged as fields without their bodies
+    #[arg(long, value_enum, default_value = "text")]
+    pub log_format: LogFormat,
+
+    /// Log verbosity per module as `RUST_LOG`-style directives, e.g.
+    /// `info,swh_server::server=debug,swh_graph=warn`, overriding `--log` and
+    /// `RUST_LOG`
+    #[arg(long, value_parser = parse_log_filter)]
+    pub log_filter: Option<String>,
+
+    /// Write the logs to this file instead of the standard output, rotated according to
+    /// `--log-rotation`
+    #[arg(long)]
+    pub log_file: Option<PathBuf>,
+
+    /// When to start a new log file
+    #[arg(long, value_enum, default_value = "daily")]
+    pub log_rotation: LogRotation,
+
+    /// Size in MB after which `--log-rotation size` starts a new log file
+    #[arg(long, default_value = "100")]
+    pub log_max_size: u64,
+
+    /// Log files kept, older ones being deleted
+    #[arg(long, default_value = "7")]
+    pub log_max_files: usize,
+
+    /// Persist the origin node ids under the data path to skip the full node scan on restart
+    #[arg(long)]
+    pub node_type_index: bool,
+
+    /// Interval in seconds between saves of metrics computed on demand (0 disables write-back)
+    #[arg(long, default_value = "300")]
+    pub write_back_interval: u64,
+
+    /// Cron expression (UTC), e.g. `0 3 * * *`, at which the origins missing metrics are
+    /// recomputed on the background workers
+    #[arg(long)]
+    pub recompute_schedule: Option<Schedule>,
+
+    /// Swap in the origins cache when another process (e.g. an offline `swh-test` run)
+    /// regenerates it, without restarting. Writers must rename new caches into place.
+    #[arg(long)]
+    pub watch_cache: bool,
+
+    /// Serve the origin table over Arrow Flight on this port, next to the HTTP API
+    #[cfg(feature = "flight")]
+    #[arg(long)]
+    pub flight_port: Option<u16>,
+
+    /// Cache the responses of the origin, cluster and aggregate endpoints in this Redis,
+    /// e.g. `redis://localhost:6379/0`, so that replicas and restarts share them
+    #[arg(long, value_parser = RedisCache::parse_url)]
+    pub redis_url: Option<String>,
+
+    /// Prefix of the Redis keys, to tell apart servers of different graphs sharing a Redis
+    #[arg(long, default_value = "swh-server")]
+    pub redis_prefix: String,
+
+    /// Seconds the responses about a single origin, cluster or object stay in Redis
+    #[arg(long, default_value = "86400")]
+    pub redis_ttl: u64,
+
+    /// Seconds the responses covering all origins, e.g. `/origins`, stay in Redis
+    #[arg(long, default_value = "3600")]
+    pub redis_aggregate_ttl: u64,
+
+    /// Interval in seconds between memory usage log lines (0 disables them)
+    #[arg(long, default_value = "600")]
+    pub memory_log_interval: u64,
+
+    /// Above this RSS in MiB, reject the requests covering many origins with 503 and
+    /// pause the jobs until the RSS is back under 90% of it (0 disables)
+    #[arg(long, default_value = "0")]
+    pub memory_limit_mb: u64,
+
+    /// Request this endpoint (path and query, e.g. `/stats/quantiles`) once the origins
+    /// are loaded, so that its result is computed and cached before the first user asks
+    /// for it (repeatable)
+    #[arg(long)]
+    pub warm_up: Vec<String>,
+
+    /// Also drop the URLs of the origins when over `--memory-limit-mb`, to be read from
+    /// the graph again when needed
+    #[arg(long, requires = "memory_limit_mb")]
+    pub shed_urls: bool,
+
+    /// Log origins whose metrics take longer than this many milliseconds (0 disables)
+    #[arg(long, default_value = "10000")]
+    pub slow_origin_ms: u64,
+
+    /// Answer 422 for the metrics of origins whose traversal would visit more than this
+    /// many nodes, leaving them unknown (0 disables)
+    #[arg(long, default_value = "0")]
+    pub max_nodes_per_origin: usize,
+
+    /// Reuse the commit and committer counts of a snapshot for the other origins whose
+    /// latest snapshot it is, keeping those of up to this many snapshots (0 disables).
+    /// Snapshots are memoized by node id, which only identifies them within one graph.
+    #[arg(long, default_value = "0", conflicts_with = "datasets")]
+    pub snapshot_memo: usize,
+
+    /// Visit at most this many graph nodes per second over all threads and datasets (0
+    /// disables), to keep the page cache of a machine shared with other graph users from
+    /// being thrashed
+    #[arg(long, default_value = "0")]
+    pub max_visits_per_second: u64,
+
+    /// Log requests taking longer than this many milliseconds, with their parameters
+    /// (0 disables)
+    #[arg(long, default_value = "5000")]
+    pub slow_request_ms: u64,
+
+    /// Number of background compute workers (0 uses one per available core)
+    #[arg(long, default_value = "0")]
+    pub workers: usize,
+
+    /// Property families to load. Labels are always loaded: picking the latest snapshot of
+    /// an origin needs the visit labels
+    #[arg(long, value_enum, default_value = "required")]
+    pub properties: PropertySet,
+
+    /// Also load the transposed graph (`<graph path>-transposed`), for the features walking
+    /// arcs backward
+    #[arg(long)]
+    pub bidirectional: bool,
+
+    /// Serve the bulk endpoints in their former shape, a map from stringified origin ids to
+    /// stringified values, for clients which were not updated to `[{"id", "value"}]` arrays
+    #[arg(long)]
+    pub legacy_bulk_maps: bool,
+
+    /// Origins cache of another graph export, e.g. the previous one, to compare the served
+    /// origins with at `/datasets/diff`
+    #[arg(long)]
+    pub compare_cache: Option<PathBuf>,
+
+    /// Only serve origins whose URL matches one of these glob patterns (repeatable),
+    /// e.g. `github.com/*`. Patterns without `/` match the host, e.g. `*.gitlab.io`.
+    #[arg(long)]
+    pub allow_url: Vec<UrlPattern>,
+
+    /// Don't serve origins whose URL matches one of these glob patterns (repeatable), e.g.
+    /// `*.googlesource.com`, even if allowed
+    #[arg(long)]
+    pub block_url: Vec<UrlPattern>,
+
+    /// Only serve origins hosted on one of these forges (repeatable), e.g. `github.com`
+    #[arg(long)]
+    pub forge: Vec<String>,
+
+    /// Also serve origins without snapshot, which have no metric
+    #[arg(long)]
+    pub allow_no_snapshot: bool,
+
+    /// Only serve the origins listed in this file, one node id, origin SWHID or URL per
+    /// line, e.g. to reproduce a published dataset
+    #[arg(long)]
+    pub origins_file: Option<PathBuf>,
+
+    /// Fewest commits an origin needs to be listed by `/origins`
+    #[arg(long, default_value = "1")]
+    pub min_commits: u32,
+
+    /// Also list origins none of whose head revisions has a committer date in `/origins`
+    #[arg(long)]
+    pub allow_undated: bool,
+}
+
+/// Property families loaded with the graph
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum PropertySet {
+    /// Only what origin metrics use: maps, timestamps, persons and strings
+    Required,
+    /// Every property family, including contents and label names
+    All,
+}
+
+/// State shared by all the handlers
+pub struct AppState<G>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    pub graph: Arc<RwLock<Graph<G>>>,
+    pub workers: Arc<WorkerPool<G>>,
+    pub jobs: Arc<JobRegistry>,
+    /// Origins loading progress; origin endpoints answer 503 until it is ready
+    pub load_progress: Arc<LoadProgress>,
+    /// See [`ServerArgs::legacy_bulk_maps`]
+    pub legacy_bulk_maps: bool,
+    /// Latency of the requests served, by route
+    pub request_metrics: Arc<RequestMetrics>,
+    /// Tags put on origins by users
+    pub tags: Arc<TagStore>,
+    /// See [`ServerArgs::memory_limit_mb`]
+    pub memory_pressure: Arc<MemoryPressure>,
+}
+
+impl<G> Clone for AppState<G>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    fn clone(&self) -> Self {
+        Self {
+            graph: self.graph.clone(),
+            workers: self.workers.clone(),
+            jobs: self.jobs.clone(),
+            load_progress: self.load_progress.clone(),
+            legacy_bulk_maps: self.legacy_bulk_maps,
+            request_metrics: self.request_metrics.clone(),
+            tags: self.tags.clone(),
+            memory_pressure: self.memory_pressure.clone(),
+        }
+    }
+}
+
+/// Lets handlers which only need the graph keep extracting `State<Arc<RwLock<Graph<G>>>>`
+impl<G> FromRef<AppState<G>> for Arc<RwLock<Graph<G>>>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    fn from_ref(state: &AppState<G>) -> Self {
+        state.graph.clone()
+    }
+}
+
+/// Lets the readiness handlers extract `State<Arc<LoadProgress>>`
+impl<G> FromRef<AppState<G>> for Arc<LoadProgress>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    fn from_ref(state: &AppState<G>) -> Self {
+        state.load_progress.clone()
+    }
 }
 
 // Struct pour encapsuler le serveur avec le type générique
 pub struct GraphServer<G>
 where
-    G: SwhLabeledForwardGraph 
-    + SwhGraphWithProperties<
-        Maps: properties::Maps,
-        Timestamps: properties::Timestamps,
-        Persons: properties::Persons,
-        Contents: properties::Contents,
-        Strings: properties::Strings,
-        LabelNames: properties::LabelNames,
-    > + Send + Sync + 'static,
+    G: GraphBackend + Send + Sync + 'static,
 {
-    graph: Arc<RwLock<Graph<G>>>,
+    state: AppState<G>,
+    response_cache: Option<Arc<ResponseCache>>,
+    frozen: Option<Arc<FrozenDatasets>>,
+    /// Graph export reported with `?provenance=true`
+    graph_export: Option<GraphExport>,
 }
 
 impl<G> GraphServer<G>
 where
-    G: SwhLabeledForwardGraph 
-    + SwhGraphWithProperties<
-        Maps: properties::Maps,
-        Timestamps: properties::Timestamps,
-        Persons: properties::Persons,
-        Contents: properties::Contents,
-        Strings: properties::Strings,
-        LabelNames: properties::LabelNames,
-    > + Send + Sync + 'static,
-{
-    pub fn new(graph: Graph<G>) -> Self {
+    G: GraphBackend + Send + Sync + 'static,
+{
+    pub fn new(graph: Graph<G>, workers: usize, legacy_bulk_maps: bool) -> Self {
+        let load_progress = graph.load_progress();
+        let graph = Arc::new(RwLock::new(graph));
         Self {
-            graph: Arc::new(RwLock::new(graph)),
+            state: AppState {
+                workers: WorkerPool::start(graph.clone(), workers),
+                graph,
+                jobs: Arc::new(JobRegistry::new()),
+                load_progress,
+                legacy_bulk_maps,
+                request_metrics: Arc::new(RequestMetrics::new(Duration::ZERO)),
+                tags: Arc::new(TagStore::new()),
+                memory_pressure: Arc::new(MemoryPressure::new(0)),
+            },
+            response_cache: None,
+            frozen: None,
+            graph_export: None,
         }
     }
 
-    pub fn create_router(&self, debug_mode: bool) -> Router {
-        let mut router = Router::new()
-            .route("/health", get(health_check))
+    /// Shed load while the RSS is over `limit_bytes`, see [`Self::spawn_memory_watch`]
+    pub fn with_memory_limit(mut self, limit_bytes: u64) -> Self {
+        self.state.memory_pressure = Arc::new(MemoryPressure::new(limit_bytes));
+        self
+    }
+
+    /// Report `export` as the graph export of the metrics in `?provenance=true` responses
+    pub fn with_graph_export(mut self, export: GraphExport) -> Self {
+        self.graph_export = Some(export);
+        self
+    }
+
+    /// Cache the responses of the `GET` endpoints needing the origins, see
+    /// [`ResponseCache`]
+    pub fn with_response_cache(mut self, cache: ResponseCache) -> Self {
+        self.response_cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Keep the jobs in `path`, so that their status and results outlive restarts, see
+    /// [`JobRegistry::persistent`]
+    pub fn with_job_history(mut self, path: PathBuf) -> Result<Self, GraphError> {
+        self.state.jobs = Arc::new(JobRegistry::persistent(path)?);
+        Ok(self)
+    }
+
+    /// Let the origin table be frozen into `datasets`, and serve them
+    pub fn with_frozen_datasets(mut self, datasets: FrozenDatasets) -> Self {
+        self.frozen = Some(Arc::new(datasets));
+        self
+    }
+
+    /// Keep the origin tags in `path`, see [`TagStore::persistent`]
+    pub fn with_tags(mut self, path: PathBuf) -> Result<Self, GraphError> {
+        self.state.tags = Arc::new(TagStore::persistent(path)?);
+        Ok(self)
+    }
+
+    /// Log the requests taking longer than `threshold`, with their parameters
+    pub fn with_slow_request_threshold(mut self, threshold: Duration) -> Self {
+        self.state.request_metrics = Arc::new(RequestMetrics::new(threshold));
+        self
+    }
+
+    /// Router serving `extra_routes` (e.g. [`backward_routes`]) along with the common ones,
+    /// logging requests in `request_log` format if given
+    pub fn create_router(&self, request_log: Option<LogFormat>, extra_routes: Router<AppState<G>>) -> Router {
+        let routes = Router::new()
             .route("/origins", get(get_origins_ids::<G>))
+            .route("/origins/count", get(get_origins_count::<G>))
+            .route("/origins/recent", get(get_recent_origins::<G>))
+            .route("/origins/lookup", get(get_origin_lookup::<G>))
+            .route("/origins/duplicates", get(get_origin_duplicates::<G>))
             .route("/origins/latest-commit-dates", get(get_all_latest_commit_dates::<G>))
             .route("/origins/commit-counts", get(get_all_commit_counts::<G>))
             .route("/origins/committer-counts", get(get_all_committer_counts::<G>))
+            .route("/origins/:id", get(get_origin::<G>))
             .route("/origins/:id/url", get(get_origin_url::<G>))
+            .route("/origins/:id/swhid", get(get_origin_swhid::<G>))
+            .route("/origins/:id/diagnostics", get(get_origin_diagnostics::<G>))
             .route("/origins/:id/latest-commit-date", get(get_latest_commit_date::<G>))
             .route("/origins/:id/committer-count", get(get_committer_count::<G>))
             .route("/origins/:id/commit-count", get(get_commit_count::<G>))
+            .route("/origins/:id/cluster", get(get_origin_cluster::<G>))
+            .route("/origins/:id/structure", get(get_origin_structure::<G>))
+            .route("/origins/:id/working-hours", get(get_origin_working_hours::<G>))
+            .route("/origins/:id/history", get(get_origin_history::<G>))
+            .route("/origins/:id/parents", get(get_origin_parents::<G>))
+            .route("/origins/:id/burstiness", get(get_origin_burstiness::<G>))
+            .route("/origins/:id/dedup", get(get_origin_dedup::<G>))
+            .route("/origins/:id/metrics", get(get_origin_metrics_as_of::<G>))
+            .route("/clusters/:id/origins", get(get_cluster_origins::<G>))
+            .route("/stats/quantiles", get(get_metric_quantiles::<G>))
+            .route("/stats/facets", get(get_facets::<G>))
+            .route("/admin/recompute", post(post_recompute::<G>))
+            .route("/query", post(post_query::<G>))
+            .merge(extra_routes)
+            // Routes above need the origins to be loaded
+            .route_layer(middleware::from_fn_with_state(
+                self.state.load_progress.clone(),
+                require_ready,
+            ))
+            // Inside the response cache, so that cached envelopes keep the provenance of
+            // the numbers they hold
+            .route_layer(middleware::from_fn_with_state(
+                Arc::new(Provenance { graph: self.state.graph.clone(), export: self.graph_export.clone() }),
+                add_provenance::<G>,
+            ));
+        let routes = routes.route_layer(middleware::from_fn_with_state(self.state.memory_pressure.clone(), shed_load));
+        // Outside `require_ready`, so that cached responses are served while loading
+        let routes = match &self.response_cache {
+            Some(cache) => routes.route_layer(middleware::from_fn_with_state(cache.clone(), cache_responses)),
+            None => routes,
+        };
+        // Tags change at any time, their responses are never cached
+        let tag_routes = Router::new()
+            .route("/origins/:id/tags", get(get_origin_tags::<G>).post(post_origin_tags::<G>))
+            .route("/origins/:id/tags/:tag", delete(delete_origin_tag::<G>))
+            .route("/tags", get(get_tags::<G>))
+            .route_layer(middleware::from_fn_with_state(
+                self.state.load_progress.clone(),
+                require_ready,
+            ));
+        let routes = routes.merge(tag_routes);
+        let routes = match &self.frozen {
+            Some(frozen) => routes.merge(frozen_routes(frozen.clone(), self.state.load_progress.clone())),
+            None => routes,
+        };
+        let mut router = routes
+            .route("/health", get(health_check))
+            .route("/readyz", get(readiness_check))
+            .route("/progress", get(get_progress))
+            .route("/metrics", get(get_metrics::<G>))
+            // Only needs the graph maps, not the origins
+            .route("/resolve", post(post_resolve::<G>))
+            .route("/admin/workers", get(get_workers::<G>))
+            .route("/admin/memory", get(get_memory::<G>))
+            .route("/jobs", get(get_jobs::<G>))
+            .route("/jobs/:id", get(get_job::<G>).delete(cancel_job::<G>))
             .layer(CorsLayer::permissive())
-            .with_state(self.graph.clone());
+            .with_state(self.state.clone());
 
-        if debug_mode {
-            router = router.layer(middleware::from_fn(log_requests_and_responses));
+        match request_log {
+            Some(LogFormat::Text) => router = router.layer(middleware::from_fn(log_requests_and_responses)),
+            Some(LogFormat::Json) => router = router.layer(middleware::from_fn(log_request_fields)),
+            None => {}
         }
+        router = router.layer(middleware::from_fn_with_state(
+            self.state.request_metrics.clone(),
+            track_requests,
+        ));
 
         router
     }
+
+    /// Load (or compute) the origins and the `/origins` list in the background, so the
+    /// server can accept connections and report its progress through `/readyz` meanwhile.
+    pub fn spawn_origin_loading(&self) -> tokio::task::JoinHandle<()> {
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            let progress = state.load_progress.clone();
+            let loaded = with_graph_blocking(state.graph.clone(), move |graph| {
+                Ok(graph.get_origins_mut().map(|origins| origins.len()).and_then(|count| {
+                    progress.set_stage(LoadStage::FilteringOrigins);
+                    graph.get_eligible_origin_ids()?;
+                    Ok(count)
+                }))
+            })
+            .await;
+            match loaded {
+                Ok(Ok(count)) => {
+                    state.load_progress.set_stage(LoadStage::Ready);
+                    info!("Loaded {} origins, ready to serve", count);
+                }
+                Ok(Err(e)) => {
+                    error!("Failed to load origins: {}", e);
+                    state.load_progress.set_stage(LoadStage::Failed(e.to_string()));
+                }
+                Err(_) => {
+                    state.load_progress.set_stage(LoadStage::Failed("origin loading task failed".to_string()));
+                }
+            }
+        })
+    }
+
+    /// Queue the origins missing metrics on the background workers each time `schedule`
+    /// fires, as a `scheduled-recompute` job, so a long-running server fills its gaps
+    /// without operator action. Workers store each origin once computed in full, the
+    /// previous metrics being served meanwhile. A run is skipped while the previous one
+    /// is still going or the origins are not loaded.
+    pub fn spawn_scheduled_recompute(&self, schedule: Schedule) -> tokio::task::JoinHandle<()> {
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            let mut previous: Option<Arc<Job>> = None;
+            loop {
+                let now = Utc::now();
+                let Some(next) = schedule.next_after(now) else {
+                    error!("Recompute schedule {:?} never fires", schedule.as_str());
+                    return;
+                };
+                tokio::time::sleep((next - now).to_std().unwrap_or_default()).await;
+                if !state.load_progress.is_ready() {
+                    info!("Skipping scheduled recompute, origins are not loaded yet");
+                    continue;
+                }
+                if let Some(job) = previous.as_ref().filter(|job| !job.is_finished()) {
+                    info!("Skipping scheduled recompute, job {} is still running", job.id);
+                    continue;
+                }
+                let graph = state.graph.clone().read_owned().await;
+                let Ok(origin_ids) = tokio::task::spawn_blocking(move || graph.stale_origin_ids()).await else {
+                    error!("Failed to list the origins to recompute");
+                    continue;
+                };
+                let job = state.jobs.create("scheduled-recompute", origin_ids.len(), json!({ "stale_only": true }));
+                info!("Job {}: scheduled recompute of {} origins missing metrics", job.id, origin_ids.len());
+                state.workers.submit_job(job.clone(), origin_ids, false);
+                previous = Some(job);
+            }
+        })
+    }
+
+    /// Reload the origins cache at `cache_file` whenever another process replaces it. Only
+    /// caches created under that name or renamed to it are picked up, not writes to the
+    /// file in place: writers must write a temporary file then rename it over the cache, as
+    /// [`Graph`] does, so that a cache is never read while half written. The new origins
+    /// replace the current ones under a short write lock, after being read without any
+    /// lock; caches which fail to be read or don't match the graph are ignored with a
+    /// warning.
+    pub fn spawn_cache_watch(&self, cache_file: PathBuf, format: SerializationFormat) -> Result<tokio::task::JoinHandle<()>, GraphError> {
+        let (changed, mut changes) = tokio::sync::mpsc::unbounded_channel();
+        let name = cache_file.file_name().map(|name| name.to_os_string());
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            // Writes in place may be in progress, only complete caches are moved into place
+            let replaced = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_)));
+            if replaced && event.paths.iter().any(|path| path.file_name() == name.as_deref()) {
+                let _ = changed.send(());
+            }
+        })
+        .map_err(std::io::Error::other)?;
+        // The directory, as the file may be replaced by a rename
+        let directory = match cache_file.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        watcher.watch(&directory, RecursiveMode::NonRecursive).map_err(std::io::Error::other)?;
+        let state = self.state.clone();
+        Ok(tokio::spawn(async move {
+            let _watcher = watcher;
+            while changes.recv().await.is_some() {
+                // A rename may be reported by several events
+                while changes.try_recv().is_ok() {}
+                if !state.load_progress.is_ready() {
+                    warn!("Origins cache {:?} changed while loading the origins, not reloading it", cache_file);
+                    continue;
+                }
+                if state.graph.read().await.saved_origins_cache() {
+                    continue;
+                }
+                let path = cache_file.clone();
+                let origins_data = match tokio::task::spawn_blocking(move || read_origins_cache(&path, format)).await {
+                    Ok(Ok(origins_data)) => origins_data,
+                    Ok(Err(e)) => {
+                        warn!("Ignoring the new origins cache: {}", e);
+                        continue;
+                    }
+                    Err(_) => continue,
+                };
+                let replaced = with_graph_blocking(state.graph.clone(), move |graph| Ok(graph.replace_origins(origins_data))).await;
+                match replaced {
+                    Ok(Ok(count)) => info!("Swapped in {} origins from the regenerated cache {:?}", count, cache_file),
+                    Ok(Err(e)) => warn!("Ignoring the new origins cache: {}", e),
+                    Err(_) => {}
+                }
+            }
+        }))
+    }
+
+    /// Save the progress of the jobs to their history every `interval`, if it changed
+    pub fn spawn_job_history(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let jobs = self.state.jobs.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let jobs = jobs.clone();
+                let _ = tokio::task::spawn_blocking(move || jobs.save_or_warn()).await;
+            }
+        })
+    }
+
+    /// Periodically save metrics computed on demand by the handlers to the origins cache,
+    /// so the cache warms up progressively instead of requiring a full offline compute.
+    pub fn spawn_write_back(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let state = self.state.graph.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick completes immediately, nothing can be pending yet
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let saved = with_graph_blocking(state.clone(), |graph| {
+                    graph.write_back().map_err(|e| {
+                        error!("Failed to write back origins cache: {}", e);
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    })
+                })
+                .await;
+                if let Ok(count) = saved {
+                    if count > 0 {
+                        info!("Wrote back {} origins with newly computed metrics", count);
+                    }
+                }
+            }
+        })
+    }
+
+    /// Log the memory usage every `interval`, skipping ticks while the graph is locked
+    /// for writing (e.g. while the origins are loading)
+    pub fn spawn_memory_log(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let state = self.state.graph.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match state.clone().try_read_owned() {
+                    Ok(graph) => {
+                        if let Ok(usage) = tokio::task::spawn_blocking(move || graph.memory_usage()).await {
+                            info!("Memory: {}", usage);
+                        }
+                    }
+                    Err(_) => match resident_set_size() {
+                        Some(rss) => info!("Memory: RSS {} bytes (graph busy, structures not measured)", rss),
+                        None => debug!("Memory: graph busy, skipping"),
+                    },
+                }
+            }
+        })
+    }
+
+    /// Check the RSS against the memory limit every [`MEMORY_CHECK_INTERVAL`]. Over it,
+    /// heavy requests are rejected and the workers stop computing the origins of jobs
+    /// until the RSS is back under 90% of the limit; with `drop_urls`, the URLs of the
+    /// origins are also dropped, to be read from the graph again when needed.
+    pub fn spawn_memory_watch(&self, drop_urls: bool) -> tokio::task::JoinHandle<()> {
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            let pressure = state.memory_pressure.clone();
+            let mut ticker = tokio::time::interval(MEMORY_CHECK_INTERVAL);
+            loop {
+                ticker.tick().await;
+                match pressure.check() {
+                    (Some(true), rss) => {
+                        warn!(
+                            "RSS of {} bytes over the limit of {} bytes, shedding load",
+                            rss.unwrap_or_default(),
+                            pressure.limit_bytes()
+                        );
+                        state.workers.pause_background(true);
+                        if drop_urls {
+                            let freed = with_graph_blocking(state.graph.clone(), |graph| Ok(graph.drop_urls())).await;
+                            if let Ok(freed) = freed {
+                                info!("Dropped {} bytes of origin URLs", freed);
+                            }
+                        }
+                    }
+                    (Some(false), rss) => {
+                        info!("RSS back to {} bytes, no longer shedding load", rss.unwrap_or_default());
+                        state.workers.pause_background(false);
+                    }
+                    (None, _) => {}
+                }
+            }
+        })
+    }
+
+    /// Serve the origin table over Arrow Flight on `address` until `shutdown` is cancelled,
+    /// see [`OriginsFlight`]
+    #[cfg(feature = "flight")]
+    pub fn spawn_flight(&self, address: std::net::SocketAddr, shutdown: CancellationToken) -> tokio::task::JoinHandle<()> {
+        let flight = OriginsFlight::new(self.state.graph.clone(), self.state.load_progress.clone());
+        tokio::spawn(async move {
+            if let Err(e) = flight.serve(address, shutdown).await {
+                error!("Arrow Flight server failed: {}", e);
+            }
+        })
+    }
+}
+
+/// Interval between checks of the RSS against `--memory-limit-mb`
+const MEMORY_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Seconds clients are told to wait before retrying requests rejected under memory pressure
+const SHED_RETRY_AFTER: &str = "30";
+
+/// Interval between saves of the job history, for the progress of running jobs
+const JOB_HISTORY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Responses cached in Redis by [`cache_responses`], see [`ServerArgs::redis_url`]
+pub struct ResponseCache {
+    pub redis: RedisCache,
+    /// TTL of the responses of routes about a single item (`/origins/:id/...`)
+    pub ttl: Duration,
+    /// TTL of the other responses
+    pub aggregate_ttl: Duration,
+}
+
+/// Header telling whether a response came from [`ResponseCache`]
+const X_CACHE: HeaderName = HeaderName::from_static("x-cache");
+
+/// Where the numbers of the responses come from, see [`add_provenance`]
+struct Provenance<G>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    graph: Arc<RwLock<Graph<G>>>,
+    export: Option<GraphExport>,
+}
+
+impl<G> Provenance<G>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    async fn to_json(&self) -> Value {
+        let graph = self.graph.read().await;
+        json!({
+            "graph": self.export,
+            "metrics_computed_at": graph.cache_modified().map(|modified| DateTime::<Utc>::from(modified).to_rfc3339()),
+            "unsaved_origins": graph.unsaved_origins(),
+            "cache_schema": cache_schema(),
+            "code_version": CODE_VERSION,
+        })
+    }
+}
+
+/// With `?provenance=true`, wrap successful JSON responses as `{"data": ...,
+/// "provenance": ...}`, so that clients can record where the numbers they downloaded
+/// come from: the graph export, when the origins cache was last written (metrics of
+/// `unsaved_origins` origins were computed since), its layout and the code version.
+async fn add_provenance<G>(
+    State(provenance): State<Arc<Provenance<G>>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let wanted = request.uri().query().is_some_and(|query| query.split('&').any(|param| param == "provenance=true"));
+    let response = next.run(request).await;
+    let is_json = response.headers().get(CONTENT_TYPE).is_some_and(|value| value == "application/json");
+    if !wanted || response.status() != StatusCode::OK || !is_json {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let data: Value = match to_bytes(body, usize::MAX).await.map_err(|e| e.to_string()).and_then(|bytes| {
+        serde_json::from_slice(&bytes).map_err(|e| e.to_string())
+    }) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("Failed to read the response to wrap its provenance: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    parts.headers.remove(CONTENT_LENGTH);
+    let envelope = json!({ "data": data, "provenance": provenance.to_json().await });
+    (parts, Json(envelope)).into_response()
+}
+
+/// Answer `GET` requests from the cache, keyed by their path and query, and cache the
+/// successful JSON responses of the others. Responses tell which with the `x-cache`
+/// header.
+///
+/// Cached responses are not invalidated when metrics are recomputed or the origins cache
+/// is swapped: they are served until their TTL runs out.
+async fn cache_responses(
+    State(cache): State<Arc<ResponseCache>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    if request.method() != axum::http::Method::GET {
+        return next.run(request).await;
+    }
+    // Listings by tag change as soon as origins are tagged
+    let by_tag = request.uri().query().is_some_and(|query| query.split('&').any(|param| param.starts_with("tag=")));
+    if by_tag {
+        return next.run(request).await;
+    }
+    let key = request.uri().path_and_query().map_or_else(|| request.uri().path().to_string(), ToString::to_string);
+    let single = request.extensions().get::<MatchedPath>().is_some_and(|route| route.as_str().contains("/:id"));
+    if let Some(body) = cache.redis.get(&key).await {
+        return ([(CONTENT_TYPE, "application/json"), (X_CACHE, "hit")], body).into_response();
+    }
+    let response = next.run(request).await;
+    let is_json = response.headers().get(CONTENT_TYPE).is_some_and(|value| value == "application/json");
+    if response.status() != StatusCode::OK || !is_json {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    parts.headers.insert(X_CACHE, HeaderValue::from_static("miss"));
+    match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => {
+            if bytes.len() <= MAX_CACHED_RESPONSE {
+                cache.redis.set(&key, &bytes, if single { cache.ttl } else { cache.aggregate_ttl }).await;
+            } else {
+                debug!("Not caching the {} bytes response to {}", bytes.len(), key);
+            }
+            Response::from_parts(parts, Body::from(bytes))
+        }
+        Err(e) => {
+            error!("Failed to read the response to {}: {}", key, e);
+            Response::from_parts(parts, Body::empty())
+        }
+    }
+}
+
+/// Largest request body logged along with a slow request, e.g. the SQL of `POST /query`
+const MAX_LOGGED_BODY: usize = 4096;
+
+/// Middleware recording the latency of each request by route in [`RequestMetrics`], and
+/// logging the requests slower than its threshold with their method, path, query string
+/// and (small) body
+async fn track_requests(
+    State(metrics): State<Arc<RequestMetrics>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let start = std::time::Instant::now();
+    let route = request.extensions().get::<MatchedPath>().map(|route| route.as_str().to_string());
+    let method = request.method().clone();
+    let uri = request.uri().clone();
+    let small_body = request
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok()?.parse::<usize>().ok())
+        .is_some_and(|length| length > 0 && length <= MAX_LOGGED_BODY);
+    // Only buffered if it may have to be logged
+    let (request, body) = if small_body {
+        let (parts, body) = request.into_parts();
+        match to_bytes(body, MAX_LOGGED_BODY).await {
+            Ok(bytes) => (Request::from_parts(parts, Body::from(bytes.clone())), Some(bytes)),
+            Err(_) => (Request::from_parts(parts, Body::empty()), None),
+        }
+    } else {
+        (request, None)
+    };
+
+    let response = next.run(request).await;
+    let elapsed = start.elapsed();
+    let status = response.status();
+    // Unmatched paths are not recorded, not to grow a route per path probed
+    if let Some(route) = &route {
+        metrics.record(route, elapsed, status.is_server_error());
+    }
+    if metrics.is_slow(elapsed) {
+        let body = body.as_deref().map(String::from_utf8_lossy);
+        warn!(
+            method = %method,
+            uri = %uri,
+            route = route.as_deref().unwrap_or_default(),
+            body = body.as_deref().unwrap_or_default(),
+            status = status.as_u16(),
+            duration_ms = elapsed.as_millis() as u64,
+            "Slow request: {} {} took {:.1}s",
+            method,
+            uri,
+            elapsed.as_secs_f64()
+        );
+    }
+    response
+}
+
+/// GET /metrics - Get the latency of the requests served by route, as Prometheus metrics
+async fn get_metrics<G>(State(state): State<AppState<G>>) -> impl IntoResponse
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    (
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.request_metrics.render(),
+    )
+}
+
+/// Identifier of the next request, to correlate the events logged while serving it
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Middleware logging each request as structured fields, for log aggregators: the fields
+/// of its span (request id, route, origin id) are attached to every event logged while
+/// serving it, and a final event gives the status and duration. Bodies are not logged.
+async fn log_request_fields(request: Request<Body>, next: Next) -> Response {
+    if !tracing::enabled!(tracing::Level::INFO) {
+        return next.run(request).await;
+    }
+    let start = std::time::Instant::now();
+    let path = request.uri().path().to_string();
+    let route = request.extensions().get::<MatchedPath>().map_or(path.as_str(), MatchedPath::as_str).to_string();
+    // Only routes under /origins/:id are about an origin, e.g. not /clusters/:id
+    let origin_id = route
+        .starts_with("/origins/:id")
+        .then(|| path.split('/').nth(2).and_then(|id| id.parse::<u64>().ok()))
+        .flatten();
+    let span = info_span!(
+        "request",
+        request_id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed),
+        method = %request.method(),
+        route = %route,
+        origin_id,
+    );
+    let response = next.run(request).instrument(span.clone()).await;
+    span.in_scope(|| {
+        info!(
+            status = response.status().as_u16(),
+            duration_ms = start.elapsed().as_secs_f64() * 1000.0,
+            "request served"
+        )
+    });
+    response
 }
 
 // Custom middleware to log requests and responses including body content
@@ -109,6 +975,10 @@ async fn log_requests_and_responses(
     request: Request<Body>,
     next: Next,
 ) -> Response {
+    // Skip buffering the body when `--log-filter` leaves this module out
+    if !tracing::enabled!(tracing::Level::DEBUG) {
+        return next.run(request).await;
+    }
     let start = std::time::Instant::now();
     let method = request.method().clone();
     let uri = request.uri().clone();
@@ -147,519 +1017,2673 @@ async fn log_requests_and_responses(
     }
 }
 
+/// Load the property families and labels of a graph fresh out of `init_properties`, then
+/// serve it. A macro because unidirectional and bidirectional graphs have the same loading
+/// methods but no common trait for them.
+macro_rules! load_properties_and_build {
+    ($graph:ident, $load_labels:ident, $routes:expr, $args:ident, $progress:ident, $shutdown:ident) => {{
+        let graph = $graph;
+        let graph = tokio::task::block_in_place(|| {
+            load_stage(&$progress, LoadStage::LoadingProperties("maps"), || graph.load_properties(|p| p.load_maps::<DynMphf>()))
+        })?;
+        let graph = tokio::task::block_in_place(|| {
+            load_stage(&$progress, LoadStage::LoadingProperties("timestamps"), || graph.load_properties(|p| p.load_timestamps()))
+        })?;
+        let graph = tokio::task::block_in_place(|| {
+            load_stage(&$progress, LoadStage::LoadingProperties("persons"), || graph.load_properties(|p| p.load_persons()))
+        })?;
+        let graph = tokio::task::block_in_place(|| {
+            load_stage(&$progress, LoadStage::LoadingProperties("strings"), || graph.load_properties(|p| p.load_strings()))
+        })?;
+        match $args.properties {
+            PropertySet::Required => {
+                let internal_graph = tokio::task::block_in_place(|| {
+                    load_stage(&$progress, LoadStage::LoadingLabels, || graph.$load_labels())
+                })?;
+                build_app($args, internal_graph, $progress, $shutdown, $routes.merge(content_routes())).await
+            }
+            PropertySet::All => {
+                let graph = tokio::task::block_in_place(|| {
+                    load_stage(&$progress, LoadStage::LoadingProperties("contents"), || graph.load_properties(|p| p.load_contents()))
+                })?;
+                let graph = tokio::task::block_in_place(|| {
+                    load_stage(&$progress, LoadStage::LoadingProperties("label names"), || graph.load_properties(|p| p.load_label_names()))
+                })?;
+                let internal_graph = tokio::task::block_in_place(|| {
+                    load_stage(&$progress, LoadStage::LoadingLabels, || graph.$load_labels())
+                })?;
+                build_app($args, internal_graph, $progress, $shutdown, $routes.merge(label_routes()).merge(content_property_routes())).await
+            }
+        }
+    }};
+}
+
 // Fonction pour créer et lancer le serveur avec le type concret
-pub async fn create_server() -> Result<(), Box<dyn std::error::Error>> {
+pub async fn create_server() -> Result<(), GraphError> {
     // Parse command line arguments
     let args = ServerArgs::parse();
+    if let Some(command) = &args.command {
+        return Ok(command.run::<ServerArgs>()?);
+    }
+    let graph_path = args.graph_path.clone().unwrap_or_default();
     
-    // Initialize tracing with appropriate level based on debug mode
-    if args.log {
-        tracing_subscriber::fmt()
-            .with_max_level(tracing::Level::DEBUG)
-            .init();
-    } else {
-        init();
+    // Initialize tracing with appropriate level based on debug mode, unless refined per module
+    let log_filter = args.log_filter.clone().or_else(|| args.log.then(|| "debug".to_string()));
+    let log_file = args.log_file.clone().map(|path| LogFile {
+        path,
+        rotation: args.log_rotation,
+        max_size: args.log_max_size * 1024 * 1024,
+        max_files: args.log_max_files,
+    });
+    let _log_guard = logging::init(args.log_format, log_filter.as_deref(), log_file.as_ref())?;
+    set_slow_origin_threshold(Duration::from_millis(args.slow_origin_ms));
+    set_origin_node_budget(args.max_nodes_per_origin);
+    if args.snapshot_memo > 0 {
+        enable_snapshot_memo(args.snapshot_memo);
+    }
+    if args.max_visits_per_second > 0 {
+        set_visit_throttle(args.max_visits_per_second);
     }
     
     info!("Starting SWH Graph API server...");
     info!("Configuration:");
     info!("  Host: {}", args.host);
     info!("  Port: {}", args.port);
-    info!("  Graph path: {}", args.graph_path);
+    info!("  Graph path: {}", graph_path);
+    info!("  Datasets: {:?}", args.datasets);
     info!("  Data path: {}", args.data_path);
+    info!("  Cache dir: {:?}", args.cache_dir);
     info!("  Log mode: {}", args.log);
+    info!("  Log format: {:?}", args.log_format);
+    info!("  Log filter: {:?}", args.log_filter);
+    info!("  Log file: {:?} ({:?} rotation)", args.log_file, args.log_rotation);
+    info!("  Node type index: {}", args.node_type_index);
+    info!("  Write-back interval: {}s", args.write_back_interval);
+    info!("  Recompute schedule: {:?}", args.recompute_schedule.as_ref().map(Schedule::as_str));
+    info!("  Watch cache: {}", args.watch_cache);
+    info!("  Redis: {:?} (prefix {:?}, TTL {}s, aggregates {}s)", args.redis_url, args.redis_prefix, args.redis_ttl, args.redis_aggregate_ttl);
+    #[cfg(feature = "flight")]
+    info!("  Arrow Flight port: {:?}", args.flight_port);
+    info!("  Compute workers: {}", args.workers);
+    info!("  Properties: {:?}", args.properties);
+    info!("  Bidirectional: {}", args.bidirectional);
+    info!("  URL allowlist: {:?}", args.allow_url.iter().map(UrlPattern::as_str).collect::<Vec<_>>());
+    info!("  URL blocklist: {:?}", args.block_url.iter().map(UrlPattern::as_str).collect::<Vec<_>>());
+    info!("  Forges: {:?}", args.forge);
+    info!("  Origins file: {:?}", args.origins_file);
     
-    // Load the graph with the provided path
-    let internal_graph = SwhUnidirectionalGraph::new(&args.graph_path)?
-        .load_all_properties::<DynMphf>()?
-        .load_labels()?;
-    
-    let mut graph = Graph::with_serialization_format(
-        &args.data_path,
-        internal_graph,
-        SerializationFormat::Bincode,
-    );
-    
-    info!("Loading origins...");
-    graph.get_origins_mut()?;
-    
-    // Créer le serveur avec le type concret
-    let server = GraphServer::new(graph);
-    
-    // Create router with debug mode
-    let app = server.create_router(args.log);
-    
-    // Start server with the provided host and port
+    // Cancelled on Ctrl-C, stops the origins discovery if it is still running
+    let shutdown = CancellationToken::new();
+
+    // Accept connections while the graph loads, so /readyz can report the progress
     let bind_address = format!("{}:{}", args.host, args.port);
-    let listener = tokio::net::TcpListener::bind(&bind_address).await?;
-    info!("Server listening on http://{}", bind_address);
-    info!("Available endpoints:");
-    info!("  GET /health - Health check");
-    info!("  GET /origins - Get all origin IDs");
-    info!("  GET /origins/latest-commit-dates - Get latest commit dates for all origins");
-    info!("  GET /origins/commit-counts - Get commit counts for all origins");
-    info!("  GET /origins/committer-counts - Get committer counts for all origins");
-    info!("  GET /origins/:id/url - Get origin URL");
-    info!("  GET /origins/:id/latest-commit-date - Get latest commit date");
-    info!("  GET /origins/:id/committer-count - Get committer count");
-    info!("  GET /origins/:id/commit-count - Get commit count");
+    let progress = Arc::new(LoadProgress::default());
+    let bootstrap = BootstrapServer::start(&bind_address, progress.clone())?;
+    info!("Server listening on http://{} (loading, see /readyz)", bind_address);
+
+    let (listener, app) = match &args.datasets {
+        Some(path) => {
+            let datasets = DatasetConfig::read(path)?;
+            // Each dataset reports its own progress at /graphs/<name>/progress once served,
+            // until then the bootstrap server only tells that graphs are loading
+            progress.set_stage(LoadStage::LoadingGraph);
+            let manager = DatasetManager::load(&args, datasets, shutdown.clone()).await?;
+            let listener = bootstrap.stop().await?;
+            manager.spawn_warm_ups(loopback_address(&listener)?, &args.warm_up);
+            info!("Available endpoints:");
+            info!("  GET /health - Health check");
+            info!("  GET /readyz - Loading status of all datasets");
+            info!("  GET /graphs - List datasets with their loading status");
+            for name in manager.names() {
+                info!("  /graphs/{}/... - Endpoints of a single graph server over {}", name, name);
+            }
+            (listener, manager.router())
+        }
+        None => {
+            let app = load_app(args.clone(), progress.clone(), shutdown.clone()).await?;
+            // Take over the bootstrap server's socket
+            let listener = bootstrap.stop().await?;
+            if !args.warm_up.is_empty() {
+                spawn_warm_up(loopback_address(&listener)?, progress, args.warm_up.clone());
+            }
+            log_endpoints(&args, &app);
+            (listener, app.router)
+        }
+    };
     
     if args.log {
         info!("Debug mode enabled - all HTTP requests will be logged");
     }
     
-    axum::serve(listener, app).await?;
+    let on_ctrl_c = shutdown.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            info!("Shutting down...");
+            on_ctrl_c.cancel();
+        }
+    });
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await?;
     
     Ok(())
 }
 
-/// Health check endpoint
-async fn health_check() -> Result<Json<Value>, StatusCode> {
-    Ok(Json(json!({
-        "status": "healthy",
-        "service": "swh-graph-api"
-    })))
+/// Router of a loaded graph, with which of the optional routes it serves
+pub(crate) struct GraphApp {
+    pub router: Router,
+    /// `/revisions/:id/origins`, from the transposed graph or the revision multimap
+    pub revision_origins: bool,
+    /// `/persons/top`, from the person commit totals
+    pub persons: bool,
 }
 
-/// GET /origins - Get all origin IDs (filtered to exclude origins with 0 commits)
-async fn get_origins_ids<G>(
-    State(state): State<Arc<RwLock<Graph<G>>>>
-) -> Result<Json<Value>, StatusCode>
-where
-    G: SwhLabeledForwardGraph 
-    + SwhGraphWithProperties<
-        Maps: properties::Maps,
-        Timestamps: properties::Timestamps,
-        Persons: properties::Persons,
-        Contents: properties::Contents,
-        Strings: properties::Strings,
-        LabelNames: properties::LabelNames,
-    > + Send + Sync + 'static,
-{
-    let mut graph = state.write().await;
-    
-    match graph.get_origins_mut() {
-        Ok(origins) => {
-            info!("Processing {} origins to filter by commit count...", origins.len());
-            
-            // Create progress bar
-            let pb = StdArc::new(ProgressBar::new(origins.len() as u64));
-            pb.set_style(
-                ProgressStyle::default_bar()
-                    .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({percent}%) | ETA: {eta} | Rate: {per_sec}")
-                    .unwrap()
-                    .progress_chars("█▉▊▋▌▍▎▏  ")
-            );
-            pb.set_message("Filtering origins");
-            
-            let mut ids: Vec<usize> = Vec::new();
-            
-            // Use parallel processing with rayon for filtering
-            let parallel_results: Vec<usize> = origins
-                .par_iter()
-                .enumerate()
-                .filter_map(|(idx, origin)| {
-                    // Update progress every 100 items
-                    if idx % 100 == 0 {
-                        pb.set_position(idx as u64);
-                    }
-                    
-                    let has_commits = origin.total_commit_latest_snp_read_only().unwrap_or(0) > 0;
-                    let has_commit_date = origin.get_latest_commit_date_read_only().is_some();
-                    
-                    if has_commits && has_commit_date {
-                        Some(origin.id())
-                    } else {
-                        None
-                    }
-                })
-                .collect();
-            
-            ids = parallel_results;
-            pb.set_position(origins.len() as u64);
-            
-            pb.finish_with_message("✅ Origin filtering completed!");
-            info!("Found {} origins with commits and commit dates", ids.len());
-            
-            Ok(Json(json!({
-                "origin_ids": ids,
-                "count": ids.len()
-            })))
-        }
-        Err(e) => {
-            error!("Failed to get origins: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+/// Load the graph at `args.graph_path` with the property families of `args` and build
+/// its router. Loading blocks for minutes, keep the runtime workers free for the
+/// bootstrap server meanwhile.
+pub(crate) async fn load_app(
+    args: ServerArgs,
+    progress: Arc<LoadProgress>,
+    shutdown: CancellationToken,
+) -> Result<GraphApp, GraphError> {
+    let graph_path = args.graph_path.clone().unwrap_or_default();
+    if args.bidirectional {
+        let graph = tokio::task::block_in_place(|| {
+            load_stage(&progress, LoadStage::LoadingGraph, || SwhBidirectionalGraph::new(&graph_path))
+        })?
+        .init_properties();
+        load_properties_and_build!(graph, load_forward_labels, backward_routes(), args, progress, shutdown)
+    } else {
+        let graph = tokio::task::block_in_place(|| {
+            load_stage(&progress, LoadStage::LoadingGraph, || SwhUnidirectionalGraph::new(&graph_path))
+        })?
+        .init_properties();
+        load_properties_and_build!(graph, load_labels, stats_routes(), args, progress, shutdown)
     }
 }
 
+/// Build the server around the loaded graph and start its background tasks, which stop
+/// with `shutdown`
+async fn build_app<G>(
+    args: ServerArgs,
+    internal_graph: G,
+    progress: Arc<LoadProgress>,
+    shutdown: CancellationToken,
+    extra_routes: Router<AppState<G>>,
+) -> Result<GraphApp, GraphError>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let mut builder = Graph::builder(&args.data_path)
+        .serialization(SerializationFormat::Compact)
+        .node_type_index(args.node_type_index)
+        .load_progress(progress)
+        .cancellation_token(shutdown.child_token())
+        .filter(OriginFilter {
+            require_snapshot: !args.allow_no_snapshot,
+            urls: UrlFilter { allow: args.allow_url.clone(), block: args.block_url.clone() },
+            forges: args.forge.clone(),
+            eligibility: Eligibility { min_commits: args.min_commits, require_commit_date: !args.allow_undated },
+        });
+    if let Some(cache_dir) = &args.cache_dir {
+        builder = builder.cache_dir(cache_dir);
+    }
+    if let Some(path) = &args.origins_file {
+        let list = OriginList::read(path)?;
+        info!("Restricting origins to the {} listed in {:?}", list.len(), path);
+        builder = builder.origin_list(list);
+    }
+    let graph = builder.build(internal_graph);
+
+    // Without the transposed graph, revision provenance can still be answered from the
+    // multimap if it was built (`swh-test --revision-origins`)
+    let multimap_file = graph.cache_path(REVISION_ORIGINS_FILE);
+    let extra_routes = if !args.bidirectional && multimap_file.exists() {
+        match tokio::task::block_in_place(|| RevisionOrigins::load(&multimap_file)) {
+            Ok(multimap) => {
+                info!("Loaded {} revisions in {} origins from {:?}", multimap.revision_count(), multimap.pair_count(), multimap_file);
+                extra_routes.merge(multimap_routes(multimap))
+            }
+            Err(e) => {
+                error!("Failed to load revision to origins multimap: {}", e);
+                extra_routes
+            }
+        }
+    } else {
+        extra_routes
+    };
+
+    // Built offline by `swh-test --person-commits`
+    let persons_file = graph.cache_path(PERSON_COMMITS_FILE);
+    let extra_routes = if persons_file.exists() {
+        match tokio::task::block_in_place(|| PersonTotals::load(&persons_file)) {
+            Ok(totals) => {
+                info!("Loaded the {} commits of {} persons from {:?}", totals.commit_count(), totals.len(), persons_file);
+                extra_routes.merge(person_routes(totals))
+            }
+            Err(e) => {
+                error!("Failed to load person commit totals: {}", e);
+                extra_routes
+            }
+        }
+    } else {
+        extra_routes
+    };
+
+    let extra_routes = match &args.compare_cache {
+        Some(path) => match tokio::task::block_in_place(|| read_origins_cache(path, SerializationFormat::Compact)) {
+            Ok(origins) => {
+                info!("Loaded {} origins to compare with from {:?}", origins.len(), path);
+                extra_routes.merge(dataset_routes(origins))
+            }
+            Err(e) => {
+                error!("Failed to load the origins cache to compare with: {}", e);
+                extra_routes
+            }
+        },
+        None => extra_routes,
+    };
+    
+    let cache_file = graph.origins_cache_file().to_path_buf();
+    let serialization_format = graph.serialization_format();
+    // Créer le serveur avec le type concret
+    let jobs_file = graph.cache_path(JOBS_FILE);
+    let tags_file = graph.cache_path(TAGS_FILE);
+    let graph_export = GraphExport::describe(args.graph_path.as_deref().unwrap_or_default());
+    let frozen = FrozenDatasets::new(
+        graph.cache_path(FROZEN_DIR),
+        graph_export.clone(),
+        json!({
+            "eligibility": graph.eligibility(),
+            "allow_url": args.allow_url.iter().map(UrlPattern::as_str).collect::<Vec<_>>(),
+            "block_url": args.block_url.iter().map(UrlPattern::as_str).collect::<Vec<_>>(),
+            "forge": args.forge,
+            "require_snapshot": !args.allow_no_snapshot,
+            "origins_file": args.origins_file,
+        }),
+    );
+    let mut server = GraphServer::new(graph, args.workers, args.legacy_bulk_maps)
+        .with_slow_request_threshold(Duration::from_millis(args.slow_request_ms))
+        .with_job_history(jobs_file)?
+        .with_tags(tags_file)?
+        .with_graph_export(graph_export)
+        .with_memory_limit(args.memory_limit_mb * 1024 * 1024)
+        .with_frozen_datasets(frozen);
+    if let Some(url) = &args.redis_url {
+        let redis = RedisCache::new(url, &args.redis_prefix)?;
+        info!("Caching responses in Redis at {}", redis.address());
+        server = server.with_response_cache(ResponseCache {
+            redis,
+            ttl: Duration::from_secs(args.redis_ttl),
+            aggregate_ttl: Duration::from_secs(args.redis_aggregate_ttl),
+        });
+    }
+    info!("Loading origins in the background, see /readyz");
+    server.spawn_origin_loading();
+    server.spawn_job_history(JOB_HISTORY_INTERVAL);
+    if args.write_back_interval > 0 {
+        server.spawn_write_back(Duration::from_secs(args.write_back_interval));
+    }
+    if args.watch_cache {
+        server.spawn_cache_watch(cache_file, serialization_format)?;
+    }
+    if let Some(schedule) = &args.recompute_schedule {
+        server.spawn_scheduled_recompute(schedule.clone());
+    }
+    #[cfg(feature = "flight")]
+    if let Some(port) = args.flight_port {
+        let address = tokio::net::lookup_host((args.host.as_str(), port))
+            .await?
+            .next()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, format!("no address for {}", args.host)))?;
+        info!("Serving the origins over Arrow Flight on {}", address);
+        server.spawn_flight(address, shutdown.clone());
+    }
+    if args.memory_log_interval > 0 {
+        server.spawn_memory_log(Duration::from_secs(args.memory_log_interval));
+    }
+    if args.memory_limit_mb > 0 {
+        info!("Shedding load above {} MiB of RSS", args.memory_limit_mb);
+        server.spawn_memory_watch(args.shed_urls);
+    }
+    
+    // Create router with debug mode
+    let router = server.create_router((args.log || args.log_filter.is_some()).then_some(args.log_format), extra_routes);
+
+    Ok(GraphApp {
+        router,
+        revision_origins: args.bidirectional || multimap_file.exists(),
+        persons: persons_file.exists(),
+    })
+}
+
+/// Address at which the server listening on `listener` can reach itself
+fn loopback_address(listener: &tokio::net::TcpListener) -> std::io::Result<SocketAddr> {
+    let mut address = listener.local_addr()?;
+    if address.ip().is_unspecified() {
+        address.set_ip(if address.is_ipv4() { Ipv4Addr::LOCALHOST.into() } else { Ipv6Addr::LOCALHOST.into() });
+    }
+    Ok(address)
+}
+
+/// Log the routes served by a single graph server
+fn log_endpoints(args: &ServerArgs, app: &GraphApp) {
+    info!("Available endpoints:");
+    info!("  GET /health - Health check");
+    info!("  GET /readyz - Loading status");
+    info!("  GET /progress - Loading stages, with rate and ETA");
+    info!("  GET /metrics - Get request latencies by route (Prometheus)");
+    info!("  GET /origins?eligible=&tag=&committed_between= - Get all origin IDs");
+    info!("  GET /origins/count - Get origin counts by completeness and eligibility");
+    info!("  GET /origins/recent?after=&limit= - Get origins by decreasing latest commit date");
+    info!("  GET /origins/lookup?swhid=&url= - Get the origin with this SWHID or URL");
+    info!("  GET /origins/duplicates?limit= - Get origins sharing a canonical URL");
+    info!("  GET /origins/latest-commit-dates - Get latest commit dates for all origins");
+    info!("  GET /origins/commit-counts - Get commit counts for all origins");
+    info!("  GET /origins/committer-counts - Get committer counts for all origins");
+    info!("  GET /origins/:id - Get origin URL, metrics, and latest snapshot and head revision SWHIDs");
+    info!("  GET /origins/:id/url - Get origin URL");
+    info!("  GET /origins/:id/swhid - Get origin SWHID and URL");
+    info!("  GET /origins/:id/diagnostics - Get why an origin is not in /origins");
+    info!("  GET /origins/:id/latest-commit-date - Get latest commit date");
+    info!("  GET /origins/:id/committer-count - Get committer count");
+    info!("  GET /origins/:id/commit-count - Get commit count");
+    info!("  GET/POST /origins/:id/tags - Get or add origin tags");
+    info!("  DELETE /origins/:id/tags/:tag - Remove an origin tag");
+    info!("  GET /tags - Get tags with their origin counts");
+    if app.revision_origins {
+        info!("  GET /revisions/:id/origins - Get origins containing a revision");
+    }
+    info!("  GET /contents/by-sha1/:hash - Get the node id of a content from its hash");
+    if args.bidirectional {
+        info!("  GET /contents/:id/origins - Get origins whose latest snapshot contains a content");
+    }
+    info!("  GET /origins/:id/cluster - Get origin cluster");
+    info!("  GET /origins/:id/structure - Get origin structure");
+    info!("  GET /origins/:id/working-hours - Get origin commit hours and timezones");
+    info!("  GET /origins/:id/history - Get origin root revisions");
+    info!("  GET /origins/:id/parents - Get origin average and max parents per commit");
+    info!("  GET /origins/:id/burstiness - Get origin weekly commit burstiness");
+    info!("  GET /origins/:id/dedup - Get origin contents shared with other origins");
+    info!("  GET /origins/:id/metrics?as_of= - Get origin metrics as of a date");
+    info!("  GET /clusters/:id/origins - Get origins of a cluster");
+    if app.persons {
+        info!("  GET /persons/top?limit=&format= - Get the persons with the most commits");
+    }
+    info!("  GET /stats/graph - Get node, arc and degree statistics of the graph");
+    info!("  GET /stats/quantiles?metric=&q= - Get estimated quantiles of an origin metric");
+    info!("  GET /stats/facets?by=forge&metric= - Get origin counts by forge and metric bucket");
+    if matches!(args.properties, PropertySet::All) {
+        info!("  GET /snapshots/diff?a=&b= - Compare two snapshots");
+        info!("  GET /origins/:id/default-branch-commit-count - Get origin commits on its default branch");
+        info!("  GET /origins/:id/has-path?path= - Get whether a path exists in the origin head revision");
+    }
+    if args.compare_cache.is_some() {
+        info!("  GET /datasets/diff - Compare the origins with another export");
+    }
+    info!("  POST /datasets/freeze - Freeze the origin table with a manifest");
+    info!("  GET /datasets/frozen - List frozen datasets");
+    info!("  GET /datasets/frozen/:name - Get a frozen dataset manifest");
+    info!("  GET /datasets/frozen/:name/:file - Download a frozen dataset file");
+    info!("  POST /query - Run an SQL query over the origin metrics");
+    info!("  POST /resolve - Translate SWHIDs to node ids and node ids to SWHIDs");
+    info!("  POST /admin/recompute - Recompute metrics of all or selected origins");
+    info!("  GET /admin/workers - Get compute workers progress");
+    info!("  GET /admin/memory - Get memory usage");
+    info!("  GET /jobs - List recompute jobs");
+    info!("  GET /jobs/:id - Get recompute job status");
+    info!("  DELETE /jobs/:id - Cancel a recompute job");
+}
+
+/// Interval between checks of whether the origins are loaded before warming up
+const WARM_UP_POLL: Duration = Duration::from_secs(1);
+
+/// Request each of `uris` from the server at `address`, one after the other, once the
+/// origins are loaded. Their results end up in the response cache with `--redis-url`,
+/// and in the memos of the graph (quantiles, facets, date index) in any case.
+pub(crate) fn spawn_warm_up(address: SocketAddr, progress: Arc<LoadProgress>, uris: Vec<String>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        while !progress.is_ready() {
+            if matches!(progress.stage(), LoadStage::Failed(_)) {
+                return;
+            }
+            tokio::time::sleep(WARM_UP_POLL).await;
+        }
+        for uri in uris {
+            let start = Instant::now();
+            match warm_up(address, &uri).await {
+                Ok(status) if status.starts_with('2') => {
+                    info!("Warmed up {} in {:.1}s", uri, start.elapsed().as_secs_f64());
+                }
+                Ok(status) => warn!("Failed to warm up {}: HTTP status {}", uri, status),
+                Err(e) => warn!("Failed to warm up {}: {}", uri, e),
+            }
+        }
+    })
+}
+
+/// `GET uri` from the server at `address`, returning the HTTP status
+async fn warm_up(address: SocketAddr, uri: &str) -> std::io::Result<String> {
+    let mut stream = tokio::net::TcpStream::connect(address).await?;
+    let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", uri, address);
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    let status_line = response.split(|&byte| byte == b'\n').next().unwrap_or_default();
+    Ok(String::from_utf8_lossy(status_line).split_whitespace().nth(1).unwrap_or_default().to_string())
+}
+
+/// Minimal server answering `/health`, `/readyz` and `/progress` while the graph is loading, before
+/// the full router (which needs the graph) can be built
+struct BootstrapServer {
+    listener: std::net::TcpListener,
+    shutdown: oneshot::Sender<()>,
+    task: tokio::task::JoinHandle<std::io::Result<()>>,
+}
+
+impl BootstrapServer {
+    fn start(bind_address: &str, progress: Arc<LoadProgress>) -> std::io::Result<Self> {
+        let listener = std::net::TcpListener::bind(bind_address)?;
+        listener.set_nonblocking(true)?;
+        let bootstrap_listener = tokio::net::TcpListener::from_std(listener.try_clone()?)?;
+        let app = Router::new()
+            .route("/health", get(health_check))
+            .route("/readyz", get(readiness_check))
+            .route("/progress", get(get_progress))
+            .with_state(progress);
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        let task = tokio::spawn(async move {
+            axum::serve(bootstrap_listener, app)
+                .with_graceful_shutdown(async {
+                    let _ = stopped.await;
+                })
+                .await
+        });
+        Ok(BootstrapServer { listener, shutdown, task })
+    }
+
+    /// Stop answering requests and hand the socket over to the full server
+    async fn stop(self) -> std::io::Result<tokio::net::TcpListener> {
+        let _ = self.shutdown.send(());
+        self.task.await.map_err(std::io::Error::other)??;
+        tokio::net::TcpListener::from_std(self.listener)
+    }
+}
+
+/// Lets handlers use `?` on graph operations, logging the failure
+impl From<GraphError> for StatusCode {
+    fn from(e: GraphError) -> Self {
+        match e {
+            GraphError::OriginNotFound(_)
+            | GraphError::ClusterNotFound(_)
+            | GraphError::NodeNotFound { .. } => {
+                debug!("{}", e);
+                StatusCode::NOT_FOUND
+            }
+            GraphError::Cancelled => {
+                debug!("{}", e);
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            GraphError::GraphNotAttached(_) => {
+                error!("{}", e);
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            // Computing it again would only fail the same way
+            GraphError::TraversalBudgetExceeded { .. } => {
+                warn!("{}", e);
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            _ => {
+                error!("{}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+}
+
+/// Run `f` against the graph on tokio's blocking thread pool.
+///
+/// Origin metrics are computed by synchronous graph traversals which can take
+/// minutes on the full corpus; running them on the async workers would starve
+/// every other request. The write guard is moved into the blocking task and
+/// released when `f` returns.
+async fn with_graph_blocking<G, T, F>(
+    state: Arc<RwLock<Graph<G>>>,
+    f: F,
+) -> Result<T, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+    T: Send + 'static,
+    F: FnOnce(&mut Graph<G>) -> Result<T, StatusCode> + Send + 'static,
+{
+    let mut graph = state.write_owned().await;
+    tokio::task::spawn_blocking(move || f(&mut graph))
+        .await
+        .map_err(|e| {
+            error!("Blocking graph task failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+}
+
+/// Like [`with_graph_blocking`], but only takes the read lock, so readers run
+/// concurrently with each other and with the compute workers' copies. Origin metrics
+/// are memoized through a shared reference, see [`Origin`].
+async fn with_graph_read_blocking<G, T, F>(
+    state: Arc<RwLock<Graph<G>>>,
+    f: F,
+) -> Result<T, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+    T: Send + 'static,
+    F: FnOnce(&Graph<G>) -> Result<T, StatusCode> + Send + 'static,
+{
+    let graph = state.read_owned().await;
+    tokio::task::spawn_blocking(move || f(&graph))
+        .await
+        .map_err(|e| {
+            error!("Blocking graph task failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+}
+
+/// Answer 503 with the loading progress until the origins are loaded
+async fn require_ready(
+    State(progress): State<Arc<LoadProgress>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    if progress.is_ready() {
+        next.run(request).await
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, Json(progress.to_json())).into_response()
+    }
+}
+
+/// Answer 503 to the requests covering many origins while memory is short, those about a
+/// single origin being cheap enough to keep being served
+async fn shed_load(
+    State(pressure): State<Arc<MemoryPressure>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let single = request.extensions().get::<MatchedPath>().is_some_and(|route| route.as_str().starts_with("/origins/:id"));
+    if single || !pressure.is_shedding() {
+        return next.run(request).await;
+    }
+    debug!("Shedding {} {}", request.method(), request.uri());
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(RETRY_AFTER, SHED_RETRY_AFTER)],
+        Json(json!({"error": "server is short of memory, retry later"})),
+    )
+        .into_response()
+}
+
+/// GET /readyz - Whether origins are loaded, with the loading stage and progress otherwise
+async fn readiness_check(
+    State(progress): State<Arc<LoadProgress>>,
+) -> (StatusCode, Json<Value>) {
+    let status = if progress.is_ready() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(progress.to_json()))
+}
+
+/// GET /progress - Stages of the startup pipeline gone through and their durations, and
+/// the items processed by the current one (e.g. origins computed) with rate and ETA
+async fn get_progress(State(progress): State<Arc<LoadProgress>>) -> Json<Value> {
+    Json(progress.progress_json())
+}
+
+/// Health check endpoint
+pub(crate) async fn health_check() -> Result<Json<Value>, StatusCode> {
+    Ok(Json(json!({
+        "status": "healthy",
+        "service": "swh-graph-api"
+    })))
+}
+
+/// Origins listed by GET /origins
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Listing {
+    /// Those passing the [`Eligibility`] of the graph
+    #[default]
+    #[serde(rename = "true")]
+    Eligible,
+    /// The loaded origins which don't
+    #[serde(rename = "false")]
+    Ineligible,
+    /// All loaded origins
+    #[serde(rename = "all")]
+    All,
+}
+
+/// Query string of GET /origins
+#[derive(Deserialize)]
+struct OriginsFilter {
+    #[serde(default)]
+    eligible: Listing,
+    /// Only list the origins with this tag, see [`TagStore`]
+    tag: Option<String>,
+    /// Only list the origins whose latest commit date is in this `from,to` range, see
+    /// [`parse_date_range`]
+    committed_between: Option<String>,
+    /// Only list the origins with (`true`) or without (`false`) a single committer, see
+    /// [`crate::origin::Origin::single_committer`]
+    single_committer: Option<bool>,
+    /// Only list the origins with (`true`) or without (`false`) a single commit
+    single_commit: Option<bool>,
+    /// Only list the origins of this ecosystem, see [`ECOSYSTEM_MANIFESTS`]
+    ecosystem: Option<String>,
+}
+
+/// GET /origins?eligible=true|false|all - Get the ids of the eligible origins (by default
+/// those with commits and a commit date, see `--min-commits` and `--allow-undated`), of
+/// the loaded origins which are not, or of all of them, along with the eligibility
+/// definition. With `tag=`, only those tagged with it are listed, and with
+/// `committed_between=2020-01-01,2020-12-31` only those whose latest commit date is in
+/// the range (see [`Graph::committed_between`]). `single_committer=false` and
+/// `single_commit=false` leave out personal one-off repositories, origins whose counts are
+/// not known being left out whatever the value. `ecosystem=rust` only lists the origins
+/// whose ecosystems (computed by `swh-test --ecosystems`) include it.
+///
+/// The eligible list is persisted alongside the origins cache, see
+/// [`Graph::get_eligible_origin_ids`].
+async fn get_origins_ids<G>(
+    Query(filter): Query<OriginsFilter>,
+    State(state): State<AppState<G>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let range = filter.committed_between.as_deref().map(parse_date_range).transpose().map_err(|e| {
+        debug!("Rejected committed_between: {}", e);
+        StatusCode::BAD_REQUEST
+    })?;
+    if let Some(ecosystem) = &filter.ecosystem {
+        if !ECOSYSTEM_MANIFESTS.iter().any(|&(_, known)| known == ecosystem) {
+            debug!("Rejected ecosystem {:?}", ecosystem);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+    let tagged = filter.tag.as_deref().map(|tag| state.tags.origins(tag));
+    // Only building the eligible list needs the write lock, listing reads it
+    if filter.eligible != Listing::All && state.graph.read().await.eligible_origin_ids().is_none() {
+        with_graph_blocking(state.graph.clone(), |graph| {
+            graph.get_eligible_origin_ids()?;
+            Ok(())
+        })
+        .await?;
+    }
+    with_graph_read_blocking(state.graph, move |graph| {
+        let eligibility = graph.eligibility();
+        // Both sorted by id
+        let committed = range.map(|(from, to)| graph.committed_between(from, to));
+        let response = |ids: &[NodeId]| {
+            let ids: Vec<NodeId> = ids
+                .iter()
+                .copied()
+                .filter(|id| tagged.as_ref().is_none_or(|tagged| tagged.binary_search(id).is_ok()))
+                .filter(|id| committed.as_ref().is_none_or(|committed| committed.binary_search(id).is_ok()))
+                .filter(|&id| {
+                    let Ok(origin) = graph.origin(id) else {
+                        return false;
+                    };
+                    let matches = |wanted: Option<bool>, known: Option<bool>| wanted.is_none() || wanted == known;
+                    matches(filter.single_committer, origin.single_committer())
+                        && matches(filter.single_commit, origin.single_commit())
+                        && filter.ecosystem.as_deref().is_none_or(|ecosystem| origin.has_ecosystem(ecosystem))
+                })
+                .collect();
+            info!(
+                "Serving {} origins (eligible={:?}, tag={:?}, committed_between={:?}, single_committer={:?}, single_commit={:?}, ecosystem={:?})",
+                ids.len(),
+                filter.eligible,
+                filter.tag,
+                filter.committed_between,
+                filter.single_committer,
+                filter.single_commit,
+                filter.ecosystem
+            );
+            Json(json!({
+                "origin_ids": ids,
+                "count": ids.len(),
+                "eligibility": eligibility,
+            }))
+        };
+        // Built above, unless the origins were reloaded meanwhile
+        let unavailable = || StatusCode::SERVICE_UNAVAILABLE;
+        match filter.eligible {
+            Listing::Eligible => Ok(response(graph.eligible_origin_ids().ok_or_else(unavailable)?)),
+            Listing::Ineligible => {
+                graph.eligible_origin_ids().ok_or_else(unavailable)?;
+                let ids: Vec<NodeId> = graph
+                    .loaded_origins()
+                    .par_iter()
+                    .map(|origin| origin.id)
+                    .filter(|&id| graph.is_listed(id) == Some(false))
+                    .collect();
+                Ok(response(&ids))
+            }
+            Listing::All => Ok(response(&graph.origin_ids())),
+        }
+    })
+    .await
+}
+
+/// Query string of GET /origins/recent
+#[derive(Deserialize)]
+struct RecentQuery {
+    /// `next` cursor of the previous page, `DATE,ID` with the date in seconds since the epoch
+    after: Option<String>,
+    limit: Option<usize>,
+    #[serde(default)]
+    raw: bool,
+}
+
+/// Cursor of GET /origins/recent: latest commit date and id of the last origin of a page
+fn parse_recent_cursor(value: &str) -> Result<(i64, NodeId), String> {
+    let invalid = || format!("invalid cursor {:?}, expected DATE,ID", value);
+    let (date, id) = value.split_once(',').ok_or_else(invalid)?;
+    Ok((date.trim().parse().map_err(|_| invalid())?, id.trim().parse().map_err(|_| invalid())?))
+}
+
+/// GET /origins/recent?after=DATE,ID&limit=N - Get the origins by decreasing latest
+/// commit date, a page at a time: `next` is the cursor of the following page, `null`
+/// after the last one. Only origins whose latest commit date is known are listed, from
+/// the date index of [`Graph::recently_committed`].
+async fn get_recent_origins<G>(
+    Query(query): Query<RecentQuery>,
+    State(state): State<Arc<RwLock<Graph<G>>>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let after = query.after.as_deref().map(parse_recent_cursor).transpose().map_err(|e| {
+        debug!("Rejected after: {}", e);
+        StatusCode::BAD_REQUEST
+    })?;
+    let limit = OriginsLimit { limit: query.limit }.get();
+    let format = DateFormat { raw: query.raw };
+    with_graph_read_blocking(state, move |graph| {
+        // One more to tell whether there is a next page
+        let mut page = graph.recently_committed(after, limit + 1);
+        let more = page.len() > limit;
+        page.truncate(limit);
+        let next = page.last().filter(|_| more).map(|(date, id)| format!("{},{}", date, id));
+        Ok(Json(json!({
+            "origins": page.iter().map(|&(date, id)| json!({
+                "origin_id": id,
+                "latest_commit_date": format.render(date),
+            })).collect::<Vec<_>>(),
+            "count": page.len(),
+            "next": next,
+        })))
+    })
+    .await
+}
+
+/// GET /origins/count - Get the number of origins, of those whose metrics are all known
+/// and of the eligible ones, from counters kept up to date by the graph
+async fn get_origins_count<G>(
+    State(state): State<Arc<RwLock<Graph<G>>>>
+) -> Json<Value>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    Json(state.read().await.origin_counts().to_json())
+}
+
+/// GET /origins/:id - Get the URL, metrics and cluster of an origin, with the SWHIDs of its
+/// latest snapshot and of its most recently committed head revision, so that clients can
+/// link to the archive without traversing the graph themselves, and the person id of the
+/// committer of that revision. Metrics are filled in by the compute workers first if they
+/// are not known yet.
+async fn get_origin<G>(
+    Path(id): Path<usize>,
+    Query(format): Query<DateFormat>,
+    State(state): State<AppState<G>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    lazy_origin_metric(&state, id, Metric::LatestCommitDate).await?;
+    with_graph_read_blocking(state.graph, move |graph| {
+        let origin = graph.origin(id)?;
+        // Only missing for origins loaded from caches written before heads and latest
+        // committers were kept
+        let computed = !origin.has_head() || !origin.has_latest_committer();
+        let head = origin.head(ComputePolicy::ComputeIfMissing)?;
+        let latest_committer = origin.latest_committer(ComputePolicy::ComputeIfMissing)?;
+        if computed {
+            // Persisted by the next write-back
+            graph.mark_origins_modified(1);
+        }
+        let backend = origin.get_graph()?;
+        let url = graph.with_origin(id, |origin| origin.get_url()).transpose()?.flatten();
+        Ok(Json(json!({
+            "origin_id": id,
+            "swhid": origin.swhid()?,
+            "url": url,
+            "latest_commit_date": origin
+                .known_metric(Metric::LatestCommitDate)
+                .map(|date| format.render(date)),
+            "commit_count": origin.known_metric(Metric::CommitCount),
+            "committer_count": origin.known_metric(Metric::CommitterCount),
+            "single_commit": origin.single_commit(),
+            "single_committer": origin.single_committer(),
+            "cluster": origin.cluster(),
+            "snapshot": head.map(|head| backend.swhid(head.snapshot).to_string()),
+            "head_revision": head.and_then(|head| head.revision).map(|rev| backend.swhid(rev).to_string()),
+            "latest_committer": latest_committer,
+            "columns": origin.columns(),
+        })))
+    })
+    .await
+}
+
 /// GET /origins/:id/url - Get URL for a specific origin
 async fn get_origin_url<G>(
     Path(id): Path<usize>,
     State(state): State<Arc<RwLock<Graph<G>>>>
 ) -> Result<Json<Value>, StatusCode>
 where
-    G: SwhLabeledForwardGraph 
-    + SwhGraphWithProperties<
-        Maps: properties::Maps,
-        Timestamps: properties::Timestamps,
-        Persons: properties::Persons,
-        Contents: properties::Contents,
-        Strings: properties::Strings,
-        LabelNames: properties::LabelNames,
-    > + Send + Sync + 'static,
+    G: GraphBackend + Send + Sync + 'static,
+{
+    with_graph_read_blocking(state, move |graph| {
+        // Reading the URL needs the graph of the origin
+        graph.origin(id)?;
+        let url = graph
+            .with_origin(id, |origin| origin.get_url())
+            .ok_or(GraphError::OriginNotFound(id))??;
+        Ok(Json(json!({
+            "origin_id": id,
+            "url": url
+        })))
+    })
+    .await
+}
+
+/// SWHID and URL of a loaded origin, the identifiers which stay valid across graph exports
+fn origin_identifiers<G>(graph: &Graph<G>, id: NodeId) -> Result<Value, GraphError>
+where
+    G: GraphBackend + Send + Sync,
+{
+    let swhid = graph.origin(id)?.swhid()?;
+    let url = graph.with_origin(id, |origin| origin.get_url()).transpose()?.flatten();
+    Ok(json!({
+        "origin_id": id,
+        "swhid": swhid,
+        "url": url,
+        "canonical_url": url.as_deref().map(canonical_url),
+    }))
+}
+
+/// GET /origins/:id/swhid - Get the SWHID and URL of an origin, to store instead of its
+/// node id, which changes from a graph export to the next
+async fn get_origin_swhid<G>(
+    Path(id): Path<usize>,
+    State(state): State<Arc<RwLock<Graph<G>>>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    with_graph_read_blocking(state, move |graph| Ok(Json(origin_identifiers(graph, id)?))).await
+}
+
+/// Query string of GET /origins/lookup, exactly one of them being set
+#[derive(Deserialize)]
+struct LookupQuery {
+    swhid: Option<String>,
+    url: Option<String>,
+}
+
+/// GET /origins/lookup - Find the node id of the origin with the given SWHID
+/// (`?swhid=swh:1:ori:...`) or URL (`?url=https://...`), the SWHID of a URL being its
+/// hash, through the minimal perfect hash of the graph. Answers 404 for origins which are
+/// not in the graph or not loaded.
+async fn get_origin_lookup<G>(
+    Query(query): Query<LookupQuery>,
+    State(state): State<Arc<RwLock<Graph<G>>>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let swhid = match (query.swhid, query.url) {
+        (Some(swhid), None) => swhid.parse::<SWHID>().map_err(|e| {
+            debug!("Rejected swhid {:?}: {}", swhid, e);
+            StatusCode::BAD_REQUEST
+        })?,
+        (None, Some(url)) => SWHID::from_origin_url(&url),
+        _ => {
+            debug!("Rejected lookup: expected either swhid or url");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    };
+    if swhid.node_type != NodeType::Origin {
+        debug!("Rejected swhid {}: not an origin", swhid);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    with_graph_read_blocking(state, move |graph| {
+        let id = graph.inner_graph().node_id(&swhid).ok_or(StatusCode::NOT_FOUND)?;
+        Ok(Json(origin_identifiers(graph, id)?))
+    })
+    .await
+}
+
+/// Body of POST /origins/:id/tags
+#[derive(Deserialize)]
+struct TagsRequest {
+    tags: Vec<String>,
+}
+
+/// 404 unless `id` is a loaded origin
+async fn require_origin<G>(state: &AppState<G>, id: usize) -> Result<(), StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    match state.graph.read().await.find_origin(id) {
+        Some(_) => Ok(()),
+        None => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+/// GET /origins/:id/tags - Get the tags of an origin
+async fn get_origin_tags<G>(
+    Path(id): Path<usize>,
+    State(state): State<AppState<G>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    require_origin(&state, id).await?;
+    Ok(Json(json!({ "origin_id": id, "tags": state.tags.tags_of(id) })))
+}
+
+/// POST /origins/:id/tags - Tag an origin, e.g. `{"tags": ["selected-for-study-x"]}`,
+/// keeping its other tags. Answers its tags.
+async fn post_origin_tags<G>(
+    Path(id): Path<usize>,
+    State(state): State<AppState<G>>,
+    Json(request): Json<TagsRequest>,
+) -> Response
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    if let Err(error) = request.tags.iter().try_for_each(|tag| TagStore::validate(tag)) {
+        return (StatusCode::BAD_REQUEST, Json(json!({ "error": error }))).into_response();
+    }
+    if let Err(status) = require_origin(&state, id).await {
+        return status.into_response();
+    }
+    let tags = state.tags.clone();
+    match tokio::task::spawn_blocking(move || tags.add(id, &request.tags)).await {
+        Ok(Ok(())) => Json(json!({ "origin_id": id, "tags": state.tags.tags_of(id) })).into_response(),
+        Ok(Err(e)) => {
+            error!("Failed to save the tags of origin {}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// DELETE /origins/:id/tags/:tag - Remove a tag from an origin, 404 if it does not have it
+async fn delete_origin_tag<G>(
+    Path((id, tag)): Path<(usize, String)>,
+    State(state): State<AppState<G>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let tags = state.tags.clone();
+    let removed = tokio::task::spawn_blocking(move || tags.remove(id, &tag))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|e| {
+            error!("Failed to save the tags of origin {}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if !removed {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(json!({ "origin_id": id, "tags": state.tags.tags_of(id) })))
+}
+
+/// GET /tags - Get the tags in use, with their number of origins
+async fn get_tags<G>(State(state): State<AppState<G>>) -> Json<Value>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    Json(json!({ "tags": state.tags.counts() }))
+}
+
+/// GET /origins/:id/diagnostics - Get why an origin is excluded from `/origins` (filtered
+/// out, no snapshot, no revisions, no timestamps, or metrics not computed yet), from what
+/// is already known about it. Nothing is computed.
+async fn get_origin_diagnostics<G>(
+    Path(id): Path<usize>,
+    Query(format): Query<DateFormat>,
+    State(state): State<Arc<RwLock<Graph<G>>>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    with_graph_read_blocking(state, move |graph| {
+        let exclusion = graph.diagnose(id)?;
+        let origin = graph.find_origin(id);
+        Ok(Json(json!({
+            "origin_id": id,
+            "loaded": origin.is_some(),
+            "eligible": exclusion.is_none(),
+            "listed": graph.is_listed(id),
+            "reason": exclusion.map(|exclusion| exclusion.as_str()),
+            "description": exclusion.map(|exclusion| exclusion.description()),
+            "latest_commit_date": origin
+                .and_then(|origin| origin.known_metric(Metric::LatestCommitDate))
+                .map(|date| format.render(date)),
+            "commit_count": origin.and_then(|origin| origin.known_metric(Metric::CommitCount)),
+        })))
+    })
+    .await
+}
+
+/// GET /origins/:id/cluster - Get the cluster of an origin, `null` if clustering didn't
+/// run or the origin has no revision
+async fn get_origin_cluster<G>(
+    Path(id): Path<usize>,
+    State(state): State<Arc<RwLock<Graph<G>>>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let graph = state.read().await;
+    let cluster = graph.origin(id)?.cluster();
+    Ok(Json(json!({
+        "origin_id": id,
+        "cluster": cluster
+    })))
+}
+
+/// GET /origins/:id/structure - Get node counts by type, arc count and max out-degree
+/// of the latest snapshot sub-graph of an origin, computed on first request
+async fn get_origin_structure<G>(
+    Path(id): Path<usize>,
+    State(state): State<Arc<RwLock<Graph<G>>>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    with_graph_read_blocking(state, move |graph| {
+        let origin = graph.origin(id)?;
+        let computed = !origin.has_structure();
+        let structure = origin.structure(ComputePolicy::ComputeIfMissing)?;
+        if computed {
+            // Persisted by the next write-back
+            graph.mark_origins_modified(1);
+        }
+        Ok(Json(json!({
+            "origin_id": id,
+            "structure": structure
+        })))
+    })
+    .await
+}
+
+/// GET /origins/:id/working-hours - Get the commits of the latest snapshot sub-graph of an
+/// origin by local hour of the day, and the timezones they were made from, computed on
+/// first request
+async fn get_origin_working_hours<G>(
+    Path(id): Path<usize>,
+    State(state): State<Arc<RwLock<Graph<G>>>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    with_graph_read_blocking(state, move |graph| {
+        let origin = graph.origin(id)?;
+        let computed = !origin.has_working_hours();
+        let working_hours = origin.working_hours(ComputePolicy::ComputeIfMissing)?;
+        if computed {
+            // Persisted by the next write-back
+            graph.mark_origins_modified(1);
+        }
+        Ok(Json(json!({
+            "origin_id": id,
+            "hours": working_hours.map(|hours| hours.hours),
+            "commits": working_hours.map(|hours| hours.commits()),
+            "timezones": working_hours.map(|hours| hours.timezones),
+            "min_offset_minutes": working_hours.and_then(|hours| hours.min_offset),
+            "max_offset_minutes": working_hours.and_then(|hours| hours.max_offset),
+            "spread_minutes": working_hours.map(|hours| hours.spread_minutes()),
+        })))
+    })
+    .await
+}
+
+/// GET /origins/:id/history - Get the number of root revisions (without parent revision)
+/// of the latest snapshot sub-graph of an origin, flagging disconnected histories. Known
+/// once the commit count is, computed on first request otherwise.
+async fn get_origin_history<G>(
+    Path(id): Path<usize>,
+    State(state): State<Arc<RwLock<Graph<G>>>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    with_graph_read_blocking(state, move |graph| {
+        let origin = graph.origin(id)?;
+        let computed = !origin.has_history();
+        let history = origin.history(ComputePolicy::ComputeIfMissing)?;
+        if computed {
+            // Persisted by the next write-back
+            graph.mark_origins_modified(1);
+        }
+        Ok(Json(json!({
+            "origin_id": id,
+            "root_revisions": history.map(|history| history.roots),
+            "multiple_roots": history.map(|history| history.has_multiple_roots()),
+        })))
+    })
+    .await
+}
+
+/// GET /origins/:id/parents - Get the average and largest number of parent revisions per
+/// revision of the latest snapshot sub-graph of an origin, telling merge-based workflows
+/// from rebase-based ones. Known once the commit count is, computed on first request
+/// otherwise.
+async fn get_origin_parents<G>(
+    Path(id): Path<usize>,
+    State(state): State<Arc<RwLock<Graph<G>>>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    with_graph_read_blocking(state, move |graph| {
+        let origin = graph.origin(id)?;
+        let computed = !origin.has_parents();
+        let parents = origin.parents(ComputePolicy::ComputeIfMissing)?;
+        if computed {
+            // Persisted by the next write-back
+            graph.mark_origins_modified(1);
+        }
+        Ok(Json(json!({
+            "origin_id": id,
+            "average_parents": parents.and_then(|parents| parents.average()),
+            "max_parents": parents.map(|parents| parents.max),
+        })))
+    })
+    .await
+}
+
+/// GET /origins/:id/burstiness - Get how bursty the weekly commit counts of the latest
+/// snapshot sub-graph of an origin are, to tell bulk-imported mirrors from organically
+/// developed repositories, computed on first request
+async fn get_origin_burstiness<G>(
+    Path(id): Path<usize>,
+    State(state): State<Arc<RwLock<Graph<G>>>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    with_graph_read_blocking(state, move |graph| {
+        let origin = graph.origin(id)?;
+        let computed = !origin.has_burstiness();
+        let burstiness = origin.burstiness(ComputePolicy::ComputeIfMissing)?;
+        if computed {
+            // Persisted by the next write-back
+            graph.mark_origins_modified(1);
+        }
+        Ok(Json(json!({
+            "origin_id": id,
+            "burstiness": burstiness.and_then(|burstiness| burstiness.score()),
+            "commits": burstiness.map(|burstiness| burstiness.commits),
+            "weeks": burstiness.map(|burstiness| burstiness.weeks),
+        })))
+    })
+    .await
+}
+
+/// GET /origins/:id/dedup - Get the share of the contents of the latest snapshot of an
+/// origin also found in other origins, a proxy for vendored or copied code. Only known if
+/// computed with `swh-test --dedup`, which needs the contents of the whole corpus.
+async fn get_origin_dedup<G>(
+    Path(id): Path<usize>,
+    State(state): State<Arc<RwLock<Graph<G>>>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let graph = state.read().await;
+    let dedup = graph.origin(id)?.dedup();
+    Ok(Json(json!({
+        "origin_id": id,
+        "dedup_ratio": dedup.and_then(|dedup| dedup.ratio()),
+        "contents": dedup.map(|dedup| dedup.contents),
+        "shared_contents": dedup.map(|dedup| dedup.shared),
+    })))
+}
+
+/// Query string of GET /origins/:id/metrics
+#[derive(Deserialize)]
+struct AsOf {
+    /// `YYYY-MM-DD`, RFC 3339 datetime or seconds since the epoch
+    as_of: String,
+}
+
+/// GET /origins/:id/metrics?as_of= - Get the latest commit date, commit count and committer
+/// count of an origin ignoring revisions committed after `as_of`, computed on each request
+async fn get_origin_metrics_as_of<G>(
+    Path(id): Path<usize>,
+    Query(as_of): Query<AsOf>,
+    Query(format): Query<DateFormat>,
+    State(state): State<Arc<RwLock<Graph<G>>>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let cutoff = parse_as_of(&as_of.as_of).map_err(|e| {
+        debug!("Rejected as_of: {}", e);
+        StatusCode::BAD_REQUEST
+    })?;
+    // Cancelled when this handler is dropped, i.e. if the client disconnects mid-walk
+    let cancel = CancellationToken::new();
+    let _cancel_on_disconnect = cancel.clone().drop_guard();
+    with_graph_read_blocking(state, move |graph| {
+        let metrics = graph.origin(id)?.metrics_as_of(cutoff, &cancel)?.ok_or(GraphError::Cancelled)?;
+        Ok(Json(json!({
+            "origin_id": id,
+            "as_of": format.render(cutoff),
+            "latest_commit_date": metrics.latest_commit_date.map(|date| format.render(date)),
+            "commit_count": metrics.commit_count,
+            "committer_count": metrics.committer_count
+        })))
+    })
+    .await
+}
+
+/// GET /clusters/:id/origins - Get the origins of a cluster, identified by its smallest
+/// origin id
+async fn get_cluster_origins<G>(
+    Path(id): Path<usize>,
+    State(state): State<Arc<RwLock<Graph<G>>>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    with_graph_read_blocking(state, move |graph| {
+        let ids: Vec<usize> = graph
+            .loaded_origins()
+            .par_iter()
+            .filter(|origin| origin.cluster() == Some(id))
+            .map(|origin| origin.id)
+            .collect();
+        if ids.is_empty() {
+            return Err(GraphError::ClusterNotFound(id).into());
+        }
+        Ok(Json(json!({
+            "cluster": id,
+            "origin_ids": ids,
+            "count": ids.len()
+        })))
+    })
+    .await
+}
+
+/// Query string of the endpoints returning dates
+#[derive(Deserialize)]
+struct DateFormat {
+    /// Return seconds since the epoch instead of ISO-8601 datetimes
+    #[serde(default)]
+    raw: bool,
+}
+
+impl DateFormat {
+    fn render(&self, timestamp: i64) -> Value {
+        if self.raw {
+            json!(timestamp)
+        } else {
+            json!(iso_datetime(timestamp))
+        }
+    }
+}
+
+/// ISO-8601 (RFC 3339) UTC datetime of a timestamp in seconds since the epoch, `None` if out of range
+fn iso_datetime(timestamp: i64) -> Option<String> {
+    DateTime::<Utc>::from_timestamp(timestamp, 0).map(|date| date.to_rfc3339())
+}
+
+/// GET /origins/:id/latest-commit-date - Get latest commit date for a specific origin
+async fn get_latest_commit_date<G>(
+    Path(id): Path<usize>,
+    Query(format): Query<DateFormat>,
+    State(state): State<AppState<G>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let latest_date = lazy_origin_metric(&state, id, Metric::LatestCommitDate).await?;
+    Ok(Json(json!({
+        "origin_id": id,
+        "latest_commit_date": latest_date.map(|date| format.render(date))
+    })))
+}
+
+/// GET /origins/:id/committer-count - Get committer count for a specific origin
+async fn get_committer_count<G>(
+    Path(id): Path<usize>,
+    State(state): State<AppState<G>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let committer_count = lazy_origin_metric(&state, id, Metric::CommitterCount).await?;
+    Ok(Json(json!({
+        "origin_id": id,
+        "committer_count": committer_count
+    })))
+}
+
+/// GET /origins/:id/commit-count - Get commit count for a specific origin
+async fn get_commit_count<G>(
+    Path(id): Path<usize>,
+    State(state): State<AppState<G>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let commit_count = lazy_origin_metric(&state, id, Metric::CommitCount).await?;
+    Ok(Json(json!({
+        "origin_id": id,
+        "commit_count": commit_count
+    })))
+}
+
+/// Compute `metric` for every origin in parallel, memoizing it where missing, and
+/// gather the defined values along with the origin ids, in origin id order.
+///
+/// Each rayon worker folds into its own `Vec` and the partial results are
+/// concatenated in order.
+/// Origins whose metrics got computed along the way are counted as unsaved so
+/// that the next write-back persists them. Remaining origins are skipped once `cancel`
+/// is cancelled, e.g. because the client went away.
+fn collect_origin_metric<G>(
+    graph: &Graph<G>,
+    what: &str,
+    cancel: &CancellationToken,
+    metric: Metric,
+) -> Result<Vec<(usize, i64)>, GraphError>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let origins = graph.loaded_origins();
+    let total_origins = origins.len();
+
+    // Create progress bar for processing all origins
+    let pb = StdArc::new(ProgressBar::new(total_origins as u64));
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("  {spinner:.cyan} [{elapsed_precise}] [{bar:30.cyan/blue}] {pos}/{len} origins ({percent}%) {msg}")
+            .unwrap()
+            .progress_chars("=>-")
+            .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"]),
+    );
+    pb.set_message(format!("Processing {}...", what));
+
+    let modified = AtomicUsize::new(0);
+    let completed = AtomicUsize::new(0);
+    let result: Result<Vec<_>, GraphError> = origins
+        .par_iter()
+        .enumerate()
+        .try_fold(Vec::new, |mut acc, (idx, origin)| {
+            if cancel.is_cancelled() {
+                return Ok(acc);
+            }
+            // Update progress every 100 items
+            if idx % 100 == 0 {
+                pb.set_position(idx as u64);
+            }
+
+            let missing_before = origin.missing_metrics();
+            let complete_before = origin.has_computed_metrics();
+            let value = skip_over_budget(origin.metric(metric, ComputePolicy::ComputeIfMissing), None);
+            if origin.missing_metrics() < missing_before {
+                modified.fetch_add(1, Ordering::Relaxed);
+            }
+            if !complete_before && origin.has_computed_metrics() {
+                completed.fetch_add(1, Ordering::Relaxed);
+            }
+            if let Some(value) = value? {
+                acc.push((origin.id(), value));
+            }
+            Ok(acc)
+        })
+        .try_reduce(Vec::new, |mut a, b| {
+            a.extend(b);
+            Ok(a)
+        });
+
+    // Metrics computed before a failure are kept all the same
+    graph.mark_origins_modified(modified.load(Ordering::Relaxed));
+    graph.mark_origins_completed(completed.load(Ordering::Relaxed));
+    let result = result.inspect_err(|_| pb.abandon_with_message(format!("Failed processing {}", what)))?;
+    if cancel.is_cancelled() {
+        pb.abandon_with_message(format!("Cancelled processing {}", what));
+        return Ok(result);
+    }
+    pb.set_position(total_origins as u64);
+    pb.finish_with_message(format!("✓ Completed processing {} {}", result.len(), what));
+    Ok(result)
+}
+
+/// Body of the bulk endpoints: `[{"id": 1, "value": 42}, ...]`, or with
+/// `--legacy-bulk-maps` `{"1": "42", ...}`
+fn bulk_response(values: Vec<(usize, i64)>, legacy: bool, render: impl Fn(i64) -> Value) -> Response {
+    if legacy {
+        let map: HashMap<String, String> = values
+            .into_iter()
+            .map(|(id, value)| {
+                let value = match render(value) {
+                    Value::String(value) => value,
+                    value => value.to_string(),
+                };
+                (id.to_string(), value)
+            })
+            .collect();
+        Json(map).into_response()
+    } else {
+        let entries: Vec<Value> = values
+            .into_iter()
+            .map(|(id, value)| json!({ "id": id, "value": render(value) }))
+            .collect();
+        Json(entries).into_response()
+    }
+}
+
+/// GET /origins/latest-commit-dates - Get latest commit dates for all origins
+async fn get_all_latest_commit_dates<G>(
+    Query(format): Query<DateFormat>,
+    State(state): State<AppState<G>>
+) -> Result<Response, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    info!("Fetching latest commit dates for all origins");
+    
+    // Cancelled when this handler is dropped, i.e. if the client disconnects mid-scan
+    let cancel = CancellationToken::new();
+    let _cancel_on_disconnect = cancel.clone().drop_guard();
+    let legacy = state.legacy_bulk_maps;
+    with_graph_read_blocking(state.graph, move |graph| {
+        let result = collect_origin_metric(graph, "latest commit dates", &cancel, Metric::LatestCommitDate)?;
+        info!("Successfully retrieved latest commit dates for {} out of {} origins", result.len(), graph.loaded_origins().len());
+        Ok(bulk_response(result, legacy, |date| format.render(date)))
+    })
+    .await
+}
+
+/// GET /origins/commit-counts - Get commit counts for all origins
+async fn get_all_commit_counts<G>(
+    State(state): State<AppState<G>>
+) -> Result<Response, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    info!("Fetching commit counts for all origins");
+    
+    // Cancelled when this handler is dropped, i.e. if the client disconnects mid-scan
+    let cancel = CancellationToken::new();
+    let _cancel_on_disconnect = cancel.clone().drop_guard();
+    let legacy = state.legacy_bulk_maps;
+    with_graph_read_blocking(state.graph, move |graph| {
+        let result = collect_origin_metric(graph, "commit counts", &cancel, Metric::CommitCount)?;
+        info!("Successfully retrieved commit counts for {} out of {} origins", result.len(), graph.loaded_origins().len());
+        Ok(bulk_response(result, legacy, |count| json!(count)))
+    })
+    .await
+}
+
+/// GET /origins/committer-counts - Get committer counts for all origins
+async fn get_all_committer_counts<G>(
+    State(state): State<AppState<G>>
+) -> Result<Response, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    info!("Fetching committer counts for all origins");
+    
+    // Cancelled when this handler is dropped, i.e. if the client disconnects mid-scan
+    let cancel = CancellationToken::new();
+    let _cancel_on_disconnect = cancel.clone().drop_guard();
+    let legacy = state.legacy_bulk_maps;
+    with_graph_read_blocking(state.graph, move |graph| {
+        let result = collect_origin_metric(graph, "committer counts", &cancel, Metric::CommitterCount)?;
+        info!("Successfully retrieved committer counts for {} out of {} origins", result.len(), graph.loaded_origins().len());
+        Ok(bulk_response(result, legacy, |count| json!(count)))
+    })
+    .await
+}
+
+/// Get a metric of an origin, having the compute workers fill it in first if it is not
+/// known yet. No lock is held while the metric is computed.
+async fn lazy_origin_metric<G>(
+    state: &AppState<G>,
+    id: usize,
+    metric: Metric,
+) -> Result<Option<i64>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let (computed, cached) = read_origin_metric(&state.graph, id, metric).await?;
+    if computed {
+        return Ok(cached);
+    }
+    // Answers 503 if the origin has no graph attached, 500 if its computation failed
+    state.workers.compute(id).await?;
+    Ok(read_origin_metric(&state.graph, id, metric).await?.1)
+}
+
+/// Read `metric` of an origin without computing it, along with whether its metrics have
+/// been computed at all
+async fn read_origin_metric<G>(
+    graph: &RwLock<Graph<G>>,
+    id: usize,
+    metric: Metric,
+) -> Result<(bool, Option<i64>), StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let graph = graph.read().await;
+    let origin = graph.origin(id)?;
+    Ok((origin.has_computed_metrics(), origin.known_metric(metric)))
+}
+
+/// Routes walking arcs backward, only served for bidirectional graphs
+fn backward_routes<G>() -> Router<AppState<G>>
+where
+    G: BackwardCapable + Send + Sync + 'static,
+{
+    let in_degree: fn(&G, NodeId) -> usize = |graph, node| graph.predecessors(node).count();
+    Router::new()
+        .route("/revisions/:id/origins", get(get_revision_origins::<G>))
+        .route("/contents/:id/origins", get(get_content_origins::<G>))
+        .route("/stats/graph", get(move |State(state)| get_graph_stats(state, Some(in_degree))))
+}
+
+/// Routes served for unidirectional graphs instead of [`backward_routes`]
+fn stats_routes<G>() -> Router<AppState<G>>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    Router::new().route("/stats/graph", get(|State(state)| get_graph_stats::<G>(state, None)))
+}
+
+/// Routes reading branch names, only served with `--properties all`
+fn label_routes<G>() -> Router<AppState<G>>
+where
+    G: LabelCapable + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/snapshots/diff", get(get_snapshot_diff::<G>))
+        .route("/origins/:id/default-branch-commit-count", get(get_default_branch_commit_count::<G>))
+        .route("/origins/:id/has-path", get(get_origin_has_path::<G>))
+}
+
+/// Routes looking contents up by hash, served without the contents property family
+/// instead of [`content_property_routes`]
+fn content_routes<G>() -> Router<AppState<G>>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    Router::new().route(
+        "/contents/by-sha1/:hash",
+        get(|Path(hash): Path<String>, State(state)| get_content_by_sha1::<G>(hash, state, None)),
+    )
+}
+
+/// Routes looking contents up by hash with their properties, only served with
+/// `--properties all`
+fn content_property_routes<G>() -> Router<AppState<G>>
+where
+    G: ContentCapable + Send + Sync + 'static,
+{
+    let properties: fn(&G, NodeId) -> Value = |graph, node| {
+        json!({
+            "length": graph.content_length(node),
+            "is_skipped": graph.is_skipped_content(node),
+        })
+    };
+    Router::new().route(
+        "/contents/by-sha1/:hash",
+        get(move |Path(hash): Path<String>, State(state)| get_content_by_sha1(hash, state, Some(properties))),
+    )
+}
+
+/// Routes comparing the served origins with those of another export (`--compare-cache`)
+fn dataset_routes<G>(other: Vec<OriginData>) -> Router<AppState<G>>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let other = Arc::new(other);
+    Router::new().route(
+        "/datasets/diff",
+        get(move |Query(limit): Query<OriginsLimit>, State(state): State<Arc<RwLock<Graph<G>>>>| {
+            get_dataset_diff(state, other.clone(), limit)
+        }),
+    )
+}
+
+/// Routes freezing the origin table and serving the frozen datasets, see
+/// [`FrozenDatasets`]. Only freezing needs the origins to be loaded; none of them are
+/// cached, as datasets are added at any time.
+fn frozen_routes<G>(frozen: Arc<FrozenDatasets>, load_progress: Arc<LoadProgress>) -> Router<AppState<G>>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let (listed, described, downloaded) = (frozen.clone(), frozen.clone(), frozen.clone());
+    Router::new()
+        .route(
+            "/datasets/freeze",
+            post(move |State(state): State<Arc<RwLock<Graph<G>>>>, request: Option<Json<FreezeRequest>>| {
+                post_freeze(state, frozen.clone(), request.map(|Json(request)| request).unwrap_or_default())
+            }),
+        )
+        .route_layer(middleware::from_fn_with_state(load_progress, require_ready))
+        .route("/datasets/frozen", get(move || get_frozen_datasets(listed.clone())))
+        .route("/datasets/frozen/:name", get(move |Path(name): Path<String>| get_frozen_dataset(described.clone(), name)))
+        .route(
+            "/datasets/frozen/:name/:file",
+            get(move |Path((name, file)): Path<(String, String)>| download_frozen_file(downloaded.clone(), name, file)),
+        )
+}
+
+/// Body of POST /datasets/freeze
+#[derive(Deserialize, Default)]
+struct FreezeRequest {
+    /// Name of the dataset, the current time by default
+    name: Option<String>,
+}
+
+/// POST /datasets/freeze - Freeze the origin table and the eligible origins, with a
+/// manifest of the graph export, code version, compute options and file checksums.
+/// Answers the manifest, or `409` if the name is taken.
+async fn post_freeze<G>(
+    state: Arc<RwLock<Graph<G>>>,
+    frozen: Arc<FrozenDatasets>,
+    request: FreezeRequest,
+) -> Response
+where
+    G: GraphBackend + Send + Sync + 'static,
 {
-    let mut graph = state.write().await;
-    
-    match graph.get_origins_mut() {
-        Ok(origins) => {
-            if let Some(origin) = origins.iter_mut().find(|o| o.id() == id) {
-                let url = origin.get_url();
-                Ok(Json(json!({
-                    "origin_id": id,
-                    "url": url
-                })))
-            } else {
-                error!("Origin with id {} not found", id);
-                Err(StatusCode::NOT_FOUND)
-            }
+    if let Some(Err(error)) = request.name.as_deref().map(FrozenDatasets::validate_name) {
+        return (StatusCode::BAD_REQUEST, Json(json!({ "error": error }))).into_response();
+    }
+    let frozen = with_graph_read_blocking(state, move |graph| {
+        frozen.freeze(graph, request.name.as_deref()).map_err(|e| match e {
+            GraphError::Io(e) if e.kind() == std::io::ErrorKind::AlreadyExists => StatusCode::CONFLICT,
+            e => e.into(),
+        })
+    })
+    .await;
+    match frozen {
+        Ok(manifest) => {
+            info!("Froze {} origins as dataset {}", manifest.origin_count, manifest.name);
+            (StatusCode::CREATED, Json(manifest)).into_response()
         }
-        Err(e) => {
-            error!("Failed to get origins: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        Err(status) => status.into_response(),
+    }
+}
+
+/// GET /datasets/frozen - Get the manifests of the frozen datasets, oldest first
+async fn get_frozen_datasets(frozen: Arc<FrozenDatasets>) -> Result<Json<Value>, StatusCode> {
+    let manifests = tokio::task::spawn_blocking(move || frozen.list())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+    Ok(Json(json!({ "datasets": manifests })))
+}
+
+/// GET /datasets/frozen/:name - Get the manifest of a frozen dataset
+async fn get_frozen_dataset(frozen: Arc<FrozenDatasets>, name: String) -> Result<Json<Value>, StatusCode> {
+    let manifest = tokio::task::spawn_blocking(move || frozen.manifest(&name))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+    Ok(Json(json!(manifest.ok_or(StatusCode::NOT_FOUND)?)))
+}
+
+/// GET /datasets/frozen/:name/:file - Download a file of a frozen dataset (one listed in
+/// its manifest, or `manifest.json`), streamed from disk
+async fn download_frozen_file(frozen: Arc<FrozenDatasets>, name: String, file: String) -> Result<Response, StatusCode> {
+    let (dataset, file_name) = (name.clone(), file.clone());
+    let path = tokio::task::spawn_blocking(move || frozen.file_path(&dataset, &file_name))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let opened = tokio::fs::File::open(&path).await.map_err(GraphError::from)?;
+    let size = opened.metadata().await.map_err(GraphError::from)?.len();
+    let content_type = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => "application/json",
+        Some("jsonl") => "application/x-ndjson",
+        Some("txt") => "text/plain",
+        _ => "application/octet-stream",
+    };
+    let disposition = format!("attachment; filename=\"{}-{}\"", name, file);
+    Ok((
+        [
+            (CONTENT_TYPE, content_type.to_string()),
+            (CONTENT_LENGTH, size.to_string()),
+            (CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(tokio_util::io::ReaderStream::new(opened)),
+    )
+        .into_response())
+}
+
+/// Routes answered from the revision to origins multimap, used instead of
+/// [`backward_routes`] for unidirectional graphs
+fn multimap_routes<S>(multimap: RevisionOrigins) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/revisions/:id/origins", get(get_multimap_revision_origins))
+        .with_state(Arc::new(multimap))
+}
+
+/// Routes answered from the commit totals of each person built by `swh-test
+/// --person-commits`
+fn person_routes<S>(totals: PersonTotals) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/persons/top", get(get_top_persons))
+        .with_state(Arc::new(totals))
+}
+
+/// Persons returned by GET /persons/top as JSON without `limit=`
+const DEFAULT_TOP_PERSONS: usize = 100;
+const MAX_TOP_PERSONS: usize = 100_000;
+
+/// Output of GET /persons/top
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum TopFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+/// Query string of GET /persons/top
+#[derive(Deserialize)]
+struct TopPersons {
+    limit: Option<usize>,
+    #[serde(default)]
+    format: TopFormat,
+}
+
+/// GET /persons/top?limit=N&format=json|csv - Get the persons with the most commits
+/// across all origins, with their number of origins. As CSV, every person is returned
+/// unless `limit` is given.
+async fn get_top_persons(
+    Query(query): Query<TopPersons>,
+    State(totals): State<Arc<PersonTotals>>,
+) -> Response {
+    match query.format {
+        TopFormat::Json => {
+            let top = totals.top(query.limit.unwrap_or(DEFAULT_TOP_PERSONS).min(MAX_TOP_PERSONS));
+            let persons: Vec<Value> = top
+                .iter()
+                .map(|total| json!({ "person_id": total.person, "commits": total.commits, "origins": total.origins }))
+                .collect();
+            Json(json!({
+                "persons": persons,
+                "count": persons.len(),
+                "total_persons": totals.len(),
+            }))
+            .into_response()
+        }
+        TopFormat::Csv => {
+            let limit = query.limit.unwrap_or(usize::MAX);
+            let csv = tokio::task::spawn_blocking(move || {
+                let mut csv = Vec::new();
+                totals.write_csv(&mut csv, limit).map(|_| csv)
+            })
+            .await;
+            match csv {
+                Ok(Ok(csv)) => ([(CONTENT_TYPE, "text/csv")], csv).into_response(),
+                _ => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            }
         }
     }
 }
 
-/// GET /origins/:id/latest-commit-date - Get latest commit date for a specific origin
-async fn get_latest_commit_date<G>(
+/// GET /revisions/:id/origins - Get origins whose latest snapshot contains a revision,
+/// from the multimap
+async fn get_multimap_revision_origins(
     Path(id): Path<usize>,
+    Query(limit): Query<OriginsLimit>,
+    State(multimap): State<Arc<RevisionOrigins>>,
+) -> Json<Value> {
+    let origins = multimap.origins_of(id);
+    let shown = &origins[..origins.len().min(limit.get())];
+    Json(json!({
+        "revision_id": id,
+        "origin_ids": shown,
+        "count": shown.len(),
+        "truncated": shown.len() < origins.len(),
+    }))
+}
+
+/// Default and maximum `limit` of the endpoints listing origins through backward walks
+const DEFAULT_ORIGINS_LIMIT: usize = 1000;
+const MAX_ORIGINS_LIMIT: usize = 100_000;
+
+/// Query string of the endpoints listing origins or revisions
+#[derive(Deserialize)]
+struct OriginsLimit {
+    limit: Option<usize>,
+}
+
+impl OriginsLimit {
+    fn get(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_ORIGINS_LIMIT).min(MAX_ORIGINS_LIMIT)
+    }
+}
+
+/// GET /datasets/diff - Get the origins added, disappeared and changed since the export
+/// of `--compare-cache`, matched by URL
+async fn get_dataset_diff<G>(
+    state: Arc<RwLock<Graph<G>>>,
+    other: Arc<Vec<OriginData>>,
+    limit: OriginsLimit,
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    with_graph_read_blocking(state, move |graph| {
+        let served: Vec<OriginData> = graph.par_origins_iter().map(|origin| origin.to_data()).collect();
+        Ok(Json(diff_origins(&other, &served).to_json(limit.get())))
+    })
+    .await
+}
+
+/// Forges returned by GET /stats/facets without `limit=`
+const DEFAULT_FACETS: usize = 100;
+
+/// Grouping of the origins of GET /stats/facets
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+enum FacetBy {
+    /// Host of the origin URL
+    #[default]
+    Forge,
+}
+
+/// Query string of GET /stats/facets
+#[derive(Deserialize)]
+struct FacetsQuery {
+    #[serde(default)]
+    by: FacetBy,
+    metric: Metric,
+    limit: Option<usize>,
+}
+
+/// GET /origins/duplicates?limit=N - Get the loaded origins whose URLs only differ by
+/// scheme, user, default port, host case, trailing `/` or `.git` (see [`canonical_url`]),
+/// e.g. mirrors counted several times otherwise, the largest groups first, from
+/// [`Graph::url_duplicates`]
+async fn get_origin_duplicates<G>(
+    Query(limit): Query<OriginsLimit>,
     State(state): State<Arc<RwLock<Graph<G>>>>
 ) -> Result<Json<Value>, StatusCode>
 where
-    G: SwhLabeledForwardGraph 
-    + SwhGraphWithProperties<
-        Maps: properties::Maps,
-        Timestamps: properties::Timestamps,
-        Persons: properties::Persons,
-        Contents: properties::Contents,
-        Strings: properties::Strings,
-        LabelNames: properties::LabelNames,
-    > + Send + Sync + 'static,
+    G: GraphBackend + Send + Sync + 'static,
 {
-    let mut graph = state.write().await;
-    
-    match graph.get_origins_mut() {
-        Ok(origins) => {
-            if let Some(origin) = origins.iter_mut().find(|o| o.id() == id) {
-                let latest_date = origin.get_latest_commit_date();
-                Ok(Json(json!({
-                    "origin_id": id,
-                    "latest_commit_date": latest_date
-                })))
-            } else {
-                error!("Origin with id {} not found", id);
-                Err(StatusCode::NOT_FOUND)
-            }
-        }
-        Err(e) => {
-            error!("Failed to get origins: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+    with_graph_read_blocking(state, move |graph| {
+        let duplicates = graph.url_duplicates()?;
+        let shown = &duplicates.groups[..duplicates.groups.len().min(limit.get())];
+        let groups: Vec<Value> = shown
+            .iter()
+            .map(|(canonical, ids)| {
+                let origins: Vec<Value> = ids
+                    .iter()
+                    .map(|&id| {
+                        let url = graph.with_origin(id, |origin| origin.get_url()).transpose()?.flatten();
+                        Ok(json!({ "origin_id": id, "url": url }))
+                    })
+                    .collect::<Result<_, GraphError>>()?;
+                Ok(json!({ "canonical_url": canonical, "origins": origins }))
+            })
+            .collect::<Result<_, GraphError>>()?;
+        Ok(Json(json!({
+            "duplicates": groups,
+            "group_count": duplicates.groups.len(),
+            "origin_count": duplicates.origin_count(),
+            "truncated": shown.len() < duplicates.groups.len(),
+        })))
+    })
+    .await
+}
+
+/// GET /stats/facets?by=forge&metric=commit_count&limit=N - Get the number of origins of
+/// each forge (at most `limit`, those with the most origins) by bucket of a metric: year
+/// of the latest commit date, power-of-two buckets of counts. Counted from the known
+/// metrics and kept until they change, see [`Graph::forge_facets`].
+async fn get_facets<G>(
+    Query(query): Query<FacetsQuery>,
+    State(state): State<Arc<RwLock<Graph<G>>>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let metric = query.metric;
+    let facets = with_graph_read_blocking(state, move |graph| Ok(graph.forge_facets(metric)?)).await?;
+    let shown = &facets.forges[..facets.forges.len().min(query.limit.unwrap_or(DEFAULT_FACETS))];
+    let forges: Vec<Value> = shown
+        .iter()
+        .map(|facet| {
+            let buckets: Vec<Value> = facet
+                .buckets
+                .iter()
+                .map(|(bucket, count)| json!({ "bucket": bucket, "count": count }))
+                .collect();
+            json!({ "forge": facet.forge, "total": facet.total, "buckets": buckets })
+        })
+        .collect();
+    Ok(Json(json!({
+        "by": query.by,
+        "metric": facets.metric,
+        "facets": forges,
+        "forge_count": facets.forges.len(),
+        "truncated": shown.len() < facets.forges.len(),
+    })))
+}
+
+/// Quantiles returned by GET /stats/quantiles without `q=`
+const DEFAULT_QUANTILES: &str = "0.5,0.9,0.99";
+
+/// Query string of GET /stats/quantiles
+#[derive(Deserialize)]
+struct QuantilesQuery {
+    metric: Metric,
+    /// Comma separated, see [`parse_quantiles`]
+    q: Option<String>,
+}
+
+/// GET /stats/quantiles?metric=committer_count&q=0.5,0.9,0.99 - Get quantiles of a metric
+/// over the origins it is known of, estimated from the t-digests kept by the graph (see
+/// [`Graph::metric_digests`]) rather than from the full column
+async fn get_metric_quantiles<G>(
+    Query(query): Query<QuantilesQuery>,
+    Query(format): Query<DateFormat>,
+    State(state): State<Arc<RwLock<Graph<G>>>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let quantiles = parse_quantiles(query.q.as_deref().unwrap_or(DEFAULT_QUANTILES)).map_err(|e| {
+        debug!("Rejected q: {}", e);
+        StatusCode::BAD_REQUEST
+    })?;
+    let metric = query.metric;
+    let digests = with_graph_read_blocking(state, |graph| Ok(graph.metric_digests())).await?;
+    let values: Vec<Value> = quantiles
+        .iter()
+        .map(|&q| {
+            let value = digests.quantile(metric, q).map(|value| match metric {
+                Metric::LatestCommitDate => format.render(value.round() as i64),
+                _ => json!(value),
+            });
+            json!({ "q": q, "value": value })
+        })
+        .collect();
+    Ok(Json(json!({
+        "metric": metric,
+        "count": digests.count(metric),
+        "quantiles": values,
+    })))
+}
+
+/// GET /contents/by-sha1/:hash - Get the node id and SWHID of the content with this
+/// hash, found through the minimal perfect hash of the graph, with its `properties`
+/// (length, whether it was skipped) if the contents property family is loaded. The hash
+/// is the `sha1_git` of the content SWHIDs (what `git hash-object FILE` prints): the
+/// compressed graph doesn't index contents by the plain SHA-1 of the file. Answers 404
+/// for contents which are not in the graph.
+async fn get_content_by_sha1<G>(
+    hash: String,
+    state: Arc<RwLock<Graph<G>>>,
+    properties: Option<fn(&G, NodeId) -> Value>,
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let swhid: SWHID = format!("swh:1:cnt:{}", hash.to_ascii_lowercase()).parse().map_err(|e| {
+        debug!("Rejected content hash {:?}: {}", hash, e);
+        StatusCode::BAD_REQUEST
+    })?;
+    with_graph_read_blocking(state, move |graph| {
+        let inner = graph.inner_graph();
+        let id = inner.node_id(&swhid).ok_or(StatusCode::NOT_FOUND)?;
+        let mut content = json!({
+            "node_id": id,
+            "swhid": swhid,
+        });
+        if let Some(properties) = properties {
+            content["properties"] = properties(&inner, id);
         }
-    }
+        Ok(Json(content))
+    })
+    .await
 }
 
-/// GET /origins/:id/committer-count - Get committer count for a specific origin
-async fn get_committer_count<G>(
+/// GET /stats/graph - Get the node counts by type, arc counts by type pair and degree
+/// distributions of the whole graph. The first request scans every node, later ones read
+/// the statistics saved in the cache directory. In-degrees need the transposed graph.
+async fn get_graph_stats<G>(
+    state: Arc<RwLock<Graph<G>>>,
+    in_degree: Option<fn(&G, NodeId) -> usize>,
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    // Don't hold the graph lock during the scan, only the inner graph is needed
+    let (inner, path) = {
+        let graph = state.read().await;
+        (graph.inner_graph(), graph.cache_path(GRAPH_STATS_FILE))
+    };
+    // Cancelled when this handler is dropped, i.e. if the client disconnects mid-scan
+    let cancel = CancellationToken::new();
+    let _cancel_on_disconnect = cancel.clone().drop_guard();
+    let stats = tokio::task::spawn_blocking(move || {
+        let graph = inner.as_ref();
+        let in_degree = in_degree.map(|in_degree| move |node| in_degree(graph, node));
+        GraphStats::load_or_compute(graph, &path, in_degree, &Progress::new(), &cancel)
+    })
+    .await
+    .map_err(|e| {
+        error!("Blocking graph task failed: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })??;
+    Ok(Json(json!(stats)))
+}
+
+/// Query string of GET /snapshots/diff
+#[derive(Deserialize)]
+struct SnapshotPair {
+    a: usize,
+    b: usize,
+}
+
+/// GET /snapshots/diff?a=&b= - Get the branches added, removed and changed from snapshot
+/// `a` to snapshot `b`, and the revisions `b` reaches but not `a`
+async fn get_snapshot_diff<G>(
+    Query(pair): Query<SnapshotPair>,
+    Query(limit): Query<OriginsLimit>,
+    State(state): State<Arc<RwLock<Graph<G>>>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: LabelCapable + Send + Sync + 'static,
+{
+    let branch = |branch: &Branch| json!({ "name": branch.name_lossy(), "target": branch.target });
+    // Cancelled when this handler is dropped, i.e. if the client disconnects mid-walk
+    let cancel = CancellationToken::new();
+    let _cancel_on_disconnect = cancel.clone().drop_guard();
+    with_graph_read_blocking(state, move |graph| {
+        let diff = graph.diff_snapshots(pair.a, pair.b, &cancel)?;
+        let shown = &diff.new_revisions[..diff.new_revisions.len().min(limit.get())];
+        Ok(Json(json!({
+            "a": pair.a,
+            "b": pair.b,
+            "added": diff.added.iter().map(branch).collect::<Vec<_>>(),
+            "removed": diff.removed.iter().map(branch).collect::<Vec<_>>(),
+            "changed": diff.changed.iter().map(|(name, from, to)| json!({
+                "name": String::from_utf8_lossy(name),
+                "from": from,
+                "to": to,
+            })).collect::<Vec<_>>(),
+            "new_revisions": shown,
+            "new_revision_count": diff.new_revisions.len(),
+            "truncated": shown.len() < diff.new_revisions.len(),
+        })))
+    })
+    .await
+}
+
+/// GET /origins/:id/default-branch-commit-count - Get the number of commits reachable from
+/// the `HEAD` branch of the latest snapshot of an origin, computed on first request
+async fn get_default_branch_commit_count<G>(
     Path(id): Path<usize>,
     State(state): State<Arc<RwLock<Graph<G>>>>
 ) -> Result<Json<Value>, StatusCode>
 where
-    G: SwhLabeledForwardGraph 
-    + SwhGraphWithProperties<
-        Maps: properties::Maps,
-        Timestamps: properties::Timestamps,
-        Persons: properties::Persons,
-        Contents: properties::Contents,
-        Strings: properties::Strings,
-        LabelNames: properties::LabelNames,
-    > + Send + Sync + 'static,
+    G: LabelCapable + Send + Sync + 'static,
 {
-    let mut graph = state.write().await;
-    
-    match graph.get_origins_mut() {
-        Ok(origins) => {
-            if let Some(origin) = origins.iter_mut().find(|o| o.id() == id) {
-                let committer_count = origin.total_commiter_latest_snp();
-                Ok(Json(json!({
-                    "origin_id": id,
-                    "committer_count": committer_count
-                })))
-            } else {
-                error!("Origin with id {} not found", id);
-                Err(StatusCode::NOT_FOUND)
-            }
-        }
-        Err(e) => {
-            error!("Failed to get origins: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+    with_graph_read_blocking(state, move |graph| {
+        let origin = graph.origin(id)?;
+        let computed = !origin.has_default_branch_commits();
+        let commits = origin.default_branch_commits(ComputePolicy::ComputeIfMissing)?;
+        if computed {
+            // Persisted by the next write-back
+            graph.mark_origins_modified(1);
         }
-    }
+        Ok(Json(json!({
+            "origin_id": id,
+            "number_of_commits_default_branch": commits
+        })))
+    })
+    .await
 }
 
-/// GET /origins/:id/commit-count - Get commit count for a specific origin
-async fn get_commit_count<G>(
+/// Query string of GET /origins/:id/has-path
+#[derive(Deserialize)]
+struct HasPathQuery {
+    path: String,
+}
+
+/// GET /origins/:id/has-path?path=Cargo.toml - Get whether a file or directory exists at
+/// a path of the tree of the head revision of an origin, with its SWHID, e.g. to find
+/// the origins of an ecosystem through their manifest. The head is computed on first
+/// request if it is not known yet.
+async fn get_origin_has_path<G>(
     Path(id): Path<usize>,
+    Query(query): Query<HasPathQuery>,
     State(state): State<Arc<RwLock<Graph<G>>>>
 ) -> Result<Json<Value>, StatusCode>
 where
-    G: SwhLabeledForwardGraph 
-    + SwhGraphWithProperties<
-        Maps: properties::Maps,
-        Timestamps: properties::Timestamps,
-        Persons: properties::Persons,
-        Contents: properties::Contents,
-        Strings: properties::Strings,
-        LabelNames: properties::LabelNames,
-    > + Send + Sync + 'static,
+    G: LabelCapable + Send + Sync + 'static,
 {
-    let mut graph = state.write().await;
-    
-    match graph.get_origins_mut() {
-        Ok(origins) => {
-            if let Some(origin) = origins.iter_mut().find(|o| o.id() == id) {
-                let commit_count = origin.total_commit_latest_snp();
-                Ok(Json(json!({
-                    "origin_id": id,
-                    "commit_count": commit_count
-                })))
-            } else {
-                error!("Origin with id {} not found", id);
-                Err(StatusCode::NOT_FOUND)
-            }
-        }
-        Err(e) => {
-            error!("Failed to get origins: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+    let path = query.path.trim_matches('/').to_string();
+    if path.is_empty() || path.split('/').any(|name| name.is_empty() || name == "." || name == "..") {
+        debug!("Rejected path {:?}", query.path);
+        return Err(StatusCode::BAD_REQUEST);
     }
+    with_graph_read_blocking(state, move |graph| {
+        let origin = graph.origin(id)?;
+        let computed = !origin.has_head();
+        let head = origin.head(ComputePolicy::ComputeIfMissing)?;
+        if computed {
+            // Persisted by the next write-back
+            graph.mark_origins_modified(1);
+        }
+        let backend = origin.get_graph()?;
+        let entry = origin.head_path(&path, ComputePolicy::CachedOnly)?;
+        Ok(Json(json!({
+            "origin_id": id,
+            "path": path,
+            "head_revision": head.and_then(|head| head.revision).map(|rev| backend.swhid(rev).to_string()),
+            "exists": entry.is_some(),
+            "swhid": entry.as_ref().map(|entry| backend.swhid(entry.target).to_string()),
+        })))
+    })
+    .await
 }
 
-/// GET /origins/latest-commit-dates - Get latest commit dates for all origins
-async fn get_all_latest_commit_dates<G>(
+/// GET /revisions/:id/origins - Get origins which archived a revision in any of their visits
+async fn get_revision_origins<G>(
+    Path(id): Path<usize>,
+    Query(limit): Query<OriginsLimit>,
     State(state): State<Arc<RwLock<Graph<G>>>>
-) -> Result<Json<HashMap<String, String>>, StatusCode>
-where
-    G: SwhLabeledForwardGraph 
-    + SwhGraphWithProperties<
-        Maps: properties::Maps,
-        Timestamps: properties::Timestamps,
-        Persons: properties::Persons,
-        Contents: properties::Contents,
-        Strings: properties::Strings,
-        LabelNames: properties::LabelNames,
-    > + Send + Sync + 'static,
+) -> Result<Json<Value>, StatusCode>
+where
+    G: BackwardCapable + Send + Sync + 'static,
 {
-    info!("Fetching latest commit dates for all origins");
-    
-    let mut graph = state.write().await;
-    
-    match graph.get_origins_mut() {
-        Ok(origins) => {
-            let total_origins = origins.len();
-            
-            // Create progress bar for processing all origins
-            let pb = StdArc::new(ProgressBar::new(total_origins as u64));
-            pb.set_style(
-                ProgressStyle::default_bar()
-                    .template("  {spinner:.cyan} [{elapsed_precise}] [{bar:30.cyan/blue}] {pos}/{len} origins ({percent}%) {msg}")
-                    .unwrap()
-                    .progress_chars("=>-")
-                    .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"]),
-            );
-            pb.set_message("Processing latest commit dates...");
-            
-            let mut result: HashMap<String, String> = HashMap::new();
-            
-            // Use parallel processing with rayon
-            let parallel_results: Vec<(String, String)> = origins
-                .par_iter_mut()
-                .enumerate()
-                .filter_map(|(idx, origin)| {
-                    // Update progress every 100 items
-                    if idx % 100 == 0 {
-                        pb.set_position(idx as u64);
-                    }
-                    
-                    if let Some(latest_commit_date) = origin.get_latest_commit_date() {
-                        Some((origin.id().to_string(), latest_commit_date.to_string()))
-                    } else {
-                        None
-                    }
-                })
-                .collect();
-            
-            // Convert results to HashMap
-            for (id, date) in parallel_results {
-                result.insert(id, date);
-            }
-            
-            pb.set_position(total_origins as u64);
-            
-            pb.finish_with_message(format!("✓ Completed processing {} latest commit dates", result.len()));
-            
-            info!("Successfully retrieved latest commit dates for {} out of {} origins", result.len(), total_origins);
-            Ok(Json(result))
-        }
+    // Cancelled when this handler is dropped, i.e. if the client disconnects mid-walk
+    let cancel = CancellationToken::new();
+    let _cancel_on_disconnect = cancel.clone().drop_guard();
+    with_graph_read_blocking(state, move |graph| {
+        let (origins, complete) = graph.origins_reaching(id, NodeType::Revision, Reach::AnyVisit, limit.get(), &cancel)?;
+        Ok(Json(json!({
+            "revision_id": id,
+            "origin_ids": origins,
+            "count": origins.len(),
+            "truncated": !complete,
+        })))
+    })
+    .await
+}
+
+/// GET /contents/:id/origins - Get origins whose latest snapshot contains a file content
+async fn get_content_origins<G>(
+    Path(id): Path<usize>,
+    Query(limit): Query<OriginsLimit>,
+    State(state): State<Arc<RwLock<Graph<G>>>>
+) -> Result<Json<Value>, StatusCode>
+where
+    G: BackwardCapable + Send + Sync + 'static,
+{
+    // Cancelled when this handler is dropped, i.e. if the client disconnects mid-walk
+    let cancel = CancellationToken::new();
+    let _cancel_on_disconnect = cancel.clone().drop_guard();
+    with_graph_read_blocking(state, move |graph| {
+        let (origins, complete) = graph.origins_reaching(id, NodeType::Content, Reach::LatestSnapshot, limit.get(), &cancel)?;
+        Ok(Json(json!({
+            "content_id": id,
+            "origin_ids": origins,
+            "count": origins.len(),
+            "truncated": !complete,
+        })))
+    })
+    .await
+}
+
+/// Body of `POST /query`
+#[derive(Deserialize)]
+struct QueryRequest {
+    sql: String,
+}
+
+/// POST /query - Run an SQL query over the origin metrics in memory, see [`SqlQuery`].
+/// Answers `400` with the error if the query is not supported.
+#[cfg(not(feature = "sql"))]
+async fn post_query<G>(
+    State(state): State<Arc<RwLock<Graph<G>>>>,
+    Json(request): Json<QueryRequest>,
+) -> Response
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let query: SqlQuery = match request.sql.parse() {
+        Ok(query) => query,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": e}))).into_response(),
+    };
+    // Cancelled when this handler is dropped, i.e. if the client disconnects mid-scan
+    let cancel = CancellationToken::new();
+    let _cancel_on_disconnect = cancel.clone().drop_guard();
+    with_graph_read_blocking(state, move |graph| {
+        let result = query.run(graph.loaded_origins(), &cancel).ok_or(GraphError::Cancelled)?;
+        Ok(Json(result))
+    })
+    .await
+    .into_response()
+}
+
+/// POST /query - Run an SQL query over the origin metrics in memory with DataFusion, see
+/// [`crate::sql::run`]. Answers `400` with the error if the query fails.
+///
+/// The read lock is only held to get the record batches of the origins, which are shared
+/// with the queries running at the same time, and the query stops if the client
+/// disconnects, its future being dropped.
+#[cfg(feature = "sql")]
+async fn post_query<G>(
+    State(state): State<Arc<RwLock<Graph<G>>>>,
+    Json(request): Json<QueryRequest>,
+) -> Response
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let batches = match with_graph_read_blocking(state, |graph| Ok(graph.origin_table()?)).await {
+        Ok(batches) => batches,
+        Err(status) => return status.into_response(),
+    };
+    match crate::sql::run(&batches, &request.sql).await {
+        Ok(result) => Json(result).into_response(),
         Err(e) => {
-            error!("Failed to get origins: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            debug!("Rejected query {:?}: {}", request.sql, e);
+            (StatusCode::BAD_REQUEST, Json(json!({"error": e.to_string()}))).into_response()
         }
     }
 }
 
-/// GET /origins/commit-counts - Get commit counts for all origins
-async fn get_all_commit_counts<G>(
-    State(state): State<Arc<RwLock<Graph<G>>>>
-) -> Result<Json<HashMap<String, String>>, StatusCode>
-where
-    G: SwhLabeledForwardGraph 
-    + SwhGraphWithProperties<
-        Maps: properties::Maps,
-        Timestamps: properties::Timestamps,
-        Persons: properties::Persons,
-        Contents: properties::Contents,
-        Strings: properties::Strings,
-        LabelNames: properties::LabelNames,
-    > + Send + Sync + 'static,
+/// Most identifiers `POST /resolve` translates at once
+const MAX_RESOLVE: usize = 100_000;
+
+/// Identifier given to `POST /resolve`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Identifier {
+    NodeId(NodeId),
+    Swhid(String),
+    /// Anything else, reported as invalid rather than failing the whole request
+    Invalid(Value),
+}
+
+/// POST /resolve - Translate a list of SWHIDs to node ids and of node ids to SWHIDs (they
+/// can be mixed), so that tools keyed on SWHIDs can use the other endpoints. Answers
+/// `[{"swhid": ..., "node_id": ..., "status": ...}, ...]` in the order of the request,
+/// each identifier being `found`, `not_found` in the graph or `invalid` (with an `error`)
+/// on its own.
+async fn post_resolve<G>(
+    State(state): State<Arc<RwLock<Graph<G>>>>,
+    Json(ids): Json<Vec<Identifier>>,
+) -> Response
+where
+    G: GraphBackend + Send + Sync + 'static,
 {
-    info!("Fetching commit counts for all origins");
-    
-    let mut graph = state.write().await;
-    
-    match graph.get_origins_mut() {
-        Ok(origins) => {
-            let total_origins = origins.len();
-            
-            // Create progress bar for processing all origins
-            let pb = StdArc::new(ProgressBar::new(total_origins as u64));
-            pb.set_style(
-                ProgressStyle::default_bar()
-                    .template("  {spinner:.cyan} [{elapsed_precise}] [{bar:30.cyan/blue}] {pos}/{len} origins ({percent}%) {msg}")
-                    .unwrap()
-                    .progress_chars("=>-")
-                    .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"]),
-            );
-            pb.set_message("Processing commit counts...");
-            
-            let mut result: HashMap<String, String> = HashMap::new();
-            
-            // Use parallel processing with rayon
-            let parallel_results: Vec<(String, String)> = origins
-                .par_iter_mut()
-                .enumerate()
-                .filter_map(|(idx, origin)| {
-                    // Update progress every 100 items
-                    if idx % 100 == 0 {
-                        pb.set_position(idx as u64);
-                    }
-                    
-                    if let Some(commit_count) = origin.total_commit_latest_snp() {
-                        Some((origin.id().to_string(), commit_count.to_string()))
-                    } else {
-                        None
+    if ids.len() > MAX_RESOLVE {
+        let error = format!("At most {} identifiers can be resolved at once", MAX_RESOLVE);
+        return (StatusCode::BAD_REQUEST, Json(json!({"error": error}))).into_response();
+    }
+    with_graph_read_blocking(state, move |graph| {
+        let backend = graph.inner_graph();
+        let found = |found: bool| if found { "found" } else { "not_found" };
+        let resolved: Vec<Value> = ids
+            .into_iter()
+            .map(|id| match id {
+                Identifier::NodeId(node) => {
+                    let swhid = (node < backend.num_nodes()).then(|| backend.swhid(node).to_string());
+                    json!({ "swhid": swhid, "node_id": node, "status": found(swhid.is_some()) })
+                }
+                Identifier::Swhid(given) => match given.parse::<SWHID>() {
+                    Ok(swhid) => {
+                        let node = backend.node_id(&swhid);
+                        json!({ "swhid": given, "node_id": node, "status": found(node.is_some()) })
                     }
-                })
-                .collect();
-            
-            // Convert results to HashMap
-            for (id, count) in parallel_results {
-                result.insert(id, count);
-            }
-            
-            pb.set_position(total_origins as u64);
-            
-            pb.finish_with_message(format!("✓ Completed processing {} get_all_commit_counts", result.len()));
-            
-            info!("Successfully retrieved commit counts for {} out of {} origins", result.len(), total_origins);
-            Ok(Json(result))
+                    Err(e) => json!({
+                        "swhid": given,
+                        "node_id": null,
+                        "status": "invalid",
+                        "error": format!("Invalid SWHID: {}", e),
+                    }),
+                },
+                Identifier::Invalid(value) => json!({
+                    "swhid": null,
+                    "node_id": null,
+                    "status": "invalid",
+                    "error": format!("Neither a SWHID nor a node id: {}", value),
+                }),
+            })
+            .collect();
+        Ok(Json(json!(resolved)))
+    })
+    .await
+    .into_response()
+}
+
+/// Body of `POST /admin/recompute`
+#[derive(Deserialize)]
+struct RecomputeRequest {
+    /// Origins to recompute, all origins if not set
+    origin_ids: Option<Vec<usize>>,
+}
+
+/// POST /admin/recompute - Recompute metrics of the given origins (or all) on the background workers
+async fn post_recompute<G>(
+    State(state): State<AppState<G>>,
+    body: Option<Json<RecomputeRequest>>,
+) -> Result<(StatusCode, Json<Value>), StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let requested = body.and_then(|Json(request)| request.origin_ids);
+    let params = json!({ "origin_ids": requested });
+    let origin_ids = match requested {
+        Some(ids) => ids,
+        None => state.graph.read().await.origin_ids(),
+    };
+
+    let job = state.jobs.create("recompute", origin_ids.len(), params);
+    info!("Job {}: recomputing {} origins", job.id, origin_ids.len());
+    state.workers.submit_job(job.clone(), origin_ids, true);
+    Ok((StatusCode::ACCEPTED, Json(job.to_json())))
+}
+
+/// GET /admin/workers - Get progress of the background compute workers
+async fn get_workers<G>(
+    State(state): State<AppState<G>>,
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    Ok(Json(state.workers.progress().to_json(state.workers.workers())))
+}
+
+/// GET /admin/memory - Get the process RSS and estimated footprint of the origins.
+///
+/// Answers with the RSS only while the graph is locked for writing, e.g. during loading.
+async fn get_memory<G>(
+    State(state): State<AppState<G>>,
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    match state.graph.try_read_owned() {
+        Ok(graph) => {
+            let usage = tokio::task::spawn_blocking(move || graph.memory_usage())
+                .await
+                .map_err(|e| {
+                    error!("Blocking graph task failed: {}", e);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?;
+            let mut usage = usage.to_json();
+            usage["shedding"] = json!(state.memory_pressure.is_shedding());
+            Ok(Json(usage))
         }
-        Err(e) => {
-            error!("Failed to get origins: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        Err(_) => Ok(Json(json!({
+            "rss_bytes": resident_set_size(),
+            "busy": true,
+            "shedding": state.memory_pressure.is_shedding(),
+        }))),
+    }
+}
+
+/// GET /jobs - List all jobs
+async fn get_jobs<G>(
+    State(state): State<AppState<G>>,
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    let jobs: Vec<Value> = state.jobs.list().iter().map(|job| job.to_json()).collect();
+    Ok(Json(json!({ "jobs": jobs })))
+}
+
+/// GET /jobs/:id - Get status of a job
+async fn get_job<G>(
+    Path(id): Path<u64>,
+    State(state): State<AppState<G>>,
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    match state.jobs.get(id) {
+        Some(job) => Ok(Json(job.to_json())),
+        None => {
+            error!("Job with id {} not found", id);
+            Err(StatusCode::NOT_FOUND)
         }
     }
 }
 
-/// GET /origins/committer-counts - Get committer counts for all origins
-async fn get_all_committer_counts<G>(
-    State(state): State<Arc<RwLock<Graph<G>>>>
-) -> Result<Json<HashMap<String, String>>, StatusCode>
-where
-    G: SwhLabeledForwardGraph 
-    + SwhGraphWithProperties<
-        Maps: properties::Maps,
-        Timestamps: properties::Timestamps,
-        Persons: properties::Persons,
-        Contents: properties::Contents,
-        Strings: properties::Strings,
-        LabelNames: properties::LabelNames,
-    > + Send + Sync + 'static,
+/// DELETE /jobs/:id - Cancel a running job
+async fn cancel_job<G>(
+    Path(id): Path<u64>,
+    State(state): State<AppState<G>>,
+) -> Result<Json<Value>, StatusCode>
+where
+    G: GraphBackend + Send + Sync + 'static,
 {
-    info!("Fetching committer counts for all origins");
-    
-    let mut graph = state.write().await;
-    
-    match graph.get_origins_mut() {
-        Ok(origins) => {
-            let total_origins = origins.len();
-            
-            // Create progress bar for processing all origins
-            let pb = StdArc::new(ProgressBar::new(total_origins as u64));
-            pb.set_style(
-                ProgressStyle::default_bar()
-                    .template("  {spinner:.cyan} [{elapsed_precise}] [{bar:30.cyan/blue}] {pos}/{len} origins ({percent}%) {msg}")
-                    .unwrap()
-                    .progress_chars("=>-")
-                    .tick_strings(&["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"]),
-            );
-            pb.set_message("Processing committer counts...");
-            
-            let mut result: HashMap<String, String> = HashMap::new();
-            
-            // Use parallel processing with rayon
-            let parallel_results: Vec<(String, String)> = origins
-                .par_iter_mut()
-                .enumerate()
-                .filter_map(|(idx, origin)| {
-                    // Update progress every 100 items
-                    if idx % 100 == 0 {
-                        pb.set_position(idx as u64);
-                    }
-                    
-                    if let Some(committer_count) = origin.total_commiter_latest_snp() {
-                        Some((origin.id().to_string(), committer_count.to_string()))
-                    } else {
-                        None
-                    }
+    let job = state.jobs.get(id).ok_or_else(|| {
+        error!("Job with id {} not found", id);
+        StatusCode::NOT_FOUND
+    })?;
+    if !job.cancel() {
+        return Err(StatusCode::CONFLICT);
+    }
+    state.jobs.save_or_warn();
+    info!("Job {} cancelled after {} of {} origins", id, job.completed(), job.total);
+    Ok(Json(job.to_json()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_graph::{InMemoryGraph, InMemoryGraphBuilder};
+    use crate::origin::LegacyOriginData;
+    use std::sync::Mutex;
+    use tokio::io::{AsyncBufReadExt, BufReader};
+    use tower::ServiceExt;
+
+    /// Empty directory of its own for `test`, under the system temporary directory
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("swh-server-{}-{}", std::process::id(), test));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Server over two origins with known metrics, origin 0 eligible and origin 1 without
+    /// commits, its origins loaded unless `ready` is false
+    fn server(test: &str, ready: bool) -> GraphServer<InMemoryGraph> {
+        let dir = temp_dir(test);
+        let mut builder = InMemoryGraphBuilder::default();
+        let ids = [builder.origin("https://example.org/a.git"), builder.origin("https://example.org/b.git")];
+        let mut graph = Graph::builder(dir.join("graph")).cache_dir(&dir).build(builder.build());
+        let origins = ids
+            .iter()
+            .map(|&id| {
+                OriginData::from(LegacyOriginData {
+                    id,
+                    url: None,
+                    latest_commit_date: Some(1_700_000_000),
+                    number_of_commits: Some(if id == ids[0] { 3 } else { 0 }),
+                    number_of_commiters: Some(1),
                 })
-                .collect();
-            
-            // Convert results to HashMap
-            for (id, count) in parallel_results {
-                result.insert(id, count);
+            })
+            .collect();
+        graph.replace_origins(origins).unwrap();
+        if ready {
+            graph.load_progress().set_stage(LoadStage::Ready);
+        }
+        GraphServer::new(graph, 1, false)
+    }
+
+    async fn get(router: &Router, uri: &str) -> (StatusCode, Option<String>, Value) {
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let cache = response.headers().get(X_CACHE).map(|value| value.to_str().unwrap().to_string());
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, cache, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    /// Redis answering `GET` and `SET` from a map, for the response cache
+    async fn fake_redis() -> (String, Arc<Mutex<HashMap<String, Vec<u8>>>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("redis://{}", listener.local_addr().unwrap());
+        let values: Arc<Mutex<HashMap<String, Vec<u8>>>> = Arc::default();
+        let store = values.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let store = store.clone();
+                tokio::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+                    let mut line = String::new();
+                    while stream.read_line(&mut line).await.is_ok_and(|read| read > 0) {
+                        let count: usize = line.trim()[1..].parse().unwrap();
+                        let mut args = Vec::new();
+                        for _ in 0..count {
+                            line.clear();
+                            stream.read_line(&mut line).await.unwrap();
+                            let mut arg = vec![0; line.trim()[1..].parse::<usize>().unwrap() + 2];
+                            stream.read_exact(&mut arg).await.unwrap();
+                            arg.truncate(arg.len() - 2);
+                            args.push(arg);
+                        }
+                        let key = String::from_utf8(args[1].clone()).unwrap();
+                        let reply = match &args[0][..] {
+                            b"GET" => match store.lock().unwrap().get(&key) {
+                                Some(value) => [format!("${}\r\n", value.len()).into_bytes(), value.clone(), b"\r\n".to_vec()].concat(),
+                                None => b"$-1\r\n".to_vec(),
+                            },
+                            _ => {
+                                store.lock().unwrap().insert(key, args[2].clone());
+                                b"+OK\r\n".to_vec()
+                            }
+                        };
+                        stream.get_mut().write_all(&reply).await.unwrap();
+                        line.clear();
+                    }
+                });
             }
-            
-            pb.set_position(total_origins as u64);
-            
-            pb.finish_with_message(format!("✓ Completed processing {}  committer counts", result.len()));
-            
-            info!("Successfully retrieved committer counts for {} out of {} origins", result.len(), total_origins);
-            Ok(Json(result))
+        });
+        (url, values)
+    }
+
+    #[tokio::test]
+    async fn status_mapping() {
+        let router = server("status", true).create_router(None, Router::new());
+        let (status, _, body) = get(&router, "/origins/0/commit-count").await;
+        assert_eq!((status, &body["commit_count"]), (StatusCode::OK, &json!(3)));
+        // Not an origin, or not even a node
+        assert_eq!(get(&router, "/origins/1000/commit-count").await.0, StatusCode::NOT_FOUND);
+        assert_eq!(get(&router, "/clusters/7/origins").await.0, StatusCode::NOT_FOUND);
+        assert_eq!(StatusCode::from(GraphError::GraphNotAttached(0)), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(StatusCode::from(GraphError::Cancelled), StatusCode::SERVICE_UNAVAILABLE);
+        let over_budget = GraphError::TraversalBudgetExceeded { origin: 0, max_nodes: 10 };
+        assert_eq!(StatusCode::from(over_budget), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn eligible_listing() {
+        let router = server("eligible", true).create_router(None, Router::new());
+        for (query, ids) in [("", json!([0])), ("?eligible=true", json!([0])), ("?eligible=false", json!([1])), ("?eligible=all", json!([0, 1]))] {
+            let (status, _, body) = get(&router, &format!("/origins{}", query)).await;
+            assert_eq!((status, &body["origin_ids"]), (StatusCode::OK, &ids), "{}", query);
         }
-        Err(e) => {
-            error!("Failed to get origins: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        assert_eq!(get(&router, "/origins?eligible=maybe").await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn require_ready_until_loaded() {
+        let server = server("loading", false);
+        let router = server.create_router(None, Router::new());
+        let (status, _, body) = get(&router, "/origins/0/commit-count").await;
+        assert_eq!((status, &body["status"]), (StatusCode::SERVICE_UNAVAILABLE, &json!("loading")));
+        assert_eq!(get(&router, "/origins").await.0, StatusCode::SERVICE_UNAVAILABLE);
+        // Not about the origins
+        assert_eq!(get(&router, "/health").await.0, StatusCode::OK);
+        assert_eq!(get(&router, "/readyz").await.0, StatusCode::SERVICE_UNAVAILABLE);
+
+        server.state.load_progress.set_stage(LoadStage::Ready);
+        assert_eq!(get(&router, "/origins").await.0, StatusCode::OK);
+        assert_eq!(get(&router, "/readyz").await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn shed_load_under_memory_pressure() {
+        // Any process is over a 1 byte limit
+        let server = server("shedding", true).with_memory_limit(1);
+        assert_eq!(server.state.memory_pressure.check().0, Some(true));
+        let router = server.create_router(None, Router::new());
+        let request = Request::builder().uri("/origins").body(Body::empty()).unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), SHED_RETRY_AFTER);
+        // Single origins are cheap enough
+        assert_eq!(get(&router, "/origins/0/commit-count").await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn response_cache_keys_per_dataset() {
+        let (url, values) = fake_redis().await;
+        let args = ServerArgs::parse_from(["swh-server", "--datasets", "datasets.json", "--redis-url", &url]);
+        let mut routers = Vec::new();
+        for name in ["python", "java"] {
+            let config: DatasetConfig =
+                serde_json::from_value(json!({"name": name, "graph_path": name, "data_path": name})).unwrap();
+            let args = config.server_args(&args);
+            let cache = ResponseCache {
+                redis: RedisCache::new(&url, &args.redis_prefix).unwrap(),
+                ttl: Duration::from_secs(60),
+                aggregate_ttl: Duration::from_secs(60),
+            };
+            let server = server(&format!("cache-{}", name), true).with_response_cache(cache);
+            routers.push(server.create_router(None, Router::new()));
         }
+
+        let (status, cache, body) = get(&routers[0], "/origins/0/commit-count").await;
+        assert_eq!((status, cache.as_deref()), (StatusCode::OK, Some("miss")));
+        let (_, cache, cached) = get(&routers[0], "/origins/0/commit-count").await;
+        assert_eq!((cache.as_deref(), &cached), (Some("hit"), &body));
+        // Same path, other dataset
+        assert_eq!(get(&routers[1], "/origins/0/commit-count").await.1.as_deref(), Some("miss"));
+        // Errors are not cached
+        assert_eq!(get(&routers[0], "/origins/1000/commit-count").await.1, None);
+
+        let mut keys: Vec<String> = values.lock().unwrap().keys().cloned().collect();
+        keys.sort();
+        assert_eq!(keys, ["swh-server:java:/origins/0/commit-count", "swh-server:python:/origins/0/commit-count"]);
     }
 }
@@ -0,0 +1,107 @@
+use arrow_array::RecordBatch;
+use datafusion::arrow::json::ArrayWriter;
+use datafusion::datasource::MemTable;
+use datafusion::error::DataFusionError;
+use datafusion::execution::context::SQLOptions;
+use datafusion::prelude::SessionContext;
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+
+use crate::origin_table::schema;
+use crate::query::MAX_QUERY_ROWS;
+
+/// Table the origins are registered as
+const TABLE: &str = "origins";
+
+/// Run `sql` over the origin table with DataFusion, the record batches of
+/// [`crate::graph::Graph::origin_table`] being registered as the `origins` table: any
+/// read-only query it supports (grouping, aggregates, functions, subqueries...), where
+/// [`crate::query::Query`] only takes a subset of SQL.
+///
+/// Answers like it, `{"columns": [...], "rows": [[...], ...], "truncated": ...}` with at
+/// most [`MAX_QUERY_ROWS`] rows, except that `latest_commit_date` is a UTC timestamp,
+/// returned as RFC 3339 and compared with e.g. `TIMESTAMP '2024-01-01T00:00:00Z'`. Fails
+/// on invalid queries and on statements writing or changing settings (`CREATE`,
+/// `INSERT`, `SET`...).
+pub async fn run(batches: &[RecordBatch], sql: &str) -> Result<Value, DataFusionError> {
+    // One partition per batch, scanned in parallel, and at least one for plans to be valid
+    let mut partitions: Vec<Vec<RecordBatch>> = batches.iter().map(|batch| vec![batch.clone()]).collect();
+    if partitions.is_empty() {
+        partitions.push(Vec::new());
+    }
+    let table = MemTable::try_new(Arc::new(schema()), partitions)?;
+    let context = SessionContext::new();
+    context.register_table(TABLE, Arc::new(table))?;
+    let options = SQLOptions::new().with_allow_ddl(false).with_allow_dml(false).with_allow_statements(false);
+    let frame = context.sql_with_options(sql, options).await?;
+    let columns: Vec<String> = frame.schema().fields().iter().map(|field| field.name().clone()).collect();
+    // One more row than returned, telling whether there were more
+    let results = frame.limit(0, Some(MAX_QUERY_ROWS + 1))?.collect().await?;
+
+    let mut writer = ArrayWriter::new(Vec::new());
+    writer.write_batches(&results.iter().collect::<Vec<_>>())?;
+    writer.finish()?;
+    let buffer = writer.into_inner();
+    // Null values are left out of the objects
+    let objects: Vec<Map<String, Value>> = if buffer.is_empty() {
+        Vec::new()
+    } else {
+        serde_json::from_slice(&buffer).map_err(|e| DataFusionError::External(Box::new(e)))?
+    };
+    let truncated = objects.len() > MAX_QUERY_ROWS;
+    let rows: Vec<Value> = objects
+        .into_iter()
+        .take(MAX_QUERY_ROWS)
+        .map(|mut object| Value::Array(columns.iter().map(|column| object.remove(column).unwrap_or(Value::Null)).collect()))
+        .collect();
+    Ok(json!({
+        "columns": columns,
+        "rows": rows,
+        "truncated": truncated,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_graph::InMemoryGraph;
+    use crate::origin::{LegacyOriginData, Origin, OriginData};
+    use crate::origin_table::record_batches;
+
+    #[tokio::test]
+    async fn queries() {
+        let graph = Arc::new(InMemoryGraph::default());
+        let origins: Vec<Origin<InMemoryGraph>> = [(1, "https://github.com/a/b", 5), (2, "https://gitlab.com/c/d", 50), (3, "https://github.com/e/f", 500)]
+            .into_iter()
+            .map(|(id, url, commits)| {
+                let data = OriginData::from(LegacyOriginData {
+                    id,
+                    url: Some(url.to_string()),
+                    latest_commit_date: Some(1_700_000_000),
+                    number_of_commits: Some(commits),
+                    number_of_commiters: None,
+                });
+                Origin::from_data(data, graph.clone())
+            })
+            .collect();
+        let batches = record_batches(&origins).unwrap();
+
+        let result = run(&batches, "SELECT url, commit_count FROM origins WHERE commit_count > 10 ORDER BY id").await.unwrap();
+        assert_eq!(
+            result,
+            json!({"columns": ["url", "commit_count"], "rows": [["https://gitlab.com/c/d", 50], ["https://github.com/e/f", 500]], "truncated": false})
+        );
+        let result = run(&batches, "SELECT latest_commit_date FROM origins LIMIT 1").await.unwrap();
+        assert_eq!(result["rows"], json!([["2023-11-14T22:13:20Z"]]));
+        // Beyond the subset of SQL of `Query`
+        let sql = "SELECT committer_count, COUNT(*) AS origins, MAX(commit_count) AS most FROM origins GROUP BY committer_count";
+        let result = run(&batches, sql).await.unwrap();
+        assert_eq!(result["rows"], json!([[null, 3, 500]]));
+        let result = run(&[], "SELECT COUNT(*) AS origins FROM origins").await.unwrap();
+        assert_eq!(result["rows"], json!([[0]]));
+
+        assert!(run(&batches, "SELECT nope FROM origins").await.is_err());
+        assert!(run(&batches, "DROP TABLE origins").await.is_err());
+        assert!(run(&batches, "INSERT INTO origins (id) VALUES (4)").await.is_err());
+    }
+}
@@ -0,0 +1,224 @@
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use swh_graph::graph::NodeId;
+use swh_graph::NodeType;
+use tokio_util::sync::CancellationToken;
+
+use crate::backend::GraphBackend;
+use crate::error::GraphError;
+use crate::progress::Progress;
+use tracing::warn;
+
+/// Name of the graph statistics file in the cache directory
+pub const GRAPH_STATS_FILE: &str = "graph_stats.json";
+
+/// Degrees up to `u64::MAX` fit in bucket 64
+const BUCKETS: usize = 65;
+
+/// Only one computation at a time: it scans the whole graph, a concurrent caller had
+/// better wait for the statistics to be saved
+static COMPUTING: Mutex<()> = Mutex::new(());
+
+/// Distribution of the degrees of the nodes of a graph
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DegreeDistribution {
+    /// `buckets[0]` counts the nodes of degree 0 and `buckets[i]` those of degree in
+    /// `[2^(i-1), 2^i)`, trailing empty buckets omitted
+    pub buckets: Vec<u64>,
+    pub max: u64,
+    pub mean: f64,
+}
+
+/// Node-type and degree statistics of a whole graph, see [`GraphStats::load_or_compute`]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GraphStats {
+    pub nodes: usize,
+    pub arcs: u64,
+    /// Node counts by type (`cnt`, `dir`, `rev`, ...)
+    pub nodes_by_type: BTreeMap<String, u64>,
+    /// Arc counts by source and destination types, e.g. `rev->dir`
+    pub arcs_by_type: BTreeMap<String, u64>,
+    pub out_degrees: DegreeDistribution,
+    /// Only computed with the transposed graph
+    pub in_degrees: Option<DegreeDistribution>,
+}
+
+struct Histogram {
+    buckets: [u64; BUCKETS],
+    max: u64,
+    sum: u128,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Histogram { buckets: [0; BUCKETS], max: 0, sum: 0 }
+    }
+
+    fn add(&mut self, degree: u64) {
+        self.buckets[(u64::BITS - degree.leading_zeros()) as usize] += 1;
+        self.max = self.max.max(degree);
+        self.sum += u128::from(degree);
+    }
+
+    fn merge(mut self, other: Histogram) -> Self {
+        for (bucket, count) in self.buckets.iter_mut().zip(other.buckets) {
+            *bucket += count;
+        }
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self
+    }
+
+    fn distribution(&self, nodes: usize) -> DegreeDistribution {
+        let used = self.buckets.iter().rposition(|&count| count > 0).map_or(0, |last| last + 1);
+        DegreeDistribution {
+            buckets: self.buckets[..used].to_vec(),
+            max: self.max,
+            mean: if nodes == 0 { 0.0 } else { self.sum as f64 / nodes as f64 },
+        }
+    }
+}
+
+/// Counts of one rayon worker, merged at the end
+struct Tally {
+    nodes: [u64; NodeType::NUMBER_OF_TYPES],
+    arcs: [[u64; NodeType::NUMBER_OF_TYPES]; NodeType::NUMBER_OF_TYPES],
+    out_degrees: Histogram,
+    in_degrees: Histogram,
+}
+
+impl Tally {
+    fn new() -> Self {
+        Tally {
+            nodes: [0; NodeType::NUMBER_OF_TYPES],
+            arcs: [[0; NodeType::NUMBER_OF_TYPES]; NodeType::NUMBER_OF_TYPES],
+            out_degrees: Histogram::new(),
+            in_degrees: Histogram::new(),
+        }
+    }
+
+    fn merge(mut self, other: Tally) -> Self {
+        for (count, other) in self.nodes.iter_mut().zip(other.nodes) {
+            *count += other;
+        }
+        for (row, other) in self.arcs.iter_mut().zip(other.arcs) {
+            for (count, other) in row.iter_mut().zip(other) {
+                *count += other;
+            }
+        }
+        self.out_degrees = self.out_degrees.merge(other.out_degrees);
+        self.in_degrees = self.in_degrees.merge(other.in_degrees);
+        self
+    }
+}
+
+impl GraphStats {
+    /// Scan every node of `graph`. In-degrees are only computed if `in_degree` is given,
+    /// out-degrees being the only ones known without the transposed graph.
+    ///
+    /// Returns `None` if cancelled meanwhile.
+    pub fn compute<G, D>(graph: &G, in_degree: Option<D>, progress: &Progress, cancel: &CancellationToken) -> Option<Self>
+    where
+        G: GraphBackend + Sync,
+        D: Fn(NodeId) -> usize + Sync,
+    {
+        let num_nodes = graph.num_nodes();
+        let tally = (0..num_nodes)
+            .into_par_iter()
+            .fold(
+                || (Tally::new(), progress.batch()),
+                |(mut tally, mut batch), node| {
+                    batch.inc();
+                    if cancel.is_cancelled() {
+                        return (tally, batch);
+                    }
+                    let src = graph.node_type(node).to_u8() as usize;
+                    tally.nodes[src] += 1;
+                    let mut out_degree = 0;
+                    for succ in graph.successors(node) {
+                        tally.arcs[src][graph.node_type(succ).to_u8() as usize] += 1;
+                        out_degree += 1;
+                    }
+                    tally.out_degrees.add(out_degree);
+                    if let Some(in_degree) = &in_degree {
+                        tally.in_degrees.add(in_degree(node) as u64);
+                    }
+                    (tally, batch)
+                },
+            )
+            .map(|(tally, _)| tally)
+            .reduce(Tally::new, Tally::merge);
+        if cancel.is_cancelled() {
+            return None;
+        }
+
+        let types = NodeType::all();
+        let nodes_by_type = types
+            .iter()
+            .map(|node_type| (node_type.to_str().to_string(), tally.nodes[node_type.to_u8() as usize]))
+            .collect();
+        let mut arcs_by_type = BTreeMap::new();
+        for src in &types {
+            for dst in &types {
+                let count = tally.arcs[src.to_u8() as usize][dst.to_u8() as usize];
+                if count > 0 {
+                    arcs_by_type.insert(format!("{}->{}", src.to_str(), dst.to_str()), count);
+                }
+            }
+        }
+        Some(GraphStats {
+            nodes: num_nodes,
+            arcs: graph.num_arcs(),
+            nodes_by_type,
+            arcs_by_type,
+            out_degrees: tally.out_degrees.distribution(num_nodes),
+            in_degrees: in_degree.is_some().then(|| tally.in_degrees.distribution(num_nodes)),
+        })
+    }
+
+    /// Statistics saved at `path` if they match `graph` (and include in-degrees when
+    /// `in_degree` is given), otherwise computed and saved there
+    pub fn load_or_compute<G, D>(
+        graph: &G,
+        path: &Path,
+        in_degree: Option<D>,
+        progress: &Progress,
+        cancel: &CancellationToken,
+    ) -> Result<Self, GraphError>
+    where
+        G: GraphBackend + Sync,
+        D: Fn(NodeId) -> usize + Sync,
+    {
+        let _computing = COMPUTING.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(stats) = Self::load(path) {
+            let matches = stats.nodes == graph.num_nodes() && stats.arcs == graph.num_arcs();
+            if matches && (in_degree.is_none() || stats.in_degrees.is_some()) {
+                return Ok(stats);
+            }
+        }
+        let stats = Self::compute(graph, in_degree, progress, cancel).ok_or(GraphError::Cancelled)?;
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, &stats).map_err(GraphError::serialization)?;
+        writer.flush()?;
+        Ok(stats)
+    }
+
+    fn load(path: &Path) -> Option<Self> {
+        if !fs::exists(path).unwrap_or(false) {
+            return None;
+        }
+        let reader = BufReader::new(File::open(path).ok()?);
+        match serde_json::from_reader(reader) {
+            Ok(stats) => Some(stats),
+            Err(e) => {
+                warn!("Ignoring unreadable graph statistics {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use swh_graph::graph::NodeId;
+
+use crate::error::GraphError;
+use crate::utils::write_atomically;
+
+/// Tags kept by the server in its cache directory, see [`TagStore::persistent`]
+pub const TAGS_FILE: &str = "origin_tags.json";
+
+/// Longest tag, in bytes
+const MAX_TAG_LEN: usize = 128;
+
+/// Tags put on origins through the API, e.g. `selected-for-study-x`, to manage curated
+/// corpora without keeping lists of ids on the side.
+///
+/// Tags are attached to origin node ids, so they only hold for one graph export. A
+/// persistent store is saved after each change, as a JSON object mapping each tag to
+/// its origins.
+#[derive(Default)]
+pub struct TagStore {
+    /// Where the tags are saved, if anywhere
+    path: Option<PathBuf>,
+    /// Origins of each tag, tags without origins being dropped
+    tags: Mutex<BTreeMap<String, BTreeSet<NodeId>>>,
+}
+
+impl TagStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store saving its tags to `path`, starting with the ones saved there before
+    pub fn persistent(path: PathBuf) -> Result<Self, GraphError> {
+        let tags = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?).map_err(|e| GraphError::CacheCorrupted {
+                path: path.clone(),
+                reason: e.to_string(),
+            })?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(TagStore { path: Some(path), tags: Mutex::new(tags) })
+    }
+
+    /// Check that `tag` can be used: not empty, at most [`MAX_TAG_LEN`] bytes, without
+    /// whitespace nor control characters
+    pub fn validate(tag: &str) -> Result<(), String> {
+        if tag.is_empty() || tag.len() > MAX_TAG_LEN {
+            return Err(format!("tags must be 1 to {} bytes long", MAX_TAG_LEN));
+        }
+        if tag.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err(format!("invalid tag {:?}: whitespace and control characters are not allowed", tag));
+        }
+        Ok(())
+    }
+
+    /// Tags of `origin`, in alphabetical order
+    pub fn tags_of(&self, origin: NodeId) -> Vec<String> {
+        let tags = self.tags.lock().unwrap();
+        tags.iter().filter(|(_, origins)| origins.contains(&origin)).map(|(tag, _)| tag.clone()).collect()
+    }
+
+    /// Origins tagged with `tag`, sorted by id
+    pub fn origins(&self, tag: &str) -> Vec<NodeId> {
+        let tags = self.tags.lock().unwrap();
+        tags.get(tag).map_or_else(Vec::new, |origins| origins.iter().copied().collect())
+    }
+
+    /// Number of origins of each tag
+    pub fn counts(&self) -> BTreeMap<String, usize> {
+        let tags = self.tags.lock().unwrap();
+        tags.iter().map(|(tag, origins)| (tag.clone(), origins.len())).collect()
+    }
+
+    /// Tag `origin` with `new_tags`, which must be [valid](Self::validate), keeping its
+    /// other tags
+    pub fn add(&self, origin: NodeId, new_tags: &[String]) -> Result<(), GraphError> {
+        let mut tags = self.tags.lock().unwrap();
+        for tag in new_tags {
+            tags.entry(tag.clone()).or_default().insert(origin);
+        }
+        self.save(&tags)
+    }
+
+    /// Remove `tag` from `origin`. Returns `false` if it did not have it.
+    pub fn remove(&self, origin: NodeId, tag: &str) -> Result<bool, GraphError> {
+        let mut tags = self.tags.lock().unwrap();
+        let Some(origins) = tags.get_mut(tag) else {
+            return Ok(false);
+        };
+        if !origins.remove(&origin) {
+            return Ok(false);
+        }
+        if origins.is_empty() {
+            tags.remove(tag);
+        }
+        self.save(&tags)?;
+        Ok(true)
+    }
+
+    fn save(&self, tags: &BTreeMap<String, BTreeSet<NodeId>>) -> Result<(), GraphError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let contents = serde_json::to_vec_pretty(tags).map_err(GraphError::serialization)?;
+        Ok(write_atomically(path, &contents)?)
+    }
+}
@@ -0,0 +1,221 @@
+/// Host of an origin URL, for `scheme://[user@]host[:port]/path` as well as scp-like
+/// `user@host:path` URLs
+pub fn host(url: &str) -> Option<&str> {
+    let authority = without_scheme(url).split('/').next()?;
+    let host = authority.split(':').next()?;
+    (!host.is_empty()).then_some(host)
+}
+
+/// `host[:port]/path` part of a URL, without scheme nor user
+fn without_scheme(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority_end = rest.find('/').unwrap_or(rest.len());
+    match rest[..authority_end].rfind('@') {
+        Some(at) => &rest[at + 1..],
+        None => rest,
+    }
+}
+
+/// Canonical form of an origin URL, the same for the origins of a repository archived
+/// under several URLs: without scheme, user, default port, trailing `/` nor `.git`, and
+/// with a lowercase host. `https://GitHub.com/rust-lang/rust.git`,
+/// `ssh://git@github.com:22/rust-lang/rust` and `git@github.com:rust-lang/rust` are all
+/// `github.com/rust-lang/rust`. Paths keep their case, which matters on most forges.
+pub fn canonical_url(url: &str) -> String {
+    let scheme = url.split_once("://").map(|(scheme, _)| scheme.to_ascii_lowercase());
+    let rest = without_scheme(url);
+    let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let (host, path) = match authority.split_once(':') {
+        // scp-like `host:path`, the path starting after the colon
+        Some((host, first)) if scheme.is_none() && first.parse::<u16>().is_err() => {
+            (host.to_string(), if path.is_empty() { first.to_string() } else { format!("{}/{}", first, path) })
+        }
+        Some((host, port)) if scheme.as_deref().and_then(default_port) == port.parse().ok() => {
+            (host.to_string(), path.to_string())
+        }
+        _ => (authority.to_string(), path.to_string()),
+    };
+    let path = path.trim_end_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path).trim_end_matches('/');
+    if path.is_empty() {
+        host.to_lowercase()
+    } else {
+        format!("{}/{}", host.to_lowercase(), path)
+    }
+}
+
+/// Port of a scheme origins are archived from, when not given in their URL
+fn default_port(scheme: &str) -> Option<u16> {
+    match scheme {
+        "http" => Some(80),
+        "https" => Some(443),
+        "ssh" | "git+ssh" => Some(22),
+        "git" => Some(9418),
+        _ => None,
+    }
+}
+
+/// Glob pattern over origin URLs, `*` matching any run of bytes and `?` any single one.
+/// Patterns without `/` are matched against the host (`*.googlesource.com`), others
+/// against the URL without its scheme (`github.com/rust-lang/*`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UrlPattern {
+    pattern: String,
+    host_only: bool,
+}
+
+impl UrlPattern {
+    pub fn new(pattern: &str) -> Self {
+        UrlPattern { pattern: pattern.to_string(), host_only: !pattern.contains('/') }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn matches(&self, url: &str) -> bool {
+        let subject = if self.host_only { host(url) } else { Some(without_scheme(url)) };
+        subject.is_some_and(|subject| wildcard_match(self.pattern.as_bytes(), subject.as_bytes(), b'*', b'?'))
+    }
+}
+
+impl std::str::FromStr for UrlPattern {
+    type Err = String;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        if pattern.is_empty() {
+            return Err("empty URL pattern".to_string());
+        }
+        Ok(UrlPattern::new(pattern))
+    }
+}
+
+/// Greedy wildcard matching, `any` matching any run of bytes and `one` any single byte,
+/// backtracking to the last `any` on mismatch
+pub fn wildcard_match(pattern: &[u8], text: &[u8], any: u8, one: u8) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(&c) if c == any => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == one || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == any)
+}
+
+/// Which origins to keep according to their URL, so that targeted corpora don't pay for
+/// the whole archive
+#[derive(Clone, Debug, Default)]
+pub struct UrlFilter {
+    /// If not empty, only origins matching one of these are kept
+    pub allow: Vec<UrlPattern>,
+    /// Origins matching one of these are dropped, even if allowed
+    pub block: Vec<UrlPattern>,
+}
+
+impl UrlFilter {
+    /// Whether the filter keeps every origin
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.block.is_empty()
+    }
+
+    /// Whether an origin with this URL is kept. Origins without URL are only kept
+    /// without allowlist.
+    pub fn keeps(&self, url: Option<&str>) -> bool {
+        let Some(url) = url else {
+            return self.allow.is_empty();
+        };
+        (self.allow.is_empty() || self.allow.iter().any(|pattern| pattern.matches(url)))
+            && !self.block.iter().any(|pattern| pattern.matches(url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_urls() {
+        for url in [
+            "https://GitHub.com/rust-lang/rust.git",
+            "git@github.com:rust-lang/rust",
+            "https://github.com/rust-lang/rust/",
+            "http://github.com:80/rust-lang/rust.git/",
+            "ssh://git@github.com:22/rust-lang/rust.git",
+            "git://github.com/rust-lang/rust",
+            "https://user@github.com/rust-lang/rust",
+        ] {
+            assert_eq!(canonical_url(url), "github.com/rust-lang/rust", "{}", url);
+        }
+        assert_eq!(canonical_url("https://github.com/Rust-Lang/Rust"), "github.com/Rust-Lang/Rust");
+        // A number after the colon is a port, not the start of an scp-like path
+        assert_eq!(canonical_url("host:22/path"), "host:22/path");
+        assert_eq!(canonical_url("ssh://git@host:22/x.git"), "host/x");
+        assert_eq!(canonical_url("ssh://git@host:2222/x.git"), "host:2222/x");
+        assert_eq!(canonical_url("https://Host:8443/x"), "host:8443/x");
+        assert_eq!(canonical_url("git@host:x.git"), "host/x");
+        assert_eq!(canonical_url("host:path/to/x.git"), "host/path/to/x");
+        assert_eq!(canonical_url("https://example.org"), "example.org");
+        assert_eq!(canonical_url("https://example.org/.git"), "example.org");
+    }
+
+    #[test]
+    fn hosts() {
+        assert_eq!(host("https://user@GitHub.com:443/rust-lang/rust"), Some("GitHub.com"));
+        assert_eq!(host("git@github.com:rust-lang/rust"), Some("github.com"));
+        assert_eq!(host("host:22/path"), Some("host"));
+        assert_eq!(host("https:///path"), None);
+    }
+
+    #[test]
+    fn wildcards() {
+        let matches = |pattern: &str, text: &str| wildcard_match(pattern.as_bytes(), text.as_bytes(), b'*', b'?');
+        assert!(matches("", ""));
+        assert!(!matches("", "a"));
+        assert!(matches("*", ""));
+        assert!(matches("***", "abc"));
+        assert!(matches("a?c", "abc"));
+        assert!(!matches("a?c", "ac"));
+        assert!(matches("*.googlesource.com", "chromium.googlesource.com"));
+        assert!(!matches("*.googlesource.com", "googlesource.com"));
+        // Backtracking past a partial match of what follows the star
+        assert!(matches("*ab", "aab"));
+        assert!(matches("a*b*c", "axbxbyc"));
+        assert!(!matches("a*b*c", "axbxby"));
+        assert!(wildcard_match(b"src/%_.rs", b"src/lib.rs", b'%', b'_'));
+    }
+
+    #[test]
+    fn patterns() {
+        let pattern = UrlPattern::new("*.googlesource.com");
+        assert!(pattern.matches("https://chromium.googlesource.com/chromium/src"));
+        assert!(!pattern.matches("https://github.com/chromium.googlesource.com"));
+        let pattern = UrlPattern::new("github.com/rust-lang/*");
+        assert!(pattern.matches("https://github.com/rust-lang/rust"));
+        assert!(pattern.matches("git@github.com/rust-lang/rust"));
+        assert!(!pattern.matches("https://github.com/rust-lang"));
+        assert!("".parse::<UrlPattern>().is_err());
+
+        let filter = UrlFilter { allow: vec![UrlPattern::new("github.com")], block: vec![UrlPattern::new("*/rust-lang/*")] };
+        assert!(filter.keeps(Some("https://github.com/torvalds/linux")));
+        assert!(!filter.keeps(Some("https://github.com/rust-lang/rust")));
+        assert!(!filter.keeps(Some("https://gitlab.com/gitlab-org/gitlab")));
+        assert!(!filter.keeps(None));
+        assert!(UrlFilter::default().keeps(None));
+    }
+}
@@ -1,45 +1,598 @@
-use std::fs::File;
 use std::fs::read_to_string;
 use std::io;
+use std::cell::RefCell;
+use std::collections::{HashSet, VecDeque};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
 use swh_graph::graph::NodeId;
-use swh_graph::graph::SwhGraphWithProperties;
-use swh_graph::properties;
 use swh_graph::NodeType;
+use tokio_util::sync::CancellationToken;
 
+use crate::backend::{ArcLabel, BackwardCapable, GraphBackend, LabelCapable};
+use crate::scratch::{reset_set, with_traversal, MAX_RETAINED_CAPACITY};
+use crate::parallel::*;
+use tracing::warn;
 
-pub fn write_node_ids(path: &PathBuf, node_ids: &Vec<NodeId>) -> Result<(), io::Error> {
-    let mut file = File::create(path)?;
+
+/// Write `node_ids` to `path`, one per line, through [`write_atomically`]
+pub fn write_node_ids(path: &Path, node_ids: &[NodeId]) -> Result<(), io::Error> {
+    let mut contents = Vec::with_capacity(node_ids.len() * 8);
     for node_id in node_ids {
-        writeln!(file, "{}", node_id)?;
+        writeln!(contents, "{}", node_id)?;
     }
-    Ok(())
+    write_atomically(path, &contents)
 }
 
-pub fn read_node_ids(path: &PathBuf) -> Result<Vec<NodeId>, io::Error> {
-    let node_ids = read_to_string(path)?
+/// Read node ids written by [`write_node_ids`]. A line which is not a node id, e.g. of a
+/// truncated file, is an [`io::ErrorKind::InvalidData`] error.
+pub fn read_node_ids(path: &Path) -> Result<Vec<NodeId>, io::Error> {
+    read_to_string(path)?
         .lines()
         .map(|x| {
-            x.parse::<usize>()
-                .expect(&format!("Failed to parse NodeId '{}' from origin file", x))
+            x.parse::<usize>().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("Failed to parse NodeId '{}' from {:?}", x, path))
+            })
         })
-        .collect();
-    Ok(node_ids)
+        .collect()
+}
+
+/// Replace `path` with `contents` through a temporary file, so that it is never left half
+/// written
+pub fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(&tmp, path)
 }
 
+/// Get all node ids of the given type, in increasing order, scanning the nodes in parallel
 pub fn filter_by_node_type<G>(graph: &G, node_type: NodeType) -> Vec<NodeId>
 where
-    G: SwhGraphWithProperties<Maps: properties::Maps>,
+    G: GraphBackend + Sync,
 {
-   let props = graph.properties();
-   
-     return (0..graph.num_nodes())
-             .filter(|&node| props.node_type(node) == node_type)
+     (0..graph.num_nodes())
+             .into_par_iter()
+             .filter(|&node| graph.node_type(node) == node_type)
              .collect()
 }
 
+/// Same as [`filter_by_node_type`], but the ids are persisted to `index_path` and read
+/// back on later calls instead of scanning the whole graph again.
+///
+/// A persisted index is only reused if every id in it still is a node of `node_type`
+/// in `graph`, so an index built against another graph export, or left unreadable, gets
+/// rebuilt.
+pub fn load_or_build_node_type_index<G>(
+    graph: &G,
+    node_type: NodeType,
+    index_path: &Path,
+) -> Result<Vec<NodeId>, io::Error>
+where
+    G: GraphBackend + Sync,
+{
+    if index_path.exists() {
+        match read_node_ids(index_path) {
+            Ok(node_ids) => {
+                let num_nodes = graph.num_nodes();
+                let valid = node_ids
+                    .par_iter()
+                    .all(|&node| node < num_nodes && graph.node_type(node) == node_type);
+                if valid {
+                    return Ok(node_ids);
+                }
+                warn!("Node type index {:?} does not match the graph, rebuilding it", index_path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                warn!("Node type index {:?} is corrupted ({}), rebuilding it", index_path, e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    let node_ids = filter_by_node_type(graph, node_type);
+    write_node_ids(index_path, &node_ids)?;
+    Ok(node_ids)
+}
+
+/// What a traversal callback wants next, see [`bfs`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Visit {
+    /// Follow the arcs of this node
+    Continue,
+    /// Don't follow the arcs of this node, but go on with the other pending nodes
+    Skip,
+    /// End the traversal
+    Stop,
+}
 
+/// How a traversal ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraversalEnd {
+    /// Every node reachable within the bounds was visited
+    Complete,
+    /// The callback returned [`Visit::Stop`]
+    Stopped,
+    /// The node budget ([`TraversalOptions::max_nodes`]) was used up with nodes left
+    BudgetExhausted,
+    /// The cancellation token ([`TraversalOptions::cancel`]) got cancelled
+    Cancelled,
+}
 
+impl TraversalEnd {
+    pub fn is_cancelled(self) -> bool {
+        self == TraversalEnd::Cancelled
+    }
+}
+
+/// Order in which [`Traversal::walk`] visits the pending nodes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Order {
+    BreadthFirst,
+    DepthFirst,
+}
+
+/// Filters and bounds of [`bfs`] and [`dfs`]:
+///
+/// ```ignore
+/// let history = TraversalOptions::new()
+///     .node_types(&[NodeType::Release, NodeType::Revision])
+///     .max_nodes(1_000_000)
+///     .cancel(&cancel);
+/// ```
+#[derive(Clone, Copy, Default)]
+pub struct TraversalOptions<'a> {
+    node_types: &'a [NodeType],
+    max_depth: Option<usize>,
+    max_nodes: Option<usize>,
+    cancel: Option<&'a CancellationToken>,
+}
+
+impl<'a> TraversalOptions<'a> {
+    /// No filter nor bound: visit everything reachable
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only follow arcs to nodes of these types. Roots are visited whatever their type.
+    pub fn node_types(mut self, node_types: &'a [NodeType]) -> Self {
+        self.node_types = node_types;
+        self
+    }
+
+    /// Don't follow arcs of nodes this many arcs away from the roots
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Stop after visiting this many nodes
+    pub fn max_nodes(mut self, max_nodes: usize) -> Self {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+
+    /// Stop early once `cancel` is cancelled
+    pub fn cancel(mut self, cancel: &'a CancellationToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    fn follows(&self, node_type: NodeType) -> bool {
+        self.node_types.is_empty() || self.node_types.contains(&node_type)
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.is_some_and(|cancel| cancel.is_cancelled())
+    }
+}
+
+/// Number of nodes visited between two checks of the cancellation token
+const CANCELLATION_CHECK_INTERVAL: usize = 4096;
+
+/// Node visits charged to the visit throttle at a time, so that the threads traversing
+/// the graph only contend on it every few thousand nodes
+const THROTTLE_BATCH: usize = 4096;
+
+/// Token bucket capping the node visits of all the traversals of the process, see
+/// [`set_visit_throttle`]
+struct VisitThrottle {
+    visits_per_second: f64,
+    /// Visits available, negative when those already granted have to be waited for, and
+    /// when they were last counted
+    tokens: Mutex<(f64, Instant)>,
+}
+
+static VISIT_THROTTLE: OnceLock<VisitThrottle> = OnceLock::new();
+
+/// Cap the node visits of all the graph traversals of this process at
+/// `visits_per_second`, so that it runs politely on a machine shared with other users of
+/// memory-mapped graphs: each visit may fault a page in, evicting theirs. Traversals may
+/// burst up to a second of visits. Can only be set once, before the first traversal.
+pub fn set_visit_throttle(visits_per_second: u64) {
+    let throttle = VisitThrottle {
+        visits_per_second: visits_per_second as f64,
+        tokens: Mutex::new((visits_per_second as f64, Instant::now())),
+    };
+    let _ = VISIT_THROTTLE.set(throttle);
+}
+
+/// Take `visits` from the throttle, sleeping until they are available. Returns at once
+/// without throttle.
+fn throttle_visits(visits: usize) {
+    let Some(throttle) = VISIT_THROTTLE.get().filter(|_| visits > 0) else {
+        return;
+    };
+    let wait = {
+        let mut tokens = throttle.tokens.lock().unwrap();
+        let now = Instant::now();
+        let (available, counted_at) = *tokens;
+        let available = (available + now.duration_since(counted_at).as_secs_f64() * throttle.visits_per_second)
+            .min(throttle.visits_per_second)
+            - visits as f64;
+        *tokens = (available, now);
+        // In debt: wait for the visits granted before these to be paid back
+        (available < 0.0).then(|| Duration::from_secs_f64(-available / throttle.visits_per_second))
+    };
+    if let Some(wait) = wait {
+        std::thread::sleep(wait);
+    }
+}
+
+/// Reusable traversal buffers, see [`with_traversal`]
+#[derive(Default)]
+pub struct Traversal {
+    visited: HashSet<NodeId>,
+    /// Nodes found but not visited yet, with their depth
+    pending: VecDeque<(NodeId, usize)>,
+}
+
+impl Traversal {
+    /// Call `f` on every node reachable from `start` through `neighbours`, each node once,
+    /// within the bounds of `options` (its node type filter is up to `neighbours`)
+    pub fn walk<I, N, F>(
+        &mut self,
+        start: impl IntoIterator<Item = NodeId>,
+        order: Order,
+        options: &TraversalOptions,
+        neighbours: N,
+        mut f: F,
+    ) -> TraversalEnd
+    where
+        I: IntoIterator<Item = NodeId>,
+        N: Fn(NodeId) -> I,
+        F: FnMut(NodeId, usize) -> Visit,
+    {
+        reset_set(&mut self.visited);
+        self.pending.clear();
+        for node in start {
+            if self.visited.insert(node) {
+                self.pending.push_back((node, 0));
+            }
+        }
+        let mut visited = 0;
+        let mut end = TraversalEnd::Complete;
+        loop {
+            let next = match order {
+                Order::BreadthFirst => self.pending.pop_front(),
+                Order::DepthFirst => self.pending.pop_back(),
+            };
+            let Some((node, depth)) = next else {
+                break;
+            };
+            if options.max_nodes == Some(visited) {
+                end = TraversalEnd::BudgetExhausted;
+                break;
+            }
+            visited += 1;
+            if visited % THROTTLE_BATCH == 0 {
+                throttle_visits(THROTTLE_BATCH);
+            }
+            if visited % CANCELLATION_CHECK_INTERVAL == 0 && options.is_cancelled() {
+                end = TraversalEnd::Cancelled;
+                break;
+            }
+            match f(node, depth) {
+                Visit::Continue => {}
+                Visit::Skip => continue,
+                Visit::Stop => {
+                    end = TraversalEnd::Stopped;
+                    break;
+                }
+            }
+            if options.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+                continue;
+            }
+            for succ in neighbours(node) {
+                if self.visited.insert(succ) {
+                    self.pending.push_back((succ, depth + 1));
+                }
+            }
+        }
+        throttle_visits(visited % THROTTLE_BATCH);
+        if self.pending.capacity() > MAX_RETAINED_CAPACITY {
+            self.pending = VecDeque::new();
+        }
+        end
+    }
+}
+
+/// Call `f` with every node reachable from `roots` (included) and its depth, in
+/// breadth-first order and each node once, within the filters and bounds of `options`.
+///
+/// Reuses this thread's traversal buffers rather than allocating new ones for each call.
+pub fn bfs<G, F>(graph: &G, roots: impl IntoIterator<Item = NodeId>, options: &TraversalOptions, f: F) -> TraversalEnd
+where
+    G: GraphBackend,
+    F: FnMut(NodeId, usize) -> Visit,
+{
+    traverse(graph, roots, Order::BreadthFirst, options, f)
+}
 
- 
\ No newline at end of file
+/// Same as [`bfs`] in depth-first order. Nodes are marked visited when found, so depths
+/// are those of the first path found, not the shortest one: a node within `max_depth`
+/// may be missed when it is first found through a longer path.
+pub fn dfs<G, F>(graph: &G, roots: impl IntoIterator<Item = NodeId>, options: &TraversalOptions, f: F) -> TraversalEnd
+where
+    G: GraphBackend,
+    F: FnMut(NodeId, usize) -> Visit,
+{
+    traverse(graph, roots, Order::DepthFirst, options, f)
+}
+
+/// Same as [`bfs`] following arcs backward
+pub fn bfs_backward<G, F>(graph: &G, roots: impl IntoIterator<Item = NodeId>, options: &TraversalOptions, f: F) -> TraversalEnd
+where
+    G: BackwardCapable,
+    F: FnMut(NodeId, usize) -> Visit,
+{
+    with_traversal(|traversal| {
+        let predecessors = |node| graph.predecessors(node).filter(|&pred| options.follows(graph.node_type(pred)));
+        traversal.walk(roots, Order::BreadthFirst, options, predecessors, f)
+    })
+}
+
+fn traverse<G, F>(
+    graph: &G,
+    roots: impl IntoIterator<Item = NodeId>,
+    order: Order,
+    options: &TraversalOptions,
+    f: F,
+) -> TraversalEnd
+where
+    G: GraphBackend,
+    F: FnMut(NodeId, usize) -> Visit,
+{
+    with_traversal(|traversal| {
+        let successors = |node| graph.successors(node).filter(|&succ| options.follows(graph.node_type(succ)));
+        traversal.walk(roots, order, options, successors, f)
+    })
+}
+
+/// Callbacks of [`visit`], which both default to doing nothing. For instance, counting
+/// the files of each extension reachable from a directory:
+///
+/// ```ignore
+/// struct Extensions(HashMap<Vec<u8>, usize>);
+///
+/// impl Visitor for Extensions {
+///     fn arc(&mut self, _src: NodeId, _dst: NodeId, labels: &[ArcLabel]) {
+///         for label in labels {
+///             if let ArcLabel::DirEntry { name, permission: Some(Permission::Content) } = label {
+///                 let extension = name.rsplit(|&b| b == b'.').next().unwrap_or_default();
+///                 *self.0.entry(extension.to_vec()).or_default() += 1;
+///             }
+///         }
+///     }
+/// }
+/// ```
+pub trait Visitor {
+    /// Called once for each visited node, with its depth; see [`Visit`]
+    fn node(&mut self, _node: NodeId, _depth: usize) -> Visit {
+        Visit::Continue
+    }
+
+    /// Called for each arc followed from a visited node, after [`Self::node`] was called
+    /// for it, with the labels of the arc. Arcs to already visited nodes are included, so
+    /// e.g. a file present in two directories is seen twice.
+    fn arc(&mut self, _src: NodeId, _dst: NodeId, _labels: &[ArcLabel]) {}
+}
+
+/// Breadth-first traversal like [`bfs`], calling `visitor` on nodes and on labeled arcs
+pub fn visit<G, V>(graph: &G, roots: impl IntoIterator<Item = NodeId>, options: &TraversalOptions, visitor: &mut V) -> TraversalEnd
+where
+    G: LabelCapable,
+    V: Visitor,
+{
+    // Both callbacks of the walk need the visitor, never at the same time
+    let visitor = RefCell::new(visitor);
+    with_traversal(|traversal| {
+        let successors = |node| {
+            let mut arcs = graph.labeled_arcs(node);
+            arcs.retain(|&(succ, _)| options.follows(graph.node_type(succ)));
+            let mut visitor = visitor.borrow_mut();
+            for (succ, labels) in &arcs {
+                visitor.arc(node, *succ, labels);
+            }
+            arcs.into_iter().map(|(succ, _)| succ)
+        };
+        traversal.walk(roots, Order::BreadthFirst, options, successors, |node, depth| {
+            visitor.borrow_mut().node(node, depth)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_graph::{InMemoryGraph, InMemoryGraphBuilder};
+
+    /// Nodes of [`history`], by id
+    const ORIGIN: NodeId = 0;
+    const SNAPSHOT: NodeId = 1;
+    const R1: NodeId = 2;
+    const R2: NodeId = 3;
+    const R3: NodeId = 4;
+    const DIRECTORY: NodeId = 5;
+    const CONTENT: NodeId = 6;
+
+    /// origin -> snapshot -> r1 -> r2 -> r3, r1 -> directory -> content
+    fn history() -> InMemoryGraph {
+        let mut builder = InMemoryGraphBuilder::default();
+        let origin = builder.origin("https://example.org/repo.git");
+        let snapshot = builder.node(NodeType::Snapshot);
+        let revisions = [builder.revision(1, 1), builder.revision(2, 1), builder.revision(3, 2)];
+        let directory = builder.node(NodeType::Directory);
+        let content = builder.node(NodeType::Content);
+        assert_eq!([origin, snapshot, revisions[0], revisions[1], revisions[2], directory, content], [0, 1, 2, 3, 4, 5, 6]);
+        builder
+            .visit(origin, snapshot, 10)
+            .branch(snapshot, "refs/heads/main", R1)
+            .arc(R1, R2)
+            .arc(R2, R3)
+            .arc(R1, DIRECTORY)
+            .arc(DIRECTORY, CONTENT);
+        builder.build()
+    }
+
+    /// Nodes visited by `traversal` with their depth, in visit order, and how it ended
+    fn collect(traversal: impl FnOnce(&mut dyn FnMut(NodeId, usize) -> Visit) -> TraversalEnd) -> (Vec<(NodeId, usize)>, TraversalEnd) {
+        let mut visited = Vec::new();
+        let end = traversal(&mut |node, depth| {
+            visited.push((node, depth));
+            Visit::Continue
+        });
+        (visited, end)
+    }
+
+    #[test]
+    fn orders() {
+        let graph = history();
+        let options = TraversalOptions::new();
+        let (visited, end) = collect(|f| bfs(&graph, [ORIGIN], &options, f));
+        assert_eq!(end, TraversalEnd::Complete);
+        assert_eq!(visited, [(ORIGIN, 0), (SNAPSHOT, 1), (R1, 2), (R2, 3), (DIRECTORY, 3), (R3, 4), (CONTENT, 4)]);
+
+        let (visited, end) = collect(|f| dfs(&graph, [ORIGIN], &options, f));
+        assert_eq!(end, TraversalEnd::Complete);
+        assert_eq!(visited, [(ORIGIN, 0), (SNAPSHOT, 1), (R1, 2), (DIRECTORY, 3), (CONTENT, 4), (R2, 3), (R3, 4)]);
+
+        let (visited, end) = collect(|f| bfs_backward(&graph, [CONTENT, R3], &options, f));
+        assert_eq!(end, TraversalEnd::Complete);
+        assert_eq!(visited, [(CONTENT, 0), (R3, 0), (DIRECTORY, 1), (R2, 1), (R1, 2), (SNAPSHOT, 3), (ORIGIN, 4)]);
+
+        // Roots given twice are visited once
+        let (visited, _) = collect(|f| bfs(&graph, [R3, R3], &options, f));
+        assert_eq!(visited, [(R3, 0)]);
+    }
+
+    #[test]
+    fn bounds() {
+        let graph = history();
+        let (visited, end) = collect(|f| bfs(&graph, [SNAPSHOT], &TraversalOptions::new().max_depth(2), f));
+        assert_eq!(end, TraversalEnd::Complete);
+        assert_eq!(visited, [(SNAPSHOT, 0), (R1, 1), (R2, 2), (DIRECTORY, 2)]);
+
+        let (visited, end) = collect(|f| bfs(&graph, [SNAPSHOT], &TraversalOptions::new().max_nodes(3), f));
+        assert_eq!(end, TraversalEnd::BudgetExhausted);
+        assert_eq!(visited, [(SNAPSHOT, 0), (R1, 1), (R2, 2)]);
+        // A budget only just enough is not exhausted
+        let (visited, end) = collect(|f| bfs(&graph, [SNAPSHOT], &TraversalOptions::new().max_nodes(6), f));
+        assert_eq!((visited.len(), end), (6, TraversalEnd::Complete));
+        let (visited, end) = collect(|f| bfs(&graph, [SNAPSHOT], &TraversalOptions::new().max_nodes(0), f));
+        assert_eq!((visited.len(), end), (0, TraversalEnd::BudgetExhausted));
+    }
+
+    #[test]
+    fn node_types() {
+        let graph = history();
+        // Roots are visited whatever their type
+        let options = TraversalOptions::new().node_types(&[NodeType::Revision]);
+        let (visited, end) = collect(|f| bfs(&graph, [SNAPSHOT], &options, f));
+        assert_eq!(end, TraversalEnd::Complete);
+        assert_eq!(visited, [(SNAPSHOT, 0), (R1, 1), (R2, 2), (R3, 3)]);
+
+        let options = TraversalOptions::new().node_types(&[NodeType::Directory, NodeType::Snapshot, NodeType::Origin]);
+        let (visited, _) = collect(|f| bfs_backward(&graph, [DIRECTORY], &options, f));
+        assert_eq!(visited, [(DIRECTORY, 0)]);
+        let (visited, _) = collect(|f| bfs_backward(&graph, [R1], &options, f));
+        assert_eq!(visited, [(R1, 0), (SNAPSHOT, 1), (ORIGIN, 2)]);
+    }
+
+    #[test]
+    fn skip_and_stop() {
+        let graph = history();
+        let options = TraversalOptions::new();
+        let mut visited = Vec::new();
+        let end = bfs(&graph, [SNAPSHOT], &options, |node, _| {
+            visited.push(node);
+            if node == DIRECTORY { Visit::Skip } else { Visit::Continue }
+        });
+        assert_eq!(end, TraversalEnd::Complete);
+        assert_eq!(visited, [SNAPSHOT, R1, R2, DIRECTORY, R3]);
+
+        let mut visited = Vec::new();
+        let end = bfs(&graph, [SNAPSHOT], &options, |node, _| {
+            visited.push(node);
+            if node == R2 { Visit::Stop } else { Visit::Continue }
+        });
+        assert_eq!(end, TraversalEnd::Stopped);
+        assert_eq!(visited, [SNAPSHOT, R1, R2]);
+    }
+
+    #[test]
+    fn cancellation() {
+        // A chain long enough for the token to be checked
+        let mut builder = InMemoryGraphBuilder::default();
+        let nodes: Vec<NodeId> = (0..2 * CANCELLATION_CHECK_INTERVAL).map(|i| builder.revision(i as i64, 0)).collect();
+        for pair in nodes.windows(2) {
+            builder.arc(pair[0], pair[1]);
+        }
+        let graph = builder.build();
+
+        let cancel = CancellationToken::new();
+        let options = TraversalOptions::new().cancel(&cancel);
+        let (visited, end) = collect(|f| bfs(&graph, [nodes[0]], &options, f));
+        assert_eq!((visited.len(), end), (nodes.len(), TraversalEnd::Complete));
+
+        cancel.cancel();
+        let (visited, end) = collect(|f| bfs(&graph, [nodes[0]], &options, f));
+        assert!(end.is_cancelled());
+        assert_eq!(visited.len(), CANCELLATION_CHECK_INTERVAL - 1);
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        nodes: Vec<NodeId>,
+        arcs: Vec<(NodeId, NodeId, Vec<ArcLabel>)>,
+    }
+
+    impl Visitor for Recorder {
+        fn node(&mut self, node: NodeId, _depth: usize) -> Visit {
+            self.nodes.push(node);
+            if node == R1 { Visit::Skip } else { Visit::Continue }
+        }
+
+        fn arc(&mut self, src: NodeId, dst: NodeId, labels: &[ArcLabel]) {
+            self.arcs.push((src, dst, labels.to_vec()));
+        }
+    }
+
+    #[test]
+    fn visitor() {
+        let graph = history();
+        let mut recorder = Recorder::default();
+        let end = visit(&graph, [ORIGIN], &TraversalOptions::new(), &mut recorder);
+        assert_eq!(end, TraversalEnd::Complete);
+        // The arcs of a skipped node are not followed
+        assert_eq!(recorder.nodes, [ORIGIN, SNAPSHOT, R1]);
+        assert_eq!(
+            recorder.arcs,
+            [
+                (ORIGIN, SNAPSHOT, vec![ArcLabel::Visit { timestamp: 10, full: true }]),
+                (SNAPSHOT, R1, vec![ArcLabel::Branch { name: b"refs/heads/main".to_vec() }]),
+            ]
+        );
+    }
+}
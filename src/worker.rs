@@ -0,0 +1,306 @@
+use serde_json::{json, Value};
+use std::any::Any;
+use std::collections::VecDeque;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use tokio::sync::{oneshot, RwLock};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info};
+
+use crate::backend::GraphBackend;
+use crate::error::GraphError;
+use crate::graph::Graph;
+use crate::jobs::{Failure, FailureKind, Job};
+use crate::origin::Metrics;
+
+/// Maximum number of background requests waiting in the queue; job feeders block
+/// beyond that so recomputing the whole corpus doesn't buffer millions of requests
+const BACKGROUND_QUEUE_CAPACITY: usize = 10_000;
+
+/// An origin waiting to be computed by a worker
+struct ComputeRequest {
+    origin_id: usize,
+    /// Discard the cached metrics before computing
+    force: bool,
+    /// Job this request belongs to, if any
+    job: Option<Arc<Job>>,
+    /// Notified once the metrics are stored in the graph, or of why they could not be
+    done: Option<oneshot::Sender<Result<(), GraphError>>>,
+    /// Skip or interrupt the computation when cancelled (job cancelled, requester gone)
+    cancel: CancellationToken,
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// Requests someone is waiting for (lazy computation from the handlers)
+    interactive: VecDeque<ComputeRequest>,
+    /// Requests from admin-triggered jobs
+    background: VecDeque<ComputeRequest>,
+    /// Background requests are left in the queue meanwhile, e.g. under memory pressure
+    background_paused: bool,
+}
+
+/// Two-level work queue: interactive requests always go before background ones
+#[derive(Default)]
+struct Queue {
+    state: Mutex<QueueState>,
+    available: Condvar,
+    space: Condvar,
+}
+
+impl Queue {
+    fn push_interactive(&self, request: ComputeRequest) {
+        self.state.lock().unwrap().interactive.push_back(request);
+        self.available.notify_one();
+    }
+
+    fn push_background(&self, request: ComputeRequest) {
+        let mut state = self.state.lock().unwrap();
+        while state.background.len() >= BACKGROUND_QUEUE_CAPACITY {
+            state = self.space.wait(state).unwrap();
+        }
+        state.background.push_back(request);
+        self.available.notify_one();
+    }
+
+    fn pause_background(&self, paused: bool) {
+        self.state.lock().unwrap().background_paused = paused;
+        self.available.notify_all();
+    }
+
+    fn pop(&self) -> ComputeRequest {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(request) = state.interactive.pop_front() {
+                return request;
+            }
+            if !state.background_paused {
+                if let Some(request) = state.background.pop_front() {
+                    self.space.notify_one();
+                    return request;
+                }
+            }
+            state = self.available.wait(state).unwrap();
+        }
+    }
+}
+
+/// Counters shared between the workers and the API
+#[derive(Default)]
+pub struct WorkerProgress {
+    queued: AtomicUsize,
+    running: AtomicUsize,
+    completed: AtomicUsize,
+    cancelled: AtomicUsize,
+    failed: AtomicUsize,
+}
+
+impl WorkerProgress {
+    pub fn to_json(&self, workers: usize) -> Value {
+        json!({
+            "workers": workers,
+            "queued": self.queued.load(Ordering::Relaxed),
+            "running": self.running.load(Ordering::Relaxed),
+            "completed": self.completed.load(Ordering::Relaxed),
+            "cancelled": self.cancelled.load(Ordering::Relaxed),
+            "failed": self.failed.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// Pool of blocking threads computing origin metrics off the request path.
+///
+/// Workers copy an origin out of the graph under a short read lock, run the
+/// traversals without holding any lock, then store the results under a short
+/// write lock.
+pub struct WorkerPool<G>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    state: Arc<RwLock<Graph<G>>>,
+    queue: Arc<Queue>,
+    progress: Arc<WorkerProgress>,
+    workers: usize,
+}
+
+impl<G> WorkerPool<G>
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    /// Start `workers` compute threads (one per available core if 0)
+    pub fn start(state: Arc<RwLock<Graph<G>>>, workers: usize) -> Arc<Self> {
+        let workers = if workers == 0 {
+            std::thread::available_parallelism().map_or(1, |n| n.get())
+        } else {
+            workers
+        };
+        let pool = Arc::new(WorkerPool {
+            state,
+            queue: Arc::new(Queue::default()),
+            progress: Arc::new(WorkerProgress::default()),
+            workers,
+        });
+        for i in 0..workers {
+            let state = pool.state.clone();
+            let queue = pool.queue.clone();
+            let progress = pool.progress.clone();
+            std::thread::Builder::new()
+                .name(format!("compute-worker-{}", i))
+                .spawn(move || worker_loop(state, queue, progress))
+                .expect("Failed to spawn compute worker");
+        }
+        info!("Started {} compute workers", workers);
+        pool
+    }
+
+    pub fn progress(&self) -> &WorkerProgress {
+        &self.progress
+    }
+
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    /// Stop (or resume) computing the origins of jobs; requests someone waits for are
+    /// still computed. Origins being computed are not interrupted.
+    pub fn pause_background(&self, paused: bool) {
+        self.queue.pause_background(paused);
+    }
+
+    /// Compute the missing metrics of an origin ahead of background work, and wait for them
+    /// to be stored in the graph. Fails with [`GraphError::OriginNotFound`],
+    /// [`GraphError::GraphNotAttached`] or [`GraphError::ComputeFailed`].
+    ///
+    /// Dropping the returned future (e.g. when the client disconnects) cancels the computation.
+    pub async fn compute(&self, origin_id: usize) -> Result<(), GraphError> {
+        let (done, wait) = oneshot::channel();
+        let cancel = CancellationToken::new();
+        let _cancel_on_drop = cancel.clone().drop_guard();
+        self.progress.queued.fetch_add(1, Ordering::Relaxed);
+        self.queue.push_interactive(ComputeRequest {
+            origin_id,
+            force: false,
+            job: None,
+            done: Some(done),
+            cancel,
+        });
+        wait.await.unwrap_or_else(|_| {
+            Err(GraphError::ComputeFailed { origin: origin_id, reason: "compute worker stopped".to_string() })
+        })
+    }
+
+    /// Queue all `origin_ids` for computation as part of `job`.
+    ///
+    /// Returns immediately; ids are fed to the workers from a dedicated thread as the
+    /// bounded background queue drains, until the job is cancelled.
+    pub fn submit_job(&self, job: Arc<Job>, origin_ids: Vec<usize>, force: bool) {
+        let queue = self.queue.clone();
+        let progress = self.progress.clone();
+        std::thread::Builder::new()
+            .name(format!("job-feeder-{}", job.id))
+            .spawn(move || {
+                let cancel = job.cancellation_token().clone();
+                for origin_id in origin_ids {
+                    if cancel.is_cancelled() {
+                        info!("Job {} cancelled, no longer queueing its origins", job.id);
+                        break;
+                    }
+                    progress.queued.fetch_add(1, Ordering::Relaxed);
+                    queue.push_background(ComputeRequest {
+                        origin_id,
+                        force,
+                        job: Some(job.clone()),
+                        done: None,
+                        cancel: cancel.clone(),
+                    });
+                }
+            })
+            .expect("Failed to spawn job feeder");
+    }
+}
+
+fn worker_loop<G>(state: Arc<RwLock<Graph<G>>>, queue: Arc<Queue>, progress: Arc<WorkerProgress>)
+where
+    G: GraphBackend + Send + Sync + 'static,
+{
+    loop {
+        let request = queue.pop();
+        progress.queued.fetch_sub(1, Ordering::Relaxed);
+        if request.cancel.is_cancelled() {
+            progress.cancelled.fetch_add(1, Ordering::Relaxed);
+            continue;
+        }
+        progress.running.fetch_add(1, Ordering::Relaxed);
+
+        // Copy the origin out so the traversals run without holding the lock
+        let origin = state.blocking_read().detached_origin(request.origin_id, request.force);
+        let failure = match origin {
+            Some(origin) => {
+                // A panic only fails this origin: the worker goes on, and the detached copy
+                // with its partial metrics is dropped
+                let computed = catch_unwind(AssertUnwindSafe(|| {
+                    origin.compute_metrics_cancellable(Metrics::ALL, &request.cancel)
+                }));
+                match computed {
+                    Ok(Ok(true)) => {
+                        state.blocking_write().store_origin(origin);
+                        None
+                    }
+                    Ok(Ok(false)) => {
+                        progress.running.fetch_sub(1, Ordering::Relaxed);
+                        debug!("Computation of origin {} cancelled", request.origin_id);
+                        progress.cancelled.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+                    Ok(Err(e)) => {
+                        error!("Computation of origin {} failed: {}", request.origin_id, e);
+                        Some((FailureKind::ComputeFailed, e))
+                    }
+                    Err(panic) => {
+                        let reason = panic_message(panic.as_ref());
+                        error!("Computation of origin {} failed: {}", request.origin_id, reason);
+                        Some((FailureKind::ComputeFailed, GraphError::ComputeFailed { origin: request.origin_id, reason }))
+                    }
+                }
+            }
+            None => {
+                debug!("Origin {} not found, skipping computation", request.origin_id);
+                Some((FailureKind::NotFound, GraphError::OriginNotFound(request.origin_id)))
+            }
+        };
+
+        progress.running.fetch_sub(1, Ordering::Relaxed);
+        if matches!(failure, Some((FailureKind::ComputeFailed, _))) {
+            progress.failed.fetch_add(1, Ordering::Relaxed);
+        } else {
+            progress.completed.fetch_add(1, Ordering::Relaxed);
+        }
+        match (request.job, failure) {
+            (Some(job), Some((status, e))) => {
+                let reason = match e {
+                    _ if status == FailureKind::NotFound => None,
+                    GraphError::ComputeFailed { reason, .. } => Some(reason),
+                    e => Some(e.to_string()),
+                };
+                job.fail(Failure { origin_id: request.origin_id, status, reason })
+            }
+            (Some(job), None) => job.complete_one(),
+            (None, failure) => {
+                if let Some(done) = request.done {
+                    // The requester may have gone away, nothing to do then
+                    let _ = done.send(failure.map_or(Ok(()), |(_, e)| Err(e)));
+                }
+            }
+        }
+    }
+}
+
+/// What a worker panicked with, for the failure report
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "worker panicked".to_string())
+}